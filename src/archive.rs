@@ -0,0 +1,261 @@
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use jiff::{Timestamp, ToSpan, civil};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    asana,
+    correlate::Correlator,
+    crypt, date,
+    google::{self, GTaskResult},
+    state::State,
+    sync::{self, DeleteReason, SyncAction},
+};
+
+/// A google task the bridge removed, with what both sides showed right before
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub at: Timestamp,
+    /// Completed on either side, rather than deleted or filtered out
+    pub completed: bool,
+    pub reason: String,
+    /// Missing when the google task wasn't linked to anything
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asana: Option<AsanaSide>,
+    pub google: GoogleSide,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AsanaSide {
+    pub gid: String,
+    pub name: String,
+    #[serde(default)]
+    pub due_on: Option<civil::Date>,
+    #[serde(default)]
+    pub completed_at: Option<Timestamp>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GoogleSide {
+    pub list: String,
+    pub id: String,
+    pub title: String,
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub due: Option<String>,
+    /// When the task was completed in google
+    #[serde(default)]
+    pub completed: Option<String>,
+}
+
+/// Entries for the google tasks `actions` are about to delete
+pub fn entries(
+    actions: &[SyncAction],
+    asana_tasks: &asana::TaskResult,
+    google_tasks: &GTaskResult,
+    state: &State,
+    correlator: &dyn Correlator,
+    at: Timestamp,
+) -> Vec<Entry> {
+    actions
+        .iter()
+        .filter_map(|action| match action {
+            SyncAction::DeleteGoogle {
+                list,
+                google_id,
+                title,
+                reason,
+            } => Some((list, google_id, title, *reason)),
+            _ => None,
+        })
+        .map(|(list, google_id, title, reason)| {
+            // deletions in other lists (section routing) only have what the plan carried
+            let gtask = google_tasks
+                .incomplete
+                .iter()
+                .chain(&google_tasks.complete)
+                .find(|t| t.id.as_deref() == Some(google_id.as_str()));
+            let gid = match gtask {
+                Some(gtask) => sync::linked_asana_gid(gtask, state, correlator),
+                None => state.asana_gid_for_google(google_id).map(str::to_string),
+            };
+
+            Entry {
+                at,
+                completed: matches!(
+                    reason,
+                    DeleteReason::CompletedInGoogle
+                        | DeleteReason::CompletedInAsana
+                        | DeleteReason::ParentCompleted
+                ),
+                reason: reason.to_string(),
+                asana: gid.and_then(|gid| asana_side(&gid, asana_tasks, state)),
+                google: GoogleSide {
+                    list: list.clone(),
+                    id: google_id.clone(),
+                    title: gtask
+                        .and_then(|t| t.title.clone())
+                        .unwrap_or_else(|| title.clone()),
+                    notes: gtask
+                        .and_then(|t| t.notes.as_deref())
+                        .map(|notes| google::notes_body(notes).to_string()),
+                    due: gtask.and_then(|t| t.due.clone()),
+                    completed: gtask.and_then(|t| t.completed.clone()),
+                },
+            }
+        })
+        .collect()
+}
+
+/// The asana task as fetched this cycle, or as the state store remembers it once it's deleted
+fn asana_side(gid: &str, asana_tasks: &asana::TaskResult, state: &State) -> Option<AsanaSide> {
+    let fetched = asana_tasks
+        .incomplete
+        .iter()
+        .chain(&asana_tasks.complete)
+        .find(|t| t.gid == gid);
+    match fetched {
+        Some(task) => Some(AsanaSide {
+            gid: task.gid.clone(),
+            name: task.name.clone(),
+            due_on: task.due_date(),
+            completed_at: task.completed_at,
+        }),
+        None => state.links.get(gid).map(|link| AsanaSide {
+            gid: gid.to_string(),
+            name: link.asana_name.clone(),
+            due_on: link.due,
+            completed_at: None,
+        }),
+    }
+}
+
+/// Add entries to the end of the archive, one json object per line, each [encrypted](crypt)
+/// when there's a key
+pub fn append(path: &str, entries: &[Entry]) -> Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    let mut out = String::new();
+    for entry in entries {
+        out.push_str(&crypt::seal_line(&serde_json::to_string(entry)?)?);
+        out.push('\n');
+    }
+
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(out.as_bytes()))
+        .with_context(|| format!("failed to append to {path}"))
+}
+
+/// Every entry in the archive, none if it doesn't exist yet
+pub fn load(path: &str) -> Result<Vec<Entry>> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {path}")),
+    };
+
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            crypt::open_line(line)
+                .and_then(|line| Ok(serde_json::from_str(&line)?))
+                .with_context(|| format!("failed to parse {path}:{}", i + 1))
+        })
+        .collect()
+}
+
+/// Print the tasks archived during the last `days` days, oldest first. Only completed ones unless
+/// `deleted` is set.
+pub fn print(path: &str, days: u32, deleted: bool) -> Result<()> {
+    let since = date::today() - i64::from(days).days();
+    let entries: Vec<Entry> = load(path)?
+        .into_iter()
+        .filter(|e| (e.completed || deleted) && date::local_date(e.at) >= since)
+        .collect();
+
+    if entries.is_empty() {
+        println!("Nothing archived since {since}");
+        return Ok(());
+    }
+    for entry in &entries {
+        let name = entry
+            .asana
+            .as_ref()
+            .map_or(entry.google.title.as_str(), |a| a.name.as_str());
+        println!("{}  {name}  ({})", date::local_date(entry.at), entry.reason);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::civil::date;
+
+    use super::*;
+    use crate::{
+        correlate::NotesMarker,
+        google::GoogleTasksApi,
+        mock::{MockGoogle, asana_task},
+    };
+
+    #[tokio::test]
+    async fn deletions_keep_both_sides() {
+        let google = MockGoogle::new();
+        let mut state = State::default();
+        let done = asana_task("100", "Write report", date(2026, 10, 20));
+        let gone = asana_task("101", "Pay rent", date(2026, 10, 22));
+        for task in [&done, &gone] {
+            let id = google
+                .new_task_from_asana(task, &task.name, "Draft", None)
+                .await
+                .unwrap();
+            state.link(task, google.list_id(), &id, None);
+        }
+        let google_tasks = google.get_tasks().await.unwrap();
+        let asana_tasks = asana::TaskResult {
+            incomplete: Vec::new(),
+            complete: vec![done.clone()],
+        };
+
+        let delete = |gid: &str, reason| {
+            let link = &state.links[gid];
+            SyncAction::DeleteGoogle {
+                list: link.google_list.clone(),
+                google_id: link.google_id.clone(),
+                title: link.asana_name.clone(),
+                reason,
+            }
+        };
+        let actions = [
+            delete("100", DeleteReason::CompletedInAsana),
+            delete("101", DeleteReason::DeletedInAsana),
+        ];
+        let entries = entries(
+            &actions,
+            &asana_tasks,
+            &google_tasks,
+            &state,
+            &NotesMarker,
+            Timestamp::now(),
+        );
+
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].completed);
+        assert_eq!(entries[0].asana.as_ref().unwrap().name, "Write report");
+        assert_eq!(entries[0].google.notes.as_deref(), Some("Draft"));
+        assert!(!entries[1].completed);
+        assert_eq!(
+            entries[1].asana.as_ref().unwrap().due_on,
+            Some(date(2026, 10, 22))
+        );
+    }
+}
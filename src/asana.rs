@@ -1,81 +1,389 @@
-use anyhow::{Result, bail};
+use anyhow::{Context, Result, anyhow, bail};
 use jiff::{Timestamp, ToSpan, civil};
+use log::warn;
 use reqwest::{
-    Response,
+    Response, StatusCode,
     header::{AUTHORIZATION, HeaderMap, HeaderValue},
 };
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, de::DeserializeOwned};
+use tokio::sync::OnceCell;
+
+use crate::{date, error::BridgeError, http, richtext};
+
+/// Asana's API root, overridable to target regional endpoints or a test server
+pub const DEFAULT_BASE_URL: &str = "https://app.asana.com/api/1.0";
+
+const TASK_OPT_FIELDS: &str = "name,notes,html_notes,permalink_url,liked,due_on,due_at,start_on,start_at,completed_at,modified_at,assignee.gid,assignee_section.name,memberships.project.gid,memberships.section.name,tags.name";
+
+const STORY_OPT_FIELDS: &str = "type,text,created_at,created_by.name";
+
+const ATTACHMENT_OPT_FIELDS: &str = "name,host,permanent_url,view_url";
+
+/// Most tasks a search returns, it has no next page to follow
+const SEARCH_LIMIT: usize = 100;
+
+/// Most actions asana takes in one request to its batch endpoint
+pub const BATCH_LIMIT: usize = 10;
+
+/// Everything the bridge does with asana, so syncing can run against something other than the
+/// live API
+pub trait AsanaApi {
+    fn source(&self) -> &TaskSource;
+
+    /// Incomplete tasks with a due date, plus those completed since `completed_since`
+    async fn get_tasks_since(&self, completed_since: Timestamp) -> Result<TaskResult>;
+
+    /// Incomplete tasks with a due date, plus those completed in the past day
+    async fn get_tasks(&self) -> Result<TaskResult> {
+        self.get_tasks_since(Timestamp::now() - 24.hours()).await
+    }
+
+    async fn get_subtasks(&self, task_gid: &str) -> Result<Vec<Task>>;
+
+    async fn get_task(&self, task_gid: &str) -> Result<Task>;
+
+    /// A single task as the source describes it, for inspecting. The parsed task unless the
+    /// source has something rawer to show.
+    async fn get_task_raw(&self, task_gid: &str) -> Result<serde_json::Value> {
+        Ok(serde_json::to_value(self.get_task(task_gid).await?)?)
+    }
+
+    /// Comments left on a task, oldest first
+    async fn get_comments(&self, task_gid: &str) -> Result<Vec<Comment>>;
+
+    /// Comment on a task as the token's user
+    async fn add_comment(&self, task_gid: &str, text: &str) -> Result<()>;
+
+    /// Files attached to a task, and links to files kept elsewhere
+    async fn get_attachments(&self, task_gid: &str) -> Result<Vec<Attachment>>;
+
+    /// Gid of the user the token belongs to
+    async fn me(&self) -> Result<String>;
+
+    /// Whether a task still exists, as opposed to having been deleted
+    async fn task_exists(&self, task_gid: &str) -> Result<bool>;
+
+    /// When a task was last edited, `None` when the source doesn't say
+    async fn modified_at(&self, task_gid: &str) -> Result<Option<Timestamp>> {
+        Ok(self.get_task(task_gid).await?.modified_at)
+    }
+
+    async fn get_sections(&self) -> Result<Vec<Section>>;
+
+    async fn add_task_to_section(&self, task_gid: &str, section_gid: &str) -> Result<()>;
+
+    /// Move a task under another parent, or make it a top level task with `None`
+    async fn set_parent(&self, task_gid: &str, parent_gid: Option<&str>) -> Result<()>;
+
+    async fn complete_task(&self, task_gid: &str) -> Result<()>;
+
+    /// Complete several tasks, with how it went for each in the same order. One at a time unless
+    /// the source can take them in fewer requests.
+    async fn complete_tasks(&self, task_gids: &[String]) -> Vec<Result<()>> {
+        let mut results = Vec::new();
+        for gid in task_gids {
+            results.push(self.complete_task(gid).await);
+        }
+        results
+    }
+
+    /// Write a task's `due_at`, or its `due_on` when it has no due time
+    async fn set_due(&self, task: &Task) -> Result<()>;
+
+    /// Like or unlike a task for the token's user
+    async fn set_liked(&self, task_gid: &str, liked: bool) -> Result<()>;
+
+    /// Rename a task or replace its notes, leaving out whichever is `None`
+    async fn set_text(&self, task_gid: &str, name: Option<&str>, notes: Option<&str>)
+    -> Result<()>;
+}
 
 pub struct AsanaClient {
     client: reqwest::Client,
-    headers: HeaderMap,
-    project: String,
+    base_url: String,
+    source: TaskSource,
+    /// Resolved on first use
+    me: OnceCell<String>,
+}
+
+/// Where the bridge pulls its Asana tasks from
+#[derive(Debug, Clone)]
+pub enum TaskSource {
+    /// A user's "My Tasks" list
+    UserTaskList(String),
+    /// A regular (possibly archived) project
+    Project(String),
+    /// Whatever a search across a workspace finds
+    Search {
+        workspace_gid: String,
+        query: SearchQuery,
+    },
+    /// GitHub issues assigned to the token's user, see [`crate::github::GitHubClient`]
+    GitHubIssues,
+}
+
+/// What a [`TaskSource::Search`] looks for. Searching needs a premium workspace.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchQuery {
+    /// Workspace searched, `asana.workspace_gid` when unset
+    pub workspace_gid: Option<String>,
+    /// Gids of the users the tasks are assigned to, `me` for the token's user
+    pub assignees: Vec<String>,
+    /// Only tasks due within this many days from today, overdue ones included
+    pub due_within_days: Option<i64>,
+    /// Gids of tags the tasks carry at least one of
+    pub tags: Vec<String>,
+}
+
+impl SearchQuery {
+    /// Query parameters for the search endpoint, without completion or paging
+    fn params(&self) -> String {
+        let mut params = vec![
+            format!("opt_fields={TASK_OPT_FIELDS},created_at"),
+            format!("limit={SEARCH_LIMIT}"),
+            "sort_by=created_at".to_string(),
+        ];
+        if !self.assignees.is_empty() {
+            params.push(format!("assignee.any={}", self.assignees.join(",")));
+        }
+        if let Some(days) = self.due_within_days {
+            let before = date::today().saturating_add(days.days());
+            params.push(format!("due_on.before={before}"));
+        }
+        if !self.tags.is_empty() {
+            params.push(format!("tags.any={}", self.tags.join(",")));
+        }
+        params.join("&")
+    }
+}
+
+impl TaskSource {
+    fn tasks_path(&self) -> String {
+        match self {
+            TaskSource::UserTaskList(gid) => format!("user_task_lists/{gid}/tasks"),
+            TaskSource::Project(gid) => format!("projects/{gid}/tasks"),
+            TaskSource::Search { workspace_gid, .. } => {
+                format!("workspaces/{workspace_gid}/tasks/search")
+            }
+            TaskSource::GitHubIssues => unreachable!("asana clients never fetch github issues"),
+        }
+    }
+
+    /// Name of the section the task sits in within this source, if any
+    pub fn section_of<'a>(&self, task: &'a Task) -> Option<&'a str> {
+        match self {
+            TaskSource::UserTaskList(_) => task.assignee_section.as_ref().map(|s| s.name.as_str()),
+            TaskSource::Project(gid) => task
+                .memberships
+                .iter()
+                .find(|m| m.project.as_ref().is_some_and(|p| &p.gid == gid))
+                .and_then(|m| m.section.as_ref())
+                .map(|s| s.name.as_str()),
+            TaskSource::Search { .. } | TaskSource::GitHubIssues => None,
+        }
+    }
 }
 
 impl AsanaClient {
-    pub fn new(personal_token: &str, project_me_gid: &str) -> Result<Self> {
-        // Create headers for authentication
-        let mut headers = HeaderMap::new();
-        headers.insert(
-            AUTHORIZATION,
-            HeaderValue::from_str(&format!("Bearer {}", personal_token))?,
-        );
+    pub fn new(personal_token: &str, source: TaskSource, base_url: &str) -> Result<Self> {
+        if matches!(source, TaskSource::GitHubIssues) {
+            bail!("github issues are fetched with a GitHubClient");
+        }
+        // sent with every request, kept out of debug output
+        let mut auth = HeaderValue::from_str(&format!("Bearer {}", personal_token))?;
+        auth.set_sensitive(true);
 
         Ok(Self {
-            client: reqwest::Client::new(),
-            headers,
-            project: project_me_gid.into(),
+            client: http::client(HeaderMap::from_iter([(AUTHORIZATION, auth)]))?,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            source,
+            me: OnceCell::new(),
         })
     }
 
+    fn url(&self, path: &str) -> String {
+        format!("{}/{path}", self.base_url)
+    }
+
     async fn request_get(&self, url: &str) -> Result<Response> {
-        let resp = self
-            .client
-            .get(url)
-            .headers(self.headers.clone())
-            .send()
+        Ok(http::send(self.client.get(url), "fetch").await?)
+    }
+
+    async fn request_put<T: Serialize>(&self, url: &str, body: T) -> Result<Response> {
+        Ok(http::send(self.client.put(url).json(&body), "put").await?)
+    }
+
+    async fn request_post<T: Serialize>(&self, url: &str, body: T) -> Result<Response> {
+        Ok(http::send(self.client.post(url).json(&body), "post").await?)
+    }
+
+    /// Send up to [`BATCH_LIMIT`] actions in one request, with how each went in the same order
+    async fn batch(&self, actions: Vec<BatchAction>) -> Result<Vec<Result<()>>> {
+        let count = actions.len();
+        let batch_body = BatchRequest {
+            data: BatchData { actions },
+        };
+        let batch_response: BatchResponse = self
+            .request_post(&self.url("batch"), batch_body)
+            .await?
+            .json()
             .await?;
+        if batch_response.data.len() != count {
+            bail!(
+                "asana answered {} of {count} batched actions",
+                batch_response.data.len()
+            );
+        }
 
-        if resp.status().is_success() {
-            return Ok(resp);
+        Ok(batch_response
+            .data
+            .into_iter()
+            .map(|result| {
+                let status = StatusCode::from_u16(result.status_code)?;
+                if !status.is_success() {
+                    return Err(BridgeError::from_status("put", status, None).into());
+                }
+                Ok(())
+            })
+            .collect())
+    }
+
+    /// Workspaces the token's user belongs to
+    pub async fn workspaces(&self) -> Result<Vec<Workspace>> {
+        self.get_paged(&self.url("workspaces?opt_fields=name&limit=100"))
+            .await
+    }
+
+    /// Gid of the token's user's "My Tasks" list in a workspace
+    pub async fn my_task_list(&self, workspace_gid: &str) -> Result<String> {
+        let list_url = self.url(&format!(
+            "users/me/user_task_list?workspace={workspace_gid}&opt_fields=gid"
+        ));
+
+        let list_response: UserTaskListResponse = self.request_get(&list_url).await?.json().await?;
+        Ok(list_response.data.gid)
+    }
+
+    /// Gid of the token's user's "My Tasks" list in `workspace_gid`, or in their only workspace
+    /// when unset
+    pub async fn find_my_task_list(&self, workspace_gid: Option<&str>) -> Result<String> {
+        let workspace_gid = self.find_workspace(workspace_gid).await?;
+        self.my_task_list(&workspace_gid).await
+    }
+
+    /// `workspace_gid`, or the token's user's only workspace when unset
+    pub async fn find_workspace(&self, workspace_gid: Option<&str>) -> Result<String> {
+        if let Some(workspace_gid) = workspace_gid {
+            return Ok(workspace_gid.to_string());
         }
 
-        bail!("Failed to fetch. Status: {}", resp.status())
+        match self.workspaces().await?.as_slice() {
+            [] => bail!("the asana user isn't in any workspace"),
+            [only] => Ok(only.gid.clone()),
+            several => {
+                let names: Vec<String> = several
+                    .iter()
+                    .map(|w| format!("{} ({})", w.name, w.gid))
+                    .collect();
+                bail!(
+                    "the asana user is in several workspaces, pick one with asana.workspace_gid: {}",
+                    names.join(", ")
+                )
+            }
+        }
     }
 
-    async fn request_put<T: Serialize>(&self, url: &str, body: T) -> Result<Response> {
-        let resp = self
-            .client
-            .put(url)
-            .headers(self.headers.clone())
-            .json(&body)
-            .send()
-            .await?;
+    /// Follow asana's offset pagination until every page has been fetched
+    async fn get_paged<T: DeserializeOwned>(&self, url: &str) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+
+        let mut next_page: Option<String> = None;
+        loop {
+            let page_url = match &next_page {
+                Some(offset) => format!("{url}&offset={offset}"),
+                None => url.to_string(),
+            };
+
+            let page_response = self.request_get(&page_url).await?;
+            let page_response: PageResponse = page_response.json().await?;
 
-        if resp.status().is_success() {
-            return Ok(resp);
+            items.extend(readable(page_response.data));
+            next_page = page_response.next_page.map(|p| p.offset);
+
+            if next_page.is_none() {
+                break;
+            }
         }
 
-        bail!("Failed to put. Status: {}", resp.status())
+        Ok(items)
     }
 
-    pub async fn get_tasks(&self) -> Result<TaskResult> {
-        let past_day_ts = jiff::Timestamp::now() - 24.hours();
+    async fn get_paged_tasks(&self, url: &str) -> Result<Vec<Task>> {
+        let tasks: Vec<Task> = self.get_paged(url).await?;
+        Ok(tasks.into_iter().map(Task::with_rendered_notes).collect())
+    }
 
-        let tasks_url = format!(
-            "https://app.asana.com/api/1.0/user_task_lists/{}/tasks?opt_fields=name,notes,due_on,due_at,completed_at&completed_since={past_day_ts}&limit=100",
-            self.project
-        );
+    /// Every task the search finds that matches `completion`. Search results have no next page,
+    /// so while they come back full, the search is repeated for tasks created before the oldest
+    /// one seen.
+    async fn search_tasks(&self, query: &SearchQuery, completion: &str) -> Result<Vec<Task>> {
+        let url = self.url(&format!(
+            "{}?{}&{completion}",
+            self.source.tasks_path(),
+            query.params()
+        ));
 
-        let tasks_response = self.request_get(&tasks_url).await?;
-        let tasks_response: TasksResponse = tasks_response.json().await?;
+        let mut tasks = Vec::new();
+        let mut created_before: Option<Timestamp> = None;
+        loop {
+            let page_url = match created_before {
+                Some(ts) => format!("{url}&created_at.before={ts}"),
+                None => url.clone(),
+            };
 
-        if tasks_response.next_page.is_some() {
-            todo!();
+            let page_response: PageResponse = self.request_get(&page_url).await?.json().await?;
+            let full = page_response.data.len() == SEARCH_LIMIT;
+            let hits: Vec<SearchHit> = readable(page_response.data);
+            created_before = hits.last().map(|hit| hit.created_at);
+            tasks.extend(hits.into_iter().map(|hit| hit.task.with_rendered_notes()));
+
+            // with nothing on the page readable there's no telling where the next one starts
+            if !full || created_before.is_none() {
+                break;
+            }
         }
 
-        let tasks: Vec<Task> = tasks_response
-            .data
+        Ok(tasks)
+    }
+}
+
+impl AsanaApi for AsanaClient {
+    fn source(&self) -> &TaskSource {
+        &self.source
+    }
+
+    async fn get_tasks_since(&self, completed_since: Timestamp) -> Result<TaskResult> {
+        let tasks = match &self.source {
+            // a search can't ask for incomplete and recently completed tasks at once
+            TaskSource::Search { query, .. } => {
+                let mut tasks = self.search_tasks(query, "completed=false").await?;
+                tasks.extend(
+                    self.search_tasks(query, &format!("completed_at.after={completed_since}"))
+                        .await?,
+                );
+                tasks
+            }
+            source => {
+                let tasks_url = self.url(&format!(
+                    "{}?opt_fields={TASK_OPT_FIELDS}&completed_since={completed_since}&limit=100",
+                    source.tasks_path()
+                ));
+                self.get_paged_tasks(&tasks_url).await?
+            }
+        };
+
+        let tasks: Vec<Task> = tasks
             .into_iter()
             .filter(|t| t.due_at.is_some() || t.due_on.is_some())
             .collect();
@@ -96,10 +404,229 @@ impl AsanaClient {
         })
     }
 
-    pub async fn complete_task(&self, task_gid: &str) -> Result<()> {
-        let update_url = format!("https://app.asana.com/api/1.0/tasks/{task_gid}");
+    async fn get_subtasks(&self, task_gid: &str) -> Result<Vec<Task>> {
+        let subtasks_url = self.url(&format!(
+            "tasks/{task_gid}/subtasks?opt_fields={TASK_OPT_FIELDS}&limit=100"
+        ));
+
+        self.get_paged_tasks(&subtasks_url).await
+    }
+
+    async fn get_task(&self, task_gid: &str) -> Result<Task> {
+        let task: Task =
+            serde_json::from_value(self.get_task_raw(task_gid).await?).with_context(|| {
+                format!("asana sent task {task_gid} in a shape that couldn't be read")
+            })?;
+        Ok(task.with_rendered_notes())
+    }
+
+    async fn get_task_raw(&self, task_gid: &str) -> Result<serde_json::Value> {
+        let task_url = self.url(&format!("tasks/{task_gid}?opt_fields={TASK_OPT_FIELDS}"));
+
+        let task_response = self.request_get(&task_url).await?;
+        let mut task_response: serde_json::Value = task_response.json().await?;
+
+        Ok(task_response["data"].take())
+    }
+
+    async fn get_comments(&self, task_gid: &str) -> Result<Vec<Comment>> {
+        let stories_url = self.url(&format!(
+            "tasks/{task_gid}/stories?opt_fields={STORY_OPT_FIELDS}&limit=100"
+        ));
+
+        // stories also record every change made to the task, only comments are kept
+        let stories: Vec<Story> = self.get_paged(&stories_url).await?;
+        Ok(stories
+            .into_iter()
+            .filter(|s| s.kind == "comment")
+            .map(|s| Comment {
+                author: s.created_by.map(|u| u.name),
+                created_at: s.created_at,
+                text: s.text,
+            })
+            .collect())
+    }
+
+    async fn add_comment(&self, task_gid: &str, text: &str) -> Result<()> {
+        let stories_url = self.url(&format!("tasks/{task_gid}/stories"));
+        let story_body = AddCommentRequest {
+            data: AddCommentData { text },
+        };
+
+        self.request_post(&stories_url, story_body).await?;
+
+        Ok(())
+    }
+
+    async fn get_attachments(&self, task_gid: &str) -> Result<Vec<Attachment>> {
+        let attachments_url = self.url(&format!(
+            "attachments?parent={task_gid}&opt_fields={ATTACHMENT_OPT_FIELDS}&limit=100"
+        ));
+
+        self.get_paged(&attachments_url).await
+    }
+
+    async fn me(&self) -> Result<String> {
+        let me = self
+            .me
+            .get_or_try_init(|| async {
+                let me_url = self.url("users/me?opt_fields=gid");
+                let me_response: MeResponse = self.request_get(&me_url).await?.json().await?;
+                Ok::<_, anyhow::Error>(me_response.data.gid)
+            })
+            .await?;
+
+        Ok(me.clone())
+    }
+
+    async fn task_exists(&self, task_gid: &str) -> Result<bool> {
+        let task_url = self.url(&format!("tasks/{task_gid}?opt_fields=gid"));
+
+        match http::send(self.client.get(&task_url), "fetch").await {
+            Ok(_) => Ok(true),
+            Err(BridgeError::NotFound { .. }) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn modified_at(&self, task_gid: &str) -> Result<Option<Timestamp>> {
+        let task_url = self.url(&format!("tasks/{task_gid}?opt_fields=modified_at"));
+
+        let task_response: TaskResponse = self.request_get(&task_url).await?.json().await?;
+        Ok(task_response.data.modified_at)
+    }
+
+    async fn get_sections(&self) -> Result<Vec<Section>> {
+        let project_gid = match &self.source {
+            // user task lists expose their sections through the projects endpoint as well
+            TaskSource::UserTaskList(gid) | TaskSource::Project(gid) => gid,
+            // a search spans projects, it has no sections of its own
+            TaskSource::Search { .. } | TaskSource::GitHubIssues => return Ok(Vec::new()),
+        };
+        let sections_url = self.url(&format!("projects/{project_gid}/sections?opt_fields=name"));
+
+        let sections_response = self.request_get(&sections_url).await?;
+        let sections_response: SectionsResponse = sections_response.json().await?;
+
+        Ok(sections_response.data)
+    }
+
+    async fn add_task_to_section(&self, task_gid: &str, section_gid: &str) -> Result<()> {
+        let add_url = self.url(&format!("sections/{section_gid}/addTask"));
+        let add_body = AddTaskRequest {
+            data: AddTaskData { task: task_gid },
+        };
+
+        self.request_post(&add_url, add_body).await?;
+
+        Ok(())
+    }
+
+    async fn set_parent(&self, task_gid: &str, parent_gid: Option<&str>) -> Result<()> {
+        let set_parent_url = self.url(&format!("tasks/{task_gid}/setParent"));
+        let set_parent_body = SetParentRequest {
+            data: SetParentData { parent: parent_gid },
+        };
+
+        self.request_post(&set_parent_url, set_parent_body).await?;
+
+        Ok(())
+    }
+
+    async fn complete_task(&self, task_gid: &str) -> Result<()> {
+        let update_url = self.url(&format!("tasks/{task_gid}"));
+        let update_body = UpdateTaskRequest {
+            data: UpdateTaskData {
+                completed: Some(true),
+                ..Default::default()
+            },
+        };
+
+        self.request_put(&update_url, update_body).await?;
+
+        Ok(())
+    }
+
+    async fn complete_tasks(&self, task_gids: &[String]) -> Vec<Result<()>> {
+        let mut results = Vec::new();
+        for chunk in task_gids.chunks(BATCH_LIMIT) {
+            if let [gid] = chunk {
+                results.push(self.complete_task(gid).await);
+                continue;
+            }
+            let actions = chunk
+                .iter()
+                .map(|gid| BatchAction {
+                    relative_path: format!("/tasks/{gid}"),
+                    method: "put",
+                    data: UpdateTaskData {
+                        completed: Some(true),
+                        ..Default::default()
+                    },
+                })
+                .collect();
+            match self.batch(actions).await {
+                Ok(batched) => results.extend(batched),
+                // every action in the request failed the same way
+                Err(e) => results.extend(chunk.iter().map(|_| {
+                    Err(match BridgeError::of(&e) {
+                        Some(error) => error.clone().into(),
+                        None => anyhow!("{e:#}"),
+                    })
+                })),
+            }
+        }
+        results
+    }
+
+    async fn set_due(&self, task: &Task) -> Result<()> {
+        let update_url = self.url(&format!("tasks/{}", task.gid));
+        // asana refuses both at once, and setting due_on clears the due time
+        let data = match task.due_at {
+            Some(due_at) => UpdateTaskData {
+                due_at: Some(due_at),
+                ..Default::default()
+            },
+            None => UpdateTaskData {
+                due_on: task.due_on,
+                ..Default::default()
+            },
+        };
+
+        self.request_put(&update_url, UpdateTaskRequest { data })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn set_liked(&self, task_gid: &str, liked: bool) -> Result<()> {
+        let update_url = self.url(&format!("tasks/{task_gid}"));
         let update_body = UpdateTaskRequest {
-            data: UpdateTaskData { completed: true },
+            data: UpdateTaskData {
+                liked: Some(liked),
+                ..Default::default()
+            },
+        };
+
+        self.request_put(&update_url, update_body).await?;
+
+        Ok(())
+    }
+
+    async fn set_text(
+        &self,
+        task_gid: &str,
+        name: Option<&str>,
+        notes: Option<&str>,
+    ) -> Result<()> {
+        let update_url = self.url(&format!("tasks/{task_gid}"));
+        // plain notes replace the html ones, formatting included
+        let update_body = UpdateTaskRequest {
+            data: UpdateTaskData {
+                name: name.map(String::from),
+                notes: notes.map(String::from),
+                ..Default::default()
+            },
         };
 
         self.request_put(&update_url, update_body).await?;
@@ -111,51 +638,791 @@ impl AsanaClient {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Task {
     pub gid: String,
-    // ... other fields
-    // assignee: Option<Assignee>,
+    #[serde(default, deserialize_with = "or_default")]
     pub name: String,
+    /// Plain text description, rendered from `html_notes` when asana sends them
+    #[serde(default, deserialize_with = "or_default")]
     pub notes: String,
+    #[serde(default)]
+    pub html_notes: Option<String>,
+    #[serde(default)]
+    pub permalink_url: Option<String>,
+    /// Whether the token's user liked the task
+    #[serde(default, deserialize_with = "or_default")]
+    pub liked: bool,
     pub due_on: Option<civil::Date>,
     pub due_at: Option<Timestamp>,
+    /// Day work on the task is meant to start, asana only takes one along with a due date
+    #[serde(default)]
+    pub start_on: Option<civil::Date>,
+    #[serde(default)]
+    pub start_at: Option<Timestamp>,
     pub completed_at: Option<Timestamp>,
+    /// Last edit in asana, compared before writing so edits made meanwhile aren't overwritten
+    #[serde(default)]
+    pub modified_at: Option<Timestamp>,
+    #[serde(default)]
+    pub assignee: Option<UserRef>,
+    #[serde(default)]
+    pub assignee_section: Option<Section>,
+    #[serde(default, deserialize_with = "or_default")]
+    pub memberships: Vec<Membership>,
+    #[serde(default, deserialize_with = "or_default")]
+    pub tags: Vec<Tag>,
+    /// Asana task whose google copy this task is nested under, set when flattening subtasks. Asana
+    /// never sends it, it's only kept when the task is written to the journal.
+    #[serde(default)]
+    pub parent_gid: Option<String>,
+    /// Latest comments, when they're shown in google. Fetched separately, like `parent_gid`.
+    #[serde(default)]
+    pub comments: Vec<Comment>,
+    /// When they're shown in google, fetched separately too
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+}
+
+impl Task {
+    /// Local calendar day the task is due, preferring the exact due time when asana has one
+    pub fn due_date(&self) -> Option<civil::Date> {
+        match (self.due_on, self.due_at) {
+            (_, Some(due_at)) => Some(date::local_date(due_at)),
+            (due_on, None) => due_on,
+        }
+    }
+
+    /// Local calendar day work on the task starts, the exact start time when asana has one
+    pub fn start_date(&self) -> Option<civil::Date> {
+        match (self.start_on, self.start_at) {
+            (_, Some(start_at)) => Some(date::local_date(start_at)),
+            (start_on, None) => start_on,
+        }
+    }
+
+    /// Replace the plain notes, which lose links and list structure, with a rendering of the rich
+    /// text ones
+    fn with_rendered_notes(mut self) -> Self {
+        if let Some(html) = &self.html_notes {
+            self.notes = richtext::to_text(html);
+        }
+        self
+    }
+
+    /// The task moved to another day, keeping the time of day when it is due at a time
+    pub fn moved_to(&self, day: civil::Date) -> Result<Task> {
+        let mut task = self.clone();
+        match self.due_at {
+            Some(due_at) => {
+                let tz = date::local_tz();
+                let time = due_at.to_zoned(tz.clone()).time();
+                task.due_at = Some(day.to_datetime(time).to_zoned(tz.clone())?.timestamp());
+            }
+            None => task.due_on = Some(day),
+        }
+        Ok(task)
+    }
+
+    pub fn assigned_to(&self, user_gid: &str) -> bool {
+        self.assignee.as_ref().is_some_and(|a| a.gid == user_gid)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Section {
+    pub gid: String,
+    #[serde(default, deserialize_with = "or_default")]
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Membership {
+    #[serde(default)]
+    pub project: Option<ProjectRef>,
+    #[serde(default)]
+    pub section: Option<Section>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tag {
+    #[serde(default, deserialize_with = "or_default")]
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectRef {
+    pub gid: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserRef {
+    pub gid: String,
+}
+
+/// A comment left on a task
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Comment {
+    /// `None` when the user who wrote it was removed from the workspace
+    pub author: Option<String>,
+    pub created_at: Timestamp,
+    pub text: String,
+}
+
+/// A file attached to a task
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attachment {
+    #[serde(default, deserialize_with = "or_default")]
+    pub name: String,
+    /// `asana` for uploaded files, otherwise the service the file is kept in, e.g. `gdrive`
+    #[serde(default)]
+    pub host: Option<String>,
+    /// Link to the file in asana, which doesn't expire like the download link does
+    #[serde(default)]
+    pub permanent_url: Option<String>,
+    /// Link to the file in the service it's kept in
+    #[serde(default)]
+    pub view_url: Option<String>,
+}
+
+impl Attachment {
+    /// Where to open the attachment, preferring the link that doesn't change between fetches
+    pub fn url(&self) -> Option<&str> {
+        self.permanent_url.as_deref().or(self.view_url.as_deref())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Story {
+    #[serde(rename = "type", default, deserialize_with = "or_default")]
+    kind: String,
+    #[serde(default, deserialize_with = "or_default")]
+    text: String,
+    created_at: Timestamp,
+    #[serde(default)]
+    created_by: Option<UserName>,
 }
 
 #[derive(Debug, Deserialize)]
-struct TasksResponse {
-    data: Vec<Task>,
-    next_page: Option<String>,
+struct UserName {
+    name: String,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct Workspace {
+    pub gid: String,
+    #[serde(default, deserialize_with = "or_default")]
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TaskResponse {
+    data: Task,
+}
+
+#[derive(Debug, Deserialize)]
+struct MeResponse {
+    data: UserRef,
+}
+
+#[derive(Debug, Deserialize)]
+struct UserTaskListResponse {
+    data: UserTaskList,
+}
+
+#[derive(Debug, Deserialize)]
+struct UserTaskList {
+    gid: String,
+}
+
+/// A task found by a search, with the creation time the next search continues from
+#[derive(Debug, Deserialize)]
+struct SearchHit {
+    created_at: Timestamp,
+    #[serde(flatten)]
+    task: Task,
+}
+
+/// A page of items, each read on its own so one asana sends in a new shape doesn't lose the rest
+#[derive(Debug, Deserialize)]
+struct PageResponse {
+    #[serde(default, deserialize_with = "or_default")]
+    data: Vec<serde_json::Value>,
+    #[serde(default)]
+    next_page: Option<NextPage>,
+}
+
+/// The items that could be read, warning about and skipping the rest
+fn readable<T: DeserializeOwned>(items: Vec<serde_json::Value>) -> Vec<T> {
+    items
+        .into_iter()
+        .filter_map(|item| {
+            let gid = item["gid"].as_str().unwrap_or("without a gid").to_string();
+            serde_json::from_value(item)
+                .inspect_err(|e| warn!("Skipping {gid} from asana, it couldn't be read: {e}"))
+                .ok()
+        })
+        .collect()
+}
+
+/// Deserialize a field that asana may send as null as its default
+fn or_default<'de, D: Deserializer<'de>, T: Default + Deserialize<'de>>(
+    d: D,
+) -> Result<T, D::Error> {
+    Ok(Option::<T>::deserialize(d)?.unwrap_or_default())
+}
+
+#[derive(Debug, Deserialize)]
+struct NextPage {
+    offset: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SectionsResponse {
+    data: Vec<Section>,
+}
+
+#[derive(Debug, Clone)]
 pub struct TaskResult {
     pub incomplete: Vec<Task>,
     pub complete: Vec<Task>,
 }
 
+impl TaskResult {
+    /// When the incomplete task `gid` was last edited, as it was fetched
+    pub fn modified_at(&self, gid: &str) -> Option<Timestamp> {
+        self.incomplete
+            .iter()
+            .find(|t| t.gid == gid)
+            .and_then(|t| t.modified_at)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct AddTaskRequest<'a> {
+    data: AddTaskData<'a>,
+}
+
+#[derive(Debug, Serialize)]
+struct AddTaskData<'a> {
+    task: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct AddCommentRequest<'a> {
+    data: AddCommentData<'a>,
+}
+
+#[derive(Debug, Serialize)]
+struct AddCommentData<'a> {
+    text: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct SetParentRequest<'a> {
+    data: SetParentData<'a>,
+}
+
+#[derive(Debug, Serialize)]
+struct SetParentData<'a> {
+    parent: Option<&'a str>,
+}
+
 #[derive(Debug, Serialize)]
 struct UpdateTaskRequest {
     data: UpdateTaskData,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Default, Serialize)]
 struct UpdateTaskData {
-    completed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    completed: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    due_on: Option<civil::Date>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    due_at: Option<Timestamp>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    liked: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    notes: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct BatchRequest {
+    data: BatchData,
+}
+
+#[derive(Debug, Serialize)]
+struct BatchData {
+    actions: Vec<BatchAction>,
+}
+
+#[derive(Debug, Serialize)]
+struct BatchAction {
+    relative_path: String,
+    method: &'static str,
+    data: UpdateTaskData,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchResponse {
+    data: Vec<BatchResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchResult {
+    status_code: u16,
 }
 
 pub fn asana_due_to_string(atask: &Task) -> Result<String> {
-    match (atask.due_on, atask.due_at) {
-        (None, None) => bail!("Somehow got to gtask with no due date"),
-        (None, Some(due_at)) => Ok(timestamp_to_local_date(due_at)),
-        (Some(due_on), None) => Ok(format!("{}T00:00:00Z", due_on)),
-        (Some(_due_on), Some(due_at)) => Ok(timestamp_to_local_date(due_at)),
-    }
-}
-
-fn timestamp_to_local_date(ts: jiff::Timestamp) -> String {
-    format!(
-        "{}T00:00:00Z",
-        ts.to_zoned(jiff::tz::TimeZone::UTC)
-            .in_tz("America/Chicago")
-            .unwrap()
-            .date()
-    )
+    match atask.due_date() {
+        Some(due) => Ok(format!("{due}T00:00:00Z")),
+        None => bail!("Somehow got to gtask with no due date"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{body_json, method, path, query_param, query_param_is_missing},
+    };
+
+    use super::*;
+
+    const TASKS_PATH: &str = "/user_task_lists/1208834419230000/tasks";
+
+    fn client(server: &MockServer) -> AsanaClient {
+        let source = TaskSource::UserTaskList("1208834419230000".to_string());
+        AsanaClient::new("token", source, &server.uri()).unwrap()
+    }
+
+    fn fixture(body: &str) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_raw(body, "application/json")
+    }
+
+    #[tokio::test]
+    async fn get_tasks_follows_pagination() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(TASKS_PATH))
+            .and(query_param_is_missing("offset"))
+            .respond_with(fixture(include_str!(
+                "../tests/fixtures/asana/tasks_page1.json"
+            )))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(TASKS_PATH))
+            .and(query_param(
+                "offset",
+                "eyJ0eXAiOiJKV1QiLCJhbGciOiJIUzI1NiJ9",
+            ))
+            .respond_with(fixture(include_str!(
+                "../tests/fixtures/asana/tasks_page2.json"
+            )))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let tasks = client(&server).get_tasks().await.unwrap();
+
+        // the task without a due date is dropped
+        let incomplete: Vec<_> = tasks.incomplete.iter().map(|t| t.name.as_str()).collect();
+        let complete: Vec<_> = tasks.complete.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(incomplete, ["Renew passport", "Call the dentist"]);
+        assert_eq!(complete, ["Pay rent"]);
+        assert_eq!(
+            tasks.incomplete[0].notes,
+            "Photos are in the drawer (https://example.com/drawer)"
+        );
+    }
+
+    #[tokio::test]
+    async fn tasks_asana_sends_in_a_new_shape_are_skipped() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(TASKS_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "data": [
+                    {"gid": "1", "name": "Pay rent", "notes": null, "due_on": "2026-10-20",
+                     "due_at": null, "completed_at": null, "liked": null, "tags": null,
+                     "memberships": [{"project": null, "section": null}]},
+                    {"gid": "2", "name": "Renew passport", "notes": "",
+                     "due_on": {"date": "2026-10-21"}, "due_at": null, "completed_at": null},
+                    {"gid": "3", "name": null, "notes": "", "due_on": "2026-10-22",
+                     "due_at": null, "completed_at": null, "tags": [{"name": null}]},
+                ],
+                "next_page": null,
+            })))
+            .mount(&server)
+            .await;
+
+        let tasks = client(&server).get_tasks().await.unwrap();
+
+        let gids: Vec<_> = tasks.incomplete.iter().map(|t| t.gid.as_str()).collect();
+        assert_eq!(gids, ["1", "3"]);
+        assert_eq!(tasks.incomplete[0].notes, "");
+        assert_eq!(tasks.incomplete[1].name, "");
+    }
+
+    #[tokio::test]
+    async fn rate_limited_request_is_an_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(TASKS_PATH))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "30"))
+            .mount(&server)
+            .await;
+
+        let err = client(&server).get_tasks().await.unwrap_err();
+        assert!(err.to_string().contains("429"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn malformed_response_is_an_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(TASKS_PATH))
+            .respond_with(fixture(r#"{"data": [{"gid": "1208834419234561", "name": "#))
+            .mount(&server)
+            .await;
+
+        assert!(client(&server).get_tasks().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn task_exists_treats_not_found_as_deleted() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/tasks/1"))
+            .respond_with(fixture(r#"{"data": {"gid": "1"}}"#))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/tasks/2"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/tasks/3"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+
+        let asana = client(&server);
+        assert!(asana.task_exists("1").await.unwrap());
+        assert!(!asana.task_exists("2").await.unwrap());
+        assert!(asana.task_exists("3").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn me_is_looked_up_once() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/users/me"))
+            .respond_with(fixture(r#"{"data": {"gid": "1208834419230001"}}"#))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let asana = client(&server);
+        assert_eq!(asana.me().await.unwrap(), "1208834419230001");
+        assert_eq!(asana.me().await.unwrap(), "1208834419230001");
+    }
+
+    #[tokio::test]
+    async fn get_comments_skips_other_stories() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/tasks/1/stories"))
+            .respond_with(fixture(
+                r#"{"data": [
+                    {"type": "system", "text": "changed the due date", "created_at": "2026-10-14T09:00:00Z", "created_by": {"name": "Alice"}},
+                    {"type": "comment", "text": "Landlord wants cash", "created_at": "2026-10-15T09:00:00Z", "created_by": {"name": "Alice"}},
+                    {"type": "comment", "text": "Paid half", "created_at": "2026-10-16T09:00:00Z", "created_by": null}
+                ], "next_page": null}"#,
+            ))
+            .mount(&server)
+            .await;
+
+        let comments = client(&server).get_comments("1").await.unwrap();
+        assert_eq!(comments.len(), 2);
+        assert_eq!(comments[0].author.as_deref(), Some("Alice"));
+        assert_eq!(comments[0].text, "Landlord wants cash");
+        assert_eq!(comments[1].author, None);
+    }
+
+    #[tokio::test]
+    async fn my_task_list_is_looked_up_per_workspace() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/users/me/user_task_list"))
+            .and(query_param("workspace", "1208834419230100"))
+            .respond_with(fixture(r#"{"data": {"gid": "1208834419230000"}}"#))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let list = client(&server)
+            .my_task_list("1208834419230100")
+            .await
+            .unwrap();
+        assert_eq!(list, "1208834419230000");
+    }
+
+    #[tokio::test]
+    async fn my_task_list_is_found_in_the_only_workspace() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/workspaces"))
+            .respond_with(fixture(
+                r#"{"data": [{"gid": "1208834419230100", "name": "Home"}], "next_page": null}"#,
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/users/me/user_task_list"))
+            .and(query_param("workspace", "1208834419230100"))
+            .respond_with(fixture(r#"{"data": {"gid": "1208834419230000"}}"#))
+            .mount(&server)
+            .await;
+
+        let list = client(&server).find_my_task_list(None).await.unwrap();
+        assert_eq!(list, "1208834419230000");
+    }
+
+    #[tokio::test]
+    async fn several_workspaces_need_one_picked() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/workspaces"))
+            .respond_with(fixture(
+                r#"{"data": [{"gid": "1208834419230100", "name": "Home"}, {"gid": "1208834419230200", "name": "Work"}], "next_page": null}"#,
+            ))
+            .mount(&server)
+            .await;
+
+        let err = client(&server).find_my_task_list(None).await.unwrap_err();
+        assert!(err.to_string().contains("Work (1208834419230200)"));
+    }
+
+    #[tokio::test]
+    async fn search_repeats_for_older_tasks_while_results_are_full() {
+        let server = MockServer::start().await;
+        let search = "/workspaces/1208834419230100/tasks/search";
+        let hit = |gid: usize, completed_at: Option<&str>| {
+            serde_json::json!({
+                "gid": gid.to_string(),
+                "name": format!("Task {gid}"),
+                "notes": "",
+                "created_at": "2026-10-01T12:00:00Z",
+                "due_on": "2026-10-20",
+                "due_at": null,
+                "completed_at": completed_at,
+            })
+        };
+        let full_page: Vec<_> = (0..SEARCH_LIMIT).map(|gid| hit(gid, None)).collect();
+        Mock::given(method("GET"))
+            .and(path(search))
+            .and(query_param("assignee.any", "me"))
+            .and(query_param("completed", "false"))
+            .and(query_param_is_missing("created_at.before"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({"data": full_page})),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(search))
+            .and(query_param("completed", "false"))
+            .and(query_param("created_at.before", "2026-10-01T12:00:00Z"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"data": [hit(500, None)]})),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(search))
+            .and(query_param_is_missing("completed"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({"data": [hit(600, Some("2026-10-15T12:00:00Z"))]}),
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let source = TaskSource::Search {
+            workspace_gid: "1208834419230100".to_string(),
+            query: SearchQuery {
+                assignees: vec!["me".to_string()],
+                ..Default::default()
+            },
+        };
+        let client = AsanaClient::new("token", source, &server.uri()).unwrap();
+        let tasks = client.get_tasks().await.unwrap();
+        assert_eq!(tasks.incomplete.len(), SEARCH_LIMIT + 1);
+        assert_eq!(tasks.complete.len(), 1);
+        assert_eq!(tasks.complete[0].gid, "600");
+        assert!(client.get_sections().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn complete_task_sends_completed_flag() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/tasks/1208834419234561"))
+            .and(body_json(serde_json::json!({"data": {"completed": true}})))
+            .respond_with(fixture(r#"{"data": {"gid": "1208834419234561"}}"#))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        client(&server)
+            .complete_task("1208834419234561")
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn set_due_sends_the_day() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/tasks/100"))
+            .and(body_json(
+                serde_json::json!({"data": {"due_on": "2026-10-22"}}),
+            ))
+            .respond_with(fixture(r#"{"data": {"gid": "100"}}"#))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let task = crate::mock::asana_task("100", "Write report", civil::date(2026, 10, 20));
+        client(&server)
+            .set_due(&task.moved_to(civil::date(2026, 10, 22)).unwrap())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn set_liked_sends_the_flag() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/tasks/100"))
+            .and(body_json(serde_json::json!({"data": {"liked": true}})))
+            .respond_with(fixture(r#"{"data": {"gid": "100"}}"#))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        client(&server).set_liked("100", true).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn complete_tasks_sends_one_batch() {
+        let server = MockServer::start().await;
+        let completed = |gid: &str| {
+            serde_json::json!({
+                "relative_path": format!("/tasks/{gid}"),
+                "method": "put",
+                "data": {"completed": true},
+            })
+        };
+        Mock::given(method("POST"))
+            .and(path("/batch"))
+            .and(body_json(serde_json::json!({"data": {"actions": [
+                completed("100"),
+                completed("101"),
+                completed("102"),
+            ]}})))
+            .respond_with(fixture(
+                r#"{"data": [
+                    {"status_code": 200, "headers": {}, "body": {"data": {"gid": "100"}}},
+                    {"status_code": 404, "headers": {}, "body": {"errors": [{"message": "task: Unknown object: 101"}]}},
+                    {"status_code": 200, "headers": {}, "body": {"data": {"gid": "102"}}}
+                ]}"#,
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let gids = ["100", "101", "102"].map(String::from);
+        let results = client(&server).complete_tasks(&gids).await;
+        assert!(results[0].is_ok());
+        assert!(matches!(
+            BridgeError::of(results[1].as_ref().unwrap_err()),
+            Some(BridgeError::NotFound { .. })
+        ));
+        assert!(results[2].is_ok());
+    }
+
+    #[tokio::test]
+    async fn a_refused_batch_fails_every_completion_in_it() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/batch"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "30"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let gids = ["100", "101"].map(String::from);
+        let results = client(&server).complete_tasks(&gids).await;
+        assert_eq!(results.len(), 2);
+        for result in &results {
+            assert!(matches!(
+                BridgeError::of(result.as_ref().unwrap_err()),
+                Some(BridgeError::RateLimited { .. })
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn get_attachments_asks_for_the_task() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/attachments"))
+            .and(query_param("parent", "100"))
+            .respond_with(fixture(
+                r#"{"data": [
+                    {"gid": "300", "name": "lease.pdf", "host": "asana", "permanent_url": "https://app.asana.com/app/asana/-/get_asset?asset_id=300", "view_url": "https://asana-user-private.s3.amazonaws.com/lease.pdf"},
+                    {"gid": "301", "name": "Budget", "host": "gdrive", "permanent_url": null, "view_url": "https://docs.google.com/spreadsheets/d/1"}
+                ], "next_page": null}"#,
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let attachments = client(&server).get_attachments("100").await.unwrap();
+        assert_eq!(
+            attachments[0].url(),
+            Some("https://app.asana.com/app/asana/-/get_asset?asset_id=300")
+        );
+        assert_eq!(
+            attachments[1].url(),
+            Some("https://docs.google.com/spreadsheets/d/1")
+        );
+    }
+
+    #[tokio::test]
+    async fn add_comment_posts_a_story() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/tasks/100/stories"))
+            .and(body_json(
+                serde_json::json!({"data": {"text": "Paid half in cash"}}),
+            ))
+            .respond_with(fixture(r#"{"data": {"gid": "200"}}"#))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        client(&server)
+            .add_comment("100", "Paid half in cash")
+            .await
+            .unwrap();
+    }
 }
@@ -0,0 +1,82 @@
+use crate::{
+    asana::{self, AsanaApi},
+    fetched::Fetched,
+    sync::FailedActions,
+    watchdog,
+};
+
+/// Give every incomplete task its attachments, reusing the ones `fetched` by earlier cycles while
+/// they're fresh, see [`Fetched::attach`]
+pub async fn attach(
+    asana_mgr: &impl AsanaApi,
+    tasks: &mut asana::TaskResult,
+    fetched: &Fetched<asana::Attachment>,
+    failures: &mut FailedActions,
+) {
+    watchdog::phase("fetching asana attachments");
+    fetched
+        .attach(
+            tasks,
+            "attachments",
+            failures,
+            async |gid| asana_mgr.get_attachments(gid).await,
+            |task, attachments| task.attachments = attachments,
+        )
+        .await;
+}
+
+/// The attachments as a block of google notes, empty when there are none
+pub fn render(attachments: &[asana::Attachment]) -> String {
+    if attachments.is_empty() {
+        return String::new();
+    }
+
+    let mut out = String::from("Attachments:");
+    for attachment in attachments {
+        match attachment.url() {
+            Some(url) => out.push_str(&format!("\n{} ({url})", attachment.name)),
+            None => out.push_str(&format!("\n{}", attachment.name)),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::civil::date;
+
+    use super::*;
+    use crate::{
+        mock::{MockAsana, asana_task},
+        sync,
+    };
+
+    #[tokio::test]
+    async fn attachments_are_listed_in_the_notes() {
+        let mut task = asana_task("100", "Sign lease", date(2026, 10, 22));
+        task.notes = "Both copies".to_string();
+        let asana = MockAsana::new(vec![task]);
+        asana.attachments.borrow_mut().insert(
+            "100".to_string(),
+            vec![asana::Attachment {
+                name: "lease.pdf".to_string(),
+                host: Some("asana".to_string()),
+                permanent_url: Some(
+                    "https://app.asana.com/app/asana/-/get_asset?asset_id=300".to_string(),
+                ),
+                view_url: None,
+            }],
+        );
+        let mut tasks = asana.get_tasks().await.unwrap();
+
+        let mut failures = FailedActions::default();
+        attach(&asana, &mut tasks, &Fetched::default(), &mut failures).await;
+        assert!(failures.failed.is_empty());
+
+        let fields = sync::google_fields(&tasks.incomplete[0], &Default::default());
+        assert_eq!(
+            fields.notes,
+            "Both copies\n\nAttachments:\nlease.pdf (https://app.asana.com/app/asana/-/get_asset?asset_id=300)"
+        );
+    }
+}
@@ -0,0 +1,323 @@
+use std::{collections::HashSet, fmt, io::Write};
+
+use anyhow::{Result, bail};
+use jiff::civil::Date;
+use log::info;
+
+use crate::{
+    asana::{self, AsanaApi},
+    correlate::Correlator,
+    google::{self, GTaskResult, GoogleTasksApi},
+    state::State,
+    sync,
+};
+
+/// Score below which `bootstrap` doesn't propose a link unless told otherwise
+pub const DEFAULT_THRESHOLD: f64 = 0.8;
+
+/// An unlinked google task that looks like the copy of an unlinked asana task
+#[derive(Debug, Clone, PartialEq)]
+pub struct Proposal {
+    pub gid: String,
+    pub name: String,
+    pub due: Option<Date>,
+    pub google_id: String,
+    pub title: String,
+    pub google_due: Option<Date>,
+    /// How alike the two are, 1 for the same title and due date
+    pub score: f64,
+}
+
+impl fmt::Display for Proposal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let due = |due: Option<Date>| due.map_or("no due date".to_string(), |d| d.to_string());
+        write!(
+            f,
+            "asana task {} \"{}\" ({}) and google task {} \"{}\" ({}), {:.0}% alike",
+            self.gid,
+            self.name,
+            due(self.due),
+            self.google_id,
+            self.title,
+            due(self.google_due),
+            self.score * 100.0
+        )
+    }
+}
+
+/// Lowercased words of a title, without punctuation
+fn normalize(title: &str) -> String {
+    title
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// How alike two titles are, 1 when they only differ in case and punctuation, going down with the
+/// edits it takes to turn one into the other
+fn title_similarity(a: &str, b: &str) -> f64 {
+    let (a, b): (Vec<char>, Vec<char>) = (
+        normalize(a).chars().collect(),
+        normalize(b).chars().collect(),
+    );
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    1.0 - previous[b.len()] as f64 / longest as f64
+}
+
+/// The title similarity, less a tenth for each day the due dates are apart, or a fifth when only
+/// one of them has one
+fn score(atask: &asana::Task, gtask: &google::Task, title: &str) -> f64 {
+    let penalty = match (atask.due_date(), google::due_date(gtask)) {
+        (Some(a), Some(g)) => (a - g).get_days().unsigned_abs() as f64 * 0.1,
+        (None, None) => 0.0,
+        _ => 0.2,
+    };
+    (title_similarity(&atask.name, title) - penalty).max(0.0)
+}
+
+/// Pair up incomplete tasks that aren't linked yet, best matches first, each task at most once
+pub fn propose(
+    asana_tasks: &asana::TaskResult,
+    google_tasks: &GTaskResult,
+    state: &State,
+    correlator: &dyn Correlator,
+    threshold: f64,
+) -> Vec<Proposal> {
+    let unlinked_asana: Vec<&asana::Task> = asana_tasks
+        .incomplete
+        .iter()
+        .filter(|t| !state.links.contains_key(&t.gid))
+        .collect();
+    let unlinked_google: Vec<(&str, &google::Task)> = google_tasks
+        .incomplete
+        .iter()
+        .filter(|t| sync::linked_asana_gid(t, state, correlator).is_none())
+        .filter_map(|t| Some((t.id.as_deref()?, t)))
+        .collect();
+
+    let mut candidates = Vec::new();
+    for (google_id, gtask) in unlinked_google {
+        let title = correlator.untagged(gtask).title.unwrap_or_default();
+        for atask in &unlinked_asana {
+            let score = score(atask, gtask, &title);
+            if score >= threshold {
+                candidates.push(Proposal {
+                    gid: atask.gid.clone(),
+                    name: atask.name.clone(),
+                    due: atask.due_date(),
+                    google_id: google_id.to_string(),
+                    title: title.clone(),
+                    google_due: google::due_date(gtask),
+                    score,
+                });
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    let (mut gids, mut google_ids) = (HashSet::new(), HashSet::new());
+    candidates.retain(|p| {
+        if gids.contains(&p.gid) || google_ids.contains(&p.google_id) {
+            return false;
+        }
+        gids.insert(p.gid.clone());
+        google_ids.insert(p.google_id.clone());
+        true
+    });
+    candidates
+}
+
+/// Link the asana task to the google task it was paired with, tagging the google task
+async fn accept(
+    gtasks_mgr: &impl GoogleTasksApi,
+    asana_tasks: &asana::TaskResult,
+    google_tasks: &GTaskResult,
+    state: &mut State,
+    correlator: &dyn Correlator,
+    proposal: &Proposal,
+) -> Result<()> {
+    let atask = asana_tasks
+        .incomplete
+        .iter()
+        .find(|t| t.gid == proposal.gid)
+        .expect("proposed tasks come from the fetched ones");
+    let gtask = google_tasks
+        .incomplete
+        .iter()
+        .find(|t| t.id.as_deref() == Some(proposal.google_id.as_str()))
+        .expect("proposed tasks come from the fetched ones");
+
+    let (title, notes) = correlator.tag(
+        gtask.title.as_deref().unwrap_or_default(),
+        gtask.notes.as_deref().unwrap_or_default(),
+        Some(&atask.gid),
+    );
+    gtasks_mgr
+        .set_text(&proposal.google_id, &title, &notes)
+        .await?;
+    // the bridge didn't write the google task, so the next cycle compares its content afresh
+    state.link(atask, gtasks_mgr.list_id(), &proposal.google_id, None);
+    info!("Linked {proposal}");
+    Ok(())
+}
+
+/// What to do with one proposal, asked on the terminal
+enum Answer {
+    Link,
+    Skip,
+    Quit,
+}
+
+fn ask(proposal: &Proposal) -> Result<Answer> {
+    print!("Link {proposal}? [y/N/q] ");
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer)? == 0 {
+        return Ok(Answer::Quit);
+    }
+    Ok(match answer.trim().to_ascii_lowercase().as_str() {
+        "y" => Answer::Link,
+        "q" => Answer::Quit,
+        _ => Answer::Skip,
+    })
+}
+
+/// Link tasks that were already in both places before the bridge was, so it doesn't copy them
+/// again. Each pair scoring at least `threshold` is asked about, or linked right away with `auto`.
+/// Returns how many were linked, the caller saves the state.
+pub async fn bootstrap(
+    asana_mgr: &impl AsanaApi,
+    gtasks_mgr: &impl GoogleTasksApi,
+    state: &mut State,
+    correlator: &dyn Correlator,
+    auto: bool,
+    threshold: f64,
+    dry_run: bool,
+) -> Result<usize> {
+    if !(0.0..=1.0).contains(&threshold) {
+        bail!("the threshold is a score between 0 and 1, not {threshold}");
+    }
+
+    let asana_tasks = asana_mgr.get_tasks().await?;
+    let google_tasks = gtasks_mgr.get_tasks().await?;
+    let proposals = propose(&asana_tasks, &google_tasks, state, correlator, threshold);
+    if proposals.is_empty() {
+        println!("No unlinked tasks look alike");
+        return Ok(0);
+    }
+
+    let mut linked = 0;
+    for proposal in &proposals {
+        if dry_run {
+            println!("Would link {proposal}");
+            continue;
+        }
+        if !auto {
+            match ask(proposal)? {
+                Answer::Link => {}
+                Answer::Skip => continue,
+                Answer::Quit => break,
+            }
+        }
+        accept(
+            gtasks_mgr,
+            &asana_tasks,
+            &google_tasks,
+            state,
+            correlator,
+            proposal,
+        )
+        .await?;
+        linked += 1;
+    }
+
+    if !dry_run {
+        println!("Linked {linked} of {} proposed pairs", proposals.len());
+    }
+    Ok(linked)
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::civil::date;
+
+    use super::*;
+    use crate::{
+        correlate::NotesMarker,
+        mock::{MockAsana, MockGoogle, asana_task},
+    };
+
+    #[test]
+    fn titles_differing_in_case_and_punctuation_are_alike() {
+        assert_eq!(title_similarity("Pay rent!", "pay  RENT"), 1.0);
+        assert!(title_similarity("Pay rent", "Pay the rent") > 0.6);
+        assert!(title_similarity("Pay rent", "Renew passport") < 0.5);
+    }
+
+    #[tokio::test]
+    async fn alike_tasks_are_linked_without_copying_them() {
+        let rent = asana_task("100", "Pay rent", date(2026, 10, 20));
+        let passport = asana_task("101", "Renew passport", date(2026, 10, 22));
+        let dentist = asana_task("102", "Call the dentist", date(2026, 10, 23));
+        let asana = MockAsana::new(vec![rent.clone(), passport.clone(), dentist.clone()]);
+        let google = MockGoogle::new();
+        let mut state = State::default();
+        // made by hand before the bridge ran
+        let rent_copy = google
+            .new_task_from_asana(&rent, "pay rent.", "landlord", None)
+            .await
+            .unwrap();
+        let passport_copy = google
+            .new_task_from_asana(&passport, "Renew passport", "", None)
+            .await
+            .unwrap();
+        google.update(&passport_copy, |t| {
+            t.due = Some("2026-11-22T00:00:00.000Z".to_string())
+        });
+        // already linked
+        let dentist_copy = google
+            .new_task_from_asana(&dentist, "Call the dentist", "", None)
+            .await
+            .unwrap();
+        state.link(&dentist, google.list_id(), &dentist_copy, None);
+
+        let linked = bootstrap(&asana, &google, &mut state, &NotesMarker, true, 0.8, false)
+            .await
+            .unwrap();
+
+        // the passport is due a month apart in google
+        assert_eq!(linked, 1);
+        assert_eq!(state.links["100"].google_id, rent_copy);
+        assert!(!state.links.contains_key("101"));
+        let tasks = google.tasks();
+        let rent_copy = tasks
+            .iter()
+            .find(|t| t.id.as_deref() == Some(&rent_copy))
+            .unwrap();
+        assert_eq!(
+            google::get_asana_task_gid(rent_copy).as_deref(),
+            Some("100")
+        );
+        assert_eq!(
+            google::notes_body(rent_copy.notes.as_deref().unwrap()),
+            "landlord"
+        );
+        assert_eq!(tasks.len(), 3);
+    }
+}
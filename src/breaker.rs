@@ -0,0 +1,228 @@
+use std::{collections::BTreeMap, fmt, future::Future, sync::Mutex, time::Duration};
+
+use anyhow::Result;
+use jiff::Timestamp;
+use log::{debug, error, info, warn};
+
+use crate::{
+    config::BridgeConfig,
+    integrity, logging,
+    notify::{self, NotifyEvent},
+    sync::FailedActions,
+};
+
+/// Longest wait between probes of a backend that keeps failing
+const MAX_PROBE_WAIT: Duration = Duration::from_secs(3600);
+
+/// Every breaker's gauges so far, keyed by user (empty outside multi-user mode) and backend
+static GAUGES: Mutex<BTreeMap<(String, String), BreakerGauges>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct BreakerGauges {
+    pub open: bool,
+    /// Cycles in a row syncing the backend failed
+    pub failures: u32,
+}
+
+impl BreakerGauges {
+    pub(crate) fn gauges(&self) -> [(&'static str, u32); 2] {
+        [
+            ("circuit_open", u32::from(self.open)),
+            ("backend_failures", self.failures),
+        ]
+    }
+}
+
+/// Syncing into a backend failed, and the breaker counted it. The loop goes on, trying the backend
+/// again until the breaker opens.
+#[derive(Debug)]
+pub struct BackendFailing {
+    pub backend: String,
+    pub failures: u32,
+}
+
+impl fmt::Display for BackendFailing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "syncing the {} backend failed, {} cycles in a row",
+            self.backend, self.failures
+        )
+    }
+}
+
+/// Stops trying a backend that fails cycle after cycle, like when its login broke, and only probes
+/// it now and then until it works again
+pub struct Breaker {
+    backend: String,
+    /// Failed cycles in a row that open the breaker, never opened when 0
+    after: u32,
+    /// Wait before the first probe, doubling with every failed one
+    probe: Duration,
+    metrics_path: Option<String>,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Default)]
+struct BreakerState {
+    failures: u32,
+    /// When the next probe is due and how long the one after waits, while open
+    open: Option<(Timestamp, Duration)>,
+}
+
+impl Breaker {
+    pub fn new(backend: &str, config: &BridgeConfig) -> Self {
+        Self {
+            backend: backend.to_string(),
+            after: config.breaker_after,
+            probe: Duration::from_secs(config.breaker_probe_secs),
+            metrics_path: config.metrics.clone(),
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    /// Run `sync`, unless the breaker is open and the next probe isn't due yet. Failed actions are
+    /// passed on as they are, other errors are counted and come back as [`BackendFailing`].
+    pub async fn call(&self, sync: impl Future<Output = Result<()>>) -> Result<()> {
+        if self.after == 0 {
+            return sync.await;
+        }
+        if let Some((probe_at, _)) = self.state.lock().unwrap().open
+            && Timestamp::now() < probe_at
+        {
+            debug!("Skipping the {} backend until {probe_at}", self.backend);
+            return Ok(());
+        }
+
+        match sync.await {
+            Err(e) if !e.is::<FailedActions>() => {
+                let failures = self.failed(&e);
+                Err(e.context(BackendFailing {
+                    backend: self.backend.clone(),
+                    failures,
+                }))
+            }
+            result => {
+                self.succeeded();
+                result
+            }
+        }
+    }
+
+    fn failed(&self, e: &anyhow::Error) -> u32 {
+        let backend = &self.backend;
+        let mut state = self.state.lock().unwrap();
+        state.failures += 1;
+        let failures = state.failures;
+        match &mut state.open {
+            Some((probe_at, wait)) => {
+                *probe_at = Timestamp::now() + *wait;
+                *wait = (*wait * 2).min(MAX_PROBE_WAIT);
+                debug!("Probing the {backend} backend failed again: {e:#}");
+            }
+            None if failures >= self.after => {
+                state.open = Some((
+                    Timestamp::now() + self.probe,
+                    (self.probe * 2).min(MAX_PROBE_WAIT),
+                ));
+                let message = format!(
+                    "The {backend} backend failed {failures} cycles in a row, skipping it and probing now and then: {e:#}"
+                );
+                error!("{message}");
+                notify::push(NotifyEvent::Errors, message);
+            }
+            None => {}
+        }
+        drop(state);
+        self.publish(failures);
+        failures
+    }
+
+    fn succeeded(&self) {
+        let mut state = self.state.lock().unwrap();
+        let was_open = state.open.take().is_some();
+        *state = BreakerState::default();
+        drop(state);
+
+        if was_open {
+            let message = format!("The {} backend works again, syncing it", self.backend);
+            info!("{message}");
+            notify::push(NotifyEvent::Errors, message);
+        }
+        self.publish(0);
+    }
+
+    /// Update the gauges, and the metrics textfile if they changed
+    fn publish(&self, failures: u32) {
+        let user = logging::USER.try_with(String::clone).unwrap_or_default();
+        let gauges = BreakerGauges {
+            open: self.state.lock().unwrap().open.is_some(),
+            failures,
+        };
+        let old = GAUGES
+            .lock()
+            .unwrap()
+            .insert((user, self.backend.clone()), gauges);
+        if old.is_none_or(|old| old.open != gauges.open || old.failures != gauges.failures)
+            && let Some(path) = &self.metrics_path
+            && let Err(e) = integrity::write_metrics(path)
+        {
+            warn!("Failed to write the metrics: {e:#}");
+        }
+    }
+}
+
+/// Every breaker's gauges, keyed by user and backend
+pub(crate) fn gauges() -> BTreeMap<(String, String), BreakerGauges> {
+    GAUGES.lock().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker(after: u32) -> Breaker {
+        let config = BridgeConfig {
+            breaker_after: after,
+            breaker_probe_secs: 3600,
+            ..BridgeConfig::default()
+        };
+        Breaker::new("google", &config)
+    }
+
+    #[tokio::test]
+    async fn an_open_breaker_skips_the_backend() {
+        let breaker = breaker(2);
+        for failures in 1..=2 {
+            let e = breaker
+                .call(async { Err(anyhow::anyhow!("invalid_grant")) })
+                .await
+                .unwrap_err();
+            assert_eq!(
+                e.downcast_ref::<BackendFailing>().unwrap().failures,
+                failures
+            );
+        }
+
+        let mut tried = false;
+        breaker
+            .call(async {
+                tried = true;
+                Ok(())
+            })
+            .await
+            .unwrap();
+        assert!(!tried);
+    }
+
+    #[tokio::test]
+    async fn failed_actions_dont_count() {
+        let breaker = breaker(1);
+        let e = breaker
+            .call(async { Err(FailedActions::default().into()) })
+            .await
+            .unwrap_err();
+        assert!(e.is::<FailedActions>());
+        assert!(breaker.state.lock().unwrap().open.is_none());
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::HashSet;
+
+use anyhow::{Context, Result};
+use google_calendar3::{CalendarHub, api::Event};
+use jiff::{SignedDuration, Timestamp};
+use log::info;
+
+use crate::{
+    asana,
+    config::{CalendarConfig, GoogleConfig},
+    google::{self, GoogleTasksApi},
+    state::{EventLink, State},
+    sync,
+};
+
+/// Everything the bridge does with a calendar, so syncing events can run against something other
+/// than the live API
+pub trait CalendarApi {
+    /// Create an event starting at `start`, returning its id
+    async fn create_event(&self, title: &str, notes: &str, start: Timestamp) -> Result<String>;
+
+    async fn update_event(
+        &self,
+        id: &str,
+        title: &str,
+        notes: &str,
+        start: Timestamp,
+    ) -> Result<()>;
+
+    async fn delete_event(&self, id: &str) -> Result<()>;
+}
+
+#[derive(Clone)]
+pub struct GoogleCalendarMgr {
+    hub: CalendarHub<
+        google_calendar3::hyper_rustls::HttpsConnector<
+            google_calendar3::hyper_util::client::legacy::connect::HttpConnector,
+        >,
+    >,
+    calendar_id: String,
+    duration: SignedDuration,
+}
+
+impl GoogleCalendarMgr {
+    pub async fn new(google: &GoogleConfig, calendar: &CalendarConfig) -> Result<Self> {
+        let client = google_calendar3::hyper_util::client::legacy::Client::builder(
+            google_calendar3::hyper_util::rt::TokioExecutor::new(),
+        )
+        .build(
+            google_calendar3::hyper_rustls::HttpsConnectorBuilder::new()
+                .with_native_roots()
+                .unwrap()
+                .https_or_http()
+                .enable_http1()
+                .build(),
+        );
+
+        Ok(Self {
+            hub: CalendarHub::new(client, google::authenticator(google).await?),
+            calendar_id: calendar.id.clone(),
+            duration: SignedDuration::from_mins(calendar.event_minutes as i64),
+        })
+    }
+
+    fn event(&self, title: &str, notes: &str, start: Timestamp) -> Result<Event> {
+        // going through json keeps the api crate's date types out of the bridge
+        serde_json::from_value(serde_json::json!({
+            "summary": title,
+            "description": notes,
+            "start": {"dateTime": start.to_string()},
+            "end": {"dateTime": (start + self.duration).to_string()},
+        }))
+        .context("failed to build calendar event")
+    }
+}
+
+impl CalendarApi for GoogleCalendarMgr {
+    async fn create_event(&self, title: &str, notes: &str, start: Timestamp) -> Result<String> {
+        let created = self
+            .hub
+            .events()
+            .insert(self.event(title, notes, start)?, &self.calendar_id)
+            .doit()
+            .await?
+            .1;
+        created.id.context("created event has no id")
+    }
+
+    async fn update_event(
+        &self,
+        id: &str,
+        title: &str,
+        notes: &str,
+        start: Timestamp,
+    ) -> Result<()> {
+        self.hub
+            .events()
+            .patch(self.event(title, notes, start)?, &self.calendar_id, id)
+            .doit()
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_event(&self, id: &str) -> Result<()> {
+        self.hub
+            .events()
+            .delete(&self.calendar_id, id)
+            .doit()
+            .await?;
+        Ok(())
+    }
+}
+
+/// Mirror incomplete asana tasks with a due time into calendar events instead of google tasks.
+/// Google copies made before the task had a time are removed, and events of tasks that were
+/// completed, removed or lost their time are deleted.
+pub async fn sync_events(
+    calendar: &impl CalendarApi,
+    asana_tasks: &asana::TaskResult,
+    gtasks_mgr: &impl GoogleTasksApi,
+    state: &mut State,
+    dry_run: bool,
+) -> Result<()> {
+    let duplicate_names = sync::duplicate_names(&asana_tasks.incomplete);
+    let mut timed_gids = HashSet::new();
+
+    for atask in &asana_tasks.incomplete {
+        let Some(due_at) = atask.due_at else {
+            continue;
+        };
+        timed_gids.insert(atask.gid.as_str());
+
+        if let Some(link) = state.links.get(&atask.gid).cloned() {
+            info!(
+                "Asana -> Google task \"{}\" has a due time now, moving it to the calendar",
+                atask.name
+            );
+            if !dry_run {
+                gtasks_mgr
+                    .for_list(&link.google_list)
+                    .del_task(&link.google_id)
+                    .await?;
+                state.unlink(&atask.gid);
+            }
+        }
+
+        let fields = sync::google_fields(atask, &duplicate_names);
+        let wanted = |event_id: String| EventLink {
+            event_id,
+            title: fields.title.clone(),
+            notes: fields.notes.clone(),
+            due_at,
+        };
+
+        let event_id = match state.events.get(&atask.gid) {
+            Some(link) if *link == wanted(link.event_id.clone()) => continue,
+            Some(link) => {
+                info!("Asana -> Calendar updating event \"{}\"", fields.title);
+                if dry_run {
+                    continue;
+                }
+                calendar
+                    .update_event(&link.event_id, &fields.title, &fields.notes, due_at)
+                    .await?;
+                link.event_id.clone()
+            }
+            None => {
+                info!("Asana -> Calendar creating event \"{}\"", fields.title);
+                if dry_run {
+                    continue;
+                }
+                calendar
+                    .create_event(&fields.title, &fields.notes, due_at)
+                    .await?
+            }
+        };
+        state.events.insert(atask.gid.clone(), wanted(event_id));
+    }
+
+    let stale: Vec<String> = state
+        .events
+        .keys()
+        .filter(|gid| !timed_gids.contains(gid.as_str()))
+        .cloned()
+        .collect();
+    for gid in stale {
+        info!("Asana -> Calendar removing event of task {gid}, it's done or no longer timed");
+        if !dry_run && let Some(link) = state.events.remove(&gid) {
+            calendar.delete_event(&link.event_id).await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::civil::date;
+
+    use super::*;
+    use crate::mock::{MockCalendar, MockGoogle, asana_task};
+
+    fn timed(gid: &str, name: &str, due_at: &str) -> asana::Task {
+        let mut task = asana_task(gid, name, date(2026, 10, 20));
+        task.due_on = None;
+        task.due_at = Some(due_at.parse().unwrap());
+        task
+    }
+
+    fn result(incomplete: Vec<asana::Task>) -> asana::TaskResult {
+        asana::TaskResult {
+            incomplete,
+            complete: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn timed_tasks_become_events_and_follow_edits() {
+        let calendar = MockCalendar::default();
+        let google = MockGoogle::new();
+        let mut state = State::default();
+
+        let mut task = timed("100", "Dentist", "2026-10-20T15:00:00Z");
+        let tasks = result(vec![
+            task.clone(),
+            asana_task("101", "Pay rent", date(2026, 10, 20)),
+        ]);
+        sync_events(&calendar, &tasks, &google, &mut state, false)
+            .await
+            .unwrap();
+
+        let events = calendar.events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].1, "Dentist");
+        assert_eq!(state.events["100"].event_id, events[0].0);
+
+        task.due_at = Some("2026-10-21T09:00:00Z".parse().unwrap());
+        sync_events(&calendar, &result(vec![task]), &google, &mut state, false)
+            .await
+            .unwrap();
+        assert_eq!(
+            calendar.events()[0].2,
+            "2026-10-21T09:00:00Z".parse::<Timestamp>().unwrap()
+        );
+
+        sync_events(&calendar, &result(Vec::new()), &google, &mut state, false)
+            .await
+            .unwrap();
+        assert!(calendar.events().is_empty());
+        assert!(state.events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn google_copy_is_replaced_once_the_task_gets_a_time() {
+        let calendar = MockCalendar::default();
+        let google = MockGoogle::new();
+        let mut state = State::default();
+
+        let task = asana_task("100", "Dentist", date(2026, 10, 20));
+        let google_id = google
+            .new_task_from_asana(&task, "Dentist", "", None)
+            .await
+            .unwrap();
+        state.link(&task, google.list_id(), &google_id, None);
+
+        let tasks = result(vec![timed("100", "Dentist", "2026-10-20T15:00:00Z")]);
+        sync_events(&calendar, &tasks, &google, &mut state, false)
+            .await
+            .unwrap();
+
+        assert!(google.tasks().is_empty());
+        assert!(state.links.is_empty());
+        assert_eq!(calendar.events().len(), 1);
+    }
+}
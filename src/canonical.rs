@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+
+use jiff::civil;
+
+use crate::{asana, google};
+
+/// A task as any provider holds it, reduced to what the bridge syncs. Each provider maps its own
+/// items to it next to their types, and the sync engine only sees the asana and google shapes it
+/// converts to, so a new backend comes down to those conversions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CanonicalTask {
+    /// Id in the provider it was read from
+    pub id: Option<String>,
+    pub title: String,
+    /// Description as plain text
+    pub body: String,
+    /// Day the task is due
+    pub due: Option<civil::Date>,
+    pub status: Status,
+    pub tags: Vec<String>,
+    pub priority: Option<Priority>,
+    /// Where to open the task, then anything else it links to
+    pub links: Vec<String>,
+    /// Ids of the same task in other providers, keyed by provider name, e.g. `asana`
+    pub external_ids: BTreeMap<String, String>,
+    /// Revision of the item it was read from, for writes that check it's still current
+    pub revision: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Status {
+    #[default]
+    Open,
+    /// Done, with when as the provider wrote it if it says
+    Done(Option<String>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Low,
+    Medium,
+    High,
+}
+
+impl CanonicalTask {
+    pub fn is_done(&self) -> bool {
+        matches!(self.status, Status::Done(_))
+    }
+}
+
+impl From<&asana::Task> for CanonicalTask {
+    fn from(task: &asana::Task) -> Self {
+        Self {
+            id: Some(task.gid.clone()),
+            title: task.name.clone(),
+            body: task.notes.clone(),
+            due: task.due_date(),
+            status: match task.completed_at {
+                Some(at) => Status::Done(Some(at.to_string())),
+                None => Status::Open,
+            },
+            tags: task.tags.iter().map(|t| t.name.clone()).collect(),
+            priority: None,
+            links: task.permalink_url.iter().cloned().collect(),
+            external_ids: BTreeMap::new(),
+            revision: None,
+        }
+    }
+}
+
+impl From<google::Task> for CanonicalTask {
+    fn from(task: google::Task) -> Self {
+        Self {
+            due: google::due_date(&task),
+            status: match task.completed {
+                Some(at) => Status::Done(Some(at)),
+                None => Status::Open,
+            },
+            links: task.web_view_link.into_iter().collect(),
+            id: task.id,
+            title: task.title.unwrap_or_default(),
+            body: task.notes.unwrap_or_default(),
+            revision: task.etag,
+            ..Default::default()
+        }
+    }
+}
+
+/// The task as google would report it, down to the format of its due date, which is how the sync
+/// engine sees every backend. Its revision stands in for the etag.
+impl From<CanonicalTask> for google::Task {
+    fn from(task: CanonicalTask) -> Self {
+        google::Task {
+            id: task.id,
+            etag: task.revision,
+            title: Some(task.title),
+            notes: Some(task.body),
+            due: task.due.map(|day| format!("{day}T00:00:00.000Z")),
+            completed: match task.status {
+                Status::Done(at) => Some(at.unwrap_or_default()),
+                Status::Open => None,
+            },
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::civil::date;
+
+    use super::*;
+    use crate::mock::asana_task;
+
+    #[test]
+    fn tasks_keep_what_is_synced_on_the_way_through() {
+        let mut task = asana_task("100", "Write report", date(2026, 10, 20));
+        task.notes = "Draft".to_string();
+        task.permalink_url = Some("https://app.asana.com/0/0/100".to_string());
+
+        let canonical = CanonicalTask::from(&task);
+        assert_eq!(canonical.links, ["https://app.asana.com/0/0/100"]);
+
+        let gtask = google::Task::from(canonical.clone());
+        assert_eq!(gtask.due.as_deref(), Some("2026-10-20T00:00:00.000Z"));
+        assert_eq!(gtask.notes.as_deref(), Some("Draft"));
+        assert!(gtask.completed.is_none());
+
+        let back = CanonicalTask::from(gtask);
+        assert_eq!(back.title, "Write report");
+        assert_eq!(back.due, canonical.due);
+        assert!(!back.is_done());
+    }
+}
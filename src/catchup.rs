@@ -0,0 +1,76 @@
+use anyhow::Result;
+use jiff::Timestamp;
+
+use crate::{
+    asana::{self, AsanaApi},
+    state::State,
+};
+
+/// Look up every linked asana task the fetch left out. Those completed since `lookback` go in
+/// with the completed tasks, however long the bridge was down, so their google copies get
+/// completed too. Returns their names.
+pub async fn completed_links(
+    asana_mgr: &impl AsanaApi,
+    asana_tasks: &mut asana::TaskResult,
+    state: &State,
+    lookback: Timestamp,
+) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    for gid in state.links.keys() {
+        let fetched = asana_tasks
+            .incomplete
+            .iter()
+            .chain(&asana_tasks.complete)
+            .any(|t| &t.gid == gid);
+        // deleted tasks are left to the cycle, which knows what to do with their copies
+        if fetched || !asana_mgr.task_exists(gid).await? {
+            continue;
+        }
+
+        let task = asana_mgr.get_task(gid).await?;
+        if task.completed_at.is_some_and(|at| at >= lookback) {
+            names.push(task.name.clone());
+            asana_tasks.complete.push(task);
+        }
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::{ToSpan, civil::date};
+
+    use super::*;
+    use crate::mock::{MockAsana, asana_task};
+
+    #[tokio::test]
+    async fn completions_beyond_the_window_are_found_through_the_links() {
+        let asana = MockAsana::new(vec![
+            asana_task("100", "Pay rent", date(2026, 10, 1)),
+            asana_task("101", "Book flights", date(2026, 10, 1)),
+            asana_task("102", "Call the bank", date(2026, 10, 1)),
+        ]);
+        let mut state = State::default();
+        for (gid, google_id) in [("100", "g100"), ("101", "g101"), ("102", "g102")] {
+            state.link(&asana.task(gid).unwrap(), "list", google_id, None);
+        }
+
+        // two left the fetch's window, one completed long before the other
+        let now = Timestamp::now();
+        asana.update("100", |t| t.completed_at = Some(now - 240.hours()));
+        asana.update("101", |t| t.completed_at = Some(now - 2400.hours()));
+        asana
+            .hidden
+            .borrow_mut()
+            .extend(["100".to_string(), "101".to_string()]);
+
+        let mut tasks = asana.get_tasks().await.unwrap();
+        let names = completed_links(&asana, &mut tasks, &state, now - 720.hours())
+            .await
+            .unwrap();
+        assert_eq!(names, ["Pay rent"]);
+        assert_eq!(tasks.complete.len(), 1);
+        // still fetched, nothing to catch up on
+        assert_eq!(tasks.incomplete.len(), 1);
+    }
+}
@@ -0,0 +1,177 @@
+use clap::{Parser, Subcommand};
+
+use gtasks_asana_bridge::{
+    bootstrap::DEFAULT_THRESHOLD,
+    export::ExportFormat,
+    report::{ReportFormat, ReportPeriod},
+};
+
+#[derive(Debug, Clone, Parser)]
+#[command(version, about = "Bridge Asana tasks into Google Tasks")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Run cycles even if they would delete more google tasks than the safety limits allow
+    #[arg(long, global = true)]
+    pub force: bool,
+
+    /// Log what each cycle would change without changing anything
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// Only act for this user when several are configured
+    #[arg(long, global = true)]
+    pub user: Option<String>,
+
+    /// Write every request to the APIs and its response to a file, `http_trace.log` unless given
+    /// as `--trace-http=PATH`, with tokens and other secrets redacted. It's rotated once it grows
+    /// past 10 MiB.
+    #[arg(
+        long,
+        global = true,
+        value_name = "PATH",
+        num_args = 0..=1,
+        require_equals = true
+    )]
+    pub trace_http: Option<Option<String>>,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// Set up the config file: pick the asana workspace and the list to sync into, and grant
+    /// access to them
+    Init,
+    /// Run the sync loop, or mirror an ad-hoc Asana project with `--project`/`--target`
+    Sync {
+        /// Asana project GID to mirror instead of the configured task list
+        #[arg(long, requires = "target")]
+        project: Option<String>,
+
+        /// Title of the Google task list to mirror into
+        #[arg(long)]
+        target: Option<String>,
+
+        /// Run a single sync pass and exit
+        #[arg(long)]
+        once: bool,
+    },
+    /// Delete google lists whose asana section no longer exists (section routing)
+    RetireLists,
+    /// Print a table of all linked tasks from the state store
+    Status,
+    /// Print how each user's sync loop is doing, as written by the running bridge
+    Users,
+    /// Forget the link between an asana task and its google copy, deleting the copy
+    Unlink {
+        /// Asana task GID
+        gid: String,
+    },
+    /// Link an asana task to a specific google task, repairing its notes marker
+    Relink {
+        /// Asana task GID
+        gid: String,
+        /// Google task ID
+        google_id: String,
+    },
+    /// Find google tasks that copy the same asana task, by marker, link or title and due date
+    Dedupe {
+        /// Merge them, deleting the extra copies and relinking the one kept, instead of only
+        /// listing them
+        #[arg(long)]
+        delete: bool,
+    },
+    /// List the tasks the bridge archived, by default the ones completed in the last week
+    Archive {
+        /// How many days back to look
+        #[arg(long, default_value_t = 7)]
+        days: u32,
+        /// Also list tasks that were deleted rather than completed
+        #[arg(long)]
+        deleted: bool,
+    },
+    /// Link tasks that were in both asana and google before the bridge was, by title and due
+    /// date, so they aren't copied again
+    Bootstrap {
+        /// Link every pair scoring at least the threshold instead of asking about each
+        #[arg(long)]
+        auto: bool,
+        /// How alike a pair has to be to be proposed, from 0 to 1 for the same title and due date
+        #[arg(long, default_value_t = DEFAULT_THRESHOLD)]
+        threshold: f64,
+    },
+    /// Delete every google task the bridge made and the state file, to stop using it
+    #[command(alias = "uninstall")]
+    Purge {
+        /// Keep the tasks, only stripping the bridge's tags from them
+        #[arg(long)]
+        keep: bool,
+        /// Don't ask before purging
+        #[arg(long)]
+        yes: bool,
+    },
+    /// List, retry or drop actions parked after failing too often
+    Failed {
+        #[command(subcommand)]
+        command: FailedCommand,
+    },
+    /// Summarize the tasks created, completed and overdue per google list
+    Report {
+        /// How far back the report goes, ending today
+        #[arg(long, value_enum, default_value_t = ReportPeriod::Week)]
+        period: ReportPeriod,
+        #[arg(long, value_enum, default_value_t = ReportFormat::Markdown)]
+        format: ReportFormat,
+    },
+    /// Write every task the bridge sees on either side, and which are linked, to a file, e.g. as
+    /// a backup before a risky change
+    Export {
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+        /// File to write, `export.json` or `export.csv` unless given
+        #[arg(long, value_name = "PATH")]
+        output: Option<String>,
+    },
+    /// Print what the bridge knows about one asana task, with its last archive entry and failing
+    /// actions, and what it will do with it next
+    Inspect {
+        /// Asana task GID
+        gid: String,
+    },
+    /// Run the sync loop behind a live dashboard of the links, pending changes, errors and rate
+    /// limits
+    #[cfg(feature = "tui")]
+    Tui,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum FailedCommand {
+    /// Print every failing action, parked or not
+    List,
+    /// Give a parked action another try next cycle, it's parked again if it fails
+    Retry {
+        /// Action key, as printed by `failed list`
+        key: String,
+    },
+    /// Forget an action's failures, e.g. after fixing its cause by hand
+    Drop {
+        /// Action key, as printed by `failed list`
+        key: String,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trace_http_leaves_the_subcommand_alone() {
+        let cli = Cli::try_parse_from(["bridge", "--trace-http", "status"]).unwrap();
+        assert_eq!(cli.trace_http, Some(None));
+        assert!(matches!(cli.command, Some(Command::Status)));
+
+        let cli = Cli::try_parse_from(["bridge", "--trace-http=/tmp/trace.log", "status"]).unwrap();
+        assert_eq!(cli.trace_http, Some(Some("/tmp/trace.log".to_string())));
+        assert!(matches!(cli.command, Some(Command::Status)));
+    }
+}
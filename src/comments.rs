@@ -0,0 +1,139 @@
+use crate::{
+    asana::{self, AsanaApi},
+    date,
+    fetched::Fetched,
+    sync::FailedActions,
+    watchdog,
+};
+
+/// Give every incomplete task its `latest` comments, newest last, reusing the ones `fetched` by
+/// earlier cycles while they're fresh, see [`Fetched::attach`]
+pub async fn attach(
+    asana_mgr: &impl AsanaApi,
+    tasks: &mut asana::TaskResult,
+    latest: usize,
+    fetched: &Fetched<asana::Comment>,
+    failures: &mut FailedActions,
+) {
+    if latest == 0 {
+        return;
+    }
+
+    watchdog::phase("fetching asana comments");
+    fetched
+        .attach(
+            tasks,
+            "comments",
+            failures,
+            async |gid| asana_mgr.get_comments(gid).await,
+            |task, mut comments| {
+                task.comments = comments.split_off(comments.len().saturating_sub(latest));
+            },
+        )
+        .await;
+}
+
+/// The comments as a block of google notes, empty when there are none
+pub fn render(comments: &[asana::Comment]) -> String {
+    if comments.is_empty() {
+        return String::new();
+    }
+
+    let mut out = String::from("Comments:");
+    for comment in comments {
+        let day = date::local_date(comment.created_at);
+        match &comment.author {
+            Some(author) => out.push_str(&format!("\n{author} ({day}): {}", comment.text)),
+            None => out.push_str(&format!("\n({day}): {}", comment.text)),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::civil::date;
+
+    use jiff::Timestamp;
+
+    use super::*;
+    use crate::mock::{MockAsana, asana_task};
+
+    fn comment(author: &str, text: &str) -> asana::Comment {
+        asana::Comment {
+            author: Some(author.to_string()),
+            created_at: "2026-10-15T12:00:00Z".parse().unwrap(),
+            text: text.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn only_the_latest_comments_are_kept() {
+        let asana = MockAsana::new(vec![asana_task("100", "Pay rent", date(2026, 10, 22))]);
+        asana.comments.borrow_mut().insert(
+            "100".to_string(),
+            vec![
+                comment("Alice", "Is it due Friday?"),
+                comment("Bob", "Yes"),
+                comment("Alice", "Landlord wants cash"),
+            ],
+        );
+        let mut tasks = asana.get_tasks().await.unwrap();
+
+        let mut failures = FailedActions::default();
+        attach(&asana, &mut tasks, 2, &Fetched::default(), &mut failures).await;
+
+        let rendered = render(&tasks.incomplete[0].comments);
+        assert!(rendered.starts_with("Comments:\nBob ("));
+        assert!(rendered.ends_with("): Landlord wants cash"));
+        assert_eq!(rendered.lines().count(), 3);
+        assert!(failures.failed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn comments_are_fetched_again_once_the_task_is_edited() {
+        let asana = MockAsana::new(vec![asana_task("100", "Pay rent", date(2026, 10, 22))]);
+        asana.comments.borrow_mut().insert(
+            "100".to_string(),
+            vec![comment("Alice", "Is it due Friday?")],
+        );
+        let fetched = Fetched::default();
+        let mut failures = FailedActions::default();
+        let mut tasks = asana.get_tasks().await.unwrap();
+        attach(&asana, &mut tasks, 5, &fetched, &mut failures).await;
+
+        // the next cycle reuses them
+        asana
+            .comments
+            .borrow_mut()
+            .get_mut("100")
+            .unwrap()
+            .push(comment("Bob", "Yes"));
+        let mut tasks = asana.get_tasks().await.unwrap();
+        attach(&asana, &mut tasks, 5, &fetched, &mut failures).await;
+        assert_eq!(tasks.incomplete[0].comments.len(), 1);
+
+        asana.update("100", |t| t.modified_at = Some(Timestamp::now()));
+        let mut tasks = asana.get_tasks().await.unwrap();
+        attach(&asana, &mut tasks, 5, &fetched, &mut failures).await;
+        assert_eq!(tasks.incomplete[0].comments.len(), 2);
+        assert!(failures.failed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_task_whose_comments_fail_is_left_out() {
+        let asana = MockAsana::new(vec![
+            asana_task("100", "Pay rent", date(2026, 10, 22)),
+            asana_task("101", "Book flights", date(2026, 10, 23)),
+        ]);
+        asana.failing.borrow_mut().insert("100".to_string());
+        let mut tasks = asana.get_tasks().await.unwrap();
+
+        let mut failures = FailedActions::default();
+        attach(&asana, &mut tasks, 2, &Fetched::default(), &mut failures).await;
+
+        let gids: Vec<&str> = tasks.incomplete.iter().map(|t| t.gid.as_str()).collect();
+        assert_eq!(gids, ["101"]);
+        assert_eq!(failures.failed.len(), 1);
+    }
+}
@@ -0,0 +1,1782 @@
+use std::{collections::HashSet, str::FromStr};
+
+use anyhow::{Context, Result, bail};
+use serde::{
+    Deserialize,
+    de::{DeserializeOwned, IntoDeserializer, value::Error as ValueError},
+};
+
+use crate::{
+    asana::SearchQuery,
+    correlate::Correlation,
+    error::BridgeError,
+    filter::TaskFilter,
+    google::AuthFlow,
+    hierarchy::{CascadePolicy, FlattenPolicy},
+    http,
+    lock::LockPolicy,
+    misplaced::MisplacedPolicy,
+    notify::{NotifyEvent, WebhookKind},
+    order::TaskOrder,
+    report::{ReportFormat, ReportPeriod},
+    schedule::Window,
+    secrets::{self, SecretSource},
+    sync::{AsanaDeletePolicy, FieldSync, GoogleDeletePolicy},
+};
+
+#[cfg(not(feature = "docker"))]
+const DATA_DIR: &str = ".";
+
+#[cfg(feature = "docker")]
+const DATA_DIR: &str = "/data";
+
+#[cfg(not(feature = "docker"))]
+const SECRET_DIR: &str = ".";
+
+#[cfg(feature = "docker")]
+const SECRET_DIR: &str = "/secret";
+
+/// Where the config file is looked for unless `BRIDGE_CONFIG` points elsewhere
+fn default_path() -> String {
+    format!("{DATA_DIR}/bridge.toml")
+}
+
+/// Where `--trace-http` writes unless it's given a path
+pub fn default_trace_path() -> String {
+    format!("{DATA_DIR}/http_trace.log")
+}
+
+/// Where `export` writes unless it's given a path
+pub fn default_export_path(extension: &str) -> String {
+    format!("{DATA_DIR}/export.{extension}")
+}
+
+/// Everything the bridge can be configured with. Loaded from `bridge.toml`, with the older env
+/// vars taking precedence over the file.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub asana: AsanaConfig,
+    pub github: GitHubConfig,
+    pub google: GoogleConfig,
+    pub mstodo: MsTodoConfig,
+    pub jira: JiraConfig,
+    pub trello: TrelloConfig,
+    pub notion: NotionConfig,
+    pub taskwarrior: TaskwarriorConfig,
+    pub calendar: CalendarConfig,
+    pub bridge: BridgeConfig,
+    pub sync: SyncConfig,
+    pub section_writeback: WritebackConfig,
+    pub report: ReportConfig,
+    pub notify: NotifyConfig,
+    pub email: EmailConfig,
+    /// People synced side by side in one process, each with their own tokens and state. Settings
+    /// outside `[[users]]` are shared.
+    pub users: Vec<UserConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AsanaConfig {
+    /// Personal access token, `ASANA_PAT`
+    #[serde(deserialize_with = "secrets::bare_value")]
+    pub pat: Option<SecretSource>,
+    /// Gid of the "My Tasks" list to mirror, `PROJECT_GID`. Looked up from the token's user when
+    /// unset.
+    pub project_gid: Option<String>,
+    /// Workspace whose "My Tasks" is looked up, `ASANA_WORKSPACE_GID`. Only needed when the user
+    /// is in more than one.
+    pub workspace_gid: Option<String>,
+    /// Gid of a shared project to mirror instead of "My Tasks", `SHARED_PROJECT_GID`
+    pub shared_project_gid: Option<String>,
+    /// A search across the workspace to mirror instead of "My Tasks"
+    pub search: Option<SearchQuery>,
+    /// `ASANA_BASE_URL`
+    pub base_url: String,
+    /// Completions sent in one request to asana's batch endpoint, at most 10,
+    /// `ASANA_BATCH_SIZE`
+    pub batch_size: usize,
+}
+
+impl Default for AsanaConfig {
+    fn default() -> Self {
+        Self {
+            pat: None,
+            project_gid: None,
+            workspace_gid: None,
+            shared_project_gid: None,
+            search: None,
+            base_url: crate::asana::DEFAULT_BASE_URL.to_string(),
+            batch_size: crate::asana::BATCH_LIMIT,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GitHubConfig {
+    /// Token with read access to the issues, write access too for `close_issues`, `GITHUB_TOKEN`
+    #[serde(deserialize_with = "secrets::bare_value")]
+    pub token: Option<SecretSource>,
+    /// `GITHUB_API_URL`
+    pub base_url: String,
+    /// Close issues completed in google instead of only remembering them as done,
+    /// `GITHUB_CLOSE_ISSUES`
+    pub close_issues: bool,
+    /// File the issues completed in google but still open are remembered in, `GITHUB_DONE_PATH`
+    pub done: String,
+}
+
+impl Default for GitHubConfig {
+    fn default() -> Self {
+        Self {
+            token: None,
+            base_url: crate::github::DEFAULT_BASE_URL.to_string(),
+            close_issues: false,
+            done: format!("{DATA_DIR}/github_done.json"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GoogleConfig {
+    /// OAuth client secret downloaded from the cloud console, `GOOGLE_CLIENT_SECRET`
+    #[serde(deserialize_with = "secrets::bare_file")]
+    pub client_secret: SecretSource,
+    /// Where the OAuth tokens are cached, `GOOGLE_TOKEN_CACHE`
+    pub token_cache: String,
+    /// How the first token is granted, `GOOGLE_AUTH_FLOW`
+    pub auth_flow: AuthFlow,
+    /// Send a device code to the webhooks when google revokes the refresh token while the bridge
+    /// runs, instead of waiting for it to be granted by hand. Needs the device flow,
+    /// `GOOGLE_REAUTH`
+    pub reauth: bool,
+    /// Service account key used instead of the OAuth client, `GOOGLE_SERVICE_ACCOUNT`. A bare
+    /// string is the path of the JSON key.
+    #[serde(deserialize_with = "secrets::optional_bare_file")]
+    pub service_account: Option<SecretSource>,
+    /// User the service account acts as through domain-wide delegation, `GOOGLE_IMPERSONATE`
+    pub impersonate: Option<String>,
+    /// Title of the google task list to mirror into, in any case, `GOOGLE_LIST`
+    pub list: String,
+    /// Id of the list, picking it instead of the title, `GOOGLE_LIST_ID`
+    pub list_id: Option<String>,
+    /// Inserts and deletes sent at once when catching up, `GOOGLE_BATCH_SIZE`
+    pub batch_size: usize,
+}
+
+impl Default for GoogleConfig {
+    fn default() -> Self {
+        Self {
+            client_secret: SecretSource::File(format!("{SECRET_DIR}/client_secret.json")),
+            token_cache: format!("{DATA_DIR}/token_cache.json"),
+            auth_flow: AuthFlow::default(),
+            reauth: false,
+            service_account: None,
+            impersonate: None,
+            list: "Asana".to_string(),
+            list_id: None,
+            batch_size: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MsTodoConfig {
+    /// Application id of an entra app registration allowing public client flows,
+    /// `MSTODO_CLIENT_ID`
+    pub client_id: Option<String>,
+    /// `consumers` for personal accounts, or a work tenant's id, `MSTODO_TENANT`
+    pub tenant: String,
+    /// Where the OAuth tokens are cached, `MSTODO_TOKEN_CACHE`
+    pub token_cache: String,
+    /// Title of the to do list to mirror into, in any case, `MSTODO_LIST`
+    pub list: String,
+    /// Id of the list, picking it instead of the title, `MSTODO_LIST_ID`
+    pub list_id: Option<String>,
+}
+
+impl Default for MsTodoConfig {
+    fn default() -> Self {
+        Self {
+            client_id: None,
+            tenant: "consumers".to_string(),
+            token_cache: format!("{DATA_DIR}/mstodo_token_cache.json"),
+            list: "Asana".to_string(),
+            list_id: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JiraConfig {
+    /// Address of the jira cloud site, e.g. `https://example.atlassian.net`, `JIRA_URL`
+    pub base_url: Option<String>,
+    /// Account the api token belongs to, `JIRA_EMAIL`
+    pub email: Option<String>,
+    /// `JIRA_TOKEN`
+    #[serde(deserialize_with = "secrets::bare_value")]
+    pub token: Option<SecretSource>,
+    /// Key or name of the project to mirror into, in any case, `JIRA_PROJECT`
+    pub project: Option<String>,
+    /// Type of the issues created, the only ones synced, `JIRA_ISSUE_TYPE`
+    pub issue_type: String,
+    /// Name of the transition closing an issue, the first one to a done status when unset,
+    /// `JIRA_DONE_TRANSITION`
+    pub done_transition: Option<String>,
+}
+
+impl Default for JiraConfig {
+    fn default() -> Self {
+        Self {
+            base_url: None,
+            email: None,
+            token: None,
+            project: None,
+            issue_type: "Task".to_string(),
+            done_transition: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrelloConfig {
+    /// Api key of a trello power-up, `TRELLO_KEY`
+    pub key: Option<String>,
+    /// Token the key was granted, `TRELLO_TOKEN`
+    #[serde(deserialize_with = "secrets::bare_value")]
+    pub token: Option<SecretSource>,
+    /// Id of the board, or the short link in its url, `TRELLO_BOARD`
+    pub board: Option<String>,
+    /// Name of the board's list to mirror into, in any case, `TRELLO_LIST`
+    pub list: String,
+    /// Id of the list, picking it instead of the name, `TRELLO_LIST_ID`
+    pub list_id: Option<String>,
+}
+
+impl Default for TrelloConfig {
+    fn default() -> Self {
+        Self {
+            key: None,
+            token: None,
+            board: None,
+            list: "Asana".to_string(),
+            list_id: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotionConfig {
+    /// Secret of an internal integration the database is shared with, `NOTION_TOKEN`
+    #[serde(deserialize_with = "secrets::bare_value")]
+    pub token: Option<SecretSource>,
+    /// Title of the database to mirror into, in any case, `NOTION_DATABASE`
+    pub database: String,
+    /// Id of the database, picking it instead of the title, `NOTION_DATABASE_ID`
+    pub database_id: Option<String>,
+    pub properties: NotionProperties,
+}
+
+impl Default for NotionConfig {
+    fn default() -> Self {
+        Self {
+            token: None,
+            database: "Asana".to_string(),
+            database_id: None,
+            properties: NotionProperties::default(),
+        }
+    }
+}
+
+/// Names of the database properties tasks are mirrored into. Those the database doesn't have are
+/// skipped, except for the title and notes.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotionProperties {
+    pub title: String,
+    /// Text property holding the notes and the bridge's marker
+    pub notes: String,
+    pub due: Option<String>,
+    /// Status, select or checkbox property telling whether the task is done
+    pub status: Option<String>,
+    /// Status or select option meaning done, a checkbox is done when ticked
+    pub done_status: String,
+    /// Url property linking back to the asana task
+    pub url: Option<String>,
+}
+
+impl Default for NotionProperties {
+    fn default() -> Self {
+        Self {
+            title: "Name".to_string(),
+            notes: "Notes".to_string(),
+            due: Some("Due".to_string()),
+            status: Some("Status".to_string()),
+            done_status: "Done".to_string(),
+            url: Some("Asana".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TaskwarriorConfig {
+    /// Program and leading arguments, e.g. `["task", "rc:~/.taskrc-work"]`,
+    /// `TASKWARRIOR_COMMAND` split on spaces
+    pub command: Vec<String>,
+    /// Project the tasks are added to, `TASKWARRIOR_PROJECT`
+    pub project: String,
+}
+
+impl Default for TaskwarriorConfig {
+    fn default() -> Self {
+        Self {
+            command: vec!["task".to_string()],
+            project: "Asana".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CalendarConfig {
+    /// Mirror tasks with a due time into calendar events instead of google tasks,
+    /// `CALENDAR_SYNC`
+    pub enabled: bool,
+    /// `CALENDAR_ID`
+    pub id: String,
+    /// Length of the events, `EVENT_MINUTES`
+    pub event_minutes: u64,
+}
+
+impl Default for CalendarConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            id: "primary".to_string(),
+            event_minutes: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReportConfig {
+    /// Write a report each time this period has passed, `REPORT_EVERY`
+    pub every: Option<ReportPeriod>,
+    /// Directory the reports are written to, `REPORT_DIR`
+    pub dir: String,
+    /// `REPORT_FORMAT`
+    pub format: ReportFormat,
+}
+
+impl Default for ReportConfig {
+    fn default() -> Self {
+        Self {
+            every: None,
+            dir: format!("{DATA_DIR}/reports"),
+            format: ReportFormat::Markdown,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifyConfig {
+    /// Slack or discord webhooks, `NOTIFY_WEBHOOK` adds one more
+    pub webhooks: Vec<WebhookConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// `NOTIFY_KIND` for the webhook from the environment
+    #[serde(default)]
+    pub kind: WebhookKind,
+    /// Events posted to the webhook, errors and conflicts by default
+    #[serde(default = "default_notify_events")]
+    pub on: Vec<NotifyEvent>,
+}
+
+fn default_notify_events() -> Vec<NotifyEvent> {
+    vec![NotifyEvent::Errors, NotifyEvent::Conflicts]
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmailConfig {
+    /// No emails are sent when unset, `SMTP_HOST`
+    pub smtp_host: Option<String>,
+    /// `SMTP_PORT`
+    pub smtp_port: u16,
+    /// `SMTP_SECURITY`
+    pub security: SmtpSecurity,
+    /// `SMTP_USERNAME`
+    pub username: Option<String>,
+    /// `SMTP_PASSWORD`
+    #[serde(deserialize_with = "secrets::bare_value")]
+    pub password: Option<SecretSource>,
+    /// `EMAIL_FROM`
+    pub from: Option<String>,
+    /// `EMAIL_TO`
+    pub to: Option<String>,
+    /// Failed cycles in a row before the digest is sent, `EMAIL_AFTER_FAILURES`
+    pub after_failures: u32,
+}
+
+impl Default for EmailConfig {
+    fn default() -> Self {
+        Self {
+            smtp_host: None,
+            smtp_port: 587,
+            security: SmtpSecurity::StartTls,
+            username: None,
+            password: None,
+            from: None,
+            to: None,
+            after_failures: 3,
+        }
+    }
+}
+
+/// How the connection to the smtp server is secured
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Upgrade a plain connection, usually on port 587
+    #[default]
+    StartTls,
+    /// TLS from the start, usually on port 465
+    Tls,
+    /// No encryption, only for a relay on the same host
+    None,
+}
+
+/// Where the tasks are mirrored from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    #[default]
+    Asana,
+    /// Issues assigned to the github token's user
+    GitHub,
+}
+
+impl Source {
+    /// Name as written in the config
+    pub fn name(self) -> &'static str {
+        match self {
+            Source::Asana => "asana",
+            Source::GitHub => "github",
+        }
+    }
+}
+
+/// Where asana tasks are mirrored to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    Google,
+    /// Microsoft To Do
+    MsTodo,
+    /// Jira Cloud issues
+    Jira,
+    /// Cards on a trello board
+    Trello,
+    /// Pages of a notion database
+    Notion,
+    /// The local taskwarrior database
+    Taskwarrior,
+}
+
+impl Backend {
+    /// Name as written in the config, which also keys a mirror's links in the state store
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Google => "google",
+            Backend::MsTodo => "mstodo",
+            Backend::Jira => "jira",
+            Backend::Trello => "trello",
+            Backend::Notion => "notion",
+            Backend::Taskwarrior => "taskwarrior",
+        }
+    }
+}
+
+/// One identity in multi-user mode
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UserConfig {
+    /// Shown in front of this user's log lines and picked with `--user`
+    pub name: String,
+    /// Asana personal access token, or github token when `bridge.source` is github
+    #[serde(deserialize_with = "secrets::bare_value")]
+    pub pat: Option<SecretSource>,
+    /// Looked up from the user's token when unset
+    pub project_gid: Option<String>,
+    /// Replaces `asana.workspace_gid` for this user
+    pub workspace_gid: Option<String>,
+    /// Replaces `asana.shared_project_gid` for this user
+    pub shared_project_gid: Option<String>,
+    /// Replaces `asana.search` for this user
+    pub search: Option<SearchQuery>,
+    /// Title of the task list to mirror into, the backend's `list` when unset
+    pub list: Option<String>,
+    /// Id of the task list, picking it instead of the title
+    pub list_id: Option<String>,
+    /// Replaces `[notion.properties]` for this user
+    pub notion_properties: Option<NotionProperties>,
+    /// Token cache of the backend, `token_cache_<name>.json` (`mstodo_token_cache_<name>.json`
+    /// for to do) in the data dir when unset
+    pub token_cache: Option<String>,
+    /// `state_<name>.json` in the data dir when unset
+    pub state: Option<String>,
+    /// `archive_<name>.ndjson` in the data dir when unset and `bridge.archive` is set
+    pub archive: Option<String>,
+    /// User the shared service account acts as
+    pub impersonate: Option<String>,
+    /// Replaces `[sync.filter]` for this user
+    pub filter: Option<FilterConfig>,
+    /// Replaces `sync.correlation` for this user
+    pub correlation: Option<Correlation>,
+    /// Replaces `sync.delete_unlinked_completed` for this user
+    pub delete_unlinked_completed: Option<bool>,
+    /// Replaces `sync.on_google_delete` for this user
+    pub on_google_delete: Option<GoogleDeletePolicy>,
+    /// Replaces `bridge.interval_secs` for this user
+    pub interval_secs: Option<u64>,
+    /// Replaces `bridge.error_budget` for this user
+    pub error_budget: Option<u32>,
+    /// Replaces `bridge.active_hours` for this user
+    pub active_hours: Option<Vec<Window>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BridgeConfig {
+    /// `STATE_PATH`
+    pub state: String,
+    /// Seconds to wait between cycles, `SYNC_INTERVAL`
+    pub interval_secs: u64,
+    /// Seconds before a request to asana, to do or a webhook is given up on, `REQUEST_TIMEOUT`
+    pub request_timeout_secs: u64,
+    /// Seconds a cycle may run before it's abandoned, no limit when 0, `CYCLE_BUDGET`
+    pub cycle_budget_secs: u64,
+    /// IANA timezone name, the system's when unset, `TIMEZONE`
+    pub timezone: Option<String>,
+    /// Prometheus textfile to write integrity gauges to, `METRICS_PATH`
+    pub metrics: Option<String>,
+    /// NDJSON file every google task is recorded in before the bridge deletes it, `ARCHIVE_PATH`
+    pub archive: Option<String>,
+    /// `SOURCE`
+    pub source: Source,
+    /// `BACKEND`
+    pub backend: Backend,
+    /// Further backends kept in line with the source alongside `backend`, each with its own
+    /// section's settings, `MIRRORS` separated by commas
+    pub mirrors: Vec<Backend>,
+    /// Failures in a row after which a user's loop is stopped while the other users go on, in
+    /// multi-user mode. Never stopped when 0, `ERROR_BUDGET`
+    pub error_budget: u32,
+    /// JSON file with how each user's loop is doing, in multi-user mode, `USERS_STATUS_PATH`
+    pub users_status: String,
+    /// 64 hex digits the state file and token caches are encrypted with, `ENCRYPTION_KEY`
+    #[serde(deserialize_with = "secrets::bare_value")]
+    pub encryption_key: Option<SecretSource>,
+    /// Failed cycles in a row after which a backend is skipped and only probed now and then. Its
+    /// errors end the loop right away when 0, `BREAKER_AFTER`
+    pub breaker_after: u32,
+    /// Seconds before the first probe of a skipped backend, doubling after each failed one,
+    /// `BREAKER_PROBE`
+    pub breaker_probe_secs: u64,
+    /// Local times of day cycles run in, any time when empty, `ACTIVE_HOURS` separated by commas
+    pub active_hours: Vec<Window>,
+    /// What to do when another bridge syncs the same state file or list, `INSTANCE_LOCK`
+    pub instance_lock: LockPolicy,
+    /// Milliseconds without another request for a cycle, like `SIGHUP`, before one runs,
+    /// `REQUEST_QUIET`
+    pub request_quiet_ms: u64,
+    /// Longest a request for a cycle waits for the burst it's part of to settle,
+    /// `REQUEST_MAX_DELAY`
+    pub request_max_delay_ms: u64,
+}
+
+impl Default for BridgeConfig {
+    fn default() -> Self {
+        Self {
+            state: format!("{DATA_DIR}/state.json"),
+            interval_secs: 10,
+            request_timeout_secs: http::DEFAULT_TIMEOUT_SECS,
+            cycle_budget_secs: 120,
+            timezone: None,
+            metrics: None,
+            archive: None,
+            source: Source::default(),
+            backend: Backend::default(),
+            mirrors: Vec::new(),
+            error_budget: 5,
+            users_status: format!("{DATA_DIR}/users.json"),
+            encryption_key: None,
+            breaker_after: 3,
+            breaker_probe_secs: 60,
+            active_hours: Vec::new(),
+            instance_lock: LockPolicy::default(),
+            request_quiet_ms: 2000,
+            request_max_delay_ms: 30_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SyncConfig {
+    /// `SUBTASKS`, subtasks aren't synced when unset
+    pub subtasks: Option<FlattenPolicy>,
+    /// `COMPLETION_CASCADE`
+    pub completion_cascade: CascadePolicy,
+    /// `ON_GOOGLE_DELETE`
+    pub on_google_delete: GoogleDeletePolicy,
+    /// `ASANA_DELETE_POLICY`
+    pub on_asana_delete: AsanaDeletePolicy,
+    /// `MAX_DELETIONS`
+    pub max_deletions: usize,
+    /// `MAX_DELETION_PERCENT`
+    pub max_deletion_percent: usize,
+    /// One google list per asana section, `SECTION_ROUTING`
+    pub section_routing: bool,
+    /// `PRIVATE_TAG`
+    pub private_tag: Option<String>,
+    /// Title prefix marking google copies of liked asana tasks, `STAR_MARKER`
+    pub star_marker: Option<String>,
+    /// Only sync tasks assigned to the token's user, `ONLY_MINE`
+    pub only_mine: bool,
+    /// `TASK_ORDER`
+    pub order: TaskOrder,
+    /// Failures before an action is parked, never parked when 0, `PARK_AFTER`
+    pub park_after: u32,
+    /// Merge google copies of the same asana task before the first cycle, `DEDUPE_ON_STARTUP`
+    pub dedupe_on_startup: bool,
+    /// How google tasks are tied to their asana tasks, `CORRELATION`
+    pub correlation: Correlation,
+    /// Latest asana comments shown in the google notes, none when 0, `COMMENTS`
+    pub comments: usize,
+    /// Post text added to google notes as asana comments, `NOTES_AS_COMMENTS`
+    pub notes_as_comments: bool,
+    /// List attachments in the google notes, `ATTACHMENTS`
+    pub attachments: bool,
+    /// Furthest back completions are caught up on after an outage, `MAX_CATCH_UP_DAYS`
+    pub max_catch_up_days: u32,
+    /// Furthest back the first cycle after startup looks for completions of linked tasks, no
+    /// catch-up pass when 0, `CATCH_UP_DAYS`
+    pub catch_up_days: u32,
+    /// What happens to google copies moved to another list, `MISPLACED_TASKS`
+    pub misplaced_tasks: MisplacedPolicy,
+    /// Delete completed google tasks the bridge didn't make, `DELETE_UNLINKED_COMPLETED`
+    pub delete_unlinked_completed: bool,
+    /// Clear completed google tasks in one call instead of deleting them, `CLEAR_COMPLETED`
+    pub clear_completed: bool,
+    pub fields: FieldsConfig,
+    pub filter: FilterConfig,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self {
+            subtasks: None,
+            completion_cascade: CascadePolicy::Leave,
+            on_google_delete: GoogleDeletePolicy::Recreate,
+            on_asana_delete: AsanaDeletePolicy::Delete,
+            max_deletions: 20,
+            max_deletion_percent: 50,
+            section_routing: false,
+            private_tag: None,
+            star_marker: None,
+            only_mine: false,
+            order: TaskOrder::None,
+            park_after: 5,
+            dedupe_on_startup: true,
+            correlation: Correlation::default(),
+            comments: 0,
+            notes_as_comments: false,
+            attachments: false,
+            max_catch_up_days: 7,
+            catch_up_days: 30,
+            misplaced_tasks: MisplacedPolicy::Off,
+            delete_unlinked_completed: false,
+            clear_completed: false,
+            fields: FieldsConfig::default(),
+            filter: FilterConfig::default(),
+        }
+    }
+}
+
+/// Which way each field of a task syncs. Fields that sync to google are overwritten there by
+/// what asana says, fields that sync to asana are never overwritten in google.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FieldsConfig {
+    /// `SYNC_TITLE`
+    pub title: FieldSync,
+    /// `SYNC_NOTES`
+    pub notes: FieldSync,
+    /// `SYNC_DUE`
+    pub due: FieldSync,
+}
+
+impl Default for FieldsConfig {
+    fn default() -> Self {
+        Self {
+            title: FieldSync::ToGoogle,
+            notes: FieldSync::ToGoogle,
+            due: FieldSync::Both,
+        }
+    }
+}
+
+/// Which asana tasks are mirrored, everything when nothing is set
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilterConfig {
+    /// Only tasks with one of these tags
+    pub tags: Vec<String>,
+    pub exclude_tags: Vec<String>,
+    /// Only tasks in one of these sections
+    pub sections: Vec<String>,
+    pub exclude_sections: Vec<String>,
+    /// Regex the task name has to match
+    pub name: Option<String>,
+    /// Only tasks due at most this many days from today, tasks without a due date pass.
+    /// Checked every cycle, so tasks show up as they come within range. `DUE_WITHIN_DAYS`
+    pub due_within_days: Option<u32>,
+    /// Leave tasks out until their start date, if they have one, `HIDE_UNTIL_START`
+    pub hide_until_start: bool,
+}
+
+impl FilterConfig {
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+            && self.exclude_tags.is_empty()
+            && self.sections.is_empty()
+            && self.exclude_sections.is_empty()
+            && self.name.is_none()
+            && self.due_within_days.is_none()
+            && !self.hide_until_start
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WritebackConfig {
+    /// `SECTION_WRITEBACK`
+    pub enabled: bool,
+    /// `LATER_SECTION`
+    pub later: String,
+    /// `TODAY_SECTION`
+    pub today: String,
+}
+
+impl Default for WritebackConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            later: "Later".to_string(),
+            today: "Today".to_string(),
+        }
+    }
+}
+
+impl Config {
+    /// Where the config file is loaded from, `BRIDGE_CONFIG` or the default location
+    pub fn path() -> String {
+        std::env::var("BRIDGE_CONFIG").unwrap_or_else(|_| default_path())
+    }
+
+    /// Load the config file from `BRIDGE_CONFIG` or the default location, then apply env var
+    /// overrides. A missing file at the default location is fine, everything can come from env.
+    pub fn load() -> Result<Self> {
+        let path = Self::path();
+        let explicit = std::env::var("BRIDGE_CONFIG").is_ok();
+
+        let mut config: Config = match std::fs::read_to_string(&path) {
+            Ok(contents) => {
+                toml::from_str(&contents).with_context(|| format!("invalid config file {path}"))?
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !explicit => Self::default(),
+            Err(e) => return Err(e).with_context(|| format!("failed to read {path}")),
+        };
+
+        config.apply_env()?;
+        config
+            .validate()
+            .map_err(|e| BridgeError::InvalidConfig(format!("{e:#}")))?;
+
+        Ok(config)
+    }
+
+    fn apply_env(&mut self) -> Result<()> {
+        if let Ok(v) = std::env::var("ASANA_PAT") {
+            self.asana.pat = Some(SecretSource::Value(v));
+        }
+        env_string("PROJECT_GID", &mut self.asana.project_gid);
+        env_string("ASANA_WORKSPACE_GID", &mut self.asana.workspace_gid);
+        env_string("SHARED_PROJECT_GID", &mut self.asana.shared_project_gid);
+        if let Ok(v) = std::env::var("ASANA_BASE_URL") {
+            self.asana.base_url = v;
+        }
+        env_parse("ASANA_BATCH_SIZE", &mut self.asana.batch_size)?;
+
+        if let Ok(v) = std::env::var("GITHUB_TOKEN") {
+            self.github.token = Some(SecretSource::Value(v));
+        }
+        if let Ok(v) = std::env::var("GITHUB_API_URL") {
+            self.github.base_url = v;
+        }
+        self.github.close_issues |= std::env::var("GITHUB_CLOSE_ISSUES").is_ok();
+        if let Ok(v) = std::env::var("GITHUB_DONE_PATH") {
+            self.github.done = v;
+        }
+
+        if let Ok(v) = std::env::var("GOOGLE_CLIENT_SECRET") {
+            self.google.client_secret = SecretSource::File(v);
+        }
+        if let Ok(v) = std::env::var("GOOGLE_TOKEN_CACHE") {
+            self.google.token_cache = v;
+        }
+        if let Ok(v) = std::env::var("GOOGLE_SERVICE_ACCOUNT") {
+            self.google.service_account = Some(SecretSource::File(v));
+        }
+        env_string("GOOGLE_IMPERSONATE", &mut self.google.impersonate);
+        if let Some(flow) = env_enum("GOOGLE_AUTH_FLOW")? {
+            self.google.auth_flow = flow;
+        }
+        self.google.reauth |= std::env::var("GOOGLE_REAUTH").is_ok();
+        if let Ok(v) = std::env::var("GOOGLE_LIST") {
+            self.google.list = v;
+        }
+        env_string("GOOGLE_LIST_ID", &mut self.google.list_id);
+        env_parse("GOOGLE_BATCH_SIZE", &mut self.google.batch_size)?;
+
+        env_string("MSTODO_CLIENT_ID", &mut self.mstodo.client_id);
+        if let Ok(v) = std::env::var("MSTODO_TENANT") {
+            self.mstodo.tenant = v;
+        }
+        if let Ok(v) = std::env::var("MSTODO_TOKEN_CACHE") {
+            self.mstodo.token_cache = v;
+        }
+        if let Ok(v) = std::env::var("MSTODO_LIST") {
+            self.mstodo.list = v;
+        }
+        env_string("MSTODO_LIST_ID", &mut self.mstodo.list_id);
+
+        env_string("JIRA_URL", &mut self.jira.base_url);
+        env_string("JIRA_EMAIL", &mut self.jira.email);
+        if let Ok(v) = std::env::var("JIRA_TOKEN") {
+            self.jira.token = Some(SecretSource::Value(v));
+        }
+        env_string("JIRA_PROJECT", &mut self.jira.project);
+        if let Ok(v) = std::env::var("JIRA_ISSUE_TYPE") {
+            self.jira.issue_type = v;
+        }
+        env_string("JIRA_DONE_TRANSITION", &mut self.jira.done_transition);
+
+        env_string("TRELLO_KEY", &mut self.trello.key);
+        if let Ok(v) = std::env::var("TRELLO_TOKEN") {
+            self.trello.token = Some(SecretSource::Value(v));
+        }
+        env_string("TRELLO_BOARD", &mut self.trello.board);
+        if let Ok(v) = std::env::var("TRELLO_LIST") {
+            self.trello.list = v;
+        }
+        env_string("TRELLO_LIST_ID", &mut self.trello.list_id);
+
+        if let Ok(v) = std::env::var("NOTION_TOKEN") {
+            self.notion.token = Some(SecretSource::Value(v));
+        }
+        if let Ok(v) = std::env::var("NOTION_DATABASE") {
+            self.notion.database = v;
+        }
+        env_string("NOTION_DATABASE_ID", &mut self.notion.database_id);
+
+        if let Ok(v) = std::env::var("TASKWARRIOR_COMMAND") {
+            self.taskwarrior.command = v.split_whitespace().map(String::from).collect();
+        }
+        if let Ok(v) = std::env::var("TASKWARRIOR_PROJECT") {
+            self.taskwarrior.project = v;
+        }
+
+        self.calendar.enabled |= std::env::var("CALENDAR_SYNC").is_ok();
+        if let Ok(v) = std::env::var("CALENDAR_ID") {
+            self.calendar.id = v;
+        }
+        env_parse("EVENT_MINUTES", &mut self.calendar.event_minutes)?;
+
+        if let Some(every) = env_enum("REPORT_EVERY")? {
+            self.report.every = Some(every);
+        }
+        if let Ok(v) = std::env::var("REPORT_DIR") {
+            self.report.dir = v;
+        }
+        if let Some(format) = env_enum("REPORT_FORMAT")? {
+            self.report.format = format;
+        }
+        env_string("SMTP_HOST", &mut self.email.smtp_host);
+        env_parse("SMTP_PORT", &mut self.email.smtp_port)?;
+        if let Some(security) = env_enum("SMTP_SECURITY")? {
+            self.email.security = security;
+        }
+        env_string("SMTP_USERNAME", &mut self.email.username);
+        if let Ok(v) = std::env::var("SMTP_PASSWORD") {
+            self.email.password = Some(SecretSource::Value(v));
+        }
+        env_string("EMAIL_FROM", &mut self.email.from);
+        env_string("EMAIL_TO", &mut self.email.to);
+        env_parse("EMAIL_AFTER_FAILURES", &mut self.email.after_failures)?;
+        if let Ok(url) = std::env::var("NOTIFY_WEBHOOK") {
+            self.notify.webhooks.push(WebhookConfig {
+                url,
+                kind: env_enum("NOTIFY_KIND")?.unwrap_or_default(),
+                on: default_notify_events(),
+            });
+        }
+
+        if let Ok(v) = std::env::var("STATE_PATH") {
+            self.bridge.state = v;
+        }
+        env_parse("SYNC_INTERVAL", &mut self.bridge.interval_secs)?;
+        env_parse("REQUEST_TIMEOUT", &mut self.bridge.request_timeout_secs)?;
+        env_parse("CYCLE_BUDGET", &mut self.bridge.cycle_budget_secs)?;
+        env_parse("ERROR_BUDGET", &mut self.bridge.error_budget)?;
+        if let Ok(v) = std::env::var("ENCRYPTION_KEY") {
+            self.bridge.encryption_key = Some(SecretSource::Value(v));
+        }
+        env_parse("BREAKER_AFTER", &mut self.bridge.breaker_after)?;
+        env_parse("BREAKER_PROBE", &mut self.bridge.breaker_probe_secs)?;
+        if let Ok(v) = std::env::var("ACTIVE_HOURS") {
+            self.bridge.active_hours = v
+                .split(',')
+                .map(|window| window.parse().context("invalid ACTIVE_HOURS"))
+                .collect::<Result<_>>()?;
+        }
+        if let Some(policy) = env_enum("INSTANCE_LOCK")? {
+            self.bridge.instance_lock = policy;
+        }
+        env_parse("REQUEST_QUIET", &mut self.bridge.request_quiet_ms)?;
+        env_parse("REQUEST_MAX_DELAY", &mut self.bridge.request_max_delay_ms)?;
+        if let Ok(v) = std::env::var("USERS_STATUS_PATH") {
+            self.bridge.users_status = v;
+        }
+        env_string("TIMEZONE", &mut self.bridge.timezone);
+        env_string("METRICS_PATH", &mut self.bridge.metrics);
+        env_string("ARCHIVE_PATH", &mut self.bridge.archive);
+        if let Some(source) = env_enum("SOURCE")? {
+            self.bridge.source = source;
+        }
+        if let Some(backend) = env_enum("BACKEND")? {
+            self.bridge.backend = backend;
+        }
+        if let Ok(v) = std::env::var("MIRRORS") {
+            self.bridge.mirrors = v
+                .split(',')
+                .map(|m| {
+                    Backend::deserialize(m.trim().into_deserializer())
+                        .map_err(|e: ValueError| anyhow::anyhow!("invalid MIRRORS: {e}"))
+                })
+                .collect::<Result<_>>()?;
+        }
+
+        if let Some(subtasks) = env_enum("SUBTASKS")? {
+            self.sync.subtasks = Some(subtasks);
+        }
+        if let Some(cascade) = env_enum("COMPLETION_CASCADE")? {
+            self.sync.completion_cascade = cascade;
+        }
+        if let Some(policy) = env_enum("ON_GOOGLE_DELETE")? {
+            self.sync.on_google_delete = policy;
+        }
+        if let Some(policy) = env_enum("ASANA_DELETE_POLICY")? {
+            self.sync.on_asana_delete = policy;
+        }
+        env_parse("MAX_DELETIONS", &mut self.sync.max_deletions)?;
+        env_parse("MAX_DELETION_PERCENT", &mut self.sync.max_deletion_percent)?;
+        self.sync.section_routing |= std::env::var("SECTION_ROUTING").is_ok();
+        env_string("PRIVATE_TAG", &mut self.sync.private_tag);
+        env_string("STAR_MARKER", &mut self.sync.star_marker);
+        self.sync.only_mine |= std::env::var("ONLY_MINE").is_ok();
+        env_parse("PARK_AFTER", &mut self.sync.park_after)?;
+        env_parse("MAX_CATCH_UP_DAYS", &mut self.sync.max_catch_up_days)?;
+        env_parse("CATCH_UP_DAYS", &mut self.sync.catch_up_days)?;
+        if let Some(policy) = env_enum("MISPLACED_TASKS")? {
+            self.sync.misplaced_tasks = policy;
+        }
+        if let Some(sync) = env_enum("SYNC_TITLE")? {
+            self.sync.fields.title = sync;
+        }
+        if let Some(sync) = env_enum("SYNC_NOTES")? {
+            self.sync.fields.notes = sync;
+        }
+        if let Some(sync) = env_enum("SYNC_DUE")? {
+            self.sync.fields.due = sync;
+        }
+        env_parse("DEDUPE_ON_STARTUP", &mut self.sync.dedupe_on_startup)?;
+        if let Some(correlation) = env_enum("CORRELATION")? {
+            self.sync.correlation = correlation;
+        }
+        env_parse("COMMENTS", &mut self.sync.comments)?;
+        self.sync.notes_as_comments |= std::env::var("NOTES_AS_COMMENTS").is_ok();
+        self.sync.attachments |= std::env::var("ATTACHMENTS").is_ok();
+        self.sync.delete_unlinked_completed |= std::env::var("DELETE_UNLINKED_COMPLETED").is_ok();
+        self.sync.clear_completed |= std::env::var("CLEAR_COMPLETED").is_ok();
+        if let Some(order) = env_enum("TASK_ORDER")? {
+            self.sync.order = order;
+        }
+        if std::env::var("DUE_WITHIN_DAYS").is_ok() {
+            let mut days = 0;
+            env_parse("DUE_WITHIN_DAYS", &mut days)?;
+            self.sync.filter.due_within_days = Some(days);
+        }
+        self.sync.filter.hide_until_start |= std::env::var("HIDE_UNTIL_START").is_ok();
+
+        self.section_writeback.enabled |= std::env::var("SECTION_WRITEBACK").is_ok();
+        if let Ok(v) = std::env::var("LATER_SECTION") {
+            self.section_writeback.later = v;
+        }
+        if let Ok(v) = std::env::var("TODAY_SECTION") {
+            self.section_writeback.today = v;
+        }
+
+        Ok(())
+    }
+
+    /// The config as seen by one user in multi-user mode
+    pub fn for_user(&self, user: &UserConfig) -> Config {
+        let mut config = self.clone();
+        config.users.clear();
+
+        match config.bridge.source {
+            Source::Asana => config.asana.pat = user.pat.clone(),
+            Source::GitHub => {
+                config.github.token = user.pat.clone();
+                config.github.done = format!("{DATA_DIR}/github_done_{}.json", user.name);
+            }
+        }
+        config.asana.project_gid = user.project_gid.clone();
+        if let Some(workspace_gid) = &user.workspace_gid {
+            config.asana.workspace_gid = Some(workspace_gid.clone());
+        }
+        // a user picking their own source doesn't inherit the shared one
+        if user.project_gid.is_some() || user.shared_project_gid.is_some() || user.search.is_some()
+        {
+            config.asana.shared_project_gid = user.shared_project_gid.clone();
+            config.asana.search = user.search.clone();
+        }
+        if let Some(list) = &user.list {
+            config.google.list = list.clone();
+            config.mstodo.list = list.clone();
+            config.jira.project = Some(list.clone());
+            config.trello.list = list.clone();
+            config.notion.database = list.clone();
+            config.taskwarrior.project = list.clone();
+        }
+        if user.list.is_some() || user.list_id.is_some() {
+            config.google.list_id = user.list_id.clone();
+            config.mstodo.list_id = user.list_id.clone();
+            config.trello.list_id = user.list_id.clone();
+            config.notion.database_id = user.list_id.clone();
+        }
+        if let Some(properties) = &user.notion_properties {
+            config.notion.properties = properties.clone();
+        }
+        config.google.token_cache = user
+            .token_cache
+            .clone()
+            .unwrap_or_else(|| format!("{DATA_DIR}/token_cache_{}.json", user.name));
+        config.mstodo.token_cache = user
+            .token_cache
+            .clone()
+            .unwrap_or_else(|| format!("{DATA_DIR}/mstodo_token_cache_{}.json", user.name));
+        config.google.impersonate = user.impersonate.clone();
+        config.bridge.state = user
+            .state
+            .clone()
+            .unwrap_or_else(|| format!("{DATA_DIR}/state_{}.json", user.name));
+        if user.archive.is_some() || config.bridge.archive.is_some() {
+            config.bridge.archive = Some(
+                user.archive
+                    .clone()
+                    .unwrap_or_else(|| format!("{DATA_DIR}/archive_{}.ndjson", user.name)),
+            );
+        }
+        if let Some(filter) = &user.filter {
+            config.sync.filter = filter.clone();
+        }
+        if let Some(correlation) = user.correlation {
+            config.sync.correlation = correlation;
+        }
+        if let Some(delete) = user.delete_unlinked_completed {
+            config.sync.delete_unlinked_completed = delete;
+        }
+        if let Some(policy) = user.on_google_delete {
+            config.sync.on_google_delete = policy;
+        }
+        if let Some(interval_secs) = user.interval_secs {
+            config.bridge.interval_secs = interval_secs;
+        }
+        if let Some(error_budget) = user.error_budget {
+            config.bridge.error_budget = error_budget;
+        }
+        if let Some(active_hours) = &user.active_hours {
+            config.bridge.active_hours = active_hours.clone();
+        }
+        config.report.dir = format!("{}/{}", self.report.dir, user.name);
+
+        config
+    }
+
+    /// Title of the list tasks are mirrored into on `backend`, which also names its section lists
+    pub fn list_title(&self, backend: Backend) -> String {
+        match backend {
+            Backend::Google => self.google.list.clone(),
+            Backend::MsTodo => self.mstodo.list.clone(),
+            Backend::Jira => self.jira.project.clone().unwrap_or_default(),
+            Backend::Trello => self.trello.list.clone(),
+            Backend::Notion => self.notion.database.clone(),
+            Backend::Taskwarrior => self.taskwarrior.project.clone(),
+        }
+    }
+
+    /// Id picking the list on `backend` instead of its title, when one is configured
+    pub fn list_id(&self, backend: Backend) -> Option<String> {
+        match backend {
+            Backend::Google => self.google.list_id.clone(),
+            Backend::MsTodo => self.mstodo.list_id.clone(),
+            // the project is picked by its key already
+            Backend::Jira => None,
+            Backend::Trello => self.trello.list_id.clone(),
+            Backend::Notion => self.notion.database_id.clone(),
+            // projects have no id but their name
+            Backend::Taskwarrior => None,
+        }
+    }
+
+    /// Whether tasks are mirrored into `backend`, as the backend or one of the mirrors
+    fn uses(&self, backend: Backend) -> bool {
+        self.bridge.backend == backend || self.bridge.mirrors.contains(&backend)
+    }
+
+    fn validate(&self) -> Result<()> {
+        let asana = &self.asana;
+        match self.bridge.source {
+            Source::Asana => {
+                if self.users.is_empty() && asana.pat.is_none() {
+                    bail!(
+                        "no asana token configured, set asana.pat in the config file or ASANA_PAT"
+                    );
+                }
+                if sources(&asana.project_gid, &asana.shared_project_gid, &asana.search) > 1 {
+                    bail!(
+                        "set only one of asana.project_gid, asana.shared_project_gid and asana.search"
+                    );
+                }
+            }
+            Source::GitHub => {
+                if self.users.is_empty() && self.github.token.is_none() {
+                    bail!(
+                        "no github token configured, set github.token in the config file or GITHUB_TOKEN"
+                    );
+                }
+                let users_pick_asana_tasks = self
+                    .users
+                    .iter()
+                    .any(|u| sources(&u.project_gid, &u.shared_project_gid, &u.search) > 0);
+                if sources(&asana.project_gid, &asana.shared_project_gid, &asana.search) > 0
+                    || users_pick_asana_tasks
+                {
+                    bail!("asana projects and searches can't be mirrored with the github source");
+                }
+                if self.sync.section_routing {
+                    bail!("sync.section_routing needs sections, which github issues don't have");
+                }
+            }
+        }
+        let mut names = HashSet::new();
+        for user in &self.users {
+            if user.name.is_empty() {
+                bail!("every entry in users needs a name");
+            }
+            if !names.insert(user.name.as_str()) {
+                bail!("user {} is configured twice", user.name);
+            }
+            if user.pat.is_none() {
+                bail!("user {} needs a pat", user.name);
+            }
+            if sources(&user.project_gid, &user.shared_project_gid, &user.search) > 1 {
+                bail!(
+                    "user {} can only set one of project_gid, shared_project_gid and search",
+                    user.name
+                );
+            }
+            if let Some(filter) = &user.filter {
+                TaskFilter::new(filter).with_context(|| format!("user {}", user.name))?;
+            }
+        }
+        if cfg!(not(feature = "metrics")) && self.bridge.metrics.is_some() {
+            bail!("bridge.metrics is set, but this build doesn't include the metrics feature");
+        }
+        if (self.google.impersonate.is_some() || self.users.iter().any(|u| u.impersonate.is_some()))
+            && self.google.service_account.is_none()
+        {
+            bail!("google.impersonate only works with a google.service_account key");
+        }
+        if self.google.reauth
+            && (self.google.auth_flow != AuthFlow::Device || self.google.service_account.is_some())
+        {
+            bail!(
+                "google.reauth sends a device code, it needs google.auth_flow = \"device\" and no service account"
+            );
+        }
+        if !(1..=crate::asana::BATCH_LIMIT).contains(&self.asana.batch_size) {
+            bail!(
+                "asana.batch_size must be between 1 and {}",
+                crate::asana::BATCH_LIMIT
+            );
+        }
+        if self.google.batch_size == 0 {
+            bail!("google.batch_size must be at least 1");
+        }
+        if self.uses(Backend::MsTodo) && self.mstodo.client_id.is_none() {
+            bail!("microsoft to do needs mstodo.client_id or MSTODO_CLIENT_ID");
+        }
+        if self.uses(Backend::Jira) {
+            let jira = &self.jira;
+            if jira.base_url.is_none() || jira.email.is_none() || jira.token.is_none() {
+                bail!(
+                    "jira needs jira.base_url, jira.email and jira.token, or JIRA_URL, JIRA_EMAIL and JIRA_TOKEN"
+                );
+            }
+            if jira.project.is_none()
+                && (self.users.is_empty() || self.users.iter().any(|u| u.list.is_none()))
+            {
+                bail!("jira needs jira.project or JIRA_PROJECT");
+            }
+            if self.sync.section_routing {
+                bail!("sync.section_routing creates lists, which the jira backend can't");
+            }
+        }
+        if self.uses(Backend::Taskwarrior) && self.taskwarrior.command.is_empty() {
+            bail!("taskwarrior.command can't be empty");
+        }
+        if self.uses(Backend::Notion) && self.notion.token.is_none() {
+            bail!("notion needs notion.token or NOTION_TOKEN");
+        }
+        if self.uses(Backend::Trello) {
+            let trello = &self.trello;
+            if trello.key.is_none() || trello.token.is_none() || trello.board.is_none() {
+                bail!(
+                    "trello needs trello.key, trello.token and trello.board, or TRELLO_KEY, TRELLO_TOKEN and TRELLO_BOARD"
+                );
+            }
+        }
+        let mut mirrors = HashSet::new();
+        for mirror in &self.bridge.mirrors {
+            if *mirror == self.bridge.backend || !mirrors.insert(mirror) {
+                bail!(
+                    "{} is in bridge.mirrors twice, or is the backend already",
+                    mirror.name()
+                );
+            }
+        }
+        if !mirrors.is_empty() && self.sync.section_routing {
+            bail!("sync.section_routing can't be used with bridge.mirrors");
+        }
+        if self.sync.clear_completed && self.bridge.backend != Backend::Google {
+            bail!("sync.clear_completed only works with the google backend");
+        }
+        if self.calendar.enabled {
+            if cfg!(not(feature = "calendar")) {
+                bail!(
+                    "calendar.enabled is set, but this build doesn't include the calendar feature"
+                );
+            }
+            if self.bridge.backend != Backend::Google {
+                bail!("calendar events are only created with the google backend");
+            }
+            if self.calendar.event_minutes == 0 {
+                bail!("calendar.event_minutes must be at least 1");
+            }
+        }
+        if self
+            .sync
+            .star_marker
+            .as_deref()
+            .is_some_and(|m| m.trim().is_empty())
+        {
+            bail!("sync.star_marker can't be blank");
+        }
+        if self.sync.notes_as_comments && self.sync.fields.notes.to_asana() {
+            bail!(
+                "sync.notes_as_comments only works when notes sync to google alone, sync.fields.notes = \"to_google\""
+            );
+        }
+        if self.sync.max_catch_up_days == 0 {
+            bail!("sync.max_catch_up_days must be at least 1");
+        }
+        if self.email.smtp_host.is_some() {
+            if cfg!(not(feature = "email")) {
+                bail!("email.smtp_host is set, but this build doesn't include the email feature");
+            }
+            if self.email.from.is_none() || self.email.to.is_none() {
+                bail!("email digests need both email.from and email.to");
+            }
+            if self.email.after_failures == 0 {
+                bail!("email.after_failures must be at least 1");
+            }
+        }
+        if self.notify.webhooks.iter().any(|w| w.url.is_empty()) {
+            bail!("every entry in notify.webhooks needs a url");
+        }
+        if self.bridge.interval_secs == 0 {
+            bail!("bridge.interval_secs must be at least 1");
+        }
+        if let Some(user) = self.users.iter().find(|u| u.interval_secs == Some(0)) {
+            bail!("interval_secs of user {} must be at least 1", user.name);
+        }
+        if self.bridge.breaker_after > 0 && self.bridge.breaker_probe_secs == 0 {
+            bail!("bridge.breaker_probe_secs must be at least 1");
+        }
+        if self.bridge.request_timeout_secs == 0 {
+            bail!("bridge.request_timeout_secs must be at least 1");
+        }
+        if self.bridge.request_quiet_ms > self.bridge.request_max_delay_ms {
+            bail!("bridge.request_quiet_ms can't be longer than bridge.request_max_delay_ms");
+        }
+        if self.sync.max_deletion_percent > 100 {
+            bail!(
+                "sync.max_deletion_percent is {}, it must be between 0 and 100",
+                self.sync.max_deletion_percent
+            );
+        }
+        // the other lists are the section lists, where routed tasks belong
+        if self.sync.section_routing && self.sync.misplaced_tasks != MisplacedPolicy::Off {
+            bail!("sync.misplaced_tasks can't be used with sync.section_routing");
+        }
+        TaskFilter::new(&self.sync.filter)?;
+        if let Some(tz) = &self.bridge.timezone {
+            jiff::tz::TimeZone::get(tz)
+                .with_context(|| format!("unknown timezone \"{tz}\" in bridge.timezone"))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// How many of the ways to pick the asana tasks are set
+fn sources(
+    project_gid: &Option<String>,
+    shared_project_gid: &Option<String>,
+    search: &Option<SearchQuery>,
+) -> usize {
+    [
+        project_gid.is_some(),
+        shared_project_gid.is_some(),
+        search.is_some(),
+    ]
+    .into_iter()
+    .filter(|set| *set)
+    .count()
+}
+
+fn env_string(name: &str, target: &mut Option<String>) {
+    if let Ok(v) = std::env::var(name) {
+        *target = Some(v);
+    }
+}
+
+fn env_parse<T: FromStr>(name: &str, target: &mut T) -> Result<()> {
+    if let Ok(v) = std::env::var(name) {
+        *target = v
+            .parse()
+            .map_err(|_| anyhow::anyhow!("{name} must be a number, got \"{v}\""))?;
+    }
+    Ok(())
+}
+
+/// Parse a policy env var the same way the config file spells it
+fn env_enum<T: DeserializeOwned>(name: &str) -> Result<Option<T>> {
+    match std::env::var(name) {
+        Ok(v) => {
+            let parsed = T::deserialize(v.as_str().into_deserializer())
+                .map_err(|e: ValueError| anyhow::anyhow!("invalid {name}: {e}"))?;
+            Ok(Some(parsed))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_partial_file_over_defaults() {
+        let config: Config = toml::from_str(
+            r#"
+            [asana]
+            pat = "2/abc"
+
+            [sync]
+            subtasks = "checklist"
+            on_asana_delete = "flag"
+            max_deletions = 5
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.asana.pat,
+            Some(SecretSource::Value("2/abc".to_string()))
+        );
+        assert_eq!(
+            config.google.client_secret,
+            SecretSource::File(format!("{SECRET_DIR}/client_secret.json"))
+        );
+        assert_eq!(config.asana.base_url, crate::asana::DEFAULT_BASE_URL);
+        assert_eq!(config.google.list, "Asana");
+        assert_eq!(config.google.auth_flow, AuthFlow::Redirect);
+        assert_eq!(config.sync.subtasks, Some(FlattenPolicy::Checklist));
+        assert_eq!(config.sync.on_asana_delete, AsanaDeletePolicy::Flag);
+        assert_eq!(config.sync.completion_cascade, CascadePolicy::Leave);
+        assert_eq!(config.sync.max_deletions, 5);
+        assert_eq!(config.sync.max_deletion_percent, 50);
+    }
+
+    #[test]
+    fn secrets_take_bare_strings_or_sources() {
+        let config: Config = toml::from_str(
+            r#"
+            [asana]
+            pat = { command = ["pass", "show", "asana"] }
+
+            [google]
+            client_secret = "/run/secrets/client_secret.json"
+            service_account = { env = "GOOGLE_KEY" }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.asana.pat,
+            Some(SecretSource::Command(vec![
+                "pass".to_string(),
+                "show".to_string(),
+                "asana".to_string()
+            ]))
+        );
+        assert_eq!(
+            config.google.client_secret,
+            SecretSource::File("/run/secrets/client_secret.json".to_string())
+        );
+        assert_eq!(
+            config.google.service_account,
+            Some(SecretSource::Env("GOOGLE_KEY".to_string()))
+        );
+    }
+
+    #[test]
+    fn users_get_their_own_tokens_and_state() {
+        let config: Config = toml::from_str(
+            r#"
+            [google]
+            list = "Asana"
+
+            [[users]]
+            name = "alice"
+            pat = "2/alice"
+            project_gid = "1"
+
+            [[users]]
+            name = "bob"
+            pat = { env = "BOB_PAT" }
+            project_gid = "2"
+            list = "Work"
+            state = "/srv/bob.json"
+            archive = "/srv/bob.ndjson"
+            filter = { tags = ["sync"] }
+            on_google_delete = "complete"
+            "#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+
+        let alice = config.for_user(&config.users[0]);
+        assert_eq!(
+            alice.asana.pat,
+            Some(SecretSource::Value("2/alice".to_string()))
+        );
+        assert_eq!(alice.asana.project_gid.as_deref(), Some("1"));
+        assert_eq!(alice.google.list, "Asana");
+        assert_eq!(
+            alice.google.token_cache,
+            format!("{DATA_DIR}/token_cache_alice.json")
+        );
+        assert_eq!(alice.bridge.state, format!("{DATA_DIR}/state_alice.json"));
+        assert_eq!(alice.bridge.archive, None);
+        assert_eq!(alice.report.dir, format!("{DATA_DIR}/reports/alice"));
+        assert!(alice.users.is_empty());
+
+        let bob = config.for_user(&config.users[1]);
+        assert_eq!(bob.google.list, "Work");
+        assert_eq!(bob.bridge.state, "/srv/bob.json");
+        assert_eq!(bob.bridge.archive.as_deref(), Some("/srv/bob.ndjson"));
+        assert_eq!(bob.sync.filter.tags, ["sync"]);
+        assert!(alice.sync.filter.is_empty());
+        assert_eq!(bob.sync.on_google_delete, GoogleDeletePolicy::Complete);
+        assert_eq!(alice.sync.on_google_delete, GoogleDeletePolicy::Recreate);
+    }
+
+    #[test]
+    fn users_can_map_their_own_notion_database() {
+        let config: Config = toml::from_str(
+            r#"
+            [bridge]
+            backend = "notion"
+
+            [notion]
+            token = "secret_abc"
+            database_id = "db0"
+
+            [[users]]
+            name = "alice"
+            pat = "2/alice"
+
+            [[users]]
+            name = "bob"
+            pat = "2/bob"
+            list_id = "db1"
+            notion_properties = { title = "Task", notes = "Details", due = "Deadline" }
+            "#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+
+        let alice = config.for_user(&config.users[0]);
+        assert_eq!(alice.notion.database_id.as_deref(), Some("db0"));
+        assert_eq!(alice.notion.properties, NotionProperties::default());
+
+        let bob = config.for_user(&config.users[1]);
+        assert_eq!(bob.notion.database_id.as_deref(), Some("db1"));
+        assert_eq!(bob.notion.properties.title, "Task");
+        assert_eq!(bob.notion.properties.due.as_deref(), Some("Deadline"));
+        assert_eq!(bob.notion.properties.done_status, "Done");
+    }
+
+    #[test]
+    fn mirrors_need_their_own_settings() {
+        let parse = |toml: &str| -> Config { toml::from_str(toml).unwrap() };
+        let base = "[asana]\npat = \"2/abc\"\n";
+
+        let config = parse(&format!(
+            "{base}[bridge]\nmirrors = [\"taskwarrior\"]\n[taskwarrior]\nproject = \"Work\"\n"
+        ));
+        config.validate().unwrap();
+        assert_eq!(config.list_title(config.bridge.mirrors[0]), "Work");
+
+        // a mirror is checked like the backend would be
+        let trello = parse(&format!("{base}[bridge]\nmirrors = [\"trello\"]\n"));
+        assert!(trello.validate().is_err());
+        let twice = parse(&format!("{base}[bridge]\nmirrors = [\"google\"]\n"));
+        assert!(twice.validate().is_err());
+    }
+
+    #[test]
+    fn github_source_needs_no_asana_token() {
+        let config: Config = toml::from_str(
+            r#"
+            [bridge]
+            source = "github"
+
+            [[users]]
+            name = "alice"
+            pat = "ghp_alice"
+            "#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+
+        let alice = config.for_user(&config.users[0]);
+        assert!(alice.asana.pat.is_none());
+        assert!(alice.github.token.is_some());
+        assert_eq!(
+            alice.github.done,
+            format!("{DATA_DIR}/github_done_alice.json")
+        );
+
+        let shared: Config = toml::from_str(
+            r#"
+            [bridge]
+            source = "github"
+
+            [github]
+            token = "ghp_abc"
+
+            [asana]
+            shared_project_gid = "10"
+            "#,
+        )
+        .unwrap();
+        assert!(shared.validate().is_err());
+    }
+
+    #[test]
+    fn users_can_mirror_a_shared_project_or_search() {
+        let config: Config = toml::from_str(
+            r#"
+            [asana]
+            shared_project_gid = "10"
+
+            [[users]]
+            name = "alice"
+            pat = "2/alice"
+
+            [[users]]
+            name = "bob"
+            pat = "2/bob"
+            project_gid = "2"
+
+            [[users]]
+            name = "carol"
+            pat = "2/carol"
+            shared_project_gid = "20"
+
+            [[users]]
+            name = "dave"
+            pat = "2/dave"
+            search = { assignees = ["me"], due_within_days = 7 }
+            "#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+
+        let alice = config.for_user(&config.users[0]);
+        assert_eq!(alice.asana.shared_project_gid.as_deref(), Some("10"));
+        let bob = config.for_user(&config.users[1]);
+        assert_eq!(bob.asana.shared_project_gid, None);
+        assert_eq!(bob.asana.project_gid.as_deref(), Some("2"));
+        let carol = config.for_user(&config.users[2]);
+        assert_eq!(carol.asana.shared_project_gid.as_deref(), Some("20"));
+        let dave = config.for_user(&config.users[3]);
+        assert_eq!(dave.asana.shared_project_gid, None);
+        assert_eq!(
+            dave.asana.search.map(|search| search.due_within_days),
+            Some(Some(7))
+        );
+    }
+
+    #[test]
+    fn webhooks_default_to_errors_and_conflicts() {
+        let config: Config = toml::from_str(
+            r#"
+            [asana]
+            pat = "2/abc"
+
+            [[notify.webhooks]]
+            url = "https://discord.com/api/webhooks/1/abc"
+            kind = "discord"
+
+            [[notify.webhooks]]
+            url = "https://hooks.slack.com/services/T/B/x"
+            on = ["mutations"]
+            "#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+
+        let webhooks = &config.notify.webhooks;
+        assert_eq!(webhooks[0].kind, WebhookKind::Discord);
+        assert_eq!(
+            webhooks[0].on,
+            [NotifyEvent::Errors, NotifyEvent::Conflicts]
+        );
+        assert_eq!(webhooks[1].kind, WebhookKind::Slack);
+        assert_eq!(webhooks[1].on, [NotifyEvent::Mutations]);
+    }
+
+    #[test]
+    fn rejects_invalid_filter_pattern() {
+        let config: Config = toml::from_str(
+            r#"
+            [asana]
+            pat = "2/abc"
+
+            [sync.filter]
+            name = "(unclosed"
+            "#,
+        )
+        .unwrap();
+
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn notes_as_comments_needs_notes_going_to_google() {
+        let config: Config = toml::from_str(
+            r#"
+            [asana]
+            pat = "2/abc"
+
+            [sync]
+            notes_as_comments = true
+
+            [sync.fields]
+            notes = "both"
+            "#,
+        )
+        .unwrap();
+
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn rejects_duplicate_users() {
+        let config: Config = toml::from_str(
+            r#"
+            [[users]]
+            name = "alice"
+            pat = "2/a"
+            project_gid = "1"
+
+            [[users]]
+            name = "alice"
+            pat = "2/b"
+            project_gid = "2"
+            "#,
+        )
+        .unwrap();
+
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_values() {
+        let typo = toml::from_str::<Config>("[sync]\nmax_deletion = 5\n").unwrap_err();
+        assert!(typo.to_string().contains("max_deletion"), "{typo}");
+
+        let bad_policy =
+            toml::from_str::<Config>("[sync]\non_google_delete = \"ignore\"\n").unwrap_err();
+        assert!(bad_policy.to_string().contains("ignore"), "{bad_policy}");
+    }
+
+    #[test]
+    fn example_file_is_valid() {
+        let config: Config = toml::from_str(include_str!("../bridge.example.toml")).unwrap();
+        config.validate().unwrap();
+    }
+}
@@ -0,0 +1,236 @@
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use google_tasks1::{
+    common::GetToken,
+    yup_oauth2::authenticator_delegate::{
+        DefaultDeviceFlowDelegate, DefaultInstalledFlowDelegate, DeviceAuthResponse,
+        DeviceFlowDelegate, InstalledFlowDelegate,
+    },
+};
+use log::{error, info, warn};
+
+use crate::{
+    logging,
+    notify::{self, NotifyEvent},
+};
+
+type TokenResult = Result<Option<String>, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Google wants consent again while the bridge runs: it refused the refresh token, which was
+/// revoked or expired
+#[derive(Debug)]
+pub struct ReauthNeeded;
+
+impl fmt::Display for ReauthNeeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "google refused the refresh token, access to google was revoked or has expired"
+        )
+    }
+}
+
+impl std::error::Error for ReauthNeeded {}
+
+/// What one authenticator went through, shared by its consent delegate and the [`Watched`]
+/// wrapper around it
+#[derive(Default)]
+pub(crate) struct Consent {
+    /// A token was handed out, so being asked for consent after means the refresh token is gone
+    granted: AtomicBool,
+    revoked: AtomicBool,
+    /// Ask for consent again with a device code, see `google.reauth`
+    reauth: bool,
+    /// A device code is out and polled for in the background
+    reauthing: AtomicBool,
+}
+
+impl Consent {
+    pub fn new(reauth: bool) -> Arc<Self> {
+        Arc::new(Self {
+            reauth,
+            ..Self::default()
+        })
+    }
+
+    fn granted(&self) -> bool {
+        self.granted.load(Ordering::SeqCst)
+    }
+
+    /// Stop asking google for tokens, and tell the user how to grant access again
+    async fn revoke(&self, message: String) {
+        self.revoked.store(true, Ordering::SeqCst);
+        error!("{message}");
+        notify::push(NotifyEvent::Errors, message);
+        // cycles fail fast from now on, this may be the only chance to send it for a while
+        notify::flush().await;
+    }
+}
+
+/// How to grant access again by hand, for the current user
+fn grant_by_hand() -> String {
+    let user = logging::USER
+        .try_with(|name| format!("--user {name} "))
+        .unwrap_or_default();
+    format!(
+        "{ReauthNeeded}. Grant it again with `gtasks-asana-bridge {user}sync --once` and restart the bridge"
+    )
+}
+
+/// Asks for consent as usual while the bridge starts up, and gives up once it has had a token
+pub(crate) struct InstalledDelegate(pub Arc<Consent>);
+
+impl InstalledFlowDelegate for InstalledDelegate {
+    fn present_user_url<'a>(
+        &'a self,
+        url: &'a str,
+        need_code: bool,
+    ) -> Pin<Box<dyn Future<Output = Result<String, String>> + Send + 'a>> {
+        if !self.0.granted() {
+            let default: &'static DefaultInstalledFlowDelegate = &DefaultInstalledFlowDelegate;
+            return default.present_user_url(url, need_code);
+        }
+        // nobody is there to open the url, the redirect would be waited on forever
+        Box::pin(async move {
+            self.0.revoke(grant_by_hand()).await;
+            Err(ReauthNeeded.to_string())
+        })
+    }
+}
+
+/// Prints the device code while the bridge starts up. Once it has had a token, the code is sent to
+/// the webhooks with `google.reauth`, and the user is told to grant access by hand without.
+pub(crate) struct DeviceDelegate(pub Arc<Consent>);
+
+impl DeviceFlowDelegate for DeviceDelegate {
+    fn present_user_code<'a>(
+        &'a self,
+        resp: &'a DeviceAuthResponse,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+        if !self.0.granted() {
+            let default: &'static DefaultDeviceFlowDelegate = &DefaultDeviceFlowDelegate;
+            return default.present_user_code(resp);
+        }
+        Box::pin(async move {
+            let message = if self.0.reauth {
+                format!(
+                    "{ReauthNeeded}. To grant it again, enter the code {} at {}",
+                    resp.user_code, resp.verification_uri
+                )
+            } else {
+                grant_by_hand()
+            };
+            self.0.revoke(message).await;
+        })
+    }
+}
+
+/// Token source that stops asking google once it refused the refresh token, instead of failing
+/// every cycle on a consent nobody gives. With `google.reauth`, a device code is polled for in the
+/// background meanwhile, and syncing resumes once it's entered.
+#[derive(Clone)]
+pub struct Watched<T> {
+    inner: T,
+    consent: Arc<Consent>,
+}
+
+impl<T: GetToken + Clone + 'static> Watched<T> {
+    pub(crate) fn new(inner: T, consent: Arc<Consent>) -> Self {
+        Self { inner, consent }
+    }
+
+    /// Ask for consent with a new device code, outside of any cycle so it's polled for until it
+    /// expires. One that expires unused makes the next cycle send another.
+    fn reauth_in_background(&self, scopes: &[&str]) {
+        if self.consent.reauthing.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let (inner, consent) = (self.inner.clone(), self.consent.clone());
+        let scopes: Vec<String> = scopes.iter().map(|scope| scope.to_string()).collect();
+        let reauth = async move {
+            let scopes: Vec<&str> = scopes.iter().map(String::as_str).collect();
+            match inner.get_token(&scopes).await {
+                Ok(Some(_)) => {
+                    consent.revoked.store(false, Ordering::SeqCst);
+                    let message = "Access to google was granted again, syncing resumes";
+                    info!("{message}");
+                    notify::push(NotifyEvent::Errors, message);
+                }
+                Ok(None) => {}
+                Err(e) => warn!("Asking for access to google again failed: {e}"),
+            }
+            consent.reauthing.store(false, Ordering::SeqCst);
+        };
+        let user = logging::USER.try_with(String::clone).ok();
+        tokio::spawn(async move {
+            match user {
+                Some(user) => logging::USER.scope(user, reauth).await,
+                None => reauth.await,
+            }
+        });
+    }
+}
+
+impl<T: GetToken + Clone + 'static> GetToken for Watched<T> {
+    fn get_token<'a>(
+        &'a self,
+        scopes: &'a [&str],
+    ) -> Pin<Box<dyn Future<Output = TokenResult> + Send + 'a>> {
+        Box::pin(async move {
+            if self.consent.revoked.load(Ordering::SeqCst) {
+                if self.consent.reauth {
+                    self.reauth_in_background(scopes);
+                }
+                return Err(ReauthNeeded.into());
+            }
+            let token = self.inner.get_token(scopes).await?;
+            if token.is_some() {
+                self.consent.granted.store(true, Ordering::SeqCst);
+            }
+            Ok(token)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Counting(Arc<AtomicUsize>);
+
+    impl GetToken for Counting {
+        fn get_token<'a>(
+            &'a self,
+            _scopes: &'a [&str],
+        ) -> Pin<Box<dyn Future<Output = TokenResult> + Send + 'a>> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async { Ok(Some("token".to_string())) })
+        }
+    }
+
+    #[tokio::test]
+    async fn a_revoked_token_isnt_asked_for_again() {
+        let calls = Counting::default();
+        let consent = Consent::new(false);
+        let watched = Watched::new(calls.clone(), consent.clone());
+
+        assert!(watched.get_token(&["tasks"]).await.unwrap().is_some());
+        assert!(consent.granted());
+
+        consent.revoked.store(true, Ordering::SeqCst);
+        let e = watched.get_token(&["tasks"]).await.unwrap_err();
+        assert!(e.is::<ReauthNeeded>());
+        assert_eq!(calls.0.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,73 @@
+use std::sync::{
+    Arc, Mutex, Weak,
+    atomic::{AtomicBool, Ordering},
+};
+
+#[cfg(unix)]
+use anyhow::{Context, Result};
+use log::info;
+
+use crate::cycle::Debounce;
+
+/// Whether cycles are skipped, for every user's loop in the process
+static PAUSED: AtomicBool = AtomicBool::new(false);
+
+/// Where each user's loop takes requests for a cycle
+static LOOPS: Mutex<Vec<Weak<Debounce>>> = Mutex::new(Vec::new());
+
+/// Skip cycles from now on, the one running goes on to the end
+pub fn pause() {
+    if !PAUSED.swap(true, Ordering::SeqCst) {
+        info!("Paused syncing, cycles are skipped until it's resumed");
+    }
+}
+
+pub fn resume() {
+    if PAUSED.swap(false, Ordering::SeqCst) {
+        info!("Resumed syncing");
+    }
+}
+
+pub fn is_paused() -> bool {
+    PAUSED.load(Ordering::SeqCst)
+}
+
+/// Have [`sync_soon`] reach a loop through `requests`
+pub fn register(requests: &Arc<Debounce>) {
+    let mut loops = LOOPS.lock().unwrap();
+    loops.retain(|l| l.strong_count() > 0);
+    loops.push(Arc::downgrade(requests));
+}
+
+/// Ask every loop for a cycle, once the burst this request is part of settles
+pub fn sync_soon() {
+    for requests in LOOPS.lock().unwrap().iter().filter_map(Weak::upgrade) {
+        requests.request();
+    }
+}
+
+/// Pause on `SIGUSR1`, resume on `SIGUSR2` and sync soon on `SIGHUP`, for as long as the process
+/// runs
+#[cfg(unix)]
+pub fn listen() -> Result<()> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    for (kind, name, paused) in [
+        (SignalKind::user_defined1(), "SIGUSR1", true),
+        (SignalKind::user_defined2(), "SIGUSR2", false),
+    ] {
+        let mut signals = signal(kind).with_context(|| format!("failed to listen for {name}"))?;
+        tokio::spawn(async move {
+            while signals.recv().await.is_some() {
+                if paused { pause() } else { resume() }
+            }
+        });
+    }
+    let mut hangups = signal(SignalKind::hangup()).context("failed to listen for SIGHUP")?;
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            sync_soon();
+        }
+    });
+    Ok(())
+}
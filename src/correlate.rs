@@ -0,0 +1,184 @@
+use std::{fmt, sync::Arc};
+
+use serde::Deserialize;
+
+use crate::google;
+
+/// How a google task is tied back to its asana task, besides the link in the state store
+pub trait Correlator: fmt::Debug + Send + Sync {
+    /// Asana gid a google task is tagged with, `None` when it carries no tag and only the state
+    /// store can link it
+    fn gid(&self, gtask: &google::Task) -> Option<String>;
+
+    /// Title and notes to write for a task showing `title` and `notes`, tagged with the asana task
+    /// `gid`, or with any tag removed for `None`. `title` and `notes` may carry a tag already.
+    fn tag(&self, title: &str, notes: &str, gid: Option<&str>) -> (String, String);
+
+    /// `gtask` as it compares with its asana task, without the parts of the tag the user sees
+    fn untagged(&self, gtask: &google::Task) -> google::Task {
+        gtask.clone()
+    }
+
+    /// Whether copies carry a tag at all, otherwise a copy without one isn't worth reporting
+    fn tags(&self) -> bool {
+        true
+    }
+}
+
+/// Which [`Correlator`] a mapping uses
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Correlation {
+    /// A metadata block at the end of the notes
+    #[default]
+    Notes,
+    /// Nothing written to google, the state store is the only link
+    State,
+    /// A `[asana:<gid>]` token at the end of the title
+    Title,
+}
+
+impl Correlation {
+    pub fn correlator(self) -> Arc<dyn Correlator> {
+        match self {
+            Correlation::Notes => Arc::new(NotesMarker),
+            Correlation::State => Arc::new(StateOnly),
+            Correlation::Title => Arc::new(TitleToken),
+        }
+    }
+}
+
+/// The gid in the bridge's metadata block at the end of the notes, see [`google::split_marker`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NotesMarker;
+
+impl Correlator for NotesMarker {
+    fn gid(&self, gtask: &google::Task) -> Option<String> {
+        google::get_asana_task_gid(gtask)
+    }
+
+    fn tag(&self, title: &str, notes: &str, gid: Option<&str>) -> (String, String) {
+        let body = google::notes_body(notes);
+        let notes = match gid {
+            Some(gid) => google::with_marker(body, gid),
+            None => body.to_string(),
+        };
+        (title.to_string(), notes)
+    }
+}
+
+/// No tag at all, for lists whose notes mustn't be touched. A copy that loses its state link, e.g.
+/// when the state file is lost, can only be found again by [`crate::repair::dedupe`]'s title and
+/// due date match.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StateOnly;
+
+impl Correlator for StateOnly {
+    fn gid(&self, _gtask: &google::Task) -> Option<String> {
+        None
+    }
+
+    fn tag(&self, title: &str, notes: &str, _gid: Option<&str>) -> (String, String) {
+        // drops a marker left behind by the notes correlator
+        (title.to_string(), google::notes_body(notes).to_string())
+    }
+
+    fn tags(&self) -> bool {
+        false
+    }
+}
+
+/// A `[asana:<gid>]` token at the end of the title
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TitleToken;
+
+const TOKEN_START: &str = " [asana:";
+
+impl TitleToken {
+    /// The title without its token, and the gid in the token
+    fn split(title: &str) -> (&str, Option<&str>) {
+        let token = title.rfind(TOKEN_START).and_then(|start| {
+            let gid = title[start + TOKEN_START.len()..].strip_suffix(']')?;
+            (!gid.is_empty() && gid.chars().all(|c| c.is_ascii_digit())).then_some((start, gid))
+        });
+        match token {
+            Some((start, gid)) => (&title[..start], Some(gid)),
+            None => (title, None),
+        }
+    }
+}
+
+impl Correlator for TitleToken {
+    fn gid(&self, gtask: &google::Task) -> Option<String> {
+        Self::split(gtask.title.as_deref()?).1.map(str::to_string)
+    }
+
+    fn tag(&self, title: &str, notes: &str, gid: Option<&str>) -> (String, String) {
+        let title = Self::split(title).0;
+        let title = match gid {
+            Some(gid) => format!("{title}{TOKEN_START}{gid}]"),
+            None => title.to_string(),
+        };
+        (title, google::notes_body(notes).to_string())
+    }
+
+    fn untagged(&self, gtask: &google::Task) -> google::Task {
+        let mut gtask = gtask.clone();
+        if let Some(title) = &gtask.title {
+            gtask.title = Some(Self::split(title).0.to_string());
+        }
+        gtask
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(title: &str, notes: &str) -> google::Task {
+        google::Task {
+            title: Some(title.to_string()),
+            notes: Some(notes.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn title_token_round_trips() {
+        let (title, notes) = TitleToken.tag("Pay rent", "Landlord", Some("100"));
+        assert_eq!(title, "Pay rent [asana:100]");
+        assert_eq!(notes, "Landlord");
+
+        let tagged = task(&title, &notes);
+        assert_eq!(TitleToken.gid(&tagged).as_deref(), Some("100"));
+        assert_eq!(
+            TitleToken.untagged(&tagged).title.as_deref(),
+            Some("Pay rent")
+        );
+
+        // retagging replaces the token rather than adding a second one
+        let (title, _) = TitleToken.tag(&title, &notes, Some("101"));
+        assert_eq!(title, "Pay rent [asana:101]");
+        let (title, _) = TitleToken.tag(&title, &notes, None);
+        assert_eq!(title, "Pay rent");
+
+        // a title that only looks like it has a token
+        assert_eq!(TitleToken.gid(&task("Call [asana:bob]", "")), None);
+    }
+
+    #[test]
+    fn state_only_writes_nothing() {
+        let (title, notes) = StateOnly.tag("Pay rent", "Landlord", Some("100"));
+        assert_eq!((title.as_str(), notes.as_str()), ("Pay rent", "Landlord"));
+
+        let marked = task("Pay rent", &google::with_marker("Landlord", "100"));
+        assert_eq!(StateOnly.gid(&marked), None);
+        assert_eq!(NotesMarker.gid(&marked).as_deref(), Some("100"));
+        assert_eq!(
+            StateOnly
+                .tag("Pay rent", marked.notes.as_deref().unwrap(), None)
+                .1,
+            "Landlord"
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result, anyhow, bail};
+use chacha20poly1305::{
+    ChaCha20Poly1305, Key, KeyInit, Nonce,
+    aead::{Aead, AeadCore, OsRng},
+};
+use log::info;
+
+/// Start of an encrypted file, followed by the nonce and the ciphertext. Anything else is read as
+/// plaintext, so files written before a key was set keep working.
+const MAGIC: &[u8] = b"gtasks-asana-bridge sealed v1\n";
+const NONCE_LEN: usize = 12;
+/// Start of an encrypted line in the files appended to a line at a time, followed by the nonce and
+/// the ciphertext in hex. Other lines are read as plaintext.
+const LINE_MAGIC: &str = "sealed:";
+
+static CIPHER: OnceLock<ChaCha20Poly1305> = OnceLock::new();
+
+/// Encrypt the state and token caches with `key`, 64 hex digits. Only the first call has any
+/// effect.
+pub fn set_key(key: &str) -> Result<()> {
+    let key = key.trim();
+    if key.len() != 64 || !key.is_ascii() {
+        bail!("the encryption key must be 64 hex digits, e.g. from `openssl rand -hex 32`");
+    }
+    let bytes = (0..32)
+        .map(|i| u8::from_str_radix(&key[2 * i..2 * i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .context("the encryption key must be 64 hex digits")?;
+    let _ = CIPHER.set(ChaCha20Poly1305::new(Key::from_slice(&bytes)));
+    Ok(())
+}
+
+/// Whether files are written encrypted
+pub fn enabled() -> bool {
+    CIPHER.get().is_some()
+}
+
+/// Contents of `path`, decrypted when it was written encrypted. `None` when there's no such file.
+pub fn read(path: &str) -> Result<Option<Vec<u8>>> {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {path}")),
+    };
+    let Some(sealed) = data.strip_prefix(MAGIC) else {
+        return Ok(Some(data));
+    };
+
+    let cipher = CIPHER
+        .get()
+        .with_context(|| format!("{path} is encrypted, but no encryption key is configured"))?;
+    if sealed.len() < NONCE_LEN {
+        bail!("{path} is truncated");
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow!("failed to decrypt {path}, it was written with another key"))?;
+    Ok(Some(plaintext))
+}
+
+/// Replace `path` with `contents`, encrypted when there's a key. Written then renamed so a crash
+/// never leaves a truncated file behind.
+pub fn write(path: &str, contents: &[u8]) -> Result<()> {
+    let data = match CIPHER.get() {
+        Some(cipher) => {
+            let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+            let ciphertext = cipher
+                .encrypt(&nonce, contents)
+                .map_err(|_| anyhow!("failed to encrypt {path}"))?;
+            [MAGIC, nonce.as_slice(), ciphertext.as_slice()].concat()
+        }
+        None => contents.to_vec(),
+    };
+
+    let tmp_path = format!("{path}.tmp");
+    std::fs::write(&tmp_path, data).with_context(|| format!("failed to write {tmp_path}"))?;
+    std::fs::rename(&tmp_path, path).with_context(|| format!("failed to replace {path}"))?;
+    Ok(())
+}
+
+/// `line` encrypted when there's a key, for files appended to a line at a time like the journal
+/// and the archive
+pub fn seal_line(line: &str) -> Result<String> {
+    let Some(cipher) = CIPHER.get() else {
+        return Ok(line.to_string());
+    };
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, line.as_bytes())
+        .map_err(|_| anyhow!("failed to encrypt a line"))?;
+    let hex: String = nonce
+        .iter()
+        .chain(&ciphertext)
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    Ok(format!("{LINE_MAGIC}{hex}"))
+}
+
+/// `line` decrypted when [`seal_line`] encrypted it, as it is otherwise
+pub fn open_line(line: &str) -> Result<String> {
+    let Some(hex) = line.strip_prefix(LINE_MAGIC) else {
+        return Ok(line.to_string());
+    };
+    let cipher = CIPHER
+        .get()
+        .context("the line is encrypted, but no encryption key is configured")?;
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        bail!("the encrypted line is truncated");
+    }
+    let sealed = (0..hex.len() / 2)
+        .map(|i| u8::from_str_radix(&hex[2 * i..2 * i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .context("the encrypted line is mangled")?;
+    if sealed.len() < NONCE_LEN {
+        bail!("the encrypted line is truncated");
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow!("failed to decrypt a line, it was written with another key"))?;
+    String::from_utf8(plaintext).context("the decrypted line isn't text")
+}
+
+/// Encrypt `path` in place if it's still plaintext, so files that are rarely written don't wait
+/// for it. Does nothing without a key.
+pub fn migrate(path: &str) -> Result<()> {
+    if !enabled() {
+        return Ok(());
+    }
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {path}")),
+    };
+    if !data.starts_with(MAGIC) {
+        write(path, &data)?;
+        info!("Encrypted {path}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plaintext_files_are_encrypted_in_place() {
+        set_key(&"ab".repeat(32)).unwrap();
+        let path = std::env::temp_dir().join("crypt_migrate.json");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, br#"{"links":{}}"#).unwrap();
+
+        assert_eq!(read(path).unwrap().unwrap(), br#"{"links":{}}"#);
+        migrate(path).unwrap();
+        let raw = std::fs::read(path).unwrap();
+        assert!(raw.starts_with(MAGIC));
+        assert!(!raw.windows(5).any(|w| w == b"links"));
+        assert_eq!(read(path).unwrap().unwrap(), br#"{"links":{}}"#);
+    }
+
+    #[test]
+    fn lines_are_sealed_one_at_a_time() {
+        set_key(&"ab".repeat(32)).unwrap();
+        let sealed = seal_line(r#"{"commit":null}"#).unwrap();
+        assert!(sealed.starts_with(LINE_MAGIC));
+        assert!(!sealed.contains("commit"));
+        assert_eq!(open_line(&sealed).unwrap(), r#"{"commit":null}"#);
+
+        // lines written before the key was set
+        assert_eq!(
+            open_line(r#"{"commit":null}"#).unwrap(),
+            r#"{"commit":null}"#
+        );
+        assert!(open_line(&sealed[..sealed.len() - 4]).is_err());
+    }
+}
@@ -0,0 +1,187 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use anyhow::Result;
+use tokio::{
+    sync::{Mutex, Notify},
+    time::Instant,
+};
+
+use crate::state::State;
+
+/// Runs sync cycles one at a time. The state store lives behind the engine's lock, so no two
+/// cycles can ever touch it at once, however they were triggered. A trigger arriving while a cycle
+/// is running doesn't queue up a second one, it makes the running cycle go again once it's done.
+pub struct Engine {
+    state: Mutex<State>,
+    rerun: AtomicBool,
+}
+
+impl Engine {
+    pub fn new(state: State) -> Self {
+        Self {
+            state: Mutex::new(state),
+            rerun: AtomicBool::new(false),
+        }
+    }
+
+    /// Copy of the state store, `None` while a cycle has it
+    pub fn snapshot(&self) -> Option<State> {
+        self.state.try_lock().ok().map(|state| state.clone())
+    }
+
+    /// Run `cycle`, or leave it to the cycle already in progress. Returns once no cycle is
+    /// pending on this trigger's behalf, with the error of the first failed cycle.
+    pub async fn trigger(
+        &self,
+        mut cycle: impl AsyncFnMut(&mut State) -> Result<()>,
+    ) -> Result<()> {
+        loop {
+            self.rerun.store(true, Ordering::SeqCst);
+            let Ok(mut state) = self.state.try_lock() else {
+                // the running cycle sees the flag and goes again
+                return Ok(());
+            };
+
+            while self.rerun.swap(false, Ordering::SeqCst) {
+                cycle(&mut state).await?;
+            }
+            drop(state);
+
+            // a trigger may have slipped in between the last check and unlocking
+            if !self.rerun.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Coalesces bursts of requests for a cycle, like webhooks fired by a bulk edit, into one. A burst
+/// settles once no request came for `quiet`, or `max_delay` after its first request, so a steady
+/// stream of them can't hold the cycle off for good.
+pub struct Debounce {
+    quiet: Duration,
+    max_delay: Duration,
+    /// When the first and the latest request of the unsettled burst came
+    pending: std::sync::Mutex<Option<(Instant, Instant)>>,
+    requested: Notify,
+}
+
+impl Debounce {
+    pub fn new(quiet: Duration, max_delay: Duration) -> Self {
+        Self {
+            quiet,
+            max_delay,
+            pending: std::sync::Mutex::new(None),
+            requested: Notify::new(),
+        }
+    }
+
+    /// Ask for a cycle once the burst this request is part of settles
+    pub fn request(&self) {
+        let now = Instant::now();
+        let mut pending = self.pending.lock().unwrap();
+        let first = pending.map_or(now, |(first, _)| first);
+        *pending = Some((first, now));
+        self.requested.notify_one();
+    }
+
+    /// Forget the requests so far, a cycle starting now takes care of them
+    pub fn clear(&self) {
+        *self.pending.lock().unwrap() = None;
+    }
+
+    /// Wait until a burst of requests settled
+    pub async fn settled(&self) {
+        loop {
+            let wait = {
+                let mut pending = self.pending.lock().unwrap();
+                match *pending {
+                    None => None,
+                    Some((first, last)) => {
+                        let due = (last + self.quiet).min(first + self.max_delay);
+                        let now = Instant::now();
+                        if now >= due {
+                            *pending = None;
+                            return;
+                        }
+                        Some(due - now)
+                    }
+                }
+            };
+            match wait {
+                // later requests only push the end of the burst back, never forward
+                Some(wait) => tokio::time::sleep(wait).await,
+                None => self.requested.notified().await,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn triggers_during_a_cycle_coalesce_into_one_rerun() {
+        let engine = Engine::new(State::default());
+        let running = AtomicUsize::new(0);
+        let runs = AtomicUsize::new(0);
+
+        let cycle = async |_: &mut State| {
+            assert_eq!(
+                running.fetch_add(1, Ordering::SeqCst),
+                0,
+                "cycles overlapped"
+            );
+            tokio::task::yield_now().await;
+            running.fetch_sub(1, Ordering::SeqCst);
+            runs.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        };
+
+        let (a, b, c) = tokio::join!(
+            engine.trigger(cycle),
+            engine.trigger(cycle),
+            engine.trigger(cycle)
+        );
+        a.unwrap();
+        b.unwrap();
+        c.unwrap();
+
+        // the first trigger runs, the other two arrive mid-cycle and share a single rerun
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_burst_of_requests_settles_once() {
+        let debounce = Debounce::new(Duration::from_millis(50), Duration::from_millis(400));
+        let started = Instant::now();
+        let burst = async {
+            for _ in 0..5 {
+                debounce.request();
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        };
+        let ((), ()) = futures::join!(burst, debounce.settled());
+        // the last request came 80ms in, the burst settled quietly 50ms later
+        assert!(started.elapsed() >= Duration::from_millis(130));
+        assert!(debounce.pending.lock().unwrap().is_none());
+
+        // requests that never stop are still cut off after the max delay
+        let started = Instant::now();
+        let endless = async {
+            loop {
+                debounce.request();
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        };
+        futures::future::select(std::pin::pin!(endless), std::pin::pin!(debounce.settled())).await;
+        let waited = started.elapsed();
+        assert!(waited >= Duration::from_millis(400) && waited < Duration::from_millis(600));
+    }
+}
@@ -0,0 +1,158 @@
+use std::{fmt, sync::OnceLock};
+
+use anyhow::Result;
+use jiff::{Timestamp, ToSpan, civil::Date, tz::TimeZone};
+
+static LOCAL_TZ: OnceLock<TimeZone> = OnceLock::new();
+
+/// Use the named timezone rather than the system's. Only the first call has any effect.
+pub fn set_timezone(name: &str) -> Result<()> {
+    let _ = LOCAL_TZ.set(TimeZone::get(name)?);
+    Ok(())
+}
+
+/// Timezone used for every "what day is it" decision, the configured one or else the system's
+pub fn local_tz() -> &'static TimeZone {
+    LOCAL_TZ.get_or_init(TimeZone::system)
+}
+
+/// Calendar date of an instant in the local timezone
+pub fn local_date(ts: Timestamp) -> Date {
+    local_date_in(ts, local_tz())
+}
+
+fn local_date_in(ts: Timestamp, tz: &TimeZone) -> Date {
+    ts.to_zoned(tz.clone()).date()
+}
+
+/// Local date right now
+pub fn today() -> Date {
+    local_date(Timestamp::now())
+}
+
+/// Last day (Sunday) of the week containing `date`
+pub fn end_of_week(date: Date) -> Date {
+    let days_left = 7 - date.weekday().to_monday_one_offset();
+    date + i64::from(days_left).days()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DueStatus {
+    Overdue,
+    Today,
+    Upcoming,
+}
+
+impl fmt::Display for DueStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DueStatus::Overdue => write!(f, "overdue"),
+            DueStatus::Today => write!(f, "due today"),
+            DueStatus::Upcoming => write!(f, "upcoming"),
+        }
+    }
+}
+
+/// Where a due date falls relative to the local day containing `now`
+pub fn due_status(due: Date, now: Timestamp) -> DueStatus {
+    due_status_in(due, now, local_tz())
+}
+
+fn due_status_in(due: Date, now: Timestamp, tz: &TimeZone) -> DueStatus {
+    let today = local_date_in(now, tz);
+    if due < today {
+        DueStatus::Overdue
+    } else if due == today {
+        DueStatus::Today
+    } else {
+        DueStatus::Upcoming
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::civil::date;
+
+    use super::*;
+
+    fn chicago() -> TimeZone {
+        TimeZone::get("America/Chicago").unwrap()
+    }
+
+    fn ts(s: &str) -> Timestamp {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn local_date_rolls_over_at_local_midnight() {
+        let tz = chicago();
+        assert_eq!(
+            local_date_in(ts("2026-01-15T05:59:59Z"), &tz),
+            date(2026, 1, 14)
+        );
+        assert_eq!(
+            local_date_in(ts("2026-01-15T06:00:00Z"), &tz),
+            date(2026, 1, 15)
+        );
+    }
+
+    #[test]
+    fn local_date_follows_spring_forward() {
+        // CDT starts 2026-03-08, so the next midnight is 05:00 UTC instead of 06:00
+        let tz = chicago();
+        assert_eq!(
+            local_date_in(ts("2026-03-08T05:59:59Z"), &tz),
+            date(2026, 3, 7)
+        );
+        assert_eq!(
+            local_date_in(ts("2026-03-09T04:59:59Z"), &tz),
+            date(2026, 3, 8)
+        );
+        assert_eq!(
+            local_date_in(ts("2026-03-09T05:00:00Z"), &tz),
+            date(2026, 3, 9)
+        );
+    }
+
+    #[test]
+    fn local_date_follows_fall_back() {
+        // CST resumes 2026-11-01, so the next midnight is 06:00 UTC instead of 05:00
+        let tz = chicago();
+        assert_eq!(
+            local_date_in(ts("2026-11-01T04:59:59Z"), &tz),
+            date(2026, 10, 31)
+        );
+        assert_eq!(
+            local_date_in(ts("2026-11-02T05:30:00Z"), &tz),
+            date(2026, 11, 1)
+        );
+        assert_eq!(
+            local_date_in(ts("2026-11-02T06:00:00Z"), &tz),
+            date(2026, 11, 2)
+        );
+    }
+
+    #[test]
+    fn end_of_week_is_the_following_sunday() {
+        // 2026-10-12 is a monday
+        assert_eq!(end_of_week(date(2026, 10, 12)), date(2026, 10, 18));
+        assert_eq!(end_of_week(date(2026, 10, 16)), date(2026, 10, 18));
+        assert_eq!(end_of_week(date(2026, 10, 18)), date(2026, 10, 18));
+    }
+
+    #[test]
+    fn due_status_uses_local_day_not_utc() {
+        let tz = chicago();
+        // 23:30 local on the 14th is already the 15th in UTC
+        let now = ts("2026-01-15T05:30:00Z");
+        assert_eq!(due_status_in(date(2026, 1, 14), now, &tz), DueStatus::Today);
+        assert_eq!(
+            due_status_in(date(2026, 1, 15), now, &tz),
+            DueStatus::Upcoming
+        );
+        assert_eq!(
+            due_status_in(date(2026, 1, 13), now, &tz),
+            DueStatus::Overdue
+        );
+    }
+}
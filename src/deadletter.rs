@@ -0,0 +1,50 @@
+use anyhow::{Result, bail};
+use gtasks_asana_bridge::{date, state::State};
+
+use crate::cli::FailedCommand;
+
+/// Run a `failed` subcommand, returning whether the state store changed
+pub fn run(state: &mut State, command: &FailedCommand) -> Result<bool> {
+    match command {
+        FailedCommand::List => {
+            print_failures(state);
+            Ok(false)
+        }
+        FailedCommand::Retry { key } => {
+            let Some(failure) = state.failures.get_mut(key) else {
+                bail!("no failing action {key}, see `failed list`");
+            };
+            if !failure.parked {
+                bail!("{key} isn't parked, it's already tried every cycle");
+            }
+            failure.parked = false;
+            println!("{key} is tried again next cycle");
+            Ok(true)
+        }
+        FailedCommand::Drop { key } => {
+            if state.failures.remove(key).is_none() {
+                bail!("no failing action {key}, see `failed list`");
+            }
+            println!("Forgot the failures of {key}");
+            Ok(true)
+        }
+    }
+}
+
+fn print_failures(state: &State) {
+    if state.failures.is_empty() {
+        println!("No failing actions");
+        return;
+    }
+
+    for (key, failure) in &state.failures {
+        let status = if failure.parked { "parked" } else { "retrying" };
+        println!(
+            "{key}  {status}, failed {} times since {}\n    {}\n    {}",
+            failure.count,
+            date::local_date(failure.first_failed),
+            failure.action,
+            failure.error
+        );
+    }
+}
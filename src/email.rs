@@ -0,0 +1,156 @@
+use anyhow::{Context, Result};
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor, message::Mailbox,
+    transport::smtp::authentication::Credentials,
+};
+use log::{info, warn};
+
+use crate::{
+    config::{EmailConfig, SmtpSecurity},
+    logging,
+    state::State,
+    sync::FailedActions,
+};
+
+/// Emails a digest once cycles have kept failing for a while
+pub struct Mailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Mailbox,
+    after_failures: u32,
+}
+
+impl Mailer {
+    /// `None` when no smtp server is configured
+    pub async fn new(config: &EmailConfig) -> Result<Option<Self>> {
+        let Some(host) = &config.smtp_host else {
+            return Ok(None);
+        };
+
+        let builder = match config.security {
+            SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+            SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+        };
+        let mut builder = builder.port(config.smtp_port);
+        if let Some(username) = &config.username {
+            let password = match &config.password {
+                Some(password) => password
+                    .resolve()
+                    .await
+                    .context("failed to load the smtp password")?,
+                None => String::new(),
+            };
+            builder = builder.credentials(Credentials::new(username.clone(), password));
+        }
+
+        let mailbox = |address: &Option<String>, key: &str| -> Result<Mailbox> {
+            let address = address
+                .as_deref()
+                .with_context(|| format!("{key} is not set"))?;
+            address
+                .parse()
+                .with_context(|| format!("invalid address \"{address}\" in {key}"))
+        };
+
+        Ok(Some(Self {
+            transport: builder.build(),
+            from: mailbox(&config.from, "email.from")?,
+            to: mailbox(&config.to, "email.to")?,
+            after_failures: config.after_failures,
+        }))
+    }
+
+    /// Count the cycle in the state store, and send the digest when it's the failure that makes
+    /// the streak long enough. Each streak gets one email.
+    pub async fn after_cycle(&self, state: &mut State, result: &Result<()>) {
+        let Err(e) = result else {
+            state.failed_cycles = 0;
+            return;
+        };
+        state.failed_cycles += 1;
+        if state.failed_cycles != self.after_failures {
+            return;
+        }
+
+        let (subject, body) = digest(state.failed_cycles, e);
+        let sent = async {
+            let message = Message::builder()
+                .from(self.from.clone())
+                .to(self.to.clone())
+                .subject(subject)
+                .body(body)?;
+            self.transport.send(message).await?;
+            Ok::<_, anyhow::Error>(())
+        }
+        .await;
+        match sent {
+            Ok(()) => info!("Emailed a digest of {} failed cycles", state.failed_cycles),
+            Err(e) => warn!("Failed to email the failure digest: {e:#}"),
+        }
+    }
+}
+
+/// Subject and body of the email about `failures` failed cycles, the last one with `error`
+fn digest(failures: u32, error: &anyhow::Error) -> (String, String) {
+    let user = logging::USER
+        .try_with(|name| format!(" for {name}"))
+        .unwrap_or_default();
+    let subject = format!("gtasks-asana-bridge{user}: {failures} sync cycles failed in a row");
+
+    let mut body = format!("The last {failures} sync cycles{user} failed.\n\nLast error:\n");
+    for (i, cause) in error.chain().enumerate() {
+        body.push_str(&format!("{}{cause}\n", "  ".repeat(i)));
+    }
+
+    if let Some(failures) = error.downcast_ref::<FailedActions>() {
+        body.push_str("\nFailed actions:\n");
+        for (action, error) in &failures.failed {
+            body.push_str(&format!("- {action}: {error}\n"));
+        }
+        if !failures.skipped.is_empty() {
+            body.push_str("\nSkipped because an earlier step for the same task failed:\n");
+            for action in &failures.skipped {
+                body.push_str(&format!("- {action}\n"));
+            }
+        }
+    }
+
+    (subject, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digest_lists_the_error_chain() {
+        let error = anyhow::anyhow!("401 Unauthorized").context("failed to fetch asana tasks");
+
+        let (subject, body) = digest(3, &error);
+
+        assert_eq!(
+            subject,
+            "gtasks-asana-bridge: 3 sync cycles failed in a row"
+        );
+        assert!(body.contains("failed to fetch asana tasks\n  401 Unauthorized\n"));
+    }
+
+    #[test]
+    fn digest_lists_failed_and_skipped_actions() {
+        let error = anyhow::Error::new(FailedActions {
+            failed: vec![(
+                "Google -> Asana task \"Pay rent\" complete, completing in asana".to_string(),
+                "401 Unauthorized".to_string(),
+            )],
+            skipped: vec!["Google task \"Pay rent\" completed in google, deleting".to_string()],
+        });
+
+        let (_, body) = digest(3, &error);
+
+        assert!(body.contains(
+            "- Google -> Asana task \"Pay rent\" complete, completing in asana: 401 Unauthorized\n"
+        ));
+        assert!(body.contains("same task failed:\n- Google task \"Pay rent\""));
+    }
+}
@@ -0,0 +1,482 @@
+use std::{
+    pin::pin,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use jiff::Timestamp;
+use log::{debug, info, warn};
+
+#[cfg(feature = "calendar")]
+use crate::calendar;
+#[cfg(feature = "email")]
+use crate::email;
+use crate::{
+    asana::AsanaApi,
+    breaker::{BackendFailing, Breaker},
+    config::{Backend, Config},
+    control,
+    cycle::{Debounce, Engine},
+    date,
+    error::Result,
+    google::GoogleTasksApi,
+    guardrail::DeletionLimits,
+    hub::{self, Mirror},
+    journal,
+    lock::{self, InstanceLock, LockPolicy},
+    notify, repair, report, schedule, sections,
+    state::State,
+    status, supervisor,
+    sync::{self, FailedActions, Policies, SyncOptions},
+    watchdog::{self, Stalled},
+};
+
+/// Longest a starting bridge waits for another one starting at the same time to claim the lock
+const LOCK_SETTLE: Duration = Duration::from_secs(5);
+
+/// How a [`SyncEngine`] runs, on top of its config
+#[derive(Debug, Clone, Copy)]
+pub struct RunOptions {
+    /// Log what each cycle would change without changing anything
+    pub dry_run: bool,
+    /// Run cycles even if they would delete more tasks than the safety limits allow
+    pub force: bool,
+    /// Load and save the configured state file. Ad-hoc mirrors of other projects keep their state
+    /// in memory.
+    pub persist_state: bool,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self {
+            dry_run: false,
+            force: false,
+            persist_state: true,
+        }
+    }
+}
+
+impl RunOptions {
+    /// What each cycle runs with, as `config` sets it up
+    pub fn sync_options(&self, config: &Config) -> SyncOptions {
+        SyncOptions {
+            policies: Policies {
+                timed_to_calendar: config.calendar.enabled,
+                ..Policies::from_config(&config.sync)
+            },
+            limits: DeletionLimits {
+                max_count: config.sync.max_deletions,
+                max_percent: config.sync.max_deletion_percent,
+                force: self.force,
+            },
+            writeback: config.section_writeback.clone(),
+            metrics_path: config.bridge.metrics.clone(),
+            archive_path: config.bridge.archive.clone(),
+            dry_run: self.dry_run,
+            order: config.sync.order,
+            park_after: config.sync.park_after,
+            max_catch_up_days: config.sync.max_catch_up_days,
+            catch_up_days: 0,
+            // the other backends are sent one request at a time
+            batch_size: match config.bridge.backend {
+                Backend::Google => config.google.batch_size,
+                _ => 1,
+            },
+            asana_batch_size: config.asana.batch_size,
+            comments_fetched: Arc::default(),
+            attachments_fetched: Arc::default(),
+            mirror: None,
+            state_path: self.persist_state.then(|| config.bridge.state.clone()),
+        }
+    }
+}
+
+/// Syncs an asana task source into one task list. Cycles run one at a time, a cycle triggered
+/// while another is running makes that one go again instead.
+pub struct SyncEngine<A, G> {
+    config: Config,
+    asana_mgr: A,
+    gtasks_mgr: G,
+    /// Further backends kept in line alongside `gtasks_mgr`
+    mirrors: Vec<Mirror>,
+    /// Base title of the task lists, for section routing
+    list_title: String,
+    options: SyncOptions,
+    persist_state: bool,
+    /// The next cycle is the first since startup, and catches up on what happened meanwhile
+    catch_up: AtomicBool,
+    /// Cycles were skipped for being outside `bridge.active_hours`
+    inactive: AtomicBool,
+    /// Keeps other bridges from syncing the same state file or list
+    lock: Option<InstanceLock<G>>,
+    cycles: Engine,
+    /// Requests for a cycle before the interval is up, coalesced
+    requests: Arc<Debounce>,
+    /// Skips `gtasks_mgr` while it keeps failing
+    breaker: Breaker,
+    /// Skips the cycle while `asana_mgr` keeps failing, so its outages aren't blamed on the backend
+    source_breaker: Breaker,
+    #[cfg(feature = "calendar")]
+    calendar: Option<calendar::GoogleCalendarMgr>,
+    #[cfg(feature = "email")]
+    mailer: Option<email::Mailer>,
+}
+
+impl<A: AsanaApi, G: GoogleTasksApi> SyncEngine<A, G> {
+    /// Load the state store and clean up after a crash: finish the interrupted action and merge
+    /// duplicate copies. Then log what the first cycle is about to fix.
+    pub async fn new(
+        config: Config,
+        asana_mgr: A,
+        gtasks_mgr: G,
+        list_title: &str,
+        run: RunOptions,
+    ) -> Result<Self> {
+        let state_path = config.bridge.state.as_str();
+        let mut run = run;
+
+        // two bridges syncing the same tasks fight over them, duplicating copies
+        let mut lock = None;
+        if run.persist_state && !run.dry_run && config.bridge.instance_lock != LockPolicy::Off {
+            let stale = lock::stale_after(config.bridge.interval_secs);
+            // the other backends would show the lock list as one of their own
+            let remote = config.bridge.backend == Backend::Google;
+            match lock::acquire(&gtasks_mgr, state_path, remote, stale, LOCK_SETTLE).await? {
+                Ok(held) => lock = Some(held),
+                Err(holder) if config.bridge.instance_lock == LockPolicy::ReadOnly => {
+                    warn!("{holder}, only logging what would change");
+                    run.dry_run = true;
+                }
+                Err(holder) => {
+                    return Err(anyhow!(
+                        "{holder}, stop it first or set bridge.instance_lock to read_only"
+                    )
+                    .into());
+                }
+            }
+        }
+
+        let options = run.sync_options(&config);
+        let mut state = if run.persist_state {
+            State::load(state_path)?
+        } else {
+            State::default()
+        };
+
+        // ad-hoc mirrors of other projects only go to the one list they were given
+        let mirrors = if run.persist_state {
+            Mirror::connect_all(&config).await?
+        } else {
+            Vec::new()
+        };
+
+        // finish whatever the bridge was doing when it was last killed, before planning against it
+        if run.persist_state && !run.dry_run {
+            journal::recover(
+                state_path,
+                &asana_mgr,
+                &gtasks_mgr,
+                &mirrors,
+                &mut state,
+                options.policies.correlator.as_ref(),
+            )
+            .await?;
+        }
+
+        // a crash or an edited marker can leave two google copies of one asana task
+        if run.persist_state && config.sync.dedupe_on_startup {
+            let merge = !run.dry_run;
+            let correlator = options.policies.correlator.as_ref();
+            match repair::dedupe(&asana_mgr, &gtasks_mgr, &mut state, correlator, merge).await {
+                Ok(duplicates) => {
+                    for duplicate in &duplicates {
+                        if merge {
+                            warn!("Merging: {duplicate}");
+                        } else {
+                            info!("[dry run] Would merge: {duplicate}");
+                        }
+                    }
+                    if merge && !duplicates.is_empty() {
+                        state.save(state_path)?;
+                    }
+                }
+                Err(e) => warn!("Failed to merge duplicate google tasks: {e:#}"),
+            }
+        }
+
+        // show what the first cycle is about to fix after downtime
+        if run.persist_state {
+            match status::reconciliation_report(&asana_mgr, &gtasks_mgr, &state).await {
+                Ok(Some(report)) => info!("{report}"),
+                Ok(None) => {}
+                Err(e) => warn!("Failed to build the startup reconciliation report: {e:#}"),
+            }
+        }
+
+        #[cfg(feature = "calendar")]
+        let calendar = if config.calendar.enabled {
+            Some(calendar::GoogleCalendarMgr::new(&config.google, &config.calendar).await?)
+        } else {
+            None
+        };
+
+        #[cfg(feature = "email")]
+        let mailer = email::Mailer::new(&config.email).await?;
+
+        let catch_up = run.persist_state && config.sync.catch_up_days > 0;
+        let breaker = Breaker::new(config.bridge.backend.name(), &config.bridge);
+        let source_breaker = Breaker::new(config.bridge.source.name(), &config.bridge);
+        let requests = Arc::new(Debounce::new(
+            Duration::from_millis(config.bridge.request_quiet_ms),
+            Duration::from_millis(config.bridge.request_max_delay_ms),
+        ));
+        control::register(&requests);
+        Ok(Self {
+            config,
+            asana_mgr,
+            gtasks_mgr,
+            mirrors,
+            list_title: list_title.to_string(),
+            options,
+            persist_state: run.persist_state,
+            catch_up: AtomicBool::new(catch_up),
+            inactive: AtomicBool::new(false),
+            lock,
+            cycles: Engine::new(state),
+            requests,
+            breaker,
+            source_breaker,
+            #[cfg(feature = "calendar")]
+            calendar,
+            #[cfg(feature = "email")]
+            mailer,
+        })
+    }
+
+    /// Where the tasks come from
+    pub fn source(&self) -> &A {
+        &self.asana_mgr
+    }
+
+    /// Where the tasks are mirrored to, besides the mirrors
+    pub fn backend(&self) -> &G {
+        &self.gtasks_mgr
+    }
+
+    /// Run a cycle in [`run_forever`](Self::run_forever) without waiting for the interval, e.g.
+    /// for a webhook. Bursts of requests make one cycle once they settle, see [`Debounce`].
+    pub fn sync_soon(&self) {
+        self.requests.request();
+    }
+
+    /// Copy of the state store, `None` while a cycle is running
+    pub fn state(&self) -> Option<State> {
+        self.cycles.snapshot()
+    }
+
+    /// Run one sync cycle, or leave it to the one already in progress. Failed actions come back
+    /// as [`FailedActions`], a cycle abandoned by the watchdog as [`Stalled`]. Does nothing while
+    /// syncing is [paused](control::pause) or outside `bridge.active_hours`, the first cycle back
+    /// inside them catches up.
+    pub async fn run_once(&self) -> Result<()> {
+        if control::is_paused() {
+            debug!("Syncing is paused, skipping the cycle");
+            return Ok(());
+        }
+        let now = Timestamp::now()
+            .to_zoned(date::local_tz().clone())
+            .datetime();
+        if !schedule::is_active(&self.config.bridge.active_hours, now) {
+            if !self.inactive.swap(true, Ordering::SeqCst) {
+                info!("Outside the active hours, cycles are skipped until they begin again");
+            }
+            return Ok(());
+        }
+        if self.inactive.swap(false, Ordering::SeqCst) {
+            info!("Active hours began, resuming");
+            if self.persist_state && self.config.sync.catch_up_days > 0 {
+                self.catch_up.store(true, Ordering::SeqCst);
+            }
+        }
+        if let Some(lock) = &self.lock {
+            lock.heartbeat().await?;
+        }
+        let (config, options) = (&self.config, &self.options);
+        let (asana_mgr, gtasks_mgr) = (&self.asana_mgr, &self.gtasks_mgr);
+        let state_path = config.bridge.state.as_str();
+
+        self.cycles
+            .trigger(async |state: &mut State| {
+                let catch_up_options;
+                let options = if self.catch_up.swap(false, Ordering::SeqCst) {
+                    catch_up_options = SyncOptions {
+                        catch_up_days: config.sync.catch_up_days,
+                        ..options.clone()
+                    };
+                    &catch_up_options
+                } else {
+                    options
+                };
+                let budget = (config.bridge.cycle_budget_secs > 0)
+                    .then(|| std::time::Duration::from_secs(config.bridge.cycle_budget_secs));
+                let result = watchdog::guard(budget, async {
+                    #[cfg(feature = "calendar")]
+                    if let Some(calendar) = &self.calendar {
+                        watchdog::phase("syncing calendar events");
+                        // fetched separately, the task sync below leaves out timed tasks
+                        let asana_tasks = asana_mgr.get_tasks().await?;
+                        calendar::sync_events(
+                            calendar,
+                            &asana_tasks,
+                            gtasks_mgr,
+                            state,
+                            options.dry_run,
+                        )
+                        .await?;
+                    }
+
+                    // fetched once for every endpoint. A backend that's down doesn't hold up the
+                    // mirrors, nor they it.
+                    let mut failures = FailedActions::default();
+                    let mut fetched = None;
+                    self.source_breaker
+                        .call(async {
+                            let prepared =
+                                sync::prepare_asana_tasks(asana_mgr, state, options, &mut failures)
+                                    .await?;
+                            let asana_sections = if config.sync.section_routing {
+                                asana_mgr.get_sections().await?
+                            } else {
+                                Vec::new()
+                            };
+                            fetched = Some((prepared, asana_sections));
+                            Ok(())
+                        })
+                        .await?;
+                    // the source's breaker is open
+                    let Some(((fetched_at, asana_tasks), asana_sections)) = fetched else {
+                        return Ok(());
+                    };
+
+                    let main = if config.sync.section_routing {
+                        self.breaker
+                            .call(sections::process_routed(
+                                asana_mgr,
+                                &asana_tasks,
+                                &asana_sections,
+                                gtasks_mgr,
+                                &self.list_title,
+                                state,
+                                options,
+                            ))
+                            .await
+                    } else {
+                        self.breaker
+                            .call(sync::sync_endpoint(
+                                asana_mgr,
+                                asana_tasks.clone(),
+                                gtasks_mgr,
+                                state,
+                                options,
+                            ))
+                            .await
+                    };
+                    let main = failures.absorb(main);
+                    // mirrors aren't routed by section
+                    let mirrored = if config.sync.section_routing {
+                        Ok(())
+                    } else {
+                        failures.absorb(
+                            hub::sync_mirrors(
+                                asana_mgr,
+                                &asana_tasks,
+                                &self.mirrors,
+                                state,
+                                options,
+                            )
+                            .await,
+                        )
+                    };
+                    main.and(mirrored)?;
+                    failures.into_result()?;
+                    state.last_fetched = Some(fetched_at);
+                    Ok(())
+                })
+                .await;
+                // the abandoned cycle may have been partway through an action
+                if let Err(e) = &result
+                    && e.is::<Stalled>()
+                    && self.persist_state
+                    && !options.dry_run
+                {
+                    journal::recover(
+                        state_path,
+                        asana_mgr,
+                        gtasks_mgr,
+                        &self.mirrors,
+                        state,
+                        options.policies.correlator.as_ref(),
+                    )
+                    .await?;
+                }
+                #[cfg(feature = "email")]
+                if let Some(mailer) = &self.mailer {
+                    mailer.after_cycle(state, &result).await;
+                }
+                if self.persist_state && !options.dry_run {
+                    if result.is_ok()
+                        && let Err(e) = report::write_scheduled(
+                            gtasks_mgr,
+                            state,
+                            &config.report,
+                            config.bridge.archive.as_deref(),
+                        )
+                        .await
+                    {
+                        warn!("Failed to write the scheduled report: {e:#}");
+                    }
+                    state.save(state_path)?;
+                }
+                notify::flush().await;
+                result
+            })
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Run cycles every `bridge.interval_secs` until one fails, or sooner when
+    /// [asked to](Self::sync_soon). Cycles where only some actions failed, that ran past
+    /// `bridge.cycle_budget_secs` or where a backend failed until its [`Breaker`] opens, are
+    /// logged and the loop goes on, retrying them.
+    pub async fn run_forever(&self) -> Result<()> {
+        loop {
+            self.requests.clear();
+            let cycle = self.run_once().await;
+            match &cycle {
+                Err(e)
+                    if e.is::<FailedActions>() || e.is::<Stalled>() || e.is::<BackendFailing>() =>
+                {
+                    warn!("{e:#}")
+                }
+                Err(_) => return cycle,
+                Ok(()) => {}
+            }
+            supervisor::record_cycle(&cycle);
+            let interval =
+                tokio::time::sleep(Duration::from_secs(self.config.bridge.interval_secs));
+            futures::future::select(pin!(interval), pin!(self.requests.settled())).await;
+        }
+    }
+
+    /// Stop, releasing the [instance lock](lock::acquire) so a bridge started next doesn't wait
+    /// for it to go stale
+    pub async fn shutdown(self) {
+        if let Some(lock) = self.lock {
+            lock.release().await;
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::{sync::Arc, time::Duration};
+
+use reqwest::StatusCode;
+
+/// Why talking to a service failed, so the sync can tell what to do about it: try again later,
+/// give up on the one action, or stop. Carried inside `anyhow::Error` within the crate, found
+/// with [`BridgeError::of`], and what [`SyncEngine`](crate::SyncEngine) returns. Cloned for every
+/// request that was sent along in a batch.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum BridgeError {
+    /// The credentials were refused or couldn't be had, nothing else works until they're fixed
+    #[error("Failed to {what}, the credentials were refused: {detail}")]
+    Auth { what: String, detail: String },
+    /// Told to slow down, with how long to wait when the service says
+    #[error("Failed to {what}. Status: 429 Too Many Requests")]
+    RateLimited {
+        what: String,
+        retry_after: Option<Duration>,
+    },
+    #[error("Failed to {what}. Status: {status}")]
+    NotFound { what: String, status: StatusCode },
+    /// The task changed since it was read, the next cycle plans against the new version
+    #[error("{0}")]
+    Conflict(String),
+    /// The service failed on its end, likely to work again later
+    #[error("Failed to {what}. Status: {status}")]
+    Server { what: String, status: StatusCode },
+    /// Refused for another reason, like a malformed or forbidden request
+    #[error("Failed to {what}. Status: {status}")]
+    Rejected { what: String, status: StatusCode },
+    /// No response at all, like a timeout or a dropped connection
+    #[error("Failed to {what}, no response")]
+    Network {
+        what: String,
+        #[source]
+        source: Arc<dyn std::error::Error + Send + Sync>,
+    },
+    #[error("invalid config: {0}")]
+    InvalidConfig(String),
+    /// Anything else, like a cycle where some actions failed or a state file that can't be read.
+    /// Whatever it carries is classified the same as it would be inside the crate.
+    #[error("{0:#}")]
+    Other(Arc<anyhow::Error>),
+}
+
+/// Result of the library's public API
+pub type Result<T, E = BridgeError> = std::result::Result<T, E>;
+
+/// What the sync does about an action that failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Disposition {
+    /// Try it again next cycle without counting it towards `sync.park_after`, the service failed
+    /// rather than the action
+    Retry,
+    /// Count it against the action, which is parked once it failed `sync.park_after` times
+    Skip,
+    /// Stop the cycle, the actions after it would fail the same way
+    Halt,
+}
+
+impl BridgeError {
+    /// Classify a response that didn't succeed
+    pub fn from_status(what: &str, status: StatusCode, retry_after: Option<Duration>) -> Self {
+        let what = what.to_string();
+        match status {
+            StatusCode::UNAUTHORIZED => Self::Auth {
+                what,
+                detail: status.to_string(),
+            },
+            StatusCode::TOO_MANY_REQUESTS => Self::RateLimited { what, retry_after },
+            StatusCode::NOT_FOUND | StatusCode::GONE => Self::NotFound { what, status },
+            StatusCode::CONFLICT | StatusCode::PRECONDITION_FAILED => Self::Conflict(format!(
+                "Failed to {what}, the task was edited since it was read"
+            )),
+            status if status.is_server_error() => Self::Server { what, status },
+            status => Self::Rejected { what, status },
+        }
+    }
+
+    /// A request to `what` that got no response
+    pub fn network(what: &str, source: impl std::error::Error + Send + Sync + 'static) -> Self {
+        Self::Network {
+            what: what.to_string(),
+            source: Arc::new(source),
+        }
+    }
+
+    /// The `BridgeError` in `e`, also when it was given as context or is further down the chain
+    pub fn of(e: &anyhow::Error) -> Option<&Self> {
+        let found: &Self = e
+            .downcast_ref()
+            .or_else(|| e.chain().find_map(|cause| cause.downcast_ref()))?;
+        match found {
+            Self::Other(inner) => Self::of(inner),
+            found => Some(found),
+        }
+    }
+
+    /// Whether this is an [`Other`](Self::Other) error of type `E`, like
+    /// [`FailedActions`](crate::sync::FailedActions)
+    pub fn is<E>(&self) -> bool
+    where
+        E: std::fmt::Display + std::fmt::Debug + Send + Sync + 'static,
+    {
+        matches!(self, Self::Other(e) if e.is::<E>())
+    }
+
+    pub fn disposition(&self) -> Disposition {
+        match self {
+            Self::Auth { .. } | Self::InvalidConfig(_) => Disposition::Halt,
+            Self::RateLimited { .. } | Self::Server { .. } | Self::Network { .. } => {
+                Disposition::Retry
+            }
+            Self::NotFound { .. } | Self::Conflict(_) | Self::Rejected { .. } => Disposition::Skip,
+            Self::Other(e) => disposition(e),
+        }
+    }
+}
+
+impl From<anyhow::Error> for BridgeError {
+    fn from(e: anyhow::Error) -> Self {
+        e.downcast().unwrap_or_else(|e| Self::Other(Arc::new(e)))
+    }
+}
+
+/// What to do about `e`, an error without a [`BridgeError`] in it counts against the action
+pub fn disposition(e: &anyhow::Error) -> Disposition {
+    BridgeError::of(e).map_or(Disposition::Skip, BridgeError::disposition)
+}
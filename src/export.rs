@@ -0,0 +1,221 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use jiff::Timestamp;
+use serde::Serialize;
+
+use crate::{
+    asana::{self, AsanaApi},
+    google::{self, GoogleTasksApi},
+    state::{Link, State},
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Csv => "csv",
+        }
+    }
+}
+
+/// Every task the bridge sees on either side, and the links between them
+#[derive(Debug, Serialize)]
+pub struct Snapshot {
+    pub taken: Timestamp,
+    /// As a cycle fetches them: incomplete tasks with a due date and those completed in the past
+    /// day
+    pub asana: Vec<asana::Task>,
+    /// The synced list and every list the state store links into, keyed by id
+    pub google: BTreeMap<String, GoogleList>,
+    /// Keyed by asana gid
+    pub links: BTreeMap<String, Link>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GoogleList {
+    pub title: String,
+    /// Completed ones included
+    pub tasks: Vec<google::Task>,
+}
+
+impl Snapshot {
+    pub async fn take(
+        asana_mgr: &impl AsanaApi,
+        gtasks_mgr: &impl GoogleTasksApi,
+        state: &State,
+    ) -> Result<Self> {
+        let taken = Timestamp::now();
+        let asana_tasks = asana_mgr.get_tasks().await?;
+
+        let wanted: BTreeSet<&str> = state
+            .links
+            .values()
+            .map(|l| l.google_list.as_str())
+            .chain([gtasks_mgr.list_id()])
+            .collect();
+        // lists that were deleted since they were linked into are left out
+        let mut google = BTreeMap::new();
+        for list in gtasks_mgr.get_lists().await? {
+            let (Some(id), Some(title)) = (list.id, list.title) else {
+                continue;
+            };
+            if !wanted.contains(id.as_str()) {
+                continue;
+            }
+            let tasks = gtasks_mgr
+                .for_list(&id)
+                .get_tasks()
+                .await
+                .with_context(|| format!("failed to fetch the tasks of list {title}"))?;
+            let tasks = tasks.incomplete.into_iter().chain(tasks.complete).collect();
+            google.insert(id, GoogleList { title, tasks });
+        }
+
+        Ok(Self {
+            taken,
+            asana: asana_tasks
+                .incomplete
+                .into_iter()
+                .chain(asana_tasks.complete)
+                .collect(),
+            google,
+            links: state.links.clone(),
+        })
+    }
+
+    pub fn render(&self, format: ExportFormat) -> Result<String> {
+        match format {
+            ExportFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+            ExportFormat::Csv => Ok(self.to_csv()),
+        }
+    }
+
+    /// One row per task, `linked` being the id of its copy on the other side
+    fn to_csv(&self) -> String {
+        let gids: HashMap<&str, &str> = self
+            .links
+            .iter()
+            .map(|(gid, link)| (link.google_id.as_str(), gid.as_str()))
+            .collect();
+
+        let mut out = String::from("side,list,id,title,due,completed,linked\n");
+        for task in &self.asana {
+            let linked = self.links.get(&task.gid).map(|l| l.google_id.clone());
+            push_row(
+                &mut out,
+                [
+                    "asana".to_string(),
+                    String::new(),
+                    task.gid.clone(),
+                    task.name.clone(),
+                    display(task.due_date()),
+                    display(task.completed_at),
+                    linked.unwrap_or_default(),
+                ],
+            );
+        }
+        for list in self.google.values() {
+            for task in &list.tasks {
+                let id = task.id.clone().unwrap_or_default();
+                // tasks the state store lost track of can still carry their marker
+                let linked = gids
+                    .get(id.as_str())
+                    .map(|gid| gid.to_string())
+                    .or_else(|| google::get_asana_task_gid(task));
+                push_row(
+                    &mut out,
+                    [
+                        "google".to_string(),
+                        list.title.clone(),
+                        id,
+                        task.title.clone().unwrap_or_default(),
+                        display(google::due_date(task)),
+                        task.completed.clone().unwrap_or_default(),
+                        linked.unwrap_or_default(),
+                    ],
+                );
+            }
+        }
+        out
+    }
+}
+
+fn display(value: Option<impl ToString>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// Append a csv row, quoting the fields that need it
+fn push_row<const N: usize>(out: &mut String, fields: [String; N]) {
+    let fields: Vec<String> = fields
+        .into_iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field
+            }
+        })
+        .collect();
+    out.push_str(&fields.join(","));
+    out.push('\n');
+}
+
+/// Write a snapshot of both sides to `path`
+pub async fn export(
+    asana_mgr: &impl AsanaApi,
+    gtasks_mgr: &impl GoogleTasksApi,
+    state: &State,
+    format: ExportFormat,
+    path: &str,
+) -> Result<()> {
+    let snapshot = Snapshot::take(asana_mgr, gtasks_mgr, state).await?;
+    std::fs::write(path, snapshot.render(format)?)
+        .with_context(|| format!("failed to write {path}"))?;
+    let google_count: usize = snapshot.google.values().map(|l| l.tasks.len()).sum();
+    println!(
+        "Exported {} asana and {google_count} google tasks to {path}",
+        snapshot.asana.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::civil::date;
+
+    use super::*;
+    use crate::mock::{MockAsana, MockGoogle, asana_task};
+
+    #[tokio::test]
+    async fn csv_rows_name_the_copy_on_the_other_side() {
+        let linked = asana_task("100", "Pay rent, on time", date(2026, 10, 20));
+        let unlinked = asana_task("101", "Renew passport", date(2026, 10, 22));
+        let asana_mgr = MockAsana::new(vec![linked.clone(), unlinked]);
+        let google = MockGoogle::new();
+        let mut state = State::default();
+        let id = google
+            .new_task_from_asana(&linked, "Pay \"rent\"", "", None)
+            .await
+            .unwrap();
+        state.link(&linked, google.list_id(), &id, None);
+
+        let snapshot = Snapshot::take(&asana_mgr, &google, &state).await.unwrap();
+
+        assert_eq!(
+            snapshot.render(ExportFormat::Csv).unwrap(),
+            "side,list,id,title,due,completed,linked\n\
+             asana,,100,\"Pay rent, on time\",2026-10-20,,g1\n\
+             asana,,101,Renew passport,2026-10-22,,\n\
+             google,Asana,g1,\"Pay \"\"rent\"\"\",2026-10-20,,100\n"
+        );
+    }
+}
@@ -0,0 +1,125 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+};
+
+use anyhow::Result;
+use jiff::{SignedDuration, Timestamp};
+
+use crate::{asana, redact, sync::FailedActions};
+
+/// How long what was fetched for a task is reused, unless the task is edited meanwhile
+pub const REFRESH_AFTER: SignedDuration = SignedDuration::from_mins(15);
+
+/// Details of asana tasks that take a request per task, like their comments, kept from one cycle
+/// to the next. They're fetched again once they're [`REFRESH_AFTER`] old or the task was edited.
+#[derive(Debug)]
+pub struct Fetched<T> {
+    tasks: Mutex<HashMap<String, Entry<T>>>,
+}
+
+#[derive(Debug)]
+struct Entry<T> {
+    at: Timestamp,
+    /// The task's `modified_at` when they were fetched
+    modified_at: Option<Timestamp>,
+    items: Vec<T>,
+}
+
+impl<T> Default for Fetched<T> {
+    fn default() -> Self {
+        Self {
+            tasks: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<T: Clone> Fetched<T> {
+    /// Give every incomplete task its details with `set`, fetched with `fetch` unless they're
+    /// still fresh. A task whose details can't be fetched keeps the ones fetched before, or without
+    /// any is left out of the cycle, and the failure to fetch its `what` is added to `failures`.
+    /// Private tasks are skipped, none of their details reach google anyway.
+    pub async fn attach(
+        &self,
+        tasks: &mut asana::TaskResult,
+        what: &str,
+        failures: &mut FailedActions,
+        fetch: impl AsyncFn(&str) -> Result<Vec<T>>,
+        set: impl Fn(&mut asana::Task, Vec<T>),
+    ) {
+        let now = Timestamp::now();
+        let mut left_out = HashSet::new();
+        for task in &mut tasks.incomplete {
+            if redact::is_private(task) {
+                continue;
+            }
+            let items = match self.fresh(task, now) {
+                Some(items) => items,
+                None => match fetch(&task.gid).await {
+                    Ok(items) => {
+                        self.insert(task, now, items.clone());
+                        items
+                    }
+                    Err(e) => {
+                        failures.failed.push((
+                            format!("Fetching the {what} of asana task \"{}\"", task.name),
+                            format!("{e:#}"),
+                        ));
+                        match self.stale(&task.gid) {
+                            Some(items) => items,
+                            None => {
+                                left_out.insert(task.gid.clone());
+                                continue;
+                            }
+                        }
+                    }
+                },
+            };
+            set(task, items);
+        }
+        tasks.incomplete.retain(|t| !left_out.contains(&t.gid));
+        self.keep_only(&tasks.incomplete);
+    }
+
+    /// What was fetched for `task`, `None` when it's due to be fetched again
+    fn fresh(&self, task: &asana::Task, now: Timestamp) -> Option<Vec<T>> {
+        self.tasks
+            .lock()
+            .unwrap()
+            .get(&task.gid)
+            .filter(|entry| {
+                entry.modified_at == task.modified_at
+                    && now.duration_since(entry.at) < REFRESH_AFTER
+            })
+            .map(|entry| entry.items.clone())
+    }
+
+    /// What was last fetched for the task `gid`, however old, for when fetching it again failed
+    fn stale(&self, gid: &str) -> Option<Vec<T>> {
+        self.tasks
+            .lock()
+            .unwrap()
+            .get(gid)
+            .map(|entry| entry.items.clone())
+    }
+
+    fn insert(&self, task: &asana::Task, now: Timestamp, items: Vec<T>) {
+        self.tasks.lock().unwrap().insert(
+            task.gid.clone(),
+            Entry {
+                at: now,
+                modified_at: task.modified_at,
+                items,
+            },
+        );
+    }
+
+    /// Forget the tasks that aren't among `tasks` anymore
+    fn keep_only(&self, tasks: &[asana::Task]) {
+        let gids: HashSet<&str> = tasks.iter().map(|t| t.gid.as_str()).collect();
+        self.tasks
+            .lock()
+            .unwrap()
+            .retain(|gid, _| gids.contains(gid.as_str()));
+    }
+}
@@ -0,0 +1,183 @@
+use anyhow::{Context, Result};
+use jiff::{ToSpan, civil::Date};
+use regex::Regex;
+
+use crate::{asana, config::FilterConfig, date, policy::DiffPolicy};
+
+/// Which asana tasks get a google copy. A task has to pass every filter that is set, tags and
+/// sections are compared ignoring case.
+#[derive(Debug, Clone)]
+pub struct TaskFilter {
+    tags: Vec<String>,
+    exclude_tags: Vec<String>,
+    sections: Vec<String>,
+    exclude_sections: Vec<String>,
+    name: Option<Regex>,
+    due_within_days: Option<u32>,
+    hide_until_start: bool,
+}
+
+impl TaskFilter {
+    pub fn new(config: &FilterConfig) -> Result<Self> {
+        let name = config
+            .name
+            .as_deref()
+            .map(|pattern| {
+                Regex::new(pattern)
+                    .with_context(|| format!("invalid name pattern \"{pattern}\" in filter.name"))
+            })
+            .transpose()?;
+
+        Ok(Self {
+            tags: config.tags.clone(),
+            exclude_tags: config.exclude_tags.clone(),
+            sections: config.sections.clone(),
+            exclude_sections: config.exclude_sections.clone(),
+            name,
+            due_within_days: config.due_within_days,
+            hide_until_start: config.hide_until_start,
+        })
+    }
+
+    fn matches_on(&self, task: &asana::Task, today: Date) -> bool {
+        let tags: Vec<&str> = task.tags.iter().map(|t| t.name.as_str()).collect();
+        let sections = sections_of(task);
+
+        (self.tags.is_empty() || any_named(&self.tags, &tags))
+            && !any_named(&self.exclude_tags, &tags)
+            && (self.sections.is_empty() || any_named(&self.sections, &sections))
+            && !any_named(&self.exclude_sections, &sections)
+            && self.name.as_ref().is_none_or(|re| re.is_match(&task.name))
+            && self.due_within_days.is_none_or(|days| {
+                // tasks without a due date never cross the horizon
+                task.due_date()
+                    .is_none_or(|due| due <= today + i64::from(days).days())
+            })
+            && (!self.hide_until_start || task.start_date().is_none_or(|start| start <= today))
+    }
+}
+
+impl DiffPolicy for TaskFilter {
+    fn include(&self, task: &asana::Task) -> bool {
+        self.matches_on(task, date::today())
+    }
+}
+
+/// Names of the sections a task sits in, in "My Tasks" and in its projects
+fn sections_of(task: &asana::Task) -> Vec<&str> {
+    task.assignee_section
+        .iter()
+        .chain(task.memberships.iter().filter_map(|m| m.section.as_ref()))
+        .map(|s| s.name.as_str())
+        .collect()
+}
+
+fn any_named(wanted: &[String], names: &[&str]) -> bool {
+    wanted
+        .iter()
+        .any(|w| names.iter().any(|n| n.eq_ignore_ascii_case(w)))
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::civil::date;
+
+    use super::*;
+    use crate::{
+        asana::{Membership, Section, Tag},
+        mock::asana_task,
+    };
+
+    const TODAY: Date = date(2026, 10, 16);
+
+    fn filter(config: FilterConfig) -> TaskFilter {
+        TaskFilter::new(&config).unwrap()
+    }
+
+    fn tagged(tags: &[&str]) -> asana::Task {
+        let mut task = asana_task("100", "Write report", date(2026, 10, 20));
+        task.tags = tags
+            .iter()
+            .map(|name| Tag {
+                name: name.to_string(),
+            })
+            .collect();
+        task
+    }
+
+    #[test]
+    fn tags_are_allowed_and_denied() {
+        let filter = filter(FilterConfig {
+            tags: vec!["sync".to_string()],
+            exclude_tags: vec!["someday".to_string()],
+            ..Default::default()
+        });
+
+        assert!(filter.matches_on(&tagged(&["Sync"]), TODAY));
+        assert!(!filter.matches_on(&tagged(&[]), TODAY));
+        assert!(!filter.matches_on(&tagged(&["sync", "someday"]), TODAY));
+    }
+
+    #[test]
+    fn sections_come_from_my_tasks_or_projects() {
+        let filter = filter(FilterConfig {
+            sections: vec!["Today".to_string()],
+            ..Default::default()
+        });
+        let section = Section {
+            gid: "1".to_string(),
+            name: "Today".to_string(),
+        };
+
+        let mut mine = tagged(&[]);
+        assert!(!filter.matches_on(&mine, TODAY));
+        mine.assignee_section = Some(section.clone());
+        assert!(filter.matches_on(&mine, TODAY));
+
+        let mut shared = tagged(&[]);
+        shared.memberships = vec![Membership {
+            project: None,
+            section: Some(section),
+        }];
+        assert!(filter.matches_on(&shared, TODAY));
+    }
+
+    #[test]
+    fn name_and_due_horizon() {
+        let filter = filter(FilterConfig {
+            name: Some("(?i)^write".to_string()),
+            due_within_days: Some(7),
+            ..Default::default()
+        });
+
+        assert!(filter.matches_on(&tagged(&[]), TODAY));
+        assert!(!filter.matches_on(&asana_task("101", "Read", date(2026, 10, 20)), TODAY));
+        assert!(!filter.matches_on(&asana_task("102", "Write", date(2026, 10, 24)), TODAY));
+        let mut undated = tagged(&[]);
+        undated.due_on = None;
+        assert!(filter.matches_on(&undated, TODAY));
+    }
+
+    #[test]
+    fn tasks_wait_for_their_start_date() {
+        let filter = filter(FilterConfig {
+            hide_until_start: true,
+            ..Default::default()
+        });
+
+        let mut later = tagged(&[]);
+        later.start_on = Some(date(2026, 10, 18));
+        assert!(!filter.matches_on(&later, TODAY));
+        assert!(filter.matches_on(&later, date(2026, 10, 18)));
+        assert!(filter.matches_on(&tagged(&[]), TODAY));
+    }
+
+    #[test]
+    fn invalid_name_pattern_is_refused() {
+        let config = FilterConfig {
+            name: Some("(unclosed".to_string()),
+            ..Default::default()
+        };
+        assert!(TaskFilter::new(&config).is_err());
+    }
+}
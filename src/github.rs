@@ -0,0 +1,628 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::Mutex,
+};
+
+use anyhow::{Context, Result, bail};
+use jiff::Timestamp;
+use log::debug;
+use reqwest::{
+    RequestBuilder, Response, StatusCode,
+    header::{ACCEPT, AUTHORIZATION, HeaderMap, HeaderName, HeaderValue, LINK, USER_AGENT},
+};
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::json;
+use tokio::sync::OnceCell;
+
+use crate::{
+    asana::{AsanaApi, Attachment, Comment, Section, Tag, Task, TaskResult, TaskSource, UserRef},
+    config::GitHubConfig,
+    date, http,
+};
+
+/// GitHub's API root, overridable for GitHub Enterprise Server or a test server
+pub const DEFAULT_BASE_URL: &str = "https://api.github.com";
+
+/// Issues assigned to the token's user, across every repository they can see, behind the same
+/// interface as asana. An issue's gid is `owner/repo#number`, so the repository and number end
+/// up in the google copy's marker. Issues are due when their milestone is, or else on the day
+/// they were opened. Completing one in google only closes it with `github.close_issues`, otherwise
+/// the bridge remembers it as done in `github.done` until it's closed upstream.
+pub struct GitHubClient {
+    client: reqwest::Client,
+    base_url: String,
+    source: TaskSource,
+    close_issues: bool,
+    /// Path of the file the issues completed in google are kept in
+    done_path: String,
+    /// When each issue still open upstream was completed in google
+    done: Mutex<BTreeMap<String, Timestamp>>,
+    /// Login of the token's user, resolved on first use
+    me: OnceCell<String>,
+}
+
+impl GitHubClient {
+    pub async fn new(config: &GitHubConfig) -> Result<Self> {
+        let token = config
+            .token
+            .as_ref()
+            .context("no github token configured")?
+            .resolve()
+            .await
+            .context("failed to load the github token")?;
+        // sent with every request, kept out of debug output
+        let mut auth = HeaderValue::from_str(&format!("Bearer {token}"))?;
+        auth.set_sensitive(true);
+        let headers = HeaderMap::from_iter([
+            (AUTHORIZATION, auth),
+            (
+                ACCEPT,
+                HeaderValue::from_static("application/vnd.github+json"),
+            ),
+            // github turns away requests without one
+            (USER_AGENT, HeaderValue::from_static("gtasks-asana-bridge")),
+            (
+                HeaderName::from_static("x-github-api-version"),
+                HeaderValue::from_static("2022-11-28"),
+            ),
+        ]);
+
+        let done = match std::fs::read_to_string(&config.done) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("failed to parse {}", config.done))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e).with_context(|| format!("failed to read {}", config.done)),
+        };
+
+        Ok(Self {
+            client: http::client(headers)?,
+            base_url: config.base_url.trim_end_matches('/').to_string(),
+            source: TaskSource::GitHubIssues,
+            close_issues: config.close_issues,
+            done_path: config.done.clone(),
+            done: Mutex::new(done),
+            me: OnceCell::new(),
+        })
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{path}", self.base_url)
+    }
+
+    /// Path of the issue `gid` names, below the API root
+    fn issue_path(gid: &str) -> Result<String> {
+        let (repo, number) = gid
+            .rsplit_once('#')
+            .with_context(|| format!("{gid} isn't a github issue"))?;
+        Ok(format!("repos/{repo}/issues/{number}"))
+    }
+
+    async fn send(&self, request: RequestBuilder, what: &str) -> Result<Response> {
+        Ok(http::send(request, what).await?)
+    }
+
+    /// Follow the `Link` headers until every page has been fetched
+    async fn get_paged<T: DeserializeOwned>(&self, url: &str) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+        let mut next = Some(url.to_string());
+        while let Some(url) = next {
+            let resp = self.send(self.client.get(&url), "fetch").await?;
+            next = resp
+                .headers()
+                .get(LINK)
+                .and_then(|link| link.to_str().ok())
+                .and_then(next_link);
+            items.extend(resp.json::<Vec<T>>().await?);
+        }
+
+        Ok(items)
+    }
+
+    /// Issues assigned to the token's user matching `params`, leaving out pull requests
+    async fn assigned_issues(&self, params: &str) -> Result<Vec<Issue>> {
+        let url = self.url(&format!("issues?filter=assigned&per_page=100&{params}"));
+        let issues: Vec<Issue> = self.get_paged(&url).await?;
+        Ok(issues
+            .into_iter()
+            .filter(|i| i.pull_request.is_none())
+            .collect())
+    }
+
+    fn save_done(&self, done: &BTreeMap<String, Timestamp>) -> Result<()> {
+        // write then rename so a crash never leaves a truncated file behind
+        let tmp_path = format!("{}.tmp", self.done_path);
+        std::fs::write(&tmp_path, serde_json::to_vec(done)?)
+            .with_context(|| format!("failed to write {tmp_path}"))?;
+        std::fs::rename(&tmp_path, &self.done_path)
+            .with_context(|| format!("failed to replace {}", self.done_path))?;
+        Ok(())
+    }
+
+    /// The issue as an asana task, completed when it was completed in google
+    fn task_of(&self, issue: Issue, me: &str) -> Task {
+        let mut task = issue.into_task(me);
+        if task.completed_at.is_none() {
+            task.completed_at = self.done.lock().unwrap().get(&task.gid).copied();
+        }
+        task
+    }
+}
+
+impl AsanaApi for GitHubClient {
+    fn source(&self) -> &TaskSource {
+        &self.source
+    }
+
+    async fn get_tasks_since(&self, completed_since: Timestamp) -> Result<TaskResult> {
+        let me = self.me().await?;
+        let open = self.assigned_issues("state=open").await?;
+        // `since` is about the last update, which closing the issue was at the latest
+        let since = completed_since.strftime("%Y-%m-%dT%H:%M:%SZ");
+        let closed = self
+            .assigned_issues(&format!("state=closed&since={since}"))
+            .await?;
+
+        // issues closed upstream or no longer assigned don't need remembering
+        {
+            let mut done = self.done.lock().unwrap();
+            let open_gids: HashSet<String> = open.iter().map(Issue::gid).collect();
+            let before = done.len();
+            done.retain(|gid, _| open_gids.contains(gid));
+            if done.len() != before {
+                self.save_done(&done)?;
+            }
+        }
+
+        let mut result = TaskResult {
+            incomplete: Vec::new(),
+            complete: Vec::new(),
+        };
+        for issue in open.into_iter().chain(closed) {
+            let task = self.task_of(issue, &me);
+            match task.completed_at {
+                None => result.incomplete.push(task),
+                Some(at) if at >= completed_since => result.complete.push(task),
+                Some(_) => {}
+            }
+        }
+
+        Ok(result)
+    }
+
+    async fn get_subtasks(&self, _task_gid: &str) -> Result<Vec<Task>> {
+        Ok(Vec::new())
+    }
+
+    async fn get_task(&self, task_gid: &str) -> Result<Task> {
+        let path = Self::issue_path(task_gid)?;
+        let issue: Issue = self
+            .send(self.client.get(self.url(&path)), "fetch issue")
+            .await?
+            .json()
+            .await?;
+        Ok(self.task_of(issue, &self.me().await?))
+    }
+
+    async fn get_comments(&self, task_gid: &str) -> Result<Vec<Comment>> {
+        let path = Self::issue_path(task_gid)?;
+        let comments: Vec<IssueComment> = self
+            .get_paged(&self.url(&format!("{path}/comments?per_page=100")))
+            .await?;
+        Ok(comments
+            .into_iter()
+            .map(|c| Comment {
+                author: c.user.map(|u| u.login),
+                created_at: c.created_at,
+                text: c.body.unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    async fn add_comment(&self, task_gid: &str, text: &str) -> Result<()> {
+        let path = Self::issue_path(task_gid)?;
+        self.send(
+            self.client
+                .post(self.url(&format!("{path}/comments")))
+                .json(&json!({ "body": text })),
+            "comment on issue",
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn get_attachments(&self, _task_gid: &str) -> Result<Vec<Attachment>> {
+        Ok(Vec::new())
+    }
+
+    async fn me(&self) -> Result<String> {
+        self.me
+            .get_or_try_init(|| async {
+                let user: Login = self
+                    .send(self.client.get(self.url("user")), "fetch the token's user")
+                    .await?
+                    .json()
+                    .await?;
+                Ok::<_, anyhow::Error>(user.login)
+            })
+            .await
+            .cloned()
+    }
+
+    async fn task_exists(&self, task_gid: &str) -> Result<bool> {
+        let path = Self::issue_path(task_gid)?;
+        let resp = self.client.get(self.url(&path)).send().await?;
+        match resp.status() {
+            // deleted issues are gone for good, transferred ones are under a new gid
+            StatusCode::NOT_FOUND | StatusCode::GONE | StatusCode::MOVED_PERMANENTLY => Ok(false),
+            _ => http::check(resp, "fetch").map(|_| true).map_err(Into::into),
+        }
+    }
+
+    async fn get_sections(&self) -> Result<Vec<Section>> {
+        Ok(Vec::new())
+    }
+
+    async fn add_task_to_section(&self, _task_gid: &str, _section_gid: &str) -> Result<()> {
+        bail!("github issues have no sections")
+    }
+
+    async fn set_parent(&self, task_gid: &str, parent_gid: Option<&str>) -> Result<()> {
+        match parent_gid {
+            // issues are never fetched as subtasks, they're at the top level already
+            None => Ok(()),
+            Some(_) => bail!("can't nest github issue {task_gid}"),
+        }
+    }
+
+    async fn complete_task(&self, task_gid: &str) -> Result<()> {
+        if self.close_issues {
+            let path = Self::issue_path(task_gid)?;
+            self.send(
+                self.client
+                    .patch(self.url(&path))
+                    .json(&json!({ "state": "closed", "state_reason": "completed" })),
+                "close issue",
+            )
+            .await?;
+            return Ok(());
+        }
+
+        debug!("Remembering {task_gid} as done, it stays open on github");
+        let mut done = self.done.lock().unwrap();
+        done.insert(task_gid.to_string(), Timestamp::now());
+        self.save_done(&done)
+    }
+
+    async fn set_due(&self, task: &Task) -> Result<()> {
+        debug!(
+            "Not moving {}, github issues are due when their milestone is",
+            task.gid
+        );
+        Ok(())
+    }
+
+    async fn set_liked(&self, _task_gid: &str, _liked: bool) -> Result<()> {
+        Ok(())
+    }
+
+    async fn set_text(
+        &self,
+        task_gid: &str,
+        name: Option<&str>,
+        notes: Option<&str>,
+    ) -> Result<()> {
+        let path = Self::issue_path(task_gid)?;
+        let mut edit = serde_json::Map::new();
+        if let Some(name) = name {
+            edit.insert("title".to_string(), json!(name));
+        }
+        if let Some(notes) = notes {
+            edit.insert("body".to_string(), json!(notes));
+        }
+        self.send(self.client.patch(self.url(&path)).json(&edit), "edit issue")
+            .await?;
+        Ok(())
+    }
+}
+
+/// The `rel="next"` url in a `Link` header
+fn next_link(header: &str) -> Option<String> {
+    header.split(',').find_map(|link| {
+        let (url, params) = link.split_once(';')?;
+        params
+            .split(';')
+            .any(|p| p.trim() == r#"rel="next""#)
+            .then(|| {
+                url.trim()
+                    .trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string()
+            })
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct Issue {
+    number: u64,
+    title: String,
+    #[serde(default)]
+    body: Option<String>,
+    html_url: String,
+    /// `https://api.github.com/repos/{owner}/{repo}`
+    repository_url: String,
+    created_at: Timestamp,
+    #[serde(default)]
+    closed_at: Option<Timestamp>,
+    #[serde(default)]
+    assignees: Vec<Login>,
+    #[serde(default)]
+    labels: Vec<Label>,
+    #[serde(default)]
+    milestone: Option<Milestone>,
+    /// Set when the "issue" is a pull request
+    #[serde(default)]
+    pull_request: Option<serde_json::Value>,
+}
+
+impl Issue {
+    /// `owner/repo` of the repository the issue is in
+    fn repo(&self) -> &str {
+        let mut segments = self.repository_url.rsplitn(3, '/');
+        let name = segments.next().unwrap_or_default();
+        let owner = segments.next().unwrap_or_default();
+        let start = self.repository_url.len() - name.len() - owner.len() - 1;
+        &self.repository_url[start..]
+    }
+
+    fn gid(&self) -> String {
+        format!("{}#{}", self.repo(), self.number)
+    }
+
+    fn into_task(self, me: &str) -> Task {
+        let gid = self.gid();
+        let due = self
+            .milestone
+            .and_then(|m| m.due_on)
+            .unwrap_or(self.created_at);
+        // the token's user when they're among the assignees, who else is doesn't matter
+        let assignee = self
+            .assignees
+            .iter()
+            .find(|a| a.login == me)
+            .or(self.assignees.first())
+            .map(|a| UserRef {
+                gid: a.login.clone(),
+            });
+
+        Task {
+            gid,
+            name: self.title,
+            notes: self.body.unwrap_or_default(),
+            html_notes: None,
+            permalink_url: Some(self.html_url),
+            liked: false,
+            due_on: Some(date::local_date(due)),
+            due_at: None,
+            start_on: None,
+            start_at: None,
+            completed_at: self.closed_at,
+            modified_at: None,
+            assignee,
+            assignee_section: None,
+            memberships: Vec::new(),
+            tags: self
+                .labels
+                .into_iter()
+                .map(|l| Tag { name: l.name })
+                .collect(),
+            parent_gid: None,
+            comments: Vec::new(),
+            attachments: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Login {
+    login: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Label {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Milestone {
+    #[serde(default)]
+    due_on: Option<Timestamp>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IssueComment {
+    #[serde(default)]
+    user: Option<Login>,
+    created_at: Timestamp,
+    #[serde(default)]
+    body: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{body_json, method, path, query_param},
+    };
+
+    use super::*;
+
+    async fn github(server: &MockServer, done: &str, close_issues: bool) -> GitHubClient {
+        Mock::given(method("GET"))
+            .and(path("/user"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"login": "octocat"})))
+            .mount(server)
+            .await;
+
+        let _ = std::fs::remove_file(done);
+        let config = GitHubConfig {
+            token: Some(crate::secrets::SecretSource::Value("token".to_string())),
+            base_url: server.uri(),
+            close_issues,
+            done: done.to_string(),
+        };
+        GitHubClient::new(&config).await.unwrap()
+    }
+
+    fn issue(server: &MockServer, number: u64, title: &str) -> serde_json::Value {
+        json!({
+            "number": number,
+            "title": title,
+            "body": "Steps to reproduce",
+            "html_url": format!("https://github.com/octo/hello/issues/{number}"),
+            "repository_url": format!("{}/repos/octo/hello", server.uri()),
+            "created_at": "2026-10-01T12:00:00Z",
+            "assignees": [{"login": "hubot"}, {"login": "octocat"}],
+            "labels": [{"name": "bug"}]
+        })
+    }
+
+    #[tokio::test]
+    async fn assigned_issues_become_tasks() {
+        let server = MockServer::start().await;
+        let done = std::env::temp_dir().join("github_done_assigned.json");
+        let github = github(&server, done.to_str().unwrap(), false).await;
+
+        let mut with_milestone = issue(&server, 7, "Fix the login page");
+        with_milestone["milestone"] = json!({"due_on": "2026-10-20T12:00:00Z"});
+        let mut pull_request = issue(&server, 8, "Fix it differently");
+        pull_request["pull_request"] = json!({"url": "https://example.com"});
+        let next = format!("<{}/issues?page=2>; rel=\"next\"", server.uri());
+        Mock::given(method("GET"))
+            .and(path("/issues"))
+            .and(query_param("state", "open"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Link", next.as_str())
+                    .set_body_json(json!([with_milestone, pull_request])),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/issues"))
+            .and(query_param("page", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([issue(
+                &server,
+                9,
+                "Add a logo"
+            )])))
+            .mount(&server)
+            .await;
+        let mut closed = issue(&server, 3, "Old crash");
+        closed["closed_at"] = json!(Timestamp::now());
+        Mock::given(method("GET"))
+            .and(path("/issues"))
+            .and(query_param("state", "closed"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([closed])))
+            .mount(&server)
+            .await;
+
+        let tasks = github.get_tasks().await.unwrap();
+
+        let incomplete: Vec<_> = tasks.incomplete.iter().map(|t| t.gid.as_str()).collect();
+        let complete: Vec<_> = tasks.complete.iter().map(|t| t.gid.as_str()).collect();
+        assert_eq!(incomplete, ["octo/hello#7", "octo/hello#9"]);
+        assert_eq!(complete, ["octo/hello#3"]);
+
+        let task = &tasks.incomplete[0];
+        assert_eq!(task.name, "Fix the login page");
+        assert_eq!(
+            task.due_date(),
+            Some(date::local_date("2026-10-20T12:00:00Z".parse().unwrap()))
+        );
+        assert!(task.assigned_to("octocat"));
+        assert_eq!(task.tags[0].name, "bug");
+        assert_eq!(
+            task.permalink_url.as_deref(),
+            Some("https://github.com/octo/hello/issues/7")
+        );
+        // without a milestone, the day it was opened
+        assert_eq!(
+            tasks.incomplete[1].due_date(),
+            Some(date::local_date("2026-10-01T12:00:00Z".parse().unwrap()))
+        );
+    }
+
+    #[tokio::test]
+    async fn completed_issues_stay_open_unless_configured() {
+        let server = MockServer::start().await;
+        let done = std::env::temp_dir().join("github_done_completed.json");
+        let github = github(&server, done.to_str().unwrap(), false).await;
+        Mock::given(method("GET"))
+            .and(path("/issues"))
+            .and(query_param("state", "open"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!([issue(&server, 7, "Fix it")])),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/issues"))
+            .and(query_param("state", "closed"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        github.complete_task("octo/hello#7").await.unwrap();
+
+        // reported done, and still after a restart
+        let tasks = github.get_tasks().await.unwrap();
+        assert!(tasks.incomplete.is_empty());
+        assert_eq!(tasks.complete[0].gid, "octo/hello#7");
+        let config = GitHubConfig {
+            token: Some(crate::secrets::SecretSource::Value("token".to_string())),
+            base_url: server.uri(),
+            close_issues: false,
+            done: done.to_str().unwrap().to_string(),
+        };
+        let restarted = GitHubClient::new(&config).await.unwrap();
+        assert_eq!(restarted.get_tasks().await.unwrap().complete.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn completing_closes_the_issue_when_configured() {
+        let server = MockServer::start().await;
+        let done = std::env::temp_dir().join("github_done_closing.json");
+        let github = github(&server, done.to_str().unwrap(), true).await;
+        Mock::given(method("PATCH"))
+            .and(path("/repos/octo/hello/issues/7"))
+            .and(body_json(
+                json!({"state": "closed", "state_reason": "completed"}),
+            ))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        github.complete_task("octo/hello#7").await.unwrap();
+
+        assert!(!done.exists());
+    }
+
+    #[test]
+    fn finds_the_next_page() {
+        let header = r#"<https://api.github.com/issues?page=3>; rel="next", <https://api.github.com/issues?page=5>; rel="last""#;
+        assert_eq!(
+            next_link(header).as_deref(),
+            Some("https://api.github.com/issues?page=3")
+        );
+        assert_eq!(
+            next_link(r#"<https://api.github.com/issues?page=1>; rel="prev""#),
+            None
+        );
+    }
+}
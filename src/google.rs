@@ -1,137 +1,513 @@
-use anyhow::{Context, Result};
-use google_tasks1::TasksHub;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
 
-pub use google_tasks1::api::Task;
+use anyhow::{Context, Result, anyhow, bail};
+use google_tasks1::{
+    TasksHub,
+    api::{Scope, Tasks},
+    common::GetToken,
+    yup_oauth2::{
+        authenticator::AuthenticatorBuilder,
+        storage::{TokenInfo, TokenStorage},
+    },
+};
+use jiff::civil;
+use reqwest::{
+    Method, StatusCode,
+    header::{HeaderMap, IF_MATCH, IF_NONE_MATCH},
+};
+use serde::{Deserialize, Serialize};
 
-use crate::asana;
+pub use google_tasks1::api::{Task, TaskList};
 
-#[derive(Debug)]
+use crate::{
+    asana,
+    config::GoogleConfig,
+    consent::{Consent, DeviceDelegate, InstalledDelegate, Watched},
+    crypt,
+    error::BridgeError,
+    http, trace,
+};
+
+/// Google Tasks' API root, overridable to point at a test server
+pub const DEFAULT_BASE_URL: &str = "https://tasks.googleapis.com/";
+
+/// Longest title google accepts for a task
+pub const MAX_TITLE_CHARS: usize = 1024;
+
+/// How the first google token is granted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthFlow {
+    /// Open the consent page and catch the redirect on a local port
+    #[default]
+    Redirect,
+    /// Print the consent url and read the code pasted back from the redirect, for use over SSH
+    Manual,
+    /// Print a code to enter on google.com/device, needs a "TVs and Limited Input" client
+    Device,
+}
+
+#[derive(Debug, Clone)]
 pub struct GTaskResult {
     pub incomplete: Vec<Task>,
     pub complete: Vec<Task>,
 }
 
-pub struct GoogleTaskMgr {
-    hub: TasksHub<
-        google_tasks1::hyper_rustls::HttpsConnector<
-            google_tasks1::hyper_util::client::legacy::connect::HttpConnector,
-        >,
+/// Everything the bridge does with google tasks, so syncing can run against something other than
+/// the live API. Each value is bound to one task list.
+pub trait GoogleTasksApi: Sized {
+    /// Manager for another task list sharing this manager's connection
+    fn for_list(&self, list_id: &str) -> Self;
+
+    fn list_id(&self) -> &str;
+
+    async fn get_lists(&self) -> Result<Vec<TaskList>>;
+
+    async fn create_list(&self, title: &str) -> Result<String>;
+
+    async fn delete_list(&self, id: &str) -> Result<()>;
+
+    /// Create the google copy of an asana task with `title` and `notes` as given, already tagged
+    /// by the [`Correlator`](crate::correlate::Correlator), returning its google id
+    async fn new_task_from_asana(
+        &self,
+        task: &asana::Task,
+        title: &str,
+        notes: &str,
+        parent: Option<&str>,
+    ) -> Result<String>;
+
+    /// Bring a task's title, notes and due date in line with asana where it is, unless it was
+    /// edited since it showed `etag`. Returns false when the backend can only replace tasks, which
+    /// is all of them but jira.
+    async fn update_from_asana(
+        &self,
+        _id: &str,
+        _etag: Option<&str>,
+        _task: &asana::Task,
+        _title: &str,
+        _notes: &str,
+    ) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Replace a task's title and notes, e.g. to retag it
+    async fn set_text(&self, id: &str, title: &str, notes: &str) -> Result<()>;
+
+    async fn move_to_top_level(&self, id: &str) -> Result<()>;
+
+    /// Place a top level task right after `previous`, or first in the list with `None`
+    async fn move_after(&self, id: &str, previous: Option<&str>) -> Result<()>;
+
+    async fn get_tasks(&self) -> Result<GTaskResult>;
+
+    async fn del_task(&self, id: &str) -> Result<()>;
+
+    /// Delete a task unless it was edited since it showed `etag`, failing instead so the next
+    /// cycle plans for the edit
+    async fn del_task_if(&self, id: &str, etag: &str) -> Result<()>;
+
+    /// Hide every completed task in the list in one go, like "Delete all completed tasks" in
+    /// google. They're still fetched, marked hidden. Only google can.
+    async fn clear_completed(&self) -> Result<()> {
+        bail!("only google tasks can clear completed tasks")
+    }
+}
+
+type Hub = TasksHub<
+    google_tasks1::hyper_rustls::HttpsConnector<
+        google_tasks1::hyper_util::client::legacy::connect::HttpConnector,
     >,
+>;
+
+#[derive(Clone)]
+pub struct GoogleTaskMgr {
+    hub: Hub,
     asana_task_list: String,
+    /// For the calls the generated client can't make, those with conditional headers
+    http: reqwest::Client,
+    base_url: String,
+    /// Each list's tasks as last fetched, with the etag google sent them with
+    listed: Arc<Mutex<HashMap<String, (String, GTaskResult)>>>,
 }
 
-impl GoogleTaskMgr {
-    pub async fn new() -> Result<Self> {
-        #[cfg(not(feature = "docker"))]
-        const SECRET_PATH: &str = "client_secret.json";
-
-        #[cfg(feature = "docker")]
-        const SECRET_PATH: &str = "/secret/client_secret.json";
+/// OAuth for google's APIs. The token cache holds a token per set of scopes, so the first use of
+/// another API asks for consent again. Consent asked for once a token was handed out means the
+/// refresh token was revoked, which stops further requests until access is granted again.
+pub async fn authenticator(
+    config: &GoogleConfig,
+) -> Result<impl google_tasks1::common::GetToken + Clone + 'static> {
+    let consent = Consent::new(config.reauth);
+    let auth = user_authenticator(config, consent.clone()).await?;
+    Ok(Watched::new(auth, consent))
+}
 
-        let secret = google_tasks1::yup_oauth2::read_application_secret(SECRET_PATH)
+async fn user_authenticator(
+    config: &GoogleConfig,
+    consent: Arc<Consent>,
+) -> Result<impl google_tasks1::common::GetToken + Clone + 'static> {
+    // workspace accounts can skip the consent screen entirely with domain-wide delegation
+    if let Some(key) = &config.service_account {
+        let key = google_tasks1::yup_oauth2::parse_service_account_key(key.resolve().await?)
+            .context("failed to parse service account key")?;
+        let mut builder = token_storage(
+            google_tasks1::yup_oauth2::ServiceAccountAuthenticator::builder(key),
+            &config.token_cache,
+        )?;
+        if let Some(user) = &config.impersonate {
+            builder = builder.subject(user.as_str());
+        }
+        return builder
+            .build()
             .await
-            .context("failed to read application secret")?;
+            .context("failed to build service account auth");
+    }
 
-        #[cfg(not(feature = "docker"))]
-        const TOKEN_PATH: &str = "token_cache.json";
+    let secret =
+        google_tasks1::yup_oauth2::parse_application_secret(config.client_secret.resolve().await?)
+            .context("failed to parse application secret")?;
 
-        #[cfg(feature = "docker")]
-        const TOKEN_PATH: &str = "/data/token_cache.json";
+    match config.auth_flow {
+        AuthFlow::Redirect | AuthFlow::Manual => {
+            let method = if config.auth_flow == AuthFlow::Redirect {
+                google_tasks1::yup_oauth2::InstalledFlowReturnMethod::HTTPRedirect
+            } else {
+                google_tasks1::yup_oauth2::InstalledFlowReturnMethod::Interactive
+            };
+            let builder =
+                google_tasks1::yup_oauth2::InstalledFlowAuthenticator::builder(secret, method);
+            token_storage(builder, &config.token_cache)?
+                .flow_delegate(Box::new(InstalledDelegate(consent)))
+                .build()
+                .await
+        }
+        AuthFlow::Device => {
+            let builder = google_tasks1::yup_oauth2::DeviceFlowAuthenticator::builder(secret);
+            token_storage(builder, &config.token_cache)?
+                .flow_delegate(Box::new(DeviceDelegate(consent)))
+                .build()
+                .await
+        }
+    }
+    .context("failed to build auth")
+}
 
-        let auth = google_tasks1::yup_oauth2::InstalledFlowAuthenticator::builder(
-            secret,
-            google_tasks1::yup_oauth2::InstalledFlowReturnMethod::HTTPRedirect,
-        )
-        .persist_tokens_to_disk(TOKEN_PATH)
-        .build()
-        .await
-        .context("failed to build auth")?;
+/// Cache tokens in `path`, encrypted when a key is configured
+fn token_storage<C, F>(
+    builder: AuthenticatorBuilder<C, F>,
+    path: &str,
+) -> Result<AuthenticatorBuilder<C, F>> {
+    if !crypt::enabled() {
+        return Ok(builder.persist_tokens_to_disk(path));
+    }
+    let tokens = match crypt::read(path)? {
+        Some(cached) => serde_json::from_slice(&cached)
+            .with_context(|| format!("invalid token cache {path}"))?,
+        None => Vec::new(),
+    };
+    Ok(builder.with_storage(Box::new(SealedTokens {
+        path: path.to_string(),
+        tokens: Mutex::new(tokens),
+    })))
+}
 
-        let client = google_tasks1::hyper_util::client::legacy::Client::builder(
-            google_tasks1::hyper_util::rt::TokioExecutor::new(),
-        )
-        .build(
-            google_tasks1::hyper_rustls::HttpsConnectorBuilder::new()
-                .with_native_roots()
-                .unwrap()
-                .https_or_http()
-                .enable_http1()
-                .build(),
-        );
-        let hub = TasksHub::new(client, auth);
+/// Token cache written through [`crypt`], in the format of yup's own, so a plaintext one written
+/// before the key was set is read as it is
+struct SealedTokens {
+    path: String,
+    tokens: Mutex<Vec<CachedToken>>,
+}
 
-        let lists = hub.tasklists().list().doit().await?.1;
+#[derive(Serialize, Deserialize)]
+struct CachedToken {
+    scopes: Vec<String>,
+    token: TokenInfo,
+}
 
-        let asana_task_list = lists
-            .items
+#[async_trait::async_trait]
+impl TokenStorage for SealedTokens {
+    async fn set(&self, scopes: &[&str], token: TokenInfo) -> anyhow::Result<()> {
+        let mut tokens = self.tokens.lock().unwrap();
+        tokens.retain(|cached| {
+            cached
+                .scopes
+                .iter()
+                .map(String::as_str)
+                .ne(scopes.iter().copied())
+        });
+        tokens.push(CachedToken {
+            scopes: scopes.iter().map(|scope| scope.to_string()).collect(),
+            token,
+        });
+        crypt::write(&self.path, &serde_json::to_vec(&*tokens)?)
+    }
+
+    async fn get(&self, scopes: &[&str]) -> Option<TokenInfo> {
+        // a token for more scopes than asked for will do
+        self.tokens
+            .lock()
             .unwrap()
             .iter()
-            .find(|a| {
-                if let Some(title) = &a.title
-                    && title == "Asana"
-                {
-                    true
-                } else {
-                    false
-                }
+            .find(|cached| {
+                scopes
+                    .iter()
+                    .all(|scope| cached.scopes.iter().any(|s| s == scope))
             })
-            .unwrap()
-            .id
-            .clone()
-            .unwrap();
+            .map(|cached| cached.token.clone())
+    }
+}
+
+impl GoogleTaskMgr {
+    /// Path of the list's tasks, or of one of them, as it shows in the trace
+    fn tasks_path(&self, task: Option<&str>) -> String {
+        let list = &self.asana_task_list;
+        match task {
+            Some(task) => format!("lists/{list}/tasks/{task}"),
+            None => format!("lists/{list}/tasks"),
+        }
+    }
+
+    pub async fn new(config: &GoogleConfig, list: &str) -> Result<Self> {
+        Self::connect(authenticator(config).await?, DEFAULT_BASE_URL, list).await
+    }
+
+    /// Manager that isn't bound to a list yet, for picking one with [`GoogleTasksApi::get_lists`]
+    /// and [`GoogleTasksApi::for_list`]. Asks for consent when there's no cached token.
+    pub async fn unbound(config: &GoogleConfig) -> Result<Self> {
+        Self::build(authenticator(config).await?, DEFAULT_BASE_URL)
+    }
+
+    /// Manager for the list `list` names, see [`find_list`], on the tasks API at `base_url`
+    pub async fn connect(
+        auth: impl google_tasks1::common::GetToken + 'static,
+        base_url: &str,
+        list: &str,
+    ) -> Result<Self> {
+        let mut mgr = Self::build(auth, base_url)?;
+        mgr.asana_task_list = find_list(&mgr.get_lists().await?, list)
+            .context("failed to pick the google task list")?;
+        Ok(mgr)
+    }
 
+    fn build(auth: impl GetToken + 'static, base_url: &str) -> Result<Self> {
         Ok(Self {
-            hub,
-            asana_task_list,
+            hub: Self::hub(auth, base_url)?,
+            asana_task_list: String::new(),
+            http: http::client(HeaderMap::new())?,
+            base_url: base_url.to_string(),
+            listed: Arc::default(),
+        })
+    }
+
+    fn hub(auth: impl GetToken + 'static, base_url: &str) -> Result<Hub> {
+        let connector = google_tasks1::hyper_rustls::HttpsConnectorBuilder::new()
+            .with_native_roots()
+            .context("failed to load the system's root certificates")?
+            .https_or_http()
+            .enable_http1()
+            .build();
+        let client = google_tasks1::hyper_util::client::legacy::Client::builder(
+            google_tasks1::hyper_util::rt::TokioExecutor::new(),
+        )
+        .build(connector);
+        let mut hub = TasksHub::new(client, auth);
+        hub.base_url(base_url.to_string());
+        hub.root_url(base_url.to_string());
+        Ok(hub)
+    }
+
+    /// Request to `path` of the tasks API, authorized like the generated client's own
+    async fn request(&self, method: Method, path: &str) -> Result<reqwest::RequestBuilder> {
+        let token = self
+            .hub
+            .auth
+            .get_token(&[Scope::Full.as_ref()])
+            .await
+            .map_err(|e| BridgeError::Auth {
+                what: "call google tasks".to_string(),
+                detail: e.to_string(),
+            })?;
+        let request = self
+            .http
+            .request(method, format!("{}tasks/v1/{path}", self.base_url));
+        Ok(match token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
         })
     }
+}
+
+impl GoogleTasksApi for GoogleTaskMgr {
+    fn for_list(&self, list_id: &str) -> Self {
+        Self {
+            asana_task_list: list_id.to_string(),
+            ..self.clone()
+        }
+    }
+
+    async fn get_lists(&self) -> Result<Vec<TaskList>> {
+        let mut result = Vec::new();
+
+        let mut next_page: Option<String> = None;
+        loop {
+            let lists_result = self.hub.tasklists().list().max_results(100);
+
+            let lists_result = if let Some(page_token) = &next_page {
+                lists_result.page_token(page_token)
+            } else {
+                lists_result
+            };
+            let lists_result = doit("GET", "users/@me/lists", None, lists_result.doit()).await?;
+
+            next_page = lists_result.next_page_token;
+            result.extend(lists_result.items.unwrap_or_default());
+
+            if next_page.is_none() {
+                break;
+            }
+        }
+
+        Ok(result)
+    }
+
+    async fn create_list(&self, title: &str) -> Result<String> {
+        let new_list = TaskList {
+            title: Some(title.to_string()),
+            ..Default::default()
+        };
+
+        let sent = sent(&new_list);
+        let call = self.hub.tasklists().insert(new_list).doit();
+        let created = doit("POST", "users/@me/lists", sent, call).await?;
+        created.id.context("created task list has no id")
+    }
 
-    pub async fn new_task_from_asana(&self, task: &asana::Task) -> Result<()> {
+    async fn delete_list(&self, id: &str) -> Result<()> {
+        let call = self.hub.tasklists().delete(id).doit();
+        doit_empty("DELETE", &format!("users/@me/lists/{id}"), call).await
+    }
+
+    fn list_id(&self) -> &str {
+        &self.asana_task_list
+    }
+
+    async fn new_task_from_asana(
+        &self,
+        task: &asana::Task,
+        title: &str,
+        notes: &str,
+        parent: Option<&str>,
+    ) -> Result<String> {
         let new_g_task = Task {
-            title: Some(task.name.clone()),
+            title: Some(title.to_string()),
             due: Some(asana::asana_due_to_string(task)?),
-            notes: Some({
-                let mut note = task.notes.clone();
-                note.push_str("\n---\n");
-                note.push_str(&task.gid);
-                note
-            }),
+            notes: Some(notes.to_string()),
+            ..Default::default()
+        };
+
+        let sent = sent(&new_g_task);
+        let mut insert = self.hub.tasks().insert(new_g_task, &self.asana_task_list);
+        if let Some(parent) = parent {
+            insert = insert.parent(parent);
+        }
+
+        let created = doit("POST", &self.tasks_path(None), sent, insert.doit()).await?;
+        created.id.context("created task has no id")
+    }
+
+    async fn set_text(&self, id: &str, title: &str, notes: &str) -> Result<()> {
+        let patch = Task {
+            title: Some(title.to_string()),
+            notes: Some(notes.to_string()),
             ..Default::default()
         };
 
-        self.hub
+        let sent = sent(&patch);
+        let call = self
+            .hub
             .tasks()
-            .insert(new_g_task, &self.asana_task_list)
-            .doit()
-            .await?;
+            .patch(patch, &self.asana_task_list, id)
+            .doit();
+        doit("PATCH", &self.tasks_path(Some(id)), sent, call).await?;
         Ok(())
     }
 
-    pub async fn get_tasks(&self) -> Result<GTaskResult> {
+    async fn move_to_top_level(&self, id: &str) -> Result<()> {
+        let call = self.hub.tasks().move_(&self.asana_task_list, id).doit();
+        doit(
+            "POST",
+            &self.tasks_path(Some(&format!("{id}/move"))),
+            None,
+            call,
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn move_after(&self, id: &str, previous: Option<&str>) -> Result<()> {
+        let mut call = self.hub.tasks().move_(&self.asana_task_list, id);
+        if let Some(previous) = previous {
+            call = call.previous(previous);
+        }
+        doit(
+            "POST",
+            &self.tasks_path(Some(&format!("{id}/move"))),
+            None,
+            call.doit(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Unchanged lists aren't sent again: the first page is asked for with the etag it came with
+    /// last time, and google answers 304 while no task in the list changed
+    async fn get_tasks(&self) -> Result<GTaskResult> {
+        let list = &self.asana_task_list;
+        let cached = self.listed.lock().unwrap().get(list).cloned();
         let mut result = GTaskResult {
             incomplete: Vec::new(),
             complete: Vec::new(),
         };
+        let mut etag = None;
 
         let mut next_page: Option<String> = None;
         loop {
-            let tasks_result = self
-                .hub
-                .tasks()
-                .list(&self.asana_task_list)
-                .max_results(100)
-                .show_completed(true)
-                .show_hidden(true);
-
-            let tasks_result = if let Some(page_token) = next_page {
-                tasks_result.page_token(&page_token).doit().await?
-            } else {
-                tasks_result.doit().await?
-            };
+            let mut request = self
+                .request(Method::GET, &self.tasks_path(None))
+                .await?
+                .query(&[
+                    ("maxResults", "100"),
+                    ("showCompleted", "true"),
+                    ("showHidden", "true"),
+                ]);
+            match (&next_page, &cached) {
+                (Some(page_token), _) => request = request.query(&[("pageToken", page_token)]),
+                (None, Some((cached_etag, _))) => {
+                    request = request.header(IF_NONE_MATCH, cached_etag)
+                }
+                (None, None) => {}
+            }
+            let resp = http::send(request, "fetch google tasks").await?;
+            if resp.status() == StatusCode::NOT_MODIFIED
+                && let Some((_, tasks)) = cached
+            {
+                return Ok(tasks);
+            }
+            let page: Tasks = resp
+                .json()
+                .await
+                .context("google sent tasks in a shape that couldn't be read")?;
+            if next_page.is_none() {
+                etag = page.etag;
+            }
 
-            next_page = tasks_result.1.next_page_token;
+            next_page = page.next_page_token;
 
-            for task in tasks_result.1.items.unwrap() {
+            // an empty list comes without any items
+            for task in page.items.unwrap_or_default() {
                 if task.completed.is_some() {
                     result.complete.push(task);
                 } else {
@@ -144,31 +520,444 @@ impl GoogleTaskMgr {
             }
         }
 
+        if let Some(etag) = etag {
+            self.listed
+                .lock()
+                .unwrap()
+                .insert(list.clone(), (etag, result.clone()));
+        }
         Ok(result)
     }
 
-    pub async fn del_task(&self, id: &str) -> Result<()> {
-        self.hub
-            .tasks()
-            .delete(&self.asana_task_list, id)
-            .doit()
-            .await?;
+    async fn del_task(&self, id: &str) -> Result<()> {
+        let call = self.hub.tasks().delete(&self.asana_task_list, id).doit();
+        doit_empty("DELETE", &self.tasks_path(Some(id)), call).await
+    }
+
+    async fn del_task_if(&self, id: &str, etag: &str) -> Result<()> {
+        // google answers 412 when the etag no longer matches, which is a conflict
+        let request = self
+            .request(Method::DELETE, &self.tasks_path(Some(id)))
+            .await?
+            .header(IF_MATCH, etag);
+        http::send(request, &format!("delete google task {id}")).await?;
         Ok(())
     }
+
+    async fn clear_completed(&self) -> Result<()> {
+        let call = self.hub.tasks().clear(&self.asana_task_list).doit();
+        let list = &self.asana_task_list;
+        doit_empty("POST", &format!("lists/{list}/clear"), call).await
+    }
 }
 
-pub fn get_asana_task_gid(task: &Task) -> Option<String> {
-    if let Some(note) = &task.notes {
-        let mut lines = note.lines();
+/// The body a call sends, for the trace. Only serialized while tracing.
+fn sent(body: &impl Serialize) -> Option<Vec<u8>> {
+    trace::enabled()
+        .then(|| serde_json::to_vec(body).ok())
+        .flatten()
+}
 
-        while let Some(line) = lines.next() {
-            if line == "---"
-                && let Some(gid) = lines.next()
-            {
-                return Some(gid.to_string());
-            }
+/// Await a call of the generated client, which doesn't go through [`crate::http::send`], and
+/// classify its error. While tracing the call is written down as `method` on `path`, with the
+/// body it `sent` and the one that came back.
+async fn doit<T: Serialize>(
+    method: &str,
+    path: &str,
+    sent: Option<Vec<u8>>,
+    call: impl Future<Output = google_tasks1::Result<(google_tasks1::common::Response, T)>>,
+) -> Result<T> {
+    let result = call.await;
+    if trace::enabled() {
+        let (status, received) = match &result {
+            Ok((resp, body)) => (
+                Some(resp.status().as_u16()),
+                serde_json::to_vec(body).ok().filter(|b| b != b"null"),
+            ),
+            Err(e) => (None, Some(e.to_string().into_bytes())),
+        };
+        let target = format!("google tasks {path}");
+        trace::exchange(
+            method,
+            &target,
+            status,
+            sent.as_deref(),
+            received.as_deref(),
+        );
+    }
+    result.map(|(_, body)| body).map_err(google_error)
+}
+
+/// [`doit`] for calls that get nothing back
+async fn doit_empty(
+    method: &str,
+    path: &str,
+    call: impl Future<Output = google_tasks1::Result<google_tasks1::common::Response>>,
+) -> Result<()> {
+    doit(method, path, None, async {
+        call.await.map(|resp| (resp, ()))
+    })
+    .await
+}
+
+/// Classify an error from the generated client as a [`BridgeError`], keeping google's message
+fn google_error(e: google_tasks1::Error) -> anyhow::Error {
+    let error = match e {
+        google_tasks1::Error::Failure(resp) => match StatusCode::from_u16(resp.status().as_u16()) {
+            Ok(status) => BridgeError::from_status("call google tasks", status, None),
+            Err(_) => return anyhow!("Failed to call google tasks. Status: {}", resp.status()),
+        },
+        google_tasks1::Error::BadRequest(ref body) => {
+            let status = body["error"]["code"]
+                .as_u64()
+                .and_then(|code| StatusCode::from_u16(u16::try_from(code).ok()?).ok());
+            let Some(status) = status else {
+                return e.into();
+            };
+            let message = body["error"]["message"].as_str().unwrap_or_default();
+            BridgeError::from_status(&format!("call google tasks ({message})"), status, None)
+        }
+        google_tasks1::Error::HttpError(e) => BridgeError::network("call google tasks", e),
+        google_tasks1::Error::MissingToken(e) => BridgeError::Auth {
+            what: "call google tasks".to_string(),
+            detail: e.to_string(),
+        },
+        e => return e.into(),
+    };
+    error.into()
+}
+
+/// Id of the list `wanted` names: its id, or its title in any case. The error names every list
+/// there is, to pick from.
+pub fn find_list(lists: &[TaskList], wanted: &str) -> Result<String> {
+    let by_title = |exact: bool| -> Vec<&TaskList> {
+        lists
+            .iter()
+            .filter(|l| {
+                l.title.as_deref().is_some_and(|title| {
+                    title == wanted || (!exact && title.to_lowercase() == wanted.to_lowercase())
+                })
+            })
+            .collect()
+    };
+    let found = match lists.iter().find(|l| l.id.as_deref() == Some(wanted)) {
+        Some(list) => Some(list),
+        // an exact title wins over ones only differing in case
+        None => match (by_title(true).as_slice(), by_title(false).as_slice()) {
+            ([list], _) | ([], [list]) => Some(*list),
+            ([], []) => None,
+            _ => bail!("more than one list is titled \"{wanted}\", set the list by its id instead"),
+        },
+    };
+    if let Some(id) = found.and_then(|l| l.id.clone()) {
+        return Ok(id);
+    }
+
+    let available: Vec<String> = lists
+        .iter()
+        .map(|l| {
+            format!(
+                "\"{}\" (id {})",
+                l.title.as_deref().unwrap_or_default(),
+                l.id.as_deref().unwrap_or_default()
+            )
+        })
+        .collect();
+    bail!(
+        "no list titled \"{wanted}\" or with that id, set the list to one of: {}",
+        available.join(", ")
+    )
+}
+
+/// First line of the metadata block the bridge appends to the notes of its tasks
+const MARKER_HEADER: &str = "--- gtasks-asana-bridge:v1 ---";
+
+/// The bridge's metadata at the end of a task's notes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Marker<'a> {
+    /// Versioned block, with its `gid=` value if it has one
+    V1(Option<&'a str>),
+    /// A bare `---` line followed by the gid, as older versions wrote it. Rewritten as a versioned
+    /// block the next time the bridge writes the task.
+    Legacy(&'a str),
+}
+
+impl<'a> Marker<'a> {
+    pub fn gid(self) -> Option<&'a str> {
+        match self {
+            Marker::V1(gid) => gid,
+            Marker::Legacy(gid) => Some(gid),
+        }
+    }
+}
+
+pub fn with_marker(notes: &str, asana_gid: &str) -> String {
+    format!("{notes}\n{MARKER_HEADER}\ngid={asana_gid}")
+}
+
+/// Split notes into the part the user wrote or sees and the bridge's marker
+pub fn split_marker(notes: &str) -> (&str, Option<Marker<'_>>) {
+    let at_line_start = |i: usize| i == 0 || notes[..i].ends_with('\n');
+    let body_before = |i: usize| {
+        let body = &notes[..i];
+        body.strip_suffix('\n').unwrap_or(body)
+    };
+
+    if let Some((start, _)) = notes
+        .match_indices(MARKER_HEADER)
+        .find(|(i, _)| at_line_start(*i))
+    {
+        let gid = notes[start + MARKER_HEADER.len()..]
+            .lines()
+            .find_map(|line| line.strip_prefix("gid="))
+            .map(str::trim);
+        return (body_before(start), Some(Marker::V1(gid)));
+    }
+
+    // the last `---` line, so one the user typed above the marker isn't mistaken for it
+    let legacy = notes
+        .rfind("\n---\n")
+        .map(|i| i + 1)
+        .or_else(|| notes.starts_with("---\n").then_some(0));
+    match legacy {
+        Some(start) => {
+            let gid = notes[start + 4..].lines().next().unwrap_or_default();
+            (body_before(start), Some(Marker::Legacy(gid)))
         }
+        None => (notes, None),
     }
+}
 
-    None
+/// Notes without the bridge's marker
+pub fn notes_body(notes: &str) -> &str {
+    split_marker(notes).0
+}
+
+/// Day a google task is due, google keeps only the date part of its due timestamps
+pub fn due_date(task: &Task) -> Option<civil::Date> {
+    task.due.as_deref()?.get(..10)?.parse().ok()
+}
+
+pub fn get_asana_task_gid(task: &Task) -> Option<String> {
+    let notes = task.notes.as_deref()?;
+    split_marker(notes)
+        .1?
+        .gid()
+        .filter(|gid| !gid.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use google_tasks1::common::NoToken;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{header, method, path, query_param, query_param_is_missing},
+    };
+
+    use super::*;
+
+    const LIST_ID: &str = "T2RsRnVxX3JkVXl0a0RYaw";
+
+    fn fixture(body: &str) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_raw(body, "application/json")
+    }
+
+    async fn server_with_lists() -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/tasks/v1/users/@me/lists"))
+            .respond_with(fixture(include_str!(
+                "../tests/fixtures/google/tasklists.json"
+            )))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    async fn connect(server: &MockServer, list_title: &str) -> Result<GoogleTaskMgr> {
+        // the connector needs a crypto provider even for plain http, main installs it otherwise
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        GoogleTaskMgr::connect(NoToken, &format!("{}/", server.uri()), list_title).await
+    }
+
+    #[tokio::test]
+    async fn connect_finds_list_by_title() {
+        let server = server_with_lists().await;
+
+        let gtasks = connect(&server, "Asana").await.unwrap();
+        assert_eq!(gtasks.list_id(), LIST_ID);
+
+        let gtasks = connect(&server, "asana").await.unwrap();
+        assert_eq!(gtasks.list_id(), LIST_ID);
+        let gtasks = connect(&server, LIST_ID).await.unwrap();
+        assert_eq!(gtasks.list_id(), LIST_ID);
+
+        let err = format!("{:#}", connect(&server, "Nope").await.err().unwrap());
+        assert!(err.contains("Nope"), "{err}");
+        // the lists to choose from
+        assert!(err.contains(&format!("\"Asana\" (id {LIST_ID})")), "{err}");
+    }
+
+    #[tokio::test]
+    async fn get_tasks_follows_pagination() {
+        let server = server_with_lists().await;
+        let tasks_path = format!("/tasks/v1/lists/{LIST_ID}/tasks");
+        Mock::given(method("GET"))
+            .and(path(tasks_path.as_str()))
+            .and(query_param_is_missing("pageToken"))
+            .respond_with(fixture(include_str!(
+                "../tests/fixtures/google/tasks_page1.json"
+            )))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(tasks_path.as_str()))
+            .and(query_param("pageToken", "CgwI0s_cqAYQ8JXl7wI"))
+            .respond_with(fixture(include_str!(
+                "../tests/fixtures/google/tasks_page2.json"
+            )))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let tasks = connect(&server, "Asana")
+            .await
+            .unwrap()
+            .get_tasks()
+            .await
+            .unwrap();
+
+        assert_eq!(tasks.incomplete.len(), 1);
+        assert_eq!(
+            get_asana_task_gid(&tasks.incomplete[0]).as_deref(),
+            Some("1208834419234561")
+        );
+        assert_eq!(tasks.complete.len(), 1);
+        assert_eq!(tasks.complete[0].title.as_deref(), Some("Pay rent"));
+    }
+
+    #[tokio::test]
+    async fn empty_list_has_no_tasks() {
+        let server = server_with_lists().await;
+        Mock::given(method("GET"))
+            .and(path(format!("/tasks/v1/lists/{LIST_ID}/tasks").as_str()))
+            .respond_with(fixture(
+                r#"{"kind": "tasks#tasks", "etag": "\"LTE2OTA4NTQ0\""}"#,
+            ))
+            .mount(&server)
+            .await;
+
+        let tasks = connect(&server, "Asana")
+            .await
+            .unwrap()
+            .get_tasks()
+            .await
+            .unwrap();
+        assert!(tasks.incomplete.is_empty());
+        assert!(tasks.complete.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unchanged_lists_arent_sent_again() {
+        let server = server_with_lists().await;
+        let tasks_path = format!("/tasks/v1/lists/{LIST_ID}/tasks");
+        Mock::given(method("GET"))
+            .and(path(tasks_path.as_str()))
+            .and(header("if-none-match", "\"LTE2OTA4NTQ0\""))
+            .respond_with(ResponseTemplate::new(304))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(tasks_path.as_str()))
+            .respond_with(fixture(
+                r#"{"kind": "tasks#tasks", "etag": "\"LTE2OTA4NTQ0\"",
+                    "items": [{"id": "g1", "title": "Pay rent"}]}"#,
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let gtasks = connect(&server, "Asana").await.unwrap();
+        let first = gtasks.get_tasks().await.unwrap();
+        let second = gtasks.get_tasks().await.unwrap();
+        assert_eq!(first.incomplete.len(), 1);
+        assert_eq!(second.incomplete[0].title.as_deref(), Some("Pay rent"));
+    }
+
+    #[tokio::test]
+    async fn deleting_a_task_edited_meanwhile_is_a_conflict() {
+        let server = server_with_lists().await;
+        Mock::given(method("DELETE"))
+            .and(path(format!("/tasks/v1/lists/{LIST_ID}/tasks/g1").as_str()))
+            .and(header("if-match", "\"old\""))
+            .respond_with(ResponseTemplate::new(412))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let gtasks = connect(&server, "Asana").await.unwrap();
+        let err = gtasks.del_task_if("g1", "\"old\"").await.unwrap_err();
+        assert!(matches!(
+            BridgeError::of(&err),
+            Some(BridgeError::Conflict(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn rate_limited_request_is_an_error() {
+        let server = server_with_lists().await;
+        Mock::given(method("GET"))
+            .and(path(format!("/tasks/v1/lists/{LIST_ID}/tasks").as_str()))
+            .respond_with(ResponseTemplate::new(429).set_body_raw(
+                r#"{"error": {"code": 429, "message": "Quota exceeded", "status": "RESOURCE_EXHAUSTED"}}"#,
+                "application/json",
+            ))
+            .mount(&server)
+            .await;
+
+        let gtasks = connect(&server, "Asana").await.unwrap();
+        assert!(gtasks.get_tasks().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn malformed_response_is_an_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/tasks/v1/users/@me/lists"))
+            .respond_with(fixture(r#"{"items": [{"id": "#))
+            .mount(&server)
+            .await;
+
+        assert!(connect(&server, "Asana").await.is_err());
+    }
+
+    #[test]
+    fn marker_survives_dashes_in_the_notes() {
+        let notes = with_marker("Agenda\n---\n12 items", "1208834419234561");
+        assert_eq!(
+            split_marker(&notes),
+            (
+                "Agenda\n---\n12 items",
+                Some(Marker::V1(Some("1208834419234561")))
+            )
+        );
+
+        let empty = with_marker("", "100");
+        assert_eq!(split_marker(&empty), ("", Some(Marker::V1(Some("100")))));
+    }
+
+    #[test]
+    fn legacy_marker_is_still_read() {
+        assert_eq!(
+            split_marker("Agenda\n---\nitems\n---\n1208834419234561"),
+            (
+                "Agenda\n---\nitems",
+                Some(Marker::Legacy("1208834419234561"))
+            )
+        );
+        assert_eq!(split_marker("No marker"), ("No marker", None));
+    }
 }
@@ -0,0 +1,38 @@
+use anyhow::{Result, bail};
+use log::warn;
+
+/// Below this many deletions the percentage limit isn't applied, so small lists can still be
+/// emptied normally
+const MIN_DELETIONS_FOR_PERCENT: usize = 4;
+
+/// Limits on how many incomplete google tasks one cycle may delete
+#[derive(Debug, Clone)]
+pub struct DeletionLimits {
+    pub max_count: usize,
+    pub max_percent: usize,
+    pub force: bool,
+}
+
+impl DeletionLimits {
+    /// Fail if deleting `deletions` of `list_len` tasks exceeds the limits
+    pub fn check(&self, deletions: usize, list_len: usize) -> Result<()> {
+        let over_count = deletions > self.max_count;
+        let over_percent =
+            deletions >= MIN_DELETIONS_FOR_PERCENT && deletions * 100 > self.max_percent * list_len;
+
+        if !over_count && !over_percent {
+            return Ok(());
+        }
+
+        if self.force {
+            warn!("Deleting {deletions} of {list_len} google tasks, allowed by --force");
+            return Ok(());
+        }
+
+        bail!(
+            "refusing to delete {deletions} of {list_len} google tasks (limits: {} tasks, {}%), rerun with --force if this is intended",
+            self.max_count,
+            self.max_percent
+        )
+    }
+}
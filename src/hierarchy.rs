@@ -0,0 +1,219 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use serde::Deserialize;
+
+use crate::{
+    asana::{self, AsanaApi},
+    redact,
+};
+
+/// How asana subtasks deeper than google's single level of nesting are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlattenPolicy {
+    /// Deeper subtasks become top level google tasks titled "Parent > Child"
+    Promote,
+    /// Deeper subtasks are rendered as a checklist in the notes of their first level ancestor
+    Checklist,
+}
+
+/// What happens to the open subtasks of a parent that was completed on either side
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CascadePolicy {
+    /// Complete the subtasks along with their parent
+    Complete,
+    /// Detach the subtasks so they carry on as top level tasks
+    Promote,
+    /// Leave the subtasks open in asana, they stop being synced with their parent
+    Leave,
+}
+
+/// Add the subtasks of every incomplete task to `tasks`. First level subtasks are nested under
+/// their parent's google task, deeper levels are flattened according to `policy`.
+pub async fn expand_subtasks(
+    asana_mgr: &impl AsanaApi,
+    tasks: &mut asana::TaskResult,
+    policy: FlattenPolicy,
+) -> Result<()> {
+    let mut seen: HashSet<String> = tasks
+        .incomplete
+        .iter()
+        .chain(tasks.complete.iter())
+        .map(|t| t.gid.clone())
+        .collect();
+
+    let parents = tasks.incomplete.clone();
+    for parent in &parents {
+        for mut child in asana_mgr.get_subtasks(&parent.gid).await? {
+            // subtasks assigned to us already show up as top level tasks
+            if !seen.insert(child.gid.clone()) {
+                continue;
+            }
+
+            inherit(&mut child, parent);
+            child.parent_gid = Some(parent.gid.clone());
+
+            let mut checklist = String::new();
+            let child_path = format!("{} > {}", parent.name, child.name);
+            let mut stack = nested_subtasks(asana_mgr, &child.gid, 2, &child_path).await?;
+            while let Some((mut task, depth, path)) = stack.pop() {
+                if !seen.insert(task.gid.clone()) {
+                    continue;
+                }
+
+                match policy {
+                    FlattenPolicy::Promote => {
+                        inherit(&mut task, parent);
+                        task.name = path.clone();
+                        push(tasks, task.clone());
+                    }
+                    FlattenPolicy::Checklist => {
+                        let mark = if task.completed_at.is_some() {
+                            "x"
+                        } else {
+                            " "
+                        };
+                        checklist.push_str(&format!(
+                            "{}[{mark}] {}\n",
+                            "  ".repeat(depth - 2),
+                            task.name
+                        ));
+                    }
+                }
+
+                stack.extend(nested_subtasks(asana_mgr, &task.gid, depth + 1, &path).await?);
+            }
+
+            if !checklist.is_empty() {
+                if !child.notes.is_empty() {
+                    child.notes.push_str("\n\n");
+                }
+                child.notes.push_str("Subtasks:\n");
+                child.notes.push_str(checklist.trim_end());
+            }
+
+            push(tasks, child);
+        }
+    }
+
+    Ok(())
+}
+
+/// Subtasks of a task as a stack that pops them in asana's order, tagged with depth and title path
+async fn nested_subtasks(
+    asana_mgr: &impl AsanaApi,
+    gid: &str,
+    depth: usize,
+    path: &str,
+) -> Result<Vec<(asana::Task, usize, String)>> {
+    let mut subtasks: Vec<_> = asana_mgr
+        .get_subtasks(gid)
+        .await?
+        .into_iter()
+        .map(|t| {
+            let subtask_path = format!("{path} > {}", t.name);
+            (t, depth, subtask_path)
+        })
+        .collect();
+    subtasks.reverse();
+
+    Ok(subtasks)
+}
+
+/// Subtasks without their own due date or section take their top level ancestor's, and stay
+/// private along with it
+fn inherit(child: &mut asana::Task, ancestor: &asana::Task) {
+    if child.due_on.is_none() && child.due_at.is_none() {
+        child.due_on = ancestor.due_on;
+        child.due_at = ancestor.due_at;
+    }
+    child.assignee_section = ancestor.assignee_section.clone();
+    child.memberships = ancestor.memberships.clone();
+    redact::inherit_private(child, ancestor);
+}
+
+fn push(tasks: &mut asana::TaskResult, task: asana::Task) {
+    if task.completed_at.is_some() {
+        tasks.complete.push(task);
+    } else {
+        tasks.incomplete.push(task);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::civil::date;
+
+    use super::*;
+    use crate::{
+        google::GoogleTasksApi,
+        mock::{MockAsana, MockGoogle, asana_task, sync_options},
+        state::State,
+        sync::{self, Policies, SyncOptions},
+    };
+
+    /// "Write report" with a subtask "Draft", which has one of its own, "Outline"
+    fn report() -> MockAsana {
+        let asana = MockAsana::new(vec![asana_task("100", "Write report", date(2026, 10, 20))]);
+        let mut draft = asana_task("101", "Draft", date(2026, 10, 20));
+        draft.due_on = None;
+        let outline = asana_task("102", "Outline", date(2026, 10, 18));
+        let mut subtasks = asana.subtasks.borrow_mut();
+        subtasks.insert("100".to_string(), vec![draft]);
+        subtasks.insert("101".to_string(), vec![outline]);
+        drop(subtasks);
+        asana
+    }
+
+    #[tokio::test]
+    async fn subtasks_are_nested_and_deeper_ones_promoted() {
+        let asana = report();
+        let google = MockGoogle::new();
+        let mut state = State::default();
+        let options = SyncOptions {
+            policies: Policies {
+                flatten: Some(FlattenPolicy::Promote),
+                ..sync_options().policies
+            },
+            ..sync_options()
+        };
+
+        sync::process_tasks(&asana, &google, &mut state, &options)
+            .await
+            .unwrap();
+
+        let parent = state.links["100"].google_id.clone();
+        let gtasks = google.get_tasks().await.unwrap().incomplete;
+        let titled = |title: &str| {
+            gtasks
+                .iter()
+                .find(|t| t.title.as_deref() == Some(title))
+                .unwrap()
+        };
+        let draft = titled("Draft");
+        assert_eq!(draft.parent.as_ref(), Some(&parent));
+        // without a due date of its own it takes its parent's
+        assert_eq!(state.links["101"].due, Some(date(2026, 10, 20)));
+        let outline = titled("Write report > Draft > Outline");
+        assert_eq!(outline.parent, None);
+        assert_eq!(state.links["102"].due, Some(date(2026, 10, 18)));
+    }
+
+    #[tokio::test]
+    async fn deeper_subtasks_become_a_checklist() {
+        let asana = report();
+        let mut tasks = asana.get_tasks().await.unwrap();
+
+        expand_subtasks(&asana, &mut tasks, FlattenPolicy::Checklist)
+            .await
+            .unwrap();
+
+        let gids: Vec<_> = tasks.incomplete.iter().map(|t| t.gid.as_str()).collect();
+        assert_eq!(gids, ["100", "101"]);
+        let draft = &tasks.incomplete[1];
+        assert_eq!(draft.parent_gid.as_deref(), Some("100"));
+        assert_eq!(draft.notes, "Subtasks:\n[ ] Outline");
+    }
+}
@@ -0,0 +1,245 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use jiff::Timestamp;
+use reqwest::{
+    Response, ResponseBuilderExt, StatusCode,
+    header::{HeaderMap, RETRY_AFTER},
+};
+
+use crate::{error::BridgeError, trace};
+
+/// Seconds a request may take, reading the response included, when `bridge.request_timeout_secs`
+/// isn't set
+pub const DEFAULT_TIMEOUT_SECS: u64 = 60;
+
+/// Connecting is quick when the server is reachable at all
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Keeps connections open across a few cycles at the default interval
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+/// Notices connections dropped by a NAT or proxy while they sat idle in the pool
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+/// Headers APIs report their rate limit in, the requests left then the size of the window
+const RATE_LIMIT_HEADERS: [(&str, &str); 2] = [
+    ("x-ratelimit-remaining", "x-ratelimit-limit"),
+    // trello's limit per token
+    (
+        "x-rate-limit-api-token-remaining",
+        "x-rate-limit-api-token-max",
+    ),
+];
+
+static TIMEOUT: OnceLock<Duration> = OnceLock::new();
+
+/// Latest rate limit seen from each API, by host
+static RATE_LIMITS: Mutex<BTreeMap<String, RateLimit>> = Mutex::new(BTreeMap::new());
+
+/// How many requests an API said it had left
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    pub remaining: u64,
+    /// Requests allowed per window, when the API says
+    pub limit: Option<u64>,
+    pub seen: Timestamp,
+}
+
+/// Give up on requests that take longer than `timeout`. Only the first call has any effect.
+pub fn set_timeout(timeout: Duration) {
+    let _ = TIMEOUT.set(timeout);
+}
+
+fn timeout() -> Duration {
+    *TIMEOUT.get_or_init(|| Duration::from_secs(DEFAULT_TIMEOUT_SECS))
+}
+
+/// Client sending `headers` with every request. Its connections are pooled and kept alive, with
+/// HTTP/2 where the server offers it, so build one per API and clone it rather than building more.
+pub fn client(headers: HeaderMap) -> Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .default_headers(headers)
+        .timeout(timeout())
+        .connect_timeout(CONNECT_TIMEOUT.min(timeout()))
+        .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+        .tcp_keepalive(KEEP_ALIVE)
+        .http2_keep_alive_interval(KEEP_ALIVE)
+        .http2_keep_alive_timeout(CONNECT_TIMEOUT)
+        .build()
+        .context("failed to build http client")
+}
+
+/// Note the rate limit `resp` reports, for [`rate_limits`]. Being told to slow down counts as none
+/// left, from APIs that don't report one otherwise.
+pub fn observe(resp: &Response) {
+    let Some(host) = resp.url().host_str() else {
+        return;
+    };
+    let number = |name: &str| resp.headers().get(name)?.to_str().ok()?.parse().ok();
+    let reported = RATE_LIMIT_HEADERS.iter().find_map(|(remaining, limit)| {
+        Some(RateLimit {
+            remaining: number(remaining)?,
+            limit: number(limit),
+            seen: Timestamp::now(),
+        })
+    });
+    let rate_limit = match reported {
+        Some(rate_limit) => rate_limit,
+        None if resp.status() == StatusCode::TOO_MANY_REQUESTS => RateLimit {
+            remaining: 0,
+            limit: None,
+            seen: Timestamp::now(),
+        },
+        None => return,
+    };
+    RATE_LIMITS
+        .lock()
+        .unwrap()
+        .insert(host.to_string(), rate_limit);
+}
+
+/// Pass `resp` on if it succeeded, or say why it didn't. Running out of requests counts as being
+/// rate limited, github answers 403 then. A 304 is passed on too, only conditional requests get
+/// one and they're after it.
+pub fn check(resp: Response, what: &str) -> Result<Response, BridgeError> {
+    let status = resp.status();
+    if status.is_success() || status == StatusCode::NOT_MODIFIED {
+        return Ok(resp);
+    }
+    let headers = resp.headers();
+    let retry_after = headers
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok()?.trim().parse().ok())
+        .map(Duration::from_secs);
+    let out_of_requests = RATE_LIMIT_HEADERS
+        .iter()
+        .any(|(remaining, _)| headers.get(*remaining).is_some_and(|v| v == "0"));
+    if status == StatusCode::FORBIDDEN && out_of_requests {
+        return Err(BridgeError::RateLimited {
+            what: what.to_string(),
+            retry_after,
+        });
+    }
+    Err(BridgeError::from_status(what, status, retry_after))
+}
+
+/// Send `request`, note its rate limit and [`check`] the response
+pub async fn send(request: reqwest::RequestBuilder, what: &str) -> Result<Response, BridgeError> {
+    let resp = if trace::enabled() {
+        send_traced(request).await
+    } else {
+        request.send().await
+    }
+    .map_err(|e| BridgeError::network(what, e))?;
+    observe(&resp);
+    check(resp, what)
+}
+
+/// Send `request` and write it to the trace along with the response. The response is read to
+/// the end for that and handed back as if it hadn't been.
+async fn send_traced(request: reqwest::RequestBuilder) -> reqwest::Result<Response> {
+    let (client, request) = request.build_split();
+    let request = request?;
+    let method = request.method().to_string();
+    let url = request.url().to_string();
+    let sent = request
+        .body()
+        .and_then(|b| b.as_bytes())
+        .map(<[u8]>::to_vec);
+    let resp = match client.execute(request).await {
+        Ok(resp) => resp,
+        Err(e) => {
+            trace::exchange(&method, &url, None, sent.as_deref(), None);
+            return Err(e);
+        }
+    };
+
+    let status = resp.status();
+    let mut rebuilt = http::Response::builder()
+        .status(status)
+        .version(resp.version())
+        .url(resp.url().clone());
+    if let Some(headers) = rebuilt.headers_mut() {
+        *headers = resp.headers().clone();
+    }
+    let received = resp.bytes().await?;
+    trace::exchange(
+        &method,
+        &url,
+        Some(status.as_u16()),
+        sent.as_deref(),
+        Some(&received),
+    );
+    Ok(rebuilt
+        .body(received)
+        .expect("the parts of a response that was received")
+        .into())
+}
+
+/// Latest rate limit each API reported, by host
+pub fn rate_limits() -> BTreeMap<String, RateLimit> {
+    RATE_LIMITS.lock().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{Mock, MockServer, ResponseTemplate, matchers::method};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn rate_limits_are_noted_by_host() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("x-ratelimit-remaining", "4990")
+                    .insert_header("x-ratelimit-limit", "5000"),
+            )
+            .mount(&server)
+            .await;
+
+        let resp = client(HeaderMap::new())
+            .unwrap()
+            .get(server.uri())
+            .send()
+            .await
+            .unwrap();
+        observe(&resp);
+
+        let limit = rate_limits()["127.0.0.1"];
+        assert_eq!(limit.remaining, 4990);
+        assert_eq!(limit.limit, Some(5000));
+    }
+
+    #[tokio::test]
+    async fn failed_responses_are_classified() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "30"))
+            .mount(&server)
+            .await;
+
+        let request = client(HeaderMap::new()).unwrap().get(server.uri());
+        let e = send(request, "fetch").await.unwrap_err();
+        assert!(matches!(
+            e,
+            BridgeError::RateLimited {
+                retry_after: Some(wait),
+                ..
+            } if wait == Duration::from_secs(30)
+        ));
+        assert_eq!(
+            e.to_string(),
+            "Failed to fetch. Status: 429 Too Many Requests"
+        );
+
+        let e = BridgeError::from_status("fetch", StatusCode::BAD_GATEWAY, None);
+        assert_eq!(e.disposition(), crate::error::Disposition::Retry);
+        let e = BridgeError::from_status("fetch", StatusCode::UNAUTHORIZED, None);
+        assert_eq!(e.disposition(), crate::error::Disposition::Halt);
+    }
+}
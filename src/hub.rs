@@ -0,0 +1,314 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use log::debug;
+
+use crate::{
+    GoogleTaskMgr, JiraMgr, MsTodoMgr, NotionMgr, TaskwarriorMgr, TrelloMgr,
+    asana::{self, AsanaApi},
+    breaker::Breaker,
+    config::{Backend, Config, WritebackConfig},
+    google::{GTaskResult, GoogleTasksApi, TaskList},
+    journal::MirrorOf,
+    state::State,
+    sync::{self, FailedActions, Policies, SyncOptions},
+};
+
+/// Any of the backends, picked by the config at runtime
+#[derive(Clone)]
+pub enum Endpoint {
+    Google(GoogleTaskMgr),
+    MsTodo(MsTodoMgr),
+    Jira(JiraMgr),
+    Trello(TrelloMgr),
+    Notion(NotionMgr),
+    Taskwarrior(TaskwarriorMgr),
+}
+
+/// Run `$body` with `$mgr` bound to whichever manager `$endpoint` holds
+macro_rules! each {
+    ($endpoint:expr, $mgr:ident => $body:expr) => {
+        match $endpoint {
+            Endpoint::Google($mgr) => $body,
+            Endpoint::MsTodo($mgr) => $body,
+            Endpoint::Jira($mgr) => $body,
+            Endpoint::Trello($mgr) => $body,
+            Endpoint::Notion($mgr) => $body,
+            Endpoint::Taskwarrior($mgr) => $body,
+        }
+    };
+}
+
+impl Endpoint {
+    /// Connect to `backend` with its section of the config, bound to its configured list
+    pub async fn connect(config: &Config, backend: Backend) -> Result<Self> {
+        let list = config
+            .list_id(backend)
+            .unwrap_or_else(|| config.list_title(backend));
+        Self::open(config, backend, &list).await
+    }
+
+    /// Connect to `backend` with its section of the config, bound to `list`
+    pub async fn open(config: &Config, backend: Backend, list: &str) -> Result<Self> {
+        Ok(match backend {
+            Backend::Google => Self::Google(GoogleTaskMgr::new(&config.google, list).await?),
+            Backend::MsTodo => Self::MsTodo(MsTodoMgr::new(&config.mstodo, list).await?),
+            Backend::Jira => Self::Jira(JiraMgr::new(&config.jira, list).await?),
+            Backend::Trello => Self::Trello(TrelloMgr::new(&config.trello, list).await?),
+            Backend::Notion => Self::Notion(NotionMgr::new(&config.notion, list).await?),
+            Backend::Taskwarrior => {
+                Self::Taskwarrior(TaskwarriorMgr::new(&config.taskwarrior, list)?)
+            }
+        })
+    }
+}
+
+impl GoogleTasksApi for Endpoint {
+    fn for_list(&self, list_id: &str) -> Self {
+        match self {
+            Endpoint::Google(mgr) => Endpoint::Google(mgr.for_list(list_id)),
+            Endpoint::MsTodo(mgr) => Endpoint::MsTodo(mgr.for_list(list_id)),
+            Endpoint::Jira(mgr) => Endpoint::Jira(mgr.for_list(list_id)),
+            Endpoint::Trello(mgr) => Endpoint::Trello(mgr.for_list(list_id)),
+            Endpoint::Notion(mgr) => Endpoint::Notion(mgr.for_list(list_id)),
+            Endpoint::Taskwarrior(mgr) => Endpoint::Taskwarrior(mgr.for_list(list_id)),
+        }
+    }
+
+    fn list_id(&self) -> &str {
+        each!(self, mgr => mgr.list_id())
+    }
+
+    async fn get_lists(&self) -> Result<Vec<TaskList>> {
+        each!(self, mgr => mgr.get_lists().await)
+    }
+
+    async fn create_list(&self, title: &str) -> Result<String> {
+        each!(self, mgr => mgr.create_list(title).await)
+    }
+
+    async fn delete_list(&self, id: &str) -> Result<()> {
+        each!(self, mgr => mgr.delete_list(id).await)
+    }
+
+    async fn new_task_from_asana(
+        &self,
+        task: &asana::Task,
+        title: &str,
+        notes: &str,
+        parent: Option<&str>,
+    ) -> Result<String> {
+        each!(self, mgr => mgr.new_task_from_asana(task, title, notes, parent).await)
+    }
+
+    async fn update_from_asana(
+        &self,
+        id: &str,
+        etag: Option<&str>,
+        task: &asana::Task,
+        title: &str,
+        notes: &str,
+    ) -> Result<bool> {
+        each!(self, mgr => mgr.update_from_asana(id, etag, task, title, notes).await)
+    }
+
+    async fn set_text(&self, id: &str, title: &str, notes: &str) -> Result<()> {
+        each!(self, mgr => mgr.set_text(id, title, notes).await)
+    }
+
+    async fn move_to_top_level(&self, id: &str) -> Result<()> {
+        each!(self, mgr => mgr.move_to_top_level(id).await)
+    }
+
+    async fn move_after(&self, id: &str, previous: Option<&str>) -> Result<()> {
+        each!(self, mgr => mgr.move_after(id, previous).await)
+    }
+
+    async fn get_tasks(&self) -> Result<GTaskResult> {
+        each!(self, mgr => mgr.get_tasks().await)
+    }
+
+    async fn del_task(&self, id: &str) -> Result<()> {
+        each!(self, mgr => mgr.del_task(id).await)
+    }
+
+    async fn del_task_if(&self, id: &str, etag: &str) -> Result<()> {
+        each!(self, mgr => mgr.del_task_if(id, etag).await)
+    }
+
+    async fn clear_completed(&self) -> Result<()> {
+        each!(self, mgr => mgr.clear_completed().await)
+    }
+}
+
+/// One of `bridge.mirrors`, kept in line with the source alongside the main backend
+pub struct Mirror {
+    pub backend: Backend,
+    pub endpoint: Endpoint,
+    /// Skips the mirror while it keeps failing
+    pub breaker: Breaker,
+}
+
+impl Mirror {
+    /// Connect to every backend in `bridge.mirrors`
+    pub async fn connect_all(config: &Config) -> Result<Vec<Mirror>> {
+        let mut mirrors = Vec::new();
+        for &backend in &config.bridge.mirrors {
+            let endpoint = Endpoint::connect(config, backend)
+                .await
+                .with_context(|| format!("failed to connect to the {} mirror", backend.name()))?;
+            let breaker = Breaker::new(backend.name(), &config.bridge);
+            mirrors.push(Mirror {
+                backend,
+                endpoint,
+                breaker,
+            });
+        }
+        Ok(mirrors)
+    }
+}
+
+/// Bring each mirror in line with `asana_tasks` in turn, after the main backend, all from the one
+/// fetch of the source. What changed in a mirror, like a completed copy, goes to the source, and
+/// reaches the other endpoints with the next cycle's fetch. A mirror failing doesn't hold up the
+/// others.
+pub async fn sync_mirrors(
+    asana_mgr: &impl AsanaApi,
+    asana_tasks: &asana::TaskResult,
+    mirrors: &[Mirror],
+    state: &mut State,
+    options: &SyncOptions,
+) -> Result<()> {
+    let mut failures = FailedActions::default();
+    let mut first_error = Ok(());
+    for mirror in mirrors {
+        let synced = mirror.breaker.call(sync_mirror(
+            asana_mgr,
+            asana_tasks,
+            mirror.backend.name(),
+            &mirror.endpoint,
+            state,
+            options,
+        ));
+        first_error = first_error.and(failures.absorb(synced.await));
+    }
+    first_error?;
+    failures.into_result()
+}
+
+/// Sync `asana_tasks` into one mirror, with its links kept apart from the main backend's in
+/// `state.mirrors` under `name`. Its actions are journaled along with the rest of the state.
+pub async fn sync_mirror(
+    asana_mgr: &impl AsanaApi,
+    asana_tasks: &asana::TaskResult,
+    name: &str,
+    mgr: &impl GoogleTasksApi,
+    state: &mut State,
+    options: &SyncOptions,
+) -> Result<()> {
+    debug!("Syncing the {name} mirror");
+    let mut mirror_state = state.mirrors.remove(name).unwrap_or_default();
+    let options = SyncOptions {
+        // the journal saves the mirror's state into the rest, which holds the main one
+        mirror: Some(Arc::new(MirrorOf {
+            name: name.to_string(),
+            root: state.clone(),
+        })),
+        // sections are written back once, along with the main backend
+        writeback: WritebackConfig::default(),
+        // only google takes requests at once, or clears completed tasks, mirrors go one by one
+        batch_size: 1,
+        policies: Policies {
+            clear_completed: false,
+            // calendar events stand in for timed tasks in the main backend only
+            timed_to_calendar: false,
+            ..options.policies.clone()
+        },
+        ..options.clone()
+    };
+
+    let result = sync::sync_endpoint(
+        asana_mgr,
+        asana_tasks.clone(),
+        mgr,
+        &mut mirror_state,
+        &options,
+    )
+    .await;
+    state.mirrors.insert(name.to_string(), mirror_state);
+    match result {
+        Err(e) if !e.is::<FailedActions>() => Err(e.context(format!("the {name} mirror"))),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::civil::date;
+
+    use super::*;
+    use crate::mock::{MockAsana, MockGoogle, asana_task, sync_options};
+
+    #[tokio::test]
+    async fn completing_in_one_mirror_completes_the_others() {
+        let asana = MockAsana::new(vec![asana_task("100", "Write report", date(2026, 10, 20))]);
+        let (todo, cards) = (MockGoogle::new(), MockGoogle::new());
+        let mut state = State::default();
+        let options = sync_options();
+
+        let tasks = asana.get_tasks().await.unwrap();
+        sync_mirror(&asana, &tasks, "mstodo", &todo, &mut state, &options)
+            .await
+            .unwrap();
+        sync_mirror(&asana, &tasks, "trello", &cards, &mut state, &options)
+            .await
+            .unwrap();
+        // each mirror links the task in its own state, keyed by the same gid
+        assert!(state.mirrors["mstodo"].links.contains_key("100"));
+        assert!(state.mirrors["trello"].links.contains_key("100"));
+        assert_eq!(todo.tasks().len(), 1);
+        assert_eq!(cards.tasks().len(), 1);
+
+        let card = state.mirrors["trello"].links["100"].google_id.clone();
+        cards.update(&card, |t| {
+            t.completed = Some("2026-10-16T12:00:00.000Z".to_string());
+        });
+        sync_mirror(&asana, &tasks, "trello", &cards, &mut state, &options)
+            .await
+            .unwrap();
+        assert!(asana.task("100").unwrap().completed_at.is_some());
+
+        // the next cycle's fetch takes the completion to the other mirror
+        let tasks = asana.get_tasks().await.unwrap();
+        sync_mirror(&asana, &tasks, "mstodo", &todo, &mut state, &options)
+            .await
+            .unwrap();
+        assert!(todo.get_tasks().await.unwrap().incomplete.is_empty());
+    }
+
+    #[tokio::test]
+    async fn mirror_actions_are_saved_into_the_state_file() {
+        let asana = MockAsana::new(vec![asana_task("100", "Write report", date(2026, 10, 20))]);
+        let (google, todo) = (MockGoogle::new(), MockGoogle::new());
+        let path = std::env::temp_dir().join("hub_mirror_state.json");
+        let path = path.to_str().unwrap().to_string();
+        let _ = std::fs::remove_file(&path);
+        let options = SyncOptions {
+            state_path: Some(path.clone()),
+            ..sync_options()
+        };
+        let mut state = State::default();
+
+        let tasks = asana.get_tasks().await.unwrap();
+        sync::sync_endpoint(&asana, tasks.clone(), &google, &mut state, &options)
+            .await
+            .unwrap();
+        sync_mirror(&asana, &tasks, "mstodo", &todo, &mut state, &options)
+            .await
+            .unwrap();
+
+        let saved = State::load(&path).unwrap();
+        assert!(saved.links.contains_key("100"));
+        assert!(saved.mirrors["mstodo"].links.contains_key("100"));
+    }
+}
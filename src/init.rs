@@ -0,0 +1,369 @@
+use std::io::Write;
+
+use anyhow::{Context, Result, bail};
+use gtasks_asana_bridge::{
+    AsanaApi, AsanaClient, GoogleTaskMgr, GoogleTasksApi, JiraMgr, MsTodoMgr, NotionMgr,
+    TaskSource, TaskwarriorMgr, TrelloMgr, asana,
+    config::{
+        Backend, Config, GoogleConfig, JiraConfig, MsTodoConfig, NotionConfig, TaskwarriorConfig,
+        TrelloConfig,
+    },
+    google::AuthFlow,
+    secrets::SecretSource,
+};
+
+/// Walk through the first-run setup: check the asana token, pick the workspace whose "My Tasks"
+/// is synced and the list it goes into, grant the bridge access to that list, then write the
+/// config file
+pub async fn run() -> Result<()> {
+    let path = Config::path();
+    if std::fs::exists(&path)? && !confirm(&format!("{path} already exists, replace it?"))? {
+        println!("Left {path} as it is");
+        return Ok(());
+    }
+
+    println!("Create an Asana personal access token at https://app.asana.com/0/my-apps");
+    let pat = ask("Asana personal access token", None)?;
+    // the source isn't known yet, and isn't needed to look it up
+    let asana_mgr = AsanaClient::new(
+        &pat,
+        TaskSource::UserTaskList(String::new()),
+        asana::DEFAULT_BASE_URL,
+    )?;
+    asana_mgr
+        .me()
+        .await
+        .context("asana didn't accept the token")?;
+    let workspaces = asana_mgr.workspaces().await?;
+    let workspace = pick("Asana workspace", &workspaces, |w| w.name.clone())?;
+    let project_gid = asana_mgr.my_task_list(&workspace.gid).await?;
+    println!(
+        "Syncing your \"My Tasks\" in {} ({project_gid})",
+        workspace.name
+    );
+
+    let mut config = toml::Table::new();
+    config.insert(
+        "asana".to_string(),
+        toml::Table::from_iter([
+            ("pat".to_string(), pat.into()),
+            ("project_gid".to_string(), project_gid.into()),
+        ])
+        .into(),
+    );
+
+    let backends = [
+        Backend::Google,
+        Backend::MsTodo,
+        Backend::Jira,
+        Backend::Trello,
+        Backend::Notion,
+        Backend::Taskwarrior,
+    ];
+    let backend = pick("Sync into", &backends, |b| match b {
+        Backend::Google => "Google Tasks".to_string(),
+        Backend::MsTodo => "Microsoft To Do".to_string(),
+        Backend::Jira => "Jira Cloud".to_string(),
+        Backend::Trello => "Trello".to_string(),
+        Backend::Notion => "A Notion database".to_string(),
+        Backend::Taskwarrior => "Taskwarrior on this machine".to_string(),
+    })?;
+    match backend {
+        Backend::Google => {
+            let defaults = GoogleConfig::default();
+            let SecretSource::File(default_secret) = &defaults.client_secret else {
+                unreachable!("the default client secret is a file");
+            };
+            println!(
+                "Create an OAuth client for a desktop app in the Google cloud console, with the Tasks API enabled, and download its JSON"
+            );
+            let client_secret = ask("Path of the downloaded JSON", Some(default_secret))?;
+            let flows = [AuthFlow::Redirect, AuthFlow::Manual, AuthFlow::Device];
+            let auth_flow = *pick("How to grant access", &flows, |f| match f {
+                AuthFlow::Redirect => {
+                    "Open the consent page in a browser on this machine".to_string()
+                }
+                AuthFlow::Manual => {
+                    "Paste the code back from another machine, e.g. over SSH".to_string()
+                }
+                AuthFlow::Device => {
+                    "Enter a code on google.com/device, needs a TV client".to_string()
+                }
+            })?;
+
+            let google = GoogleConfig {
+                client_secret: SecretSource::File(client_secret.clone()),
+                auth_flow,
+                ..defaults
+            };
+            let list = pick_list(&GoogleTaskMgr::unbound(&google).await?).await?;
+            let auth_flow = match auth_flow {
+                AuthFlow::Redirect => "redirect",
+                AuthFlow::Manual => "manual",
+                AuthFlow::Device => "device",
+            };
+            config.insert(
+                "google".to_string(),
+                toml::Table::from_iter([
+                    ("client_secret".to_string(), client_secret.into()),
+                    ("auth_flow".to_string(), auth_flow.into()),
+                    ("list".to_string(), list.into()),
+                ])
+                .into(),
+            );
+        }
+        Backend::MsTodo => {
+            println!(
+                "Register an app in the Microsoft Entra admin center with public client flows allowed and the Tasks.ReadWrite permission"
+            );
+            let client_id = ask("Application id", None)?;
+            let tenant = ask(
+                "Tenant id, or consumers for a personal account",
+                Some("consumers"),
+            )?;
+
+            let mstodo = MsTodoConfig {
+                client_id: Some(client_id.clone()),
+                tenant: tenant.clone(),
+                ..MsTodoConfig::default()
+            };
+            let list = pick_list(&MsTodoMgr::unbound(&mstodo).await?).await?;
+            config.insert(
+                "bridge".to_string(),
+                toml::Table::from_iter([("backend".to_string(), "mstodo".into())]).into(),
+            );
+            config.insert(
+                "mstodo".to_string(),
+                toml::Table::from_iter([
+                    ("client_id".to_string(), client_id.into()),
+                    ("tenant".to_string(), tenant.into()),
+                    ("list".to_string(), list.into()),
+                ])
+                .into(),
+            );
+        }
+        Backend::Jira => {
+            println!(
+                "Create an API token at https://id.atlassian.com/manage-profile/security/api-tokens"
+            );
+            let base_url = ask("Jira site, e.g. https://example.atlassian.net", None)?;
+            let email = ask("Email of your Atlassian account", None)?;
+            let token = ask("API token", None)?;
+
+            let jira = JiraConfig {
+                base_url: Some(base_url.clone()),
+                email: Some(email.clone()),
+                token: Some(SecretSource::Value(token.clone())),
+                ..JiraConfig::default()
+            };
+            // projects aren't created from here, that takes a jira admin
+            let projects = JiraMgr::unbound(&jira)
+                .await?
+                .get_lists()
+                .await
+                .context("jira didn't accept the token")?;
+            let project = pick("Project to sync into", &projects, |p| {
+                format!(
+                    "{} ({})",
+                    p.title.as_deref().unwrap_or_default(),
+                    p.id.as_deref().unwrap_or_default()
+                )
+            })?;
+            config.insert(
+                "bridge".to_string(),
+                toml::Table::from_iter([("backend".to_string(), "jira".into())]).into(),
+            );
+            config.insert(
+                "jira".to_string(),
+                toml::Table::from_iter([
+                    ("base_url".to_string(), base_url.into()),
+                    ("email".to_string(), email.into()),
+                    ("token".to_string(), token.into()),
+                    (
+                        "project".to_string(),
+                        project.id.clone().unwrap_or_default().into(),
+                    ),
+                ])
+                .into(),
+            );
+        }
+        Backend::Trello => {
+            println!(
+                "Create a power-up at https://trello.com/power-ups/admin, generate its API key, then follow the Token link next to it"
+            );
+            let key = ask("API key", None)?;
+            let token = ask("Token", None)?;
+
+            let trello = TrelloConfig {
+                key: Some(key.clone()),
+                token: Some(SecretSource::Value(token.clone())),
+                ..TrelloConfig::default()
+            };
+            let trello = TrelloMgr::unbound(&trello).await?;
+            let boards = trello
+                .boards()
+                .await
+                .context("trello didn't accept the key and token")?;
+            let board = pick("Board to sync into", &boards, |b| {
+                b.title.clone().unwrap_or_default()
+            })?;
+            let board = board.id.clone().unwrap_or_default();
+            let list = pick_list(&trello.for_board(&board)).await?;
+            config.insert(
+                "bridge".to_string(),
+                toml::Table::from_iter([("backend".to_string(), "trello".into())]).into(),
+            );
+            config.insert(
+                "trello".to_string(),
+                toml::Table::from_iter([
+                    ("key".to_string(), key.into()),
+                    ("token".to_string(), token.into()),
+                    ("board".to_string(), board.into()),
+                    ("list".to_string(), list.into()),
+                ])
+                .into(),
+            );
+        }
+        Backend::Notion => {
+            println!(
+                "Create an internal integration at https://www.notion.so/profile/integrations, then share the database to sync into with it"
+            );
+            let token = ask("Integration secret", None)?;
+
+            let notion = NotionConfig {
+                token: Some(SecretSource::Value(token.clone())),
+                ..NotionConfig::default()
+            };
+            let databases = NotionMgr::unbound(&notion)
+                .await?
+                .get_lists()
+                .await
+                .context("notion didn't accept the secret")?;
+            // a new database needs a page to go in, that's quicker to set up in notion
+            let database = pick("Database to sync into", &databases, |d| {
+                d.title.clone().unwrap_or_default()
+            })?;
+            config.insert(
+                "bridge".to_string(),
+                toml::Table::from_iter([("backend".to_string(), "notion".into())]).into(),
+            );
+            config.insert(
+                "notion".to_string(),
+                toml::Table::from_iter([
+                    ("token".to_string(), token.into()),
+                    (
+                        "database_id".to_string(),
+                        database.id.clone().unwrap_or_default().into(),
+                    ),
+                ])
+                .into(),
+            );
+            println!(
+                "Pages get their title, notes, due date, status and asana link from the properties Name, Notes, Due, Status and Asana, set [notion.properties] to use others"
+            );
+        }
+        Backend::Taskwarrior => {
+            let project =
+                pick_list(&TaskwarriorMgr::new(&TaskwarriorConfig::default(), "")?).await?;
+            config.insert(
+                "bridge".to_string(),
+                toml::Table::from_iter([("backend".to_string(), "taskwarrior".into())]).into(),
+            );
+            config.insert(
+                "taskwarrior".to_string(),
+                toml::Table::from_iter([("project".to_string(), project.into())]).into(),
+            );
+        }
+    }
+
+    write_private(&path, &toml::to_string(&config)?)?;
+    println!("Wrote {path}, see bridge.example.toml for everything else that can be set");
+    println!("Run `sync --once` to try it out");
+    Ok(())
+}
+
+/// Title of an existing list picked by the user, or of one created for the bridge
+async fn pick_list(mgr: &impl GoogleTasksApi) -> Result<String> {
+    let mut titles: Vec<String> = mgr
+        .get_lists()
+        .await?
+        .into_iter()
+        .filter_map(|l| l.title)
+        .collect();
+    const NEW_LIST: &str = "A new list";
+    titles.push(NEW_LIST.to_string());
+
+    let title = pick("List to sync into", &titles, Clone::clone)?;
+    if title != NEW_LIST {
+        return Ok(title.clone());
+    }
+    let title = ask("Title of the new list", Some("Asana"))?;
+    mgr.create_list(&title).await?;
+    Ok(title)
+}
+
+/// The file holds the asana token, so only its owner may read it
+fn write_private(path: &str, contents: &str) -> Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .with_context(|| format!("failed to write {path}"))
+}
+
+fn read_answer() -> Result<String> {
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer)? == 0 {
+        bail!("setup cancelled");
+    }
+    Ok(answer.trim().to_string())
+}
+
+/// Ask until there's an answer, or take `default` for an empty one
+fn ask(question: &str, default: Option<&str>) -> Result<String> {
+    loop {
+        match default {
+            Some(default) => print!("{question} [{default}]: "),
+            None => print!("{question}: "),
+        }
+        let answer = read_answer()?;
+        match (answer.is_empty(), default) {
+            (false, _) => return Ok(answer),
+            (true, Some(default)) => return Ok(default.to_string()),
+            (true, None) => continue,
+        }
+    }
+}
+
+fn confirm(question: &str) -> Result<bool> {
+    print!("{question} [y/N] ");
+    Ok(read_answer()?.eq_ignore_ascii_case("y"))
+}
+
+/// Let the user choose one of `items` by number, or take the only one
+fn pick<'a, T>(what: &str, items: &'a [T], label: impl Fn(&T) -> String) -> Result<&'a T> {
+    match items {
+        [] => bail!("no {} to choose from", what.to_lowercase()),
+        [only] => {
+            println!("{what}: {}", label(only));
+            return Ok(only);
+        }
+        _ => {}
+    }
+
+    println!("{what}:");
+    for (i, item) in items.iter().enumerate() {
+        println!("  {}) {}", i + 1, label(item));
+    }
+    loop {
+        print!("Number: ");
+        match read_answer()?.parse::<usize>() {
+            Ok(n) if (1..=items.len()).contains(&n) => return Ok(&items[n - 1]),
+            _ => println!("Pick a number between 1 and {}", items.len()),
+        }
+    }
+}
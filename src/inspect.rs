@@ -0,0 +1,169 @@
+use anyhow::{Context, Result};
+
+use crate::{
+    archive,
+    asana::{self, AsanaApi},
+    attachments,
+    canonical::CanonicalTask,
+    comments, date,
+    google::{self, GoogleTasksApi},
+    state::State,
+    sync::{self, FailedActions, SyncOptions},
+};
+
+/// Print everything the bridge knows about one asana task and what the next cycle would do with
+/// it, fetching the tasks the way a cycle does
+pub async fn inspect(
+    asana_mgr: &impl AsanaApi,
+    gtasks_mgr: &impl GoogleTasksApi,
+    state: &State,
+    options: &SyncOptions,
+    gid: &str,
+) -> Result<()> {
+    let policies = &options.policies;
+    let raw_asana = asana_mgr.get_task_raw(gid).await?;
+    println!("== Asana task {gid} ==");
+    println!("{}", serde_json::to_string_pretty(&raw_asana)?);
+
+    let atask: asana::Task =
+        serde_json::from_value(raw_asana).context("failed to parse asana task")?;
+    if let Some(due) = atask.due_date() {
+        println!(
+            "Due {due} ({})",
+            date::due_status(due, jiff::Timestamp::now())
+        );
+    }
+
+    println!();
+    match state.links.get(gid) {
+        Some(link) => {
+            println!("== Link ==");
+            println!("{}", serde_json::to_string_pretty(link)?);
+        }
+        None => println!("== Not linked in the state store =="),
+    }
+    for (mirror, mirror_state) in &state.mirrors {
+        if let Some(link) = mirror_state.links.get(gid) {
+            println!("== Link in the {mirror} mirror ==");
+            println!("{}", serde_json::to_string_pretty(link)?);
+        }
+    }
+
+    let google_tasks = gtasks_mgr.get_tasks().await?;
+    let linked: Vec<&google::Task> = google_tasks
+        .incomplete
+        .iter()
+        .chain(google_tasks.complete.iter())
+        .filter(|t| {
+            sync::linked_asana_gid(t, state, policies.correlator.as_ref()).as_deref() == Some(gid)
+        })
+        .collect();
+
+    println!();
+    if linked.is_empty() {
+        println!("== No linked google task ==");
+    }
+    for gtask in &linked {
+        println!(
+            "== Google task {} ==",
+            gtask.id.as_deref().unwrap_or("<no id>")
+        );
+        println!("{}", serde_json::to_string_pretty(gtask)?);
+    }
+
+    let (_, mut asana_tasks) = sync::fetch_asana_tasks(asana_mgr, state, options).await?;
+    sync::drop_others_tasks(asana_mgr, &mut asana_tasks, policies.only_mine).await?;
+    // comments and attachments only change what this task plans, so only it gets them
+    if let Some(i) = asana_tasks.incomplete.iter().position(|t| t.gid == gid) {
+        let mut this = asana::TaskResult {
+            incomplete: vec![asana_tasks.incomplete.remove(i)],
+            complete: Vec::new(),
+        };
+        let mut failures = FailedActions::default();
+        comments::attach(
+            asana_mgr,
+            &mut this,
+            policies.comments,
+            &options.comments_fetched,
+            &mut failures,
+        )
+        .await;
+        if policies.attachments {
+            attachments::attach(
+                asana_mgr,
+                &mut this,
+                &options.attachments_fetched,
+                &mut failures,
+            )
+            .await;
+        }
+        failures.into_result()?;
+        asana_tasks.incomplete.insert(i, this.incomplete.remove(0));
+    }
+
+    let fetched = asana_tasks
+        .incomplete
+        .iter()
+        .chain(asana_tasks.complete.iter())
+        .find(|t| t.gid == gid);
+    println!();
+    match fetched {
+        Some(_) => println!("== As the bridge sees it =="),
+        None => println!("== As the bridge would see it, it's not among the fetched tasks =="),
+    }
+    println!("{:#?}", CanonicalTask::from(fetched.unwrap_or(&atask)));
+
+    let linked_ids: Vec<&str> = linked.iter().filter_map(|t| t.id.as_deref()).collect();
+    if let Some(path) = &options.archive_path
+        && let Some(entry) = archive::load(path)?.into_iter().rev().find(|entry| {
+            entry.asana.as_ref().is_some_and(|a| a.gid == gid)
+                || linked_ids.contains(&entry.google.id.as_str())
+        })
+    {
+        println!();
+        println!("== Last archived ==");
+        println!("{}", serde_json::to_string_pretty(&entry)?);
+    }
+    for (key, failure) in &state.failures {
+        let id = key.split_once(':').map_or("", |(_, id)| id);
+        if id == gid || linked_ids.contains(&id) {
+            println!();
+            println!("== Failing: {key} ==");
+            println!("{}", serde_json::to_string_pretty(failure)?);
+        }
+    }
+
+    let vanished =
+        sync::find_vanished_asana_tasks(asana_mgr, &asana_tasks, &google_tasks, state, policies)
+            .await?;
+    let actions = sync::plan(
+        &asana_tasks,
+        &google_tasks,
+        state,
+        &vanished,
+        gtasks_mgr.list_id(),
+        policies,
+    );
+
+    let planned: Vec<_> = actions
+        .iter()
+        .filter(|a| {
+            a.asana_gid() == Some(gid) || a.google_id().is_some_and(|id| linked_ids.contains(&id))
+        })
+        .collect();
+
+    println!();
+    println!("== Next planned actions ==");
+    if planned.is_empty() {
+        if atask.due_date().is_none() {
+            println!("none, task has no due date so it is not synced");
+        } else {
+            println!("none");
+        }
+    }
+    for action in planned {
+        println!("{action}");
+    }
+
+    Ok(())
+}
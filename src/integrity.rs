@@ -0,0 +1,210 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Mutex,
+};
+
+use anyhow::{Context, Result};
+use log::warn;
+
+use crate::{
+    correlate::Correlator,
+    google,
+    notify::{self, NotifyEvent},
+    state::State,
+};
+
+/// Integrity gauges of every list checked so far, keyed by google list id
+static GAUGES: Mutex<BTreeMap<String, IntegrityReport>> = Mutex::new(BTreeMap::new());
+
+/// Counts of markers and state links that don't line up in one google list
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    /// Linked google tasks that lost their tag
+    pub missing_markers: usize,
+    /// Markers that aren't followed by an asana gid
+    pub malformed_markers: usize,
+    /// Extra incomplete google tasks carrying a gid that's already marked on another task
+    pub duplicate_markers: usize,
+    /// Google tasks whose marker names a different asana task than the state link
+    pub mismatched_links: usize,
+    /// State links pointing at a google task that isn't in the list anymore
+    pub dangling_links: usize,
+}
+
+impl IntegrityReport {
+    fn is_clean(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn gauges(&self) -> [(&'static str, usize); 5] {
+        [
+            ("missing_markers", self.missing_markers),
+            ("malformed_markers", self.malformed_markers),
+            ("duplicate_markers", self.duplicate_markers),
+            ("mismatched_links", self.mismatched_links),
+            ("dangling_links", self.dangling_links),
+        ]
+    }
+}
+
+/// Compare the tags in a google list with the state links that point into it
+pub fn check(
+    list_id: &str,
+    google_tasks: &google::GTaskResult,
+    state: &State,
+    correlator: &dyn Correlator,
+) -> IntegrityReport {
+    let mut report = IntegrityReport::default();
+
+    let linked_by_google_id: HashMap<&str, &str> = state
+        .links
+        .iter()
+        .filter(|(_, link)| link.google_list == list_id)
+        .map(|(gid, link)| (link.google_id.as_str(), gid.as_str()))
+        .collect();
+
+    let mut marked_gids = HashSet::new();
+    for gtask in google_tasks
+        .incomplete
+        .iter()
+        .chain(google_tasks.complete.iter())
+    {
+        let linked_gid = gtask
+            .id
+            .as_deref()
+            .and_then(|id| linked_by_google_id.get(id));
+
+        match marker(gtask, correlator) {
+            // nothing to lose when the correlator doesn't tag
+            Marker::Missing if linked_gid.is_some() && correlator.tags() => {
+                report.missing_markers += 1
+            }
+            Marker::Missing => {}
+            Marker::Malformed => report.malformed_markers += 1,
+            Marker::Gid(gid) => {
+                if linked_gid.is_some_and(|linked| *linked != gid) {
+                    report.mismatched_links += 1;
+                }
+                if gtask.completed.is_none() && !marked_gids.insert(gid) {
+                    report.duplicate_markers += 1;
+                }
+            }
+        }
+    }
+
+    let google_ids: HashSet<&str> = google_tasks
+        .incomplete
+        .iter()
+        .chain(google_tasks.complete.iter())
+        .filter_map(|t| t.id.as_deref())
+        .collect();
+    report.dangling_links = linked_by_google_id
+        .keys()
+        .filter(|id| !google_ids.contains(*id))
+        .count();
+
+    report
+}
+
+/// Warn about a dirty report and publish it as gauges to `metrics_path`, if set. New problems are
+/// also posted to the webhooks that want conflicts.
+pub fn record(list_id: &str, report: IntegrityReport, metrics_path: Option<&str>) -> Result<()> {
+    let mut gauges = GAUGES.lock().unwrap();
+    if !report.is_clean() {
+        let message = format!(
+            "Google list {list_id} has marker/mapping problems: {} missing, {} malformed, {} duplicate markers, {} mismatched, {} dangling links",
+            report.missing_markers,
+            report.malformed_markers,
+            report.duplicate_markers,
+            report.mismatched_links,
+            report.dangling_links
+        );
+        warn!("{message}");
+        if gauges.get(list_id) != Some(&report) {
+            notify::push(NotifyEvent::Conflicts, message);
+        }
+    }
+
+    gauges.insert(list_id.to_string(), report);
+
+    if let Some(path) = metrics_path {
+        write_textfile(path, &gauges)?;
+    }
+
+    Ok(())
+}
+
+/// Publish the gauges to `path` again, after the [breakers](crate::breaker) changed
+pub(crate) fn write_metrics(path: &str) -> Result<()> {
+    write_textfile(path, &GAUGES.lock().unwrap())
+}
+
+/// Write the gauges in the prometheus text format, for node_exporter's textfile collector
+#[cfg(feature = "metrics")]
+fn write_textfile(path: &str, gauges: &BTreeMap<String, IntegrityReport>) -> Result<()> {
+    let mut out = String::new();
+    for (i, (name, _)) in IntegrityReport::default().gauges().into_iter().enumerate() {
+        out.push_str(&format!("# TYPE gtasks_asana_bridge_{name} gauge\n"));
+        for (list_id, report) in gauges {
+            let value = report.gauges()[i].1;
+            out.push_str(&format!(
+                "gtasks_asana_bridge_{name}{{list=\"{list_id}\"}} {value}\n"
+            ));
+        }
+    }
+    let breakers = crate::breaker::gauges();
+    for (i, (name, _)) in crate::breaker::BreakerGauges::default()
+        .gauges()
+        .into_iter()
+        .enumerate()
+    {
+        out.push_str(&format!("# TYPE gtasks_asana_bridge_{name} gauge\n"));
+        for ((user, backend), gauges) in &breakers {
+            let value = gauges.gauges()[i].1;
+            // users share the textfile in multi-user mode
+            let user = if user.is_empty() {
+                String::new()
+            } else {
+                format!(",user=\"{user}\"")
+            };
+            out.push_str(&format!(
+                "gtasks_asana_bridge_{name}{{backend=\"{backend}\"{user}}} {value}\n"
+            ));
+        }
+    }
+
+    // same write then rename as the state file, so the collector never reads a partial file
+    let tmp_path = format!("{path}.tmp");
+    std::fs::write(&tmp_path, out).with_context(|| format!("failed to write {tmp_path}"))?;
+    std::fs::rename(&tmp_path, path).with_context(|| format!("failed to replace {path}"))?;
+    Ok(())
+}
+
+#[cfg(not(feature = "metrics"))]
+fn write_textfile(_path: &str, _gauges: &BTreeMap<String, IntegrityReport>) -> Result<()> {
+    anyhow::bail!("metrics need the bridge to be built with the metrics feature")
+}
+
+enum Marker {
+    Missing,
+    Malformed,
+    Gid(String),
+}
+
+fn marker(gtask: &google::Task, correlator: &dyn Correlator) -> Marker {
+    if let Some(gid) = correlator.gid(gtask) {
+        return Marker::Gid(gid);
+    }
+
+    // a notes marker block is the bridge's own, whichever correlator the list uses now
+    let Some(notes) = &gtask.notes else {
+        return Marker::Missing;
+    };
+    match google::split_marker(notes).1.map(google::Marker::gid) {
+        Some(Some(gid)) if !gid.is_empty() && gid.chars().all(|c| c.is_ascii_digit()) => {
+            Marker::Missing
+        }
+        Some(_) => Marker::Malformed,
+        None => Marker::Missing,
+    }
+}
@@ -0,0 +1,646 @@
+use anyhow::{Context, Result, bail};
+use log::debug;
+use reqwest::{RequestBuilder, Response, header::HeaderMap};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::{
+    asana,
+    canonical::{self, CanonicalTask},
+    config::JiraConfig,
+    error::BridgeError,
+    google::{self, GTaskResult, GoogleTasksApi, Task, TaskList},
+    http,
+};
+
+/// Label put on issues the bridge closed, so they aren't fetched again
+pub const CLOSED_LABEL: &str = "gtasks-asana-bridge-closed";
+
+const FIELDS: &str = "summary,description,duedate,status,resolutiondate,updated";
+
+/// Jira Cloud behind the same interface as google tasks, with a project standing in for the task
+/// list and its issues of one type for the tasks. Issues are closed rather than deleted: the bridge
+/// moves them to done and labels them, after which they're no longer fetched. Jira has no order
+/// the REST API can set outside a board, and subtasks end up at the top level of the project.
+#[derive(Clone)]
+pub struct JiraMgr {
+    client: reqwest::Client,
+    base_url: String,
+    email: String,
+    token: String,
+    issue_type: String,
+    done_transition: Option<String>,
+    project: String,
+}
+
+impl JiraMgr {
+    pub async fn new(config: &JiraConfig, project: &str) -> Result<Self> {
+        Self::connect(Self::unbound(config).await?, project).await
+    }
+
+    /// Manager that isn't bound to a project yet, for picking one with
+    /// [`GoogleTasksApi::get_lists`] and [`GoogleTasksApi::for_list`]
+    pub async fn unbound(config: &JiraConfig) -> Result<Self> {
+        let token = config
+            .token
+            .as_ref()
+            .context("no jira api token configured")?
+            .resolve()
+            .await
+            .context("failed to load the jira api token")?;
+
+        Ok(Self {
+            client: http::client(HeaderMap::new())?,
+            base_url: config
+                .base_url
+                .clone()
+                .context("no jira site configured")?
+                .trim_end_matches('/')
+                .to_string(),
+            email: config.email.clone().context("no jira email configured")?,
+            token,
+            issue_type: config.issue_type.clone(),
+            done_transition: config.done_transition.clone(),
+            project: String::new(),
+        })
+    }
+
+    /// Bind `mgr` to the project `project` names, by key or by name, see [`google::find_list`]
+    async fn connect(mut mgr: Self, project: &str) -> Result<Self> {
+        mgr.project = google::find_list(&mgr.get_lists().await?, project)
+            .context("failed to pick the jira project")?;
+        Ok(mgr)
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/rest/api/3/{path}", self.base_url)
+    }
+
+    async fn send(&self, request: RequestBuilder, what: &str) -> Result<Response> {
+        let request = request.basic_auth(&self.email, Some(&self.token));
+        Ok(http::send(request, what).await?)
+    }
+
+    async fn get_issue(&self, id: &str) -> Result<Issue> {
+        Ok(self
+            .send(
+                self.client
+                    .get(self.url(&format!("issue/{id}")))
+                    .query(&[("fields", FIELDS)]),
+                "fetch issue",
+            )
+            .await?
+            .json()
+            .await?)
+    }
+
+    async fn edit_issue(&self, id: &str, body: Value) -> Result<()> {
+        self.send(
+            self.client
+                .put(self.url(&format!("issue/{id}")))
+                .json(&body),
+            "edit issue",
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Move an issue to done, through the configured transition or else the first one leading
+    /// to a done status
+    async fn transition_to_done(&self, id: &str) -> Result<()> {
+        let transitions: Transitions = self
+            .send(
+                self.client
+                    .get(self.url(&format!("issue/{id}/transitions"))),
+                "fetch transitions",
+            )
+            .await?
+            .json()
+            .await?;
+
+        let transition = transitions
+            .transitions
+            .iter()
+            .find(|t| match &self.done_transition {
+                Some(name) => t.name.eq_ignore_ascii_case(name),
+                None => t.to.status_category.key == "done",
+            })
+            .with_context(|| {
+                let names: Vec<&str> = transitions
+                    .transitions
+                    .iter()
+                    .map(|t| t.name.as_str())
+                    .collect();
+                format!(
+                    "no transition to done for issue {id}, it can go through: {}",
+                    names.join(", ")
+                )
+            })?;
+
+        self.send(
+            self.client
+                .post(self.url(&format!("issue/{id}/transitions")))
+                .json(&json!({"transition": {"id": transition.id}})),
+            "transition issue",
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+impl GoogleTasksApi for JiraMgr {
+    fn for_list(&self, list_id: &str) -> Self {
+        Self {
+            project: list_id.to_string(),
+            ..self.clone()
+        }
+    }
+
+    fn list_id(&self) -> &str {
+        &self.project
+    }
+
+    async fn get_lists(&self) -> Result<Vec<TaskList>> {
+        let mut lists = Vec::new();
+
+        let mut start_at = 0;
+        loop {
+            let page: ProjectPage = self
+                .send(
+                    self.client
+                        .get(self.url("project/search"))
+                        .query(&[("startAt", start_at)]),
+                    "fetch projects",
+                )
+                .await?
+                .json()
+                .await?;
+
+            let fetched = page.values.len();
+            start_at += fetched;
+            lists.extend(page.values.into_iter().map(|p| TaskList {
+                id: Some(p.key),
+                title: Some(p.name),
+                ..Default::default()
+            }));
+            if page.is_last || fetched == 0 {
+                break;
+            }
+        }
+
+        Ok(lists)
+    }
+
+    async fn create_list(&self, title: &str) -> Result<String> {
+        bail!("the bridge doesn't create jira projects, create \"{title}\" in jira first")
+    }
+
+    async fn delete_list(&self, id: &str) -> Result<()> {
+        bail!("the bridge doesn't delete jira projects, {id} is left alone")
+    }
+
+    async fn new_task_from_asana(
+        &self,
+        task: &asana::Task,
+        title: &str,
+        notes: &str,
+        parent: Option<&str>,
+    ) -> Result<String> {
+        if parent.is_some() {
+            debug!("jira subtasks need their own issue type, adding \"{title}\" at the top level");
+        }
+
+        let due = asana::asana_due_to_string(task)?;
+        let created: Created = self
+            .send(
+                self.client.post(self.url("issue")).json(&json!({
+                    "fields": {
+                        "project": {"key": self.project},
+                        "issuetype": {"name": self.issue_type},
+                        "summary": title,
+                        "description": document(notes),
+                        "duedate": due.get(..10),
+                    }
+                })),
+                "create issue",
+            )
+            .await?
+            .json()
+            .await?;
+        Ok(created.id)
+    }
+
+    async fn update_from_asana(
+        &self,
+        id: &str,
+        etag: Option<&str>,
+        task: &asana::Task,
+        title: &str,
+        notes: &str,
+    ) -> Result<bool> {
+        // jira has no conditional edits either, so the update time is compared right before
+        if let Some(etag) = etag
+            && self.get_issue(id).await?.fields.updated.as_deref() != Some(etag)
+        {
+            return Err(BridgeError::Conflict(format!(
+                "jira issue {id} was edited since it was read"
+            ))
+            .into());
+        }
+
+        let due = asana::asana_due_to_string(task)?;
+        self.edit_issue(
+            id,
+            json!({
+                "fields": {
+                    "summary": title,
+                    "description": document(notes),
+                    "duedate": due.get(..10),
+                }
+            }),
+        )
+        .await?;
+        Ok(true)
+    }
+
+    async fn set_text(&self, id: &str, title: &str, notes: &str) -> Result<()> {
+        self.edit_issue(
+            id,
+            json!({"fields": {"summary": title, "description": document(notes)}}),
+        )
+        .await
+    }
+
+    async fn move_to_top_level(&self, _id: &str) -> Result<()> {
+        // every issue the bridge creates is already at the top level
+        Ok(())
+    }
+
+    async fn move_after(&self, id: &str, _previous: Option<&str>) -> Result<()> {
+        // ranking is part of the agile API and only means something on a board
+        debug!("jira issues aren't ranked by the bridge, leaving {id} in place");
+        Ok(())
+    }
+
+    async fn get_tasks(&self) -> Result<GTaskResult> {
+        let jql = format!(
+            "project = \"{}\" AND issuetype = \"{}\" AND (labels IS EMPTY OR labels != \"{CLOSED_LABEL}\")",
+            self.project, self.issue_type
+        );
+
+        let mut issues = Vec::new();
+        let mut next_page: Option<String> = None;
+        loop {
+            let page: SearchPage = self
+                .send(
+                    self.client.post(self.url("search/jql")).json(&json!({
+                        "jql": jql,
+                        "fields": FIELDS.split(',').collect::<Vec<_>>(),
+                        "maxResults": 100,
+                        "nextPageToken": next_page,
+                    })),
+                    "search issues",
+                )
+                .await?
+                .json()
+                .await?;
+
+            issues.extend(page.issues);
+            next_page = page.next_page_token;
+            if page.is_last || next_page.is_none() {
+                break;
+            }
+        }
+
+        let mut result = GTaskResult {
+            incomplete: Vec::new(),
+            complete: Vec::new(),
+        };
+        for task in issues
+            .into_iter()
+            .map(|issue| Task::from(CanonicalTask::from(issue)))
+        {
+            if task.completed.is_some() {
+                result.complete.push(task);
+            } else {
+                result.incomplete.push(task);
+            }
+        }
+
+        Ok(result)
+    }
+
+    async fn del_task(&self, id: &str) -> Result<()> {
+        if !self.get_issue(id).await?.fields.is_done() {
+            self.transition_to_done(id).await?;
+        }
+        self.edit_issue(id, json!({"update": {"labels": [{"add": CLOSED_LABEL}]}}))
+            .await
+    }
+
+    async fn del_task_if(&self, id: &str, etag: &str) -> Result<()> {
+        let issue = self.get_issue(id).await?;
+        if issue.fields.updated.as_deref() != Some(etag) {
+            return Err(BridgeError::Conflict(format!(
+                "jira issue {id} was edited since it was read"
+            ))
+            .into());
+        }
+        self.del_task(id).await
+    }
+}
+
+/// An Atlassian document with a paragraph per line of `text`
+fn document(text: &str) -> Value {
+    let paragraphs: Vec<Value> = text
+        .split('\n')
+        .map(|line| match line {
+            "" => json!({"type": "paragraph", "content": []}),
+            line => json!({"type": "paragraph", "content": [{"type": "text", "text": line}]}),
+        })
+        .collect();
+    json!({"type": "doc", "version": 1, "content": paragraphs})
+}
+
+/// The text of an Atlassian document, a line per paragraph, so [`document`] round-trips.
+/// Formatting added in jira is dropped.
+fn plain_text(doc: &Value) -> String {
+    fn inline(node: &Value, out: &mut String) {
+        match node["type"].as_str() {
+            Some("text") => out.push_str(node["text"].as_str().unwrap_or_default()),
+            Some("hardBreak") => out.push('\n'),
+            _ => {
+                for child in node["content"].as_array().into_iter().flatten() {
+                    inline(child, out);
+                }
+            }
+        }
+    }
+
+    let blocks: Vec<String> = doc["content"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|block| {
+            let mut text = String::new();
+            inline(block, &mut text);
+            text
+        })
+        .collect();
+    blocks.join("\n")
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProjectPage {
+    values: Vec<Project>,
+    #[serde(default)]
+    is_last: bool,
+}
+
+#[derive(Deserialize)]
+struct Project {
+    key: String,
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchPage {
+    issues: Vec<Issue>,
+    next_page_token: Option<String>,
+    #[serde(default)]
+    is_last: bool,
+}
+
+#[derive(Deserialize)]
+struct Created {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct Issue {
+    id: String,
+    fields: Fields,
+}
+
+#[derive(Deserialize)]
+struct Fields {
+    summary: Option<String>,
+    description: Option<Value>,
+    duedate: Option<String>,
+    status: Option<Status>,
+    resolutiondate: Option<String>,
+    updated: Option<String>,
+}
+
+impl Fields {
+    fn is_done(&self) -> bool {
+        self.status
+            .as_ref()
+            .is_some_and(|s| s.status_category.key == "done")
+    }
+}
+
+impl From<Issue> for CanonicalTask {
+    /// Its update time stands in for the revision
+    fn from(issue: Issue) -> Self {
+        let fields = issue.fields;
+        let status = if fields.is_done() {
+            canonical::Status::Done(fields.resolutiondate.clone())
+        } else {
+            canonical::Status::Open
+        };
+
+        CanonicalTask {
+            id: Some(issue.id),
+            title: fields.summary.unwrap_or_default(),
+            body: fields
+                .description
+                .as_ref()
+                .map(plain_text)
+                .unwrap_or_default(),
+            due: fields.duedate.and_then(|day| day.parse().ok()),
+            status,
+            revision: fields.updated,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Status {
+    status_category: StatusCategory,
+}
+
+#[derive(Deserialize)]
+struct StatusCategory {
+    key: String,
+}
+
+#[derive(Deserialize)]
+struct Transitions {
+    transitions: Vec<Transition>,
+}
+
+#[derive(Deserialize)]
+struct Transition {
+    id: String,
+    name: String,
+    to: Status,
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{body_partial_json, method, path},
+    };
+
+    use super::*;
+
+    async fn jira(server: &MockServer) -> JiraMgr {
+        Mock::given(method("GET"))
+            .and(path("/rest/api/3/project/search"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "values": [
+                    {"key": "OPS", "name": "Operations"},
+                    {"key": "ME", "name": "My work"}
+                ],
+                "isLast": true
+            })))
+            .mount(server)
+            .await;
+
+        let config = JiraConfig {
+            base_url: Some(server.uri()),
+            email: Some("me@example.com".to_string()),
+            token: Some(crate::secrets::SecretSource::Value("token".to_string())),
+            ..JiraConfig::default()
+        };
+        JiraMgr::new(&config, "my work").await.unwrap()
+    }
+
+    #[test]
+    fn notes_survive_the_trip_through_a_document() {
+        let notes = "Draft\n\n--- gtasks-asana-bridge:v1 ---\ngid=100";
+        assert_eq!(plain_text(&document(notes)), notes);
+    }
+
+    #[tokio::test]
+    async fn issues_look_like_google_tasks() {
+        let server = MockServer::start().await;
+        let jira = jira(&server).await;
+        assert_eq!(jira.list_id(), "ME");
+
+        Mock::given(method("POST"))
+            .and(path("/rest/api/3/search/jql"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "issues": [{
+                    "id": "10001",
+                    "fields": {
+                        "summary": "Write report",
+                        "description": document("Draft\n---\n1208834419234561"),
+                        "duedate": "2026-10-20",
+                        "status": {"statusCategory": {"key": "indeterminate"}},
+                        "updated": "2026-10-16T09:00:00.000+0000"
+                    }
+                }, {
+                    "id": "10002",
+                    "fields": {
+                        "summary": "Pay rent",
+                        "status": {"statusCategory": {"key": "done"}},
+                        "resolutiondate": "2026-10-16T12:00:00.000+0000"
+                    }
+                }],
+                "isLast": true
+            })))
+            .mount(&server)
+            .await;
+
+        let tasks = jira.get_tasks().await.unwrap();
+        assert_eq!(tasks.incomplete.len(), 1);
+        let task = &tasks.incomplete[0];
+        assert_eq!(
+            google::get_asana_task_gid(task).as_deref(),
+            Some("1208834419234561")
+        );
+        assert_eq!(task.due.as_deref(), Some("2026-10-20T00:00:00.000Z"));
+        assert_eq!(task.etag.as_deref(), Some("2026-10-16T09:00:00.000+0000"));
+        assert_eq!(tasks.complete.len(), 1);
+        assert_eq!(tasks.complete[0].title.as_deref(), Some("Pay rent"));
+    }
+
+    #[tokio::test]
+    async fn new_issues_carry_the_project_type_and_due_day() {
+        let server = MockServer::start().await;
+        let jira = jira(&server).await;
+        Mock::given(method("POST"))
+            .and(path("/rest/api/3/issue"))
+            .and(body_partial_json(json!({
+                "fields": {
+                    "project": {"key": "ME"},
+                    "issuetype": {"name": "Task"},
+                    "summary": "Write report",
+                    "duedate": "2026-10-20"
+                }
+            })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "id": "10001",
+                "key": "ME-1"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let task = crate::mock::asana_task("100", "Write report", jiff::civil::date(2026, 10, 20));
+        let id = jira
+            .new_task_from_asana(&task, "Write report", "Draft", None)
+            .await
+            .unwrap();
+        assert_eq!(id, "10001");
+    }
+
+    #[tokio::test]
+    async fn closed_issues_are_moved_to_done_and_labelled() {
+        let server = MockServer::start().await;
+        let jira = jira(&server).await;
+        Mock::given(method("GET"))
+            .and(path("/rest/api/3/issue/10001"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "10001",
+                "fields": {"status": {"statusCategory": {"key": "new"}}}
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/rest/api/3/issue/10001/transitions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "transitions": [
+                    {"id": "21", "name": "Start", "to": {"statusCategory": {"key": "indeterminate"}}},
+                    {"id": "31", "name": "Close", "to": {"statusCategory": {"key": "done"}}}
+                ]
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/rest/api/3/issue/10001/transitions"))
+            .and(body_partial_json(json!({"transition": {"id": "31"}})))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/rest/api/3/issue/10001"))
+            .and(body_partial_json(
+                json!({"update": {"labels": [{"add": CLOSED_LABEL}]}}),
+            ))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        jira.del_task("10001").await.unwrap();
+    }
+}
@@ -0,0 +1,515 @@
+use std::{fs::File, io::Write, sync::Arc};
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    asana::AsanaApi,
+    correlate::Correlator,
+    crypt,
+    google::GoogleTasksApi,
+    hub::Mirror,
+    state::State,
+    sync::{self, SyncAction},
+};
+
+/// One line of the journal
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Record {
+    /// About to run `action` against the google list `list`, in the mirror `mirror` if it's set
+    Begin {
+        list: String,
+        action: SyncAction,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mirror: Option<String>,
+    },
+    /// About to run `actions` against the google list `list` all at once
+    Batch {
+        list: String,
+        actions: Vec<SyncAction>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mirror: Option<String>,
+    },
+    /// The last begun action, or batch, ran and the state store was saved
+    Commit,
+    /// The last begun action failed, the planner retries it next cycle
+    Fail,
+}
+
+pub(crate) fn journal_path(state_path: &str) -> String {
+    format!("{state_path}.journal")
+}
+
+/// Write-ahead journal next to the state file. Each action is written down before it runs and
+/// marked done once it ran and the state store was saved, so at most one action, or one batch of
+/// them, is in doubt after the bridge is killed. [`recover`] finishes it at the next startup.
+pub struct Journal {
+    path: String,
+    state_path: String,
+    file: File,
+    mirror: Option<Arc<MirrorOf>>,
+}
+
+/// The rest of the state store while one of `bridge.mirrors` is synced with a state of its own,
+/// which the journal saves into it
+#[derive(Debug)]
+pub struct MirrorOf {
+    /// The mirror's backend name, its key in [`State::mirrors`]
+    pub name: String,
+    pub root: State,
+}
+
+impl Journal {
+    /// Start journaling the actions run against the state store at `state_path`, or against the
+    /// state of `mirror` kept in it
+    pub fn open(state_path: &str, mirror: Option<Arc<MirrorOf>>) -> Result<Self> {
+        let path = journal_path(state_path);
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("failed to open {path}"))?;
+        Ok(Self {
+            path,
+            state_path: state_path.to_string(),
+            file,
+            mirror,
+        })
+    }
+
+    fn save(&self, state: &State) -> Result<()> {
+        match &self.mirror {
+            Some(mirror) => {
+                let mut root = mirror.root.clone();
+                root.mirrors.insert(mirror.name.clone(), state.clone());
+                root.save(&self.state_path)
+            }
+            None => state.save(&self.state_path),
+        }
+    }
+
+    fn mirror_name(&self) -> Option<String> {
+        self.mirror.as_ref().map(|mirror| mirror.name.clone())
+    }
+
+    fn write(&mut self, record: &Record) -> Result<()> {
+        let mut line = crypt::seal_line(&serde_json::to_string(record)?)?;
+        line.push('\n');
+        self.file
+            .write_all(line.as_bytes())
+            .and_then(|()| self.file.sync_data())
+            .with_context(|| format!("failed to write to {}", self.path))
+    }
+
+    pub fn begin(&mut self, list: &str, action: &SyncAction) -> Result<()> {
+        self.write(&Record::Begin {
+            list: list.to_string(),
+            action: action.clone(),
+            mirror: self.mirror_name(),
+        })
+    }
+
+    /// Write down actions that run all at once, marked done together with [`Journal::commit`]
+    pub fn begin_batch(&mut self, list: &str, actions: &[SyncAction]) -> Result<()> {
+        self.write(&Record::Batch {
+            list: list.to_string(),
+            actions: actions.to_vec(),
+            mirror: self.mirror_name(),
+        })
+    }
+
+    /// Save `state` and mark the last begun action done
+    pub fn commit(&mut self, state: &State) -> Result<()> {
+        self.save(state)?;
+        self.write(&Record::Commit)
+    }
+
+    /// Save `state` and mark the last begun action failed. The state keeps what the action set out
+    /// to do, like the intent of an insert that may have landed anyway.
+    pub fn fail(&mut self, state: &State) -> Result<()> {
+        self.save(state)?;
+        self.write(&Record::Fail)
+    }
+
+    /// Every action ran, the journal isn't needed anymore
+    pub fn close(self) -> Result<()> {
+        drop(self.file);
+        std::fs::remove_file(&self.path).with_context(|| format!("failed to remove {}", self.path))
+    }
+}
+
+/// An action that was begun but neither committed nor failed
+#[derive(Debug)]
+struct InDoubt {
+    /// The mirror it ran against, the main backend when `None`
+    mirror: Option<String>,
+    list: String,
+    action: SyncAction,
+}
+
+/// The actions that were begun but neither committed nor failed
+fn in_doubt(contents: &str) -> Result<Vec<InDoubt>> {
+    let lines: Vec<&str> = contents.lines().filter(|l| !l.trim().is_empty()).collect();
+    let mut pending = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let record =
+            crypt::open_line(line).and_then(|line| Ok(serde_json::from_str::<Record>(&line)?));
+        let record = match record {
+            Ok(record) => record,
+            // the bridge was killed while writing the line, so the action never started
+            Err(_) if i + 1 == lines.len() => break,
+            Err(e) => return Err(e).with_context(|| format!("line {} is broken", i + 1)),
+        };
+        pending = match record {
+            Record::Begin {
+                list,
+                action,
+                mirror,
+            } => vec![InDoubt {
+                mirror,
+                list,
+                action,
+            }],
+            Record::Batch {
+                list,
+                actions,
+                mirror,
+            } => actions
+                .into_iter()
+                .map(|action| InDoubt {
+                    mirror: mirror.clone(),
+                    list: list.clone(),
+                    action,
+                })
+                .collect(),
+            Record::Commit | Record::Fail => Vec::new(),
+        };
+    }
+    Ok(pending)
+}
+
+/// Finish the action the bridge was running when it was last stopped, if any, and save the state
+/// store. Run before the first cycle. Actions in a mirror that's no longer configured are dropped.
+pub async fn recover(
+    state_path: &str,
+    asana_mgr: &impl AsanaApi,
+    gtasks_mgr: &impl GoogleTasksApi,
+    mirrors: &[Mirror],
+    state: &mut State,
+    correlator: &dyn Correlator,
+) -> Result<()> {
+    let path = journal_path(state_path);
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {path}")),
+    };
+
+    let pending = in_doubt(&contents).with_context(|| format!("failed to parse {path}"))?;
+    for InDoubt {
+        mirror,
+        list,
+        action,
+    } in pending
+    {
+        // deletions carry the list the task is in, which can be another one with section routing
+        let list = match &action {
+            SyncAction::DeleteGoogle { list, .. } => list.clone(),
+            _ => list,
+        };
+        let finished = match mirror {
+            None => {
+                warn!("The bridge was stopped during: {action}, finishing it");
+                finish(
+                    action,
+                    asana_mgr,
+                    &gtasks_mgr.for_list(&list),
+                    state,
+                    correlator,
+                )
+                .await
+            }
+            Some(name) => {
+                let Some(endpoint) = mirrors
+                    .iter()
+                    .find(|m| m.backend.name() == name)
+                    .map(|m| &m.endpoint)
+                else {
+                    warn!(
+                        "The bridge was stopped during: {action}, in the {name} mirror that's no longer configured"
+                    );
+                    continue;
+                };
+                warn!(
+                    "The bridge was stopped during: {action}, in the {name} mirror, finishing it"
+                );
+                let mut mirror_state = state.mirrors.remove(&name).unwrap_or_default();
+                let finished = finish(
+                    action,
+                    asana_mgr,
+                    &endpoint.for_list(&list),
+                    &mut mirror_state,
+                    correlator,
+                )
+                .await;
+                state.mirrors.insert(name, mirror_state);
+                finished
+            }
+        };
+        finished.context("failed to finish the interrupted action")?;
+        state.save(state_path)?;
+    }
+    std::fs::remove_file(&path).with_context(|| format!("failed to remove {path}"))
+}
+
+/// Run an action that may have been cut off partway. Google side steps that already happened are
+/// detected instead of repeated, everything else is safe to run twice.
+async fn finish(
+    action: SyncAction,
+    asana_mgr: &impl AsanaApi,
+    gtasks_mgr: &impl GoogleTasksApi,
+    state: &mut State,
+    correlator: &dyn Correlator,
+) -> Result<()> {
+    let google_tasks = gtasks_mgr.get_tasks().await?;
+    let find = |id: &str| {
+        google_tasks
+            .incomplete
+            .iter()
+            .chain(&google_tasks.complete)
+            .find(|t| t.id.as_deref() == Some(id))
+    };
+    // a copy created before the bridge stopped, found by its tag. Untagged copies can't be told
+    // apart from the user's tasks, those are left to the startup dedupe.
+    let created = |gid: &str, except: Option<&str>| {
+        google_tasks
+            .incomplete
+            .iter()
+            .chain(&google_tasks.complete)
+            .filter(|t| t.id.as_deref() != except)
+            .find(|t| correlator.gid(t).as_deref() == Some(gid))
+            .and_then(|t| t.id.clone())
+    };
+
+    match action {
+        SyncAction::CreateGoogle { task, fields } => match created(&task.gid, None) {
+            Some(google_id) => {
+                info!("\"{}\" was already created in google", task.name);
+                state.link(
+                    &task,
+                    gtasks_mgr.list_id(),
+                    &google_id,
+                    Some(fields.hash(&task)),
+                );
+            }
+            None => {
+                // an untagged copy may have landed all the same, the insert looks for it
+                sync::record_create_intent(&task, &fields, gtasks_mgr.list_id(), state, correlator);
+                let action = SyncAction::CreateGoogle { task, fields };
+                sync::execute_action(action, asana_mgr, gtasks_mgr, state, correlator).await?;
+            }
+        },
+        SyncAction::RecreateGoogle {
+            google_id,
+            task,
+            fields,
+            ..
+        } => {
+            if find(&google_id).is_some() {
+                gtasks_mgr.del_task(&google_id).await?;
+            }
+            let action = match created(&task.gid, Some(google_id.as_str())) {
+                Some(google_id) => {
+                    info!("\"{}\" was already recreated in google", task.name);
+                    let hash = fields.hash(&task);
+                    SyncAction::LinkGoogle {
+                        task,
+                        google_id,
+                        hash,
+                    }
+                }
+                None => {
+                    sync::record_create_intent(
+                        &task,
+                        &fields,
+                        gtasks_mgr.list_id(),
+                        state,
+                        correlator,
+                    );
+                    SyncAction::CreateGoogle { task, fields }
+                }
+            };
+            sync::execute_action(action, asana_mgr, gtasks_mgr, state, correlator).await?;
+        }
+        // a comment isn't safe to post twice
+        SyncAction::CommentAsana { gid, name, text } => {
+            let comments = asana_mgr.get_comments(&gid).await?;
+            if comments.iter().any(|c| c.text == text) {
+                info!("The note added to \"{name}\" was already posted to asana");
+            } else {
+                let action = SyncAction::CommentAsana { gid, name, text };
+                sync::execute_action(action, asana_mgr, gtasks_mgr, state, correlator).await?;
+            }
+        }
+        SyncAction::DeleteGoogle { ref google_id, .. } if find(google_id).is_none() => {
+            info!("Google task {google_id} was already deleted");
+        }
+        // flagging strips the tag, so a task that still has it wasn't flagged yet
+        SyncAction::FlagGoogle { ref task }
+            if task
+                .id
+                .as_deref()
+                .and_then(find)
+                .is_none_or(|t| correlator.gid(t).is_none()) =>
+        {
+            info!(
+                "Google task \"{}\" was already flagged",
+                task.title.as_deref().unwrap_or_default()
+            );
+        }
+        action => sync::execute_action(action, asana_mgr, gtasks_mgr, state, correlator).await?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::civil::date;
+
+    use super::*;
+    use crate::{
+        correlate::{NotesMarker, StateOnly},
+        google,
+        mock::{MockAsana, MockGoogle, asana_task},
+        sync::GoogleFields,
+    };
+
+    fn line(record: &Record) -> String {
+        serde_json::to_string(record).unwrap() + "\n"
+    }
+
+    #[test]
+    fn only_an_unfinished_action_is_in_doubt() {
+        let begin = |gid: &str| Record::Begin {
+            list: "list".to_string(),
+            action: SyncAction::CompleteAsana {
+                gid: gid.to_string(),
+                name: "Pay rent".to_string(),
+                modified_at: None,
+            },
+            mirror: None,
+        };
+        let done = line(&begin("100")) + &line(&Record::Commit);
+        assert!(in_doubt(&done).unwrap().is_empty());
+
+        let cut_short = done.clone() + &line(&begin("101"));
+        let pending = in_doubt(&cut_short).unwrap();
+        let [InDoubt { list, action, .. }] = pending.as_slice() else {
+            panic!("{pending:?}");
+        };
+        assert_eq!(list, "list");
+        assert_eq!(action.key(), "complete-asana:101");
+
+        // killed while writing the begin line, the action never ran
+        let torn = done.clone() + "{\"begin\":{\"li";
+        assert!(in_doubt(&torn).unwrap().is_empty());
+
+        // every action of a batch is in doubt until it's committed
+        let batch = Record::Batch {
+            list: "list".to_string(),
+            actions: ["102", "103"]
+                .map(|gid| SyncAction::DeleteGoogle {
+                    list: "list".to_string(),
+                    google_id: gid.to_string(),
+                    title: "Pay rent".to_string(),
+                    reason: sync::DeleteReason::CompletedInGoogle,
+                })
+                .to_vec(),
+            mirror: Some("trello".to_string()),
+        };
+        let pending = in_doubt(&(done.clone() + &line(&batch))).unwrap();
+        assert_eq!(pending.len(), 2);
+        assert!(
+            pending
+                .iter()
+                .all(|p| p.mirror.as_deref() == Some("trello"))
+        );
+        let committed = done + &line(&batch) + &line(&Record::Commit);
+        assert!(in_doubt(&committed).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn interrupted_create_links_the_copy_instead_of_duplicating() {
+        let asana = MockAsana::new(Vec::new());
+        let google = MockGoogle::new();
+        let mut state = State::default();
+        let task = asana_task("100", "Write report", date(2026, 10, 20));
+        let fields = GoogleFields {
+            title: task.name.clone(),
+            notes: String::new(),
+        };
+        // created in google, then killed before the link was saved
+        let notes = google::with_marker(&fields.notes, &task.gid);
+        let google_id = google
+            .new_task_from_asana(&task, &fields.title, &notes, None)
+            .await
+            .unwrap();
+
+        let action = SyncAction::CreateGoogle { task, fields };
+        finish(action, &asana, &google, &mut state, &NotesMarker)
+            .await
+            .unwrap();
+
+        assert_eq!(google.get_tasks().await.unwrap().incomplete.len(), 1);
+        assert_eq!(state.links["100"].google_id, google_id);
+    }
+
+    #[tokio::test]
+    async fn interrupted_untagged_create_is_found_by_its_contents() {
+        let asana = MockAsana::new(Vec::new());
+        let google = MockGoogle::new();
+        let mut state = State::default();
+        let task = asana_task("100", "Write report", date(2026, 10, 20));
+        let fields = GoogleFields {
+            title: task.name.clone(),
+            notes: "Quarterly numbers".to_string(),
+        };
+        // a user's task that only shares the title
+        google
+            .new_task_from_asana(&task, &fields.title, "", None)
+            .await
+            .unwrap();
+        let google_id = google
+            .new_task_from_asana(&task, &fields.title, &fields.notes, None)
+            .await
+            .unwrap();
+
+        let action = SyncAction::CreateGoogle { task, fields };
+        finish(action, &asana, &google, &mut state, &StateOnly)
+            .await
+            .unwrap();
+
+        assert_eq!(google.get_tasks().await.unwrap().incomplete.len(), 2);
+        assert_eq!(state.links["100"].google_id, google_id);
+    }
+
+    #[tokio::test]
+    async fn interrupted_delete_isnt_repeated() {
+        let asana = MockAsana::new(Vec::new());
+        let google = MockGoogle::new();
+        let mut state = State::default();
+
+        let action = SyncAction::DeleteGoogle {
+            list: google.list_id().to_string(),
+            google_id: "gone".to_string(),
+            title: "Pay rent".to_string(),
+            reason: sync::DeleteReason::CompletedInGoogle,
+        };
+        finish(action, &asana, &google, &mut state, &NotesMarker)
+            .await
+            .unwrap();
+        assert!(state.links.is_empty());
+    }
+}
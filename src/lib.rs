@@ -0,0 +1,82 @@
+//! Mirrors Asana tasks, or GitHub issues, into Google Tasks, Microsoft To Do, Jira, Trello,
+//! Notion or Taskwarrior. [`SyncEngine`] runs the sync between anything implementing
+//! [`AsanaApi`], like [`AsanaClient`], and anything implementing [`GoogleTasksApi`], the
+//! `gtasks-asana-bridge` binary wires the config and command line to it. The engine's errors
+//! come back as a [`BridgeError`].
+
+pub mod archive;
+pub mod asana;
+mod attachments;
+pub mod bootstrap;
+pub mod breaker;
+#[cfg(feature = "calendar")]
+mod calendar;
+pub mod canonical;
+mod catchup;
+mod comments;
+pub mod config;
+mod consent;
+pub mod control;
+pub mod correlate;
+pub mod crypt;
+mod cycle;
+pub mod date;
+#[cfg(feature = "email")]
+mod email;
+pub mod engine;
+pub mod error;
+pub mod export;
+pub mod fetched;
+mod filter;
+pub mod github;
+pub mod google;
+mod guardrail;
+mod hierarchy;
+pub mod http;
+pub mod hub;
+pub mod inspect;
+mod integrity;
+pub mod jira;
+mod journal;
+pub mod lock;
+pub mod logging;
+mod misplaced;
+#[cfg(test)]
+mod mock;
+pub mod mstodo;
+pub mod notify;
+pub mod notion;
+mod order;
+pub mod policy;
+pub mod purge;
+pub mod redact;
+pub mod repair;
+pub mod report;
+mod richtext;
+pub mod schedule;
+pub mod secrets;
+pub mod sections;
+mod snooze;
+pub mod star;
+pub mod state;
+pub mod status;
+pub mod supervisor;
+pub mod sync;
+pub mod taskwarrior;
+pub mod trace;
+pub mod trello;
+pub mod watchdog;
+
+pub use crate::{
+    asana::{AsanaApi, AsanaClient, TaskSource},
+    config::Config,
+    engine::{RunOptions, SyncEngine},
+    error::BridgeError,
+    github::GitHubClient,
+    google::{GoogleTaskMgr, GoogleTasksApi},
+    jira::JiraMgr,
+    mstodo::MsTodoMgr,
+    notion::NotionMgr,
+    taskwarrior::TaskwarriorMgr,
+    trello::TrelloMgr,
+};
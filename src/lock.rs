@@ -0,0 +1,463 @@
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    hash::{BuildHasher, RandomState},
+    io::Write,
+    sync::Mutex,
+    time::Duration,
+};
+
+use anyhow::{Context, Result, bail};
+use jiff::{SignedDuration, Timestamp};
+use log::{info, warn};
+use serde::Deserialize;
+
+use crate::{
+    asana,
+    google::{GTaskResult, GoogleTasksApi},
+};
+
+/// Title of the list each running bridge writes its claim into
+pub const LOCK_LIST: &str = "gtasks-asana-bridge lock";
+
+/// Title of the task the claim is written into
+const LOCK_TITLE: &str = "A bridge is syncing, don't edit or complete this";
+
+/// Least time a claim counts as live after it was last renewed
+const MIN_STALE_SECS: u64 = 300;
+
+/// What to do when another bridge already syncs the same state file or list
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LockPolicy {
+    /// Stop with an error
+    #[default]
+    Refuse,
+    /// Run, but only log what would change, like `--dry-run`
+    ReadOnly,
+    /// Don't look for other bridges at all
+    Off,
+}
+
+/// How long a claim counts as live without being renewed, some cycles' worth of `interval_secs`
+pub fn stale_after(interval_secs: u64) -> SignedDuration {
+    SignedDuration::from_secs(MIN_STALE_SECS.max(interval_secs * 3) as i64)
+}
+
+/// A random number, different in every process
+fn random() -> u64 {
+    RandomState::new().hash_one(std::process::id())
+}
+
+/// Names this bridge to other bridges: the host and the state file. It's the same after a
+/// restart, so a claim left behind by the last run on this machine is taken back at once.
+fn instance_id(state_path: &str) -> String {
+    let host = std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+        .unwrap_or_else(|| "unknown".to_string());
+    let path = std::path::absolute(state_path)
+        .map_or_else(|_| state_path.to_string(), |p| p.display().to_string());
+    format!("{host}:{path}")
+}
+
+/// What an instance writes into the notes of its lock task
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Claim {
+    instance: String,
+    heartbeat: Timestamp,
+}
+
+impl Claim {
+    fn notes(&self) -> String {
+        format!("instance={}\nheartbeat={}", self.instance, self.heartbeat)
+    }
+
+    fn parse(notes: &str) -> Option<Self> {
+        let field = |name: &str| notes.lines().find_map(|line| line.strip_prefix(name));
+        Some(Self {
+            instance: field("instance=")?.to_string(),
+            heartbeat: field("heartbeat=")?.parse().ok()?,
+        })
+    }
+}
+
+/// The live claim that wins, the one by the smallest instance id when two were written at once
+fn holder(tasks: &GTaskResult, now: Timestamp, stale: SignedDuration) -> Option<Claim> {
+    tasks
+        .incomplete
+        .iter()
+        .filter_map(|t| Claim::parse(t.notes.as_deref()?))
+        .filter(|claim| now.duration_since(claim.heartbeat) < stale)
+        .min_by(|a, b| a.instance.cmp(&b.instance))
+}
+
+/// The claim in the backend, renewed as cycles run
+pub struct RemoteLock<G> {
+    /// Manager of the lock list
+    mgr: G,
+    task_id: String,
+    instance: String,
+    stale: SignedDuration,
+    last_beat: Mutex<Timestamp>,
+}
+
+impl<G: GoogleTasksApi> RemoteLock<G> {
+    /// Claim the lock list for `instance`, or name the bridge that holds it. `settle` is how long
+    /// to wait for another bridge starting at the same time to write its claim too.
+    async fn claim(
+        gtasks_mgr: &G,
+        instance: &str,
+        stale: SignedDuration,
+        settle: Duration,
+    ) -> Result<Result<Self, String>> {
+        let lists = gtasks_mgr.get_lists().await?;
+        let list_id = match lists
+            .iter()
+            .find(|l| l.title.as_deref() == Some(LOCK_LIST))
+            .and_then(|l| l.id.clone())
+        {
+            Some(id) => id,
+            None => gtasks_mgr.create_list(LOCK_LIST).await?,
+        };
+        let mgr = gtasks_mgr.for_list(&list_id);
+
+        let tasks = mgr.get_tasks().await?;
+        if let Some(claim) = holder(&tasks, Timestamp::now(), stale)
+            && claim.instance != instance
+        {
+            return Ok(Err(held_by(&claim)));
+        }
+        let claim = Claim {
+            instance: instance.to_string(),
+            heartbeat: Timestamp::now(),
+        };
+        let task_id = match tasks.incomplete.first().and_then(|t| t.id.clone()) {
+            Some(task_id) => {
+                mgr.set_text(&task_id, LOCK_TITLE, &claim.notes()).await?;
+                task_id
+            }
+            None => {
+                mgr.new_task_from_asana(&lock_task(), LOCK_TITLE, &claim.notes(), None)
+                    .await?
+            }
+        };
+        let lock = Self {
+            mgr,
+            task_id,
+            instance: instance.to_string(),
+            stale,
+            last_beat: Mutex::new(claim.heartbeat),
+        };
+
+        // two bridges starting together both saw the list free, the smallest instance id wins
+        tokio::time::sleep(settle).await;
+        let tasks = lock.mgr.get_tasks().await?;
+        match holder(&tasks, Timestamp::now(), stale) {
+            Some(claim) if claim.instance != instance => Ok(Err(held_by(&claim))),
+            _ => Ok(Ok(lock)),
+        }
+    }
+
+    /// Delete the claim, unless another bridge took it over meanwhile
+    async fn release(&self) -> Result<()> {
+        let tasks = self.mgr.get_tasks().await?;
+        let ours = tasks
+            .incomplete
+            .iter()
+            .find(|t| t.id.as_deref() == Some(self.task_id.as_str()))
+            .and_then(|t| Claim::parse(t.notes.as_deref()?))
+            .is_some_and(|claim| claim.instance == self.instance);
+        if ours {
+            self.mgr.del_task(&self.task_id).await?;
+        }
+        Ok(())
+    }
+
+    async fn renew(&self) -> Result<()> {
+        let claim = Claim {
+            instance: self.instance.clone(),
+            heartbeat: Timestamp::now(),
+        };
+        self.mgr
+            .set_text(&self.task_id, LOCK_TITLE, &claim.notes())
+            .await?;
+        *self.last_beat.lock().unwrap() = claim.heartbeat;
+        Ok(())
+    }
+
+    /// Renew the claim once a third of its lifetime has passed. Fails when another bridge took
+    /// the lock over meanwhile, failing to reach the backend only warns.
+    async fn heartbeat(&self) -> Result<()> {
+        let now = Timestamp::now();
+        if now.duration_since(*self.last_beat.lock().unwrap()) < self.stale / 3 {
+            return Ok(());
+        }
+
+        let tasks = match self.mgr.get_tasks().await {
+            Ok(tasks) => tasks,
+            Err(e) => {
+                warn!("Failed to check the instance lock: {e:#}");
+                return Ok(());
+            }
+        };
+        if let Some(claim) = holder(&tasks, now, self.stale)
+            && claim.instance != self.instance
+        {
+            bail!("{}, stopping", held_by(&claim));
+        }
+        if let Err(e) = self.renew().await {
+            warn!("Failed to renew the instance lock: {e:#}");
+        }
+        Ok(())
+    }
+}
+
+fn held_by(claim: &Claim) -> String {
+    format!(
+        "another bridge ({}) syncs this list, it was last seen at {}",
+        claim.instance, claim.heartbeat
+    )
+}
+
+/// The task the lock task is created from, the backends only make tasks out of asana ones
+fn lock_task() -> asana::Task {
+    asana::Task {
+        gid: String::new(),
+        name: LOCK_TITLE.to_string(),
+        notes: String::new(),
+        html_notes: None,
+        permalink_url: None,
+        liked: false,
+        // undated, a claim isn't something to do
+        due_on: None,
+        due_at: None,
+        start_on: None,
+        start_at: None,
+        completed_at: None,
+        modified_at: None,
+        assignee: None,
+        assignee_section: None,
+        memberships: Vec::new(),
+        tags: Vec::new(),
+        parent_gid: None,
+        comments: Vec::new(),
+        attachments: Vec::new(),
+    }
+}
+
+/// Held for as long as the bridge runs: the lockfile next to the state file, which keeps out
+/// bridges on the same machine, and the claim in the backend, which keeps out those elsewhere
+pub struct InstanceLock<G> {
+    _file: File,
+    remote: Option<RemoteLock<G>>,
+}
+
+impl<G: GoogleTasksApi> InstanceLock<G> {
+    /// Keep the claim in the backend alive, see [`RemoteLock::heartbeat`]
+    pub async fn heartbeat(&self) -> Result<()> {
+        match &self.remote {
+            Some(remote) => remote.heartbeat().await,
+            None => Ok(()),
+        }
+    }
+
+    /// Give the lock up as the bridge stops, so the next bridge doesn't wait for the claim to go
+    /// stale. The lockfile is let go along with the lock.
+    pub async fn release(self) {
+        if let Some(remote) = &self.remote
+            && let Err(e) = remote.release().await
+        {
+            warn!("Failed to release the instance lock: {e:#}");
+        }
+    }
+}
+
+pub(crate) fn lock_path(state_path: &str) -> String {
+    format!("{state_path}.lock")
+}
+
+/// Lock the state file at `state_path` and, with `remote`, claim the backend `gtasks_mgr` syncs
+/// into, or say who has either. Only google is claimed, the other backends would show the lock
+/// list as a database, a board list or a project of its own. A backend the claim can't be written
+/// to is only warned about. Waits a random part of `settle` first, so bridges started together
+/// don't check at the same moment.
+pub async fn acquire<G: GoogleTasksApi>(
+    gtasks_mgr: &G,
+    state_path: &str,
+    remote: bool,
+    stale: SignedDuration,
+    settle: Duration,
+) -> Result<Result<InstanceLock<G>, String>> {
+    let path = lock_path(state_path);
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .with_context(|| format!("failed to open {path}"))?;
+    let instance = instance_id(state_path);
+    match file.try_lock() {
+        Ok(()) => {
+            file.set_len(0)?;
+            file.write_all(instance.as_bytes())?;
+        }
+        Err(TryLockError::WouldBlock) => {
+            let holder = std::fs::read_to_string(&path).unwrap_or_default();
+            return Ok(Err(format!(
+                "another bridge ({}) holds {path}",
+                holder.trim()
+            )));
+        }
+        Err(TryLockError::Error(e)) => {
+            return Err(e).with_context(|| format!("failed to lock {path}"));
+        }
+    }
+
+    if !remote {
+        return Ok(Ok(InstanceLock {
+            _file: file,
+            remote: None,
+        }));
+    }
+    if !settle.is_zero() {
+        tokio::time::sleep(settle.mul_f64((random() % 1000) as f64 / 1000.0)).await;
+    }
+    let remote = match RemoteLock::claim(gtasks_mgr, &instance, stale, settle).await {
+        Ok(Ok(remote)) => {
+            info!("Claimed the instance lock as {instance}");
+            Some(remote)
+        }
+        Ok(Err(held)) => return Ok(Err(held)),
+        Err(e) => {
+            warn!("Failed to claim the instance lock, only this machine is checked: {e:#}");
+            None
+        }
+    };
+    Ok(Ok(InstanceLock {
+        _file: file,
+        remote,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockGoogle;
+
+    fn state_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(name);
+        path.to_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn a_second_bridge_is_refused_until_the_first_goes_quiet() {
+        let google = MockGoogle::new();
+        let stale = SignedDuration::from_secs(300);
+        let first = acquire(
+            &google,
+            &state_path("lock_first.json"),
+            true,
+            stale,
+            Duration::ZERO,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        // elsewhere, with its own state file
+        let second = acquire(
+            &google,
+            &state_path("lock_second.json"),
+            true,
+            stale,
+            Duration::ZERO,
+        )
+        .await
+        .unwrap();
+        assert!(second.is_err());
+
+        // the first one stopped renewing its claim long ago
+        let remote = first.remote.as_ref().unwrap();
+        let quiet = Claim {
+            instance: remote.instance.clone(),
+            heartbeat: Timestamp::now() - SignedDuration::from_secs(600),
+        };
+        remote
+            .mgr
+            .set_text(&remote.task_id, LOCK_TITLE, &quiet.notes())
+            .await
+            .unwrap();
+        *remote.last_beat.lock().unwrap() = quiet.heartbeat;
+        let second = acquire(
+            &google,
+            &state_path("lock_second.json"),
+            true,
+            stale,
+            Duration::ZERO,
+        )
+        .await
+        .unwrap();
+        assert!(second.is_ok());
+        assert!(first.heartbeat().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn the_state_file_is_locked_on_this_machine() {
+        let google = MockGoogle::new();
+        let stale = SignedDuration::from_secs(300);
+        let path = state_path("lock_local.json");
+        let _held = acquire(&google, &path, true, stale, Duration::ZERO)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let held = acquire(&MockGoogle::new(), &path, true, stale, Duration::ZERO)
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert!(held.contains("lock_local.json.lock"), "{held}");
+    }
+
+    #[tokio::test]
+    async fn a_restarted_bridge_takes_its_claim_back() {
+        let google = MockGoogle::new();
+        let stale = SignedDuration::from_secs(300);
+        let path = state_path("lock_restart.json");
+        // killed, its claim left behind
+        drop(
+            acquire(&google, &path, true, stale, Duration::ZERO)
+                .await
+                .unwrap()
+                .unwrap(),
+        );
+        let lock = acquire(&google, &path, true, stale, Duration::ZERO)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // stopped properly, another bridge can start right away
+        lock.release().await;
+        let other = state_path("lock_restart_other.json");
+        acquire(&google, &other, true, stale, Duration::ZERO)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn only_google_is_claimed() {
+        let google = MockGoogle::new();
+        let stale = SignedDuration::from_secs(300);
+        let path = state_path("lock_local_only.json");
+        let lock = acquire(&google, &path, false, stale, Duration::ZERO)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(lock.remote.is_none());
+        let lists = google.get_lists().await.unwrap();
+        assert!(lists.iter().all(|l| l.title.as_deref() != Some(LOCK_LIST)));
+    }
+}
@@ -0,0 +1,63 @@
+use std::{collections::VecDeque, io::Write, sync::Mutex};
+
+use log::Level;
+
+/// Warnings and errors kept for [`captured`]
+const CAPTURED_LINES: usize = 100;
+
+/// Where log lines go instead of stderr while something else owns the terminal
+static CAPTURED: Mutex<Option<VecDeque<String>>> = Mutex::new(None);
+
+tokio::task_local! {
+    /// Name of the user whose sync loop is running, in multi-user mode
+    pub static USER: String;
+}
+
+/// Set up env_logger, putting the current user's name in front of each message
+pub fn init() {
+    env_logger::Builder::from_default_env()
+        .format(|buf, record| {
+            let user = USER
+                .try_with(|name| format!("{name}: "))
+                .unwrap_or_default();
+            if let Some(lines) = CAPTURED.lock().unwrap().as_mut() {
+                if record.level() <= Level::Warn {
+                    if lines.len() == CAPTURED_LINES {
+                        lines.pop_front();
+                    }
+                    lines.push_back(format!(
+                        "{} {:<5} {user}{}",
+                        buf.timestamp_seconds(),
+                        record.level(),
+                        record.args()
+                    ));
+                }
+                return Ok(());
+            }
+            let style = buf.default_level_style(record.level());
+            writeln!(
+                buf,
+                "[{} {style}{:<5}{style:#} {}] {user}{}",
+                buf.timestamp(),
+                record.level(),
+                record.target(),
+                record.args()
+            )
+        })
+        .init();
+}
+
+/// Stop printing log lines and keep the latest warnings and errors for [`captured`] instead, e.g.
+/// while a full screen view is up. [`release`] goes back to printing.
+pub fn capture() {
+    *CAPTURED.lock().unwrap() = Some(VecDeque::new());
+}
+
+pub fn release() {
+    *CAPTURED.lock().unwrap() = None;
+}
+
+/// Warnings and errors logged since [`capture`], oldest first
+pub fn captured() -> Vec<String> {
+    CAPTURED.lock().unwrap().iter().flatten().cloned().collect()
+}
@@ -1,15 +1,36 @@
-use anyhow::{Context, Result};
-use log::{debug, info};
+use anyhow::{Context, Result, bail};
+use clap::Parser;
+use gtasks_asana_bridge::{
+    AsanaApi, AsanaClient, GitHubClient, GoogleTasksApi, RunOptions, SyncEngine, TaskSource,
+    archive, bootstrap,
+    config::{self, Config, Source},
+    control, crypt, date, export, http,
+    hub::{Endpoint, Mirror},
+    inspect, logging,
+    notify::{self, NotifyEvent},
+    purge, redact, repair, report, sections, star,
+    state::State,
+    status, supervisor,
+    sync::Policies,
+    trace,
+};
+use log::info;
+use tokio::task::{JoinSet, LocalSet};
 
-use crate::{asana::AsanaClient, google::GoogleTaskMgr};
+use crate::cli::{Cli, Command};
 
-mod asana;
-mod google;
+mod cli;
+mod deadletter;
+mod init;
+#[cfg(feature = "tui")]
+mod tui;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenv::dotenv().ok();
-    env_logger::init();
+    logging::init();
+
+    let cli = Cli::parse();
 
     if std::env::var("SLEEP_TO_CONFIG").is_ok() {
         println!(
@@ -24,147 +45,394 @@ async fn main() -> Result<()> {
         .install_default()
         .unwrap();
 
-    let asana_token = std::env::var("ASANA_PAT").context("ASANA_PAT env var missing")?;
-    let project_gid = std::env::var("PROJECT_GID").context("PROJECT_GID env var missing")?;
+    if let Some(path) = &cli.trace_http {
+        let path = path.clone().unwrap_or_else(config::default_trace_path);
+        trace::enable(&path)?;
+        info!("Tracing http requests to {path}");
+    }
 
-    let asana_mgr = AsanaClient::new(&asana_token, &project_gid)?;
-    let gtasks_mgr = GoogleTaskMgr::new().await?;
+    // runs before there's a config to load
+    if matches!(cli.command, Some(Command::Init)) {
+        return init::run().await;
+    }
 
-    loop {
-        process_tasks(&asana_mgr, &gtasks_mgr).await?;
-        tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+    let config = Config::load()?;
+    if let Some(key) = &config.bridge.encryption_key {
+        let key = key
+            .resolve()
+            .await
+            .context("failed to load the encryption key")?;
+        crypt::set_key(&key)?;
+    }
+    if let Some(tz) = &config.bridge.timezone {
+        date::set_timezone(tz)?;
+    }
+    http::set_timeout(std::time::Duration::from_secs(
+        config.bridge.request_timeout_secs,
+    ));
+    if let Some(tag) = &config.sync.private_tag {
+        redact::set_private_tag(tag);
+    }
+    if let Some(marker) = &config.sync.star_marker {
+        star::set_star_marker(marker);
+    }
+    notify::set_webhooks(config.notify.webhooks.clone());
+    #[cfg(unix)]
+    control::listen()?;
+
+    // reads what the running bridge wrote, for every user at once
+    if matches!(cli.command, Some(Command::Users)) {
+        return supervisor::print_statuses(&config.bridge.users_status);
     }
-}
 
-async fn process_tasks(asana_mgr: &AsanaClient, gtasks_mgr: &GoogleTaskMgr) -> Result<()> {
-    let asana_tasks = asana_mgr.get_tasks().await?;
-    let google_tasks = gtasks_mgr.get_tasks().await?;
+    if config.users.is_empty() {
+        return run_notified(&cli, config).await;
+    }
 
-    // One way sync of new asana task to google task
-    for atask in &asana_tasks.incomplete {
-        let mut matching_google_task = None;
-        for gtask in google_tasks
-            .incomplete
+    if let Some(name) = &cli.user {
+        let user = config
+            .users
             .iter()
-            .chain(google_tasks.complete.iter())
-        {
-            if let Some(asana_task_gid) = google::get_asana_task_gid(gtask)
-                && atask.gid == asana_task_gid
-            {
-                matching_google_task = Some(gtask.clone());
-                break;
+            .find(|u| &u.name == name)
+            .with_context(|| format!("no user named {name} is configured"))?;
+        return logging::USER
+            .scope(name.clone(), run_notified(&cli, config.for_user(user)))
+            .await;
+    }
+    if !matches!(
+        cli.command,
+        None | Some(Command::Sync { project: None, .. })
+    ) {
+        bail!("several users are configured, pick one with --user");
+    }
+
+    supervisor::set_status_path(&config.bridge.users_status);
+    let once = matches!(cli.command, Some(Command::Sync { once: true, .. }));
+
+    // the users' loops share this thread, they spend nearly all their time waiting on the network
+    LocalSet::new()
+        .run_until(async {
+            let mut loops = JoinSet::new();
+            for user in &config.users {
+                let (cli, config, name) = (cli.clone(), config.for_user(user), user.name.clone());
+                loops.spawn_local(logging::USER.scope(name.clone(), async move {
+                    let interval = std::time::Duration::from_secs(config.bridge.interval_secs);
+                    // a single pass isn't retried
+                    let error_budget = if once { 1 } else { config.bridge.error_budget };
+                    let error = supervisor::supervise(&name, interval, error_budget, || {
+                        run_notified(&cli, config.clone())
+                    })
+                    .await;
+                    (name, error)
+                }));
             }
-        }
 
-        if let Some(google_task) = matching_google_task {
-            // check if it needs updating, since asana might report different names or notes
-            if !asana_google_same(atask, &google_task) {
-                info!(
-                    "Asana -> Google task mismatch, updating google task (Asana: \"{}\")",
-                    atask.name
-                );
-                gtasks_mgr
-                    .del_task(google_task.id.as_ref().unwrap())
-                    .await?;
-                gtasks_mgr.new_task_from_asana(atask).await?;
+            // a user whose loop is given up on leaves the others running
+            let mut failed = Vec::new();
+            while let Some(result) = loops.join_next().await {
+                if let (name, Some(_)) = result? {
+                    failed.push(name);
+                }
             }
-        } else {
-            // create task in google
-            info!(
-                "Asana -> Google new task \"{}\" created, creating in google",
-                atask.name
-            );
-            gtasks_mgr.new_task_from_asana(atask).await?;
-        }
-    }
+            if !failed.is_empty() {
+                bail!("sync failed for {}", failed.join(", "));
+            }
+            Ok::<_, anyhow::Error>(())
+        })
+        .await
+}
 
-    // remove google completed tasks from asana
-    for gtask in &google_tasks.complete {
-        if let Some(asana_task_gid) = google::get_asana_task_gid(gtask) {
-            info!(
-                "Google -> Asana task \"{}\" complete, completing in asana",
-                gtask.title.as_ref().unwrap()
-            );
-            asana_mgr.complete_task(&asana_task_gid).await?;
-        }
+/// [`run`], posting why it stopped to the webhooks that want errors
+async fn run_notified(cli: &Cli, config: Config) -> Result<()> {
+    let result = run(cli, config).await;
+    if let Err(e) = &result {
+        notify::push(NotifyEvent::Errors, format!("Bridge stopped: {e:#}"));
+        notify::flush().await;
+    }
+    result
+}
 
-        // remove this google task
-        info!(
-            "Deleting task {} from google",
-            gtask.title.as_ref().unwrap()
-        );
-        gtasks_mgr.del_task(gtask.id.as_ref().unwrap()).await?;
+/// Everything after loading the config, for one user
+async fn run(cli: &Cli, config: Config) -> Result<()> {
+    // files written before the key was set, the token caches aren't rewritten for a while
+    for path in [
+        &config.bridge.state,
+        &config.google.token_cache,
+        &config.mstodo.token_cache,
+    ] {
+        crypt::migrate(path)?;
     }
 
-    // remove asana completed tasks from google
-    for atask in &asana_tasks.complete {
-        for gtask in &google_tasks.incomplete {
-            if let Some(asana_task_gid) = google::get_asana_task_gid(gtask)
-                && atask.gid == asana_task_gid
-            {
-                info!(
-                    "Asana -> Google task \"{}\" complete, deleting in google",
-                    gtask.title.as_ref().unwrap()
-                );
-                gtasks_mgr.del_task(gtask.id.as_ref().unwrap()).await?;
+    let (project, target, once) = match &cli.command {
+        Some(Command::Sync {
+            project,
+            target,
+            once,
+        }) => (project.clone(), target.clone(), *once),
+        Some(Command::Failed { command }) => {
+            let state_path = config.bridge.state.as_str();
+            let mut state = State::load(state_path)?;
+            if deadletter::run(&mut state, command)? {
+                state.save(state_path)?;
             }
+            return Ok(());
         }
-    }
+        Some(Command::Archive { days, deleted }) => {
+            let path = config.bridge.archive.as_deref().context(
+                "no archive configured, set bridge.archive in the config file or ARCHIVE_PATH",
+            )?;
+            return archive::print(path, *days, *deleted);
+        }
+        Some(_) | None => (None, None, false),
+    };
 
-    Ok(())
-}
+    // ad-hoc mirrors of other projects don't touch the persisted state
+    let persist_state = project.is_none();
 
-fn asana_google_same(atask: &asana::Task, gtask: &google::Task) -> bool {
-    // Check title
-    match &gtask.title {
-        Some(gtask_title) => {
-            if gtask_title != &atask.name {
-                debug!(
-                    "name mismatch. Asana: \"{}\", Gtasks: \"{gtask_title}\"",
-                    atask.name
-                );
-                return false;
-            }
-        }
-        None => {
-            debug!("name mismatch. gtask has no name");
-            return false;
+    let list_title = target
+        .clone()
+        .unwrap_or_else(|| config.list_title(config.bridge.backend));
+    // a configured id picks the list instead, the title still names the section lists
+    let list_id = config.list_id(config.bridge.backend);
+    let list = match (&target, list_id) {
+        (None, Some(id)) => id,
+        _ => list_title.clone(),
+    };
+
+    let project = project.or_else(|| config.asana.shared_project_gid.clone());
+    if config.bridge.source == Source::GitHub {
+        if project.is_some() {
+            bail!("--project mirrors an asana project, the github source has none");
         }
+        let github = GitHubClient::new(&config.github).await?;
+        return serve_backend(cli, config, github, &list, &list_title, persist_state, once).await;
     }
 
-    // Check Due Time
-    match &gtask.due {
-        Some(gtask_due) => {
-            let gtask_due = gtask_due.replace(".000Z", "Z");
-            let asana_due = asana::asana_due_to_string(atask).unwrap();
-            if gtask_due != asana_due {
-                debug!("due time mismatch. Asana: \"{asana_due}\", Gtasks: \"{gtask_due}\"");
-                return false;
+    let asana_token = config
+        .asana
+        .pat
+        .as_ref()
+        .context("no asana token configured")?
+        .resolve()
+        .await
+        .context("failed to load the asana token")?;
+    // the source isn't known yet, and isn't needed to look it up
+    let lookup = AsanaClient::new(
+        &asana_token,
+        TaskSource::UserTaskList(String::new()),
+        &config.asana.base_url,
+    )?;
+    let source = match (project, &config.asana.search, &config.asana.project_gid) {
+        (Some(project), _, _) => TaskSource::Project(project),
+        (None, Some(query), _) => {
+            let workspace_gid = query
+                .workspace_gid
+                .as_deref()
+                .or(config.asana.workspace_gid.as_deref());
+            TaskSource::Search {
+                workspace_gid: lookup.find_workspace(workspace_gid).await?,
+                query: query.clone(),
             }
         }
-        None => {
-            debug!("due time mismatch. gtask has no due date");
-            return false;
+        (None, None, Some(project_gid)) => TaskSource::UserTaskList(project_gid.clone()),
+        (None, None, None) => {
+            let project_gid = lookup
+                .find_my_task_list(config.asana.workspace_gid.as_deref())
+                .await
+                .context("failed to look up the asana \"My Tasks\" list")?;
+            info!("Syncing asana \"My Tasks\" {project_gid}");
+            TaskSource::UserTaskList(project_gid)
         }
-    }
+    };
+    let asana_mgr = AsanaClient::new(&asana_token, source, &config.asana.base_url)?;
+    serve_backend(
+        cli,
+        config,
+        asana_mgr,
+        &list,
+        &list_title,
+        persist_state,
+        once,
+    )
+    .await
+}
+
+/// Connect to whichever task backend is configured, and [`serve`] with it
+async fn serve_backend(
+    cli: &Cli,
+    config: Config,
+    asana_mgr: impl AsanaApi,
+    list: &str,
+    list_title: &str,
+    persist_state: bool,
+    once: bool,
+) -> Result<()> {
+    let gtasks_mgr = Endpoint::open(&config, config.bridge.backend, list).await?;
+    serve(
+        cli,
+        config,
+        asana_mgr,
+        gtasks_mgr,
+        list_title,
+        persist_state,
+        once,
+    )
+    .await
+}
 
-    // Check Notes Body
-    match &gtask.notes {
-        Some(gtask_notes) => {
-            let lines = gtask_notes.lines().take_while(|l| *l != "---");
+/// Run the requested command or the sync loop against whichever task backend is configured
+async fn serve(
+    cli: &Cli,
+    config: Config,
+    asana_mgr: impl AsanaApi,
+    gtasks_mgr: impl GoogleTasksApi,
+    list_title: &str,
+    persist_state: bool,
+    once: bool,
+) -> Result<()> {
+    let state_path = config.bridge.state.as_str();
 
-            for (gtask_lines, atask_lines) in lines.zip(atask.notes.lines()) {
-                if gtask_lines != atask_lines {
-                    debug!("notes mismatch. Asana: \"{atask_lines}\", Gtasks: \"{gtask_lines}\"");
-                    return false;
-                }
+    match &cli.command {
+        Some(Command::RetireLists) => {
+            return sections::retire_lists(&asana_mgr, &gtasks_mgr, list_title).await;
+        }
+        Some(Command::Inspect { gid }) => {
+            let state = State::load(state_path)?;
+            let options = RunOptions::default().sync_options(&config);
+            return inspect::inspect(&asana_mgr, &gtasks_mgr, &state, &options, gid).await;
+        }
+        Some(Command::Status) => {
+            let state = State::load(state_path)?;
+            return status::print_status(&asana_mgr, &gtasks_mgr, &state).await;
+        }
+        Some(Command::Unlink { gid }) => {
+            let mut state = State::load(state_path)?;
+            repair::unlink(&gtasks_mgr, &mut state, gid).await?;
+            return state.save(state_path);
+        }
+        Some(Command::Relink { gid, google_id }) => {
+            let mut state = State::load(state_path)?;
+            let correlator = Policies::from_config(&config.sync).correlator;
+            repair::relink(
+                &asana_mgr,
+                &gtasks_mgr,
+                &mut state,
+                correlator.as_ref(),
+                gid,
+                google_id,
+            )
+            .await?;
+            return state.save(state_path);
+        }
+        Some(Command::Bootstrap { auto, threshold }) => {
+            let mut state = State::load(state_path)?;
+            let correlator = Policies::from_config(&config.sync).correlator;
+            bootstrap::bootstrap(
+                &asana_mgr,
+                &gtasks_mgr,
+                &mut state,
+                correlator.as_ref(),
+                *auto,
+                *threshold,
+                cli.dry_run,
+            )
+            .await?;
+            return state.save(state_path);
+        }
+        Some(Command::Purge { keep, yes }) => {
+            let correlator = Policies::from_config(&config.sync).correlator;
+            let mirrors = Mirror::connect_all(&config).await?;
+            let mirrors: Vec<_> = mirrors
+                .iter()
+                .map(|m| (m.backend.name(), &m.endpoint))
+                .collect();
+            return purge::purge(
+                &gtasks_mgr,
+                &mirrors,
+                state_path,
+                correlator.as_ref(),
+                *keep,
+                *yes,
+                cli.dry_run,
+            )
+            .await;
+        }
+        Some(Command::Dedupe { delete }) => {
+            let mut state = State::load(state_path)?;
+            let correlator = Policies::from_config(&config.sync).correlator;
+            let duplicates = repair::dedupe(
+                &asana_mgr,
+                &gtasks_mgr,
+                &mut state,
+                correlator.as_ref(),
+                *delete,
+            )
+            .await?;
+            for duplicate in &duplicates {
+                println!("{duplicate}");
             }
+            if duplicates.is_empty() {
+                println!("No duplicates found");
+            } else if *delete {
+                println!("Merged {} tasks", duplicates.len());
+            }
+            return state.save(state_path);
+        }
+        Some(Command::Report { period, format }) => {
+            let state = State::load(state_path)?;
+            let archive_path = config.bridge.archive.as_deref();
+            let report =
+                report::generate(&gtasks_mgr, &state, archive_path, *period, date::today()).await?;
+            print!("{}", report.render(*format)?);
+            return Ok(());
+        }
+        Some(Command::Export { format, output }) => {
+            let state = State::load(state_path)?;
+            let path = output
+                .clone()
+                .unwrap_or_else(|| config::default_export_path(format.extension()));
+            return export::export(&asana_mgr, &gtasks_mgr, &state, *format, &path).await;
         }
-        None => {
-            debug!("notes mismatch. gtask has no notes");
-            return false;
+        #[cfg(feature = "tui")]
+        Some(Command::Tui) => {
+            let interval = std::time::Duration::from_secs(config.bridge.interval_secs);
+            let run = RunOptions {
+                dry_run: cli.dry_run,
+                force: cli.force,
+                persist_state,
+            };
+            let engine = SyncEngine::new(config, asana_mgr, gtasks_mgr, list_title, run).await?;
+            let result = tui::run(&engine, interval).await;
+            engine.shutdown().await;
+            return result;
         }
+        Some(
+            Command::Init
+            | Command::Sync { .. }
+            | Command::Archive { .. }
+            | Command::Failed { .. }
+            | Command::Users,
+        )
+        | None => {}
     }
 
-    true
+    let run = RunOptions {
+        dry_run: cli.dry_run,
+        force: cli.force,
+        persist_state,
+    };
+    let engine = SyncEngine::new(config, asana_mgr, gtasks_mgr, list_title, run).await?;
+    let result = if once {
+        engine.run_once().await
+    } else {
+        tokio::select! {
+            result = engine.run_forever() => result,
+            _ = tokio::signal::ctrl_c() => {
+                info!("Interrupted, stopping");
+                Ok(())
+            }
+        }
+    };
+    engine.shutdown().await;
+    result.map_err(Into::into)
 }
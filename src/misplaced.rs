@@ -0,0 +1,201 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use log::info;
+use serde::Deserialize;
+
+use crate::{
+    asana::{self, AsanaApi},
+    google::{self, GoogleTasksApi},
+    state::{Link, State},
+    sync::{self, DeleteReason, FailedActions, SyncAction, SyncOptions},
+    watchdog,
+};
+
+/// What happens to a linked google copy the user dragged into another of their lists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MisplacedPolicy {
+    /// Other lists aren't looked at, the copy counts as deleted from the bridge's list
+    #[default]
+    Off,
+    /// Remove the copy from the other list, a fresh one is created in the bridge's list
+    Return,
+    /// Leave the copy where it is, completing it on either side still carries over
+    Follow,
+}
+
+/// Look for linked copies missing from the bridge's list in the account's other lists, and put
+/// them back or follow them there. Copies followed earlier have their completions carried over
+/// instead of being synced with the rest, so they're taken out of `asana_tasks`. Failed actions
+/// end up in `failures`.
+pub async fn handle(
+    asana_mgr: &impl AsanaApi,
+    gtasks_mgr: &impl GoogleTasksApi,
+    asana_tasks: &mut asana::TaskResult,
+    state: &mut State,
+    options: &SyncOptions,
+    failures: &mut FailedActions,
+) -> Result<()> {
+    let policy = options.policies.misplaced;
+    if policy == MisplacedPolicy::Off {
+        return Ok(());
+    }
+
+    watchdog::phase("looking for linked tasks moved to other lists");
+    let base = gtasks_mgr.list_id().to_string();
+    let in_base: HashSet<String> = {
+        let tasks = gtasks_mgr.get_tasks().await?;
+        tasks
+            .incomplete
+            .iter()
+            .chain(&tasks.complete)
+            .filter_map(|t| t.id.clone())
+            .collect()
+    };
+    let missing = |link: &Link| link.google_list == base && !in_base.contains(&link.google_id);
+    if !state.links.values().any(|l| l.moved || missing(l)) {
+        return Ok(());
+    }
+
+    // the tasks of every other list, with the list's title
+    let mut lists: HashMap<String, (String, Vec<google::Task>)> = HashMap::new();
+    for list in gtasks_mgr.get_lists().await? {
+        let Some(id) = list.id.filter(|id| *id != base) else {
+            continue;
+        };
+        let tasks = gtasks_mgr.for_list(&id).get_tasks().await?;
+        let tasks = tasks.incomplete.into_iter().chain(tasks.complete).collect();
+        lists.insert(id, (list.title.unwrap_or_default(), tasks));
+    }
+
+    let mut actions = Vec::new();
+    // copies followed earlier, unless they moved on again
+    for (gid, link) in state.links.iter_mut().filter(|(_, l)| l.moved) {
+        let copy = lists.get(&link.google_list).and_then(|(_, tasks)| {
+            tasks
+                .iter()
+                .find(|t| t.id.as_deref() == Some(link.google_id.as_str()))
+        });
+        let Some(copy) = copy else {
+            // looked for again below
+            link.moved = false;
+            link.google_list = base.clone();
+            continue;
+        };
+
+        let fetched = asana_tasks.incomplete.iter().any(|t| &t.gid == gid);
+        let reason = if copy.completed.is_some() {
+            actions.push(SyncAction::CompleteAsana {
+                gid: gid.clone(),
+                name: link.asana_name.clone(),
+                modified_at: asana_tasks.modified_at(gid),
+            });
+            actions.push(SyncAction::Unlink { gid: gid.clone() });
+            continue;
+        } else if asana_tasks.complete.iter().any(|t| &t.gid == gid) {
+            DeleteReason::CompletedInAsana
+        } else if !fetched && !asana_mgr.task_exists(gid).await? {
+            DeleteReason::DeletedInAsana
+        } else {
+            continue;
+        };
+        actions.push(SyncAction::DeleteGoogle {
+            list: link.google_list.clone(),
+            google_id: link.google_id.clone(),
+            title: copy.title.clone().unwrap_or_default(),
+            reason,
+        });
+        actions.push(SyncAction::Unlink { gid: gid.clone() });
+    }
+
+    let correlator = options.policies.correlator.as_ref();
+    let mut followed = Vec::new();
+    for (gid, link) in state
+        .links
+        .iter_mut()
+        .filter(|(_, l)| !l.moved && missing(l))
+    {
+        // a copy dragged to another list gets a new id there, its tag still names the task
+        let found = lists.iter().find_map(|(list, (title, tasks))| {
+            tasks
+                .iter()
+                .find(|t| {
+                    t.id.as_deref() == Some(link.google_id.as_str())
+                        || correlator.gid(t).as_deref() == Some(gid.as_str())
+                })
+                .map(|copy| (list, title, copy))
+        });
+        let Some((list, title, copy)) = found else {
+            continue;
+        };
+
+        if copy.completed.is_some() {
+            info!(
+                "\"{}\" was moved to the list \"{title}\" and completed there",
+                link.asana_name
+            );
+            actions.push(SyncAction::CompleteAsana {
+                gid: gid.clone(),
+                name: link.asana_name.clone(),
+                modified_at: asana_tasks.modified_at(gid),
+            });
+            actions.push(SyncAction::Unlink { gid: gid.clone() });
+        } else if policy == MisplacedPolicy::Return {
+            info!(
+                "\"{}\" was moved to the list \"{title}\", moving it back",
+                link.asana_name
+            );
+            actions.push(SyncAction::DeleteGoogle {
+                list: list.clone(),
+                google_id: copy.id.clone().unwrap_or_default(),
+                title: copy.title.clone().unwrap_or_default(),
+                reason: DeleteReason::Misplaced,
+            });
+            actions.push(SyncAction::Unlink { gid: gid.clone() });
+        } else {
+            info!(
+                "\"{}\" was moved to the list \"{title}\", following it there",
+                link.asana_name
+            );
+            link.moved = true;
+            link.google_list = list.clone();
+            followed.push((gid.clone(), copy.id.clone().unwrap_or_default()));
+        }
+    }
+    for (gid, google_id) in followed {
+        state.set_google_id(&gid, &google_id);
+    }
+
+    // completed where they were moved to, the regular sync would recreate them
+    let mut left_alone: HashSet<String> = actions
+        .iter()
+        .filter_map(|action| match action {
+            SyncAction::CompleteAsana { gid, .. } => Some(gid.clone()),
+            _ => None,
+        })
+        .collect();
+    if options.dry_run {
+        for action in &actions {
+            info!("[dry run] {action}");
+        }
+    } else {
+        failures.absorb(sync::execute(actions, asana_mgr, gtasks_mgr, state, options).await)?;
+    }
+
+    // and it would take followed ones for copies missing from the bridge's list
+    left_alone.extend(
+        state
+            .links
+            .iter()
+            .filter(|(_, l)| l.moved)
+            .map(|(gid, _)| gid.clone()),
+    );
+    asana_tasks
+        .incomplete
+        .retain(|t| !left_alone.contains(&t.gid));
+    asana_tasks
+        .complete
+        .retain(|t| !left_alone.contains(&t.gid));
+    Ok(())
+}
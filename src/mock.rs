@@ -0,0 +1,605 @@
+//! In-memory stand-ins for the asana and google clients, for testing the sync engine
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashSet},
+    rc::Rc,
+    sync::Arc,
+};
+
+use anyhow::{Context, Result, bail};
+use jiff::{Timestamp, civil};
+use reqwest::StatusCode;
+
+use crate::{
+    asana::{self, AsanaApi, Section, TaskResult, TaskSource},
+    config::{FieldsConfig, WritebackConfig},
+    correlate::NotesMarker,
+    error::BridgeError,
+    google::{self, GTaskResult, GoogleTasksApi, TaskList},
+    guardrail::DeletionLimits,
+    hierarchy::CascadePolicy,
+    misplaced::MisplacedPolicy,
+    order::TaskOrder,
+    policy::DefaultPolicy,
+    state::State,
+    sync::{self, AsanaDeletePolicy, GoogleDeletePolicy, Policies, SyncOptions},
+};
+
+/// Gid of the user the mock asana token belongs to, tasks are assigned to them by default
+pub const MOCK_ME: &str = "42";
+
+pub fn asana_task(gid: &str, name: &str, due: civil::Date) -> asana::Task {
+    asana::Task {
+        gid: gid.to_string(),
+        name: name.to_string(),
+        notes: String::new(),
+        html_notes: None,
+        permalink_url: None,
+        liked: false,
+        due_on: Some(due),
+        due_at: None,
+        start_on: None,
+        start_at: None,
+        completed_at: None,
+        modified_at: None,
+        assignee: Some(asana::UserRef {
+            gid: MOCK_ME.to_string(),
+        }),
+        assignee_section: None,
+        memberships: Vec::new(),
+        tags: Vec::new(),
+        parent_gid: None,
+        comments: Vec::new(),
+        attachments: Vec::new(),
+    }
+}
+
+pub struct MockAsana {
+    pub source: TaskSource,
+    pub tasks: RefCell<Vec<asana::Task>>,
+    pub subtasks: RefCell<BTreeMap<String, Vec<asana::Task>>>,
+    pub comments: RefCell<BTreeMap<String, Vec<asana::Comment>>>,
+    pub attachments: RefCell<BTreeMap<String, Vec<asana::Attachment>>>,
+    pub sections: Vec<Section>,
+    /// Gids whose writes and comment and attachment fetches fail, like a task asana refuses to change
+    pub failing: RefCell<HashSet<String>>,
+    /// Every write is refused with a 429, like after running out of requests
+    pub throttled: Cell<bool>,
+    /// Gids that still exist but aren't returned with the tasks, like ones a search stops finding
+    pub hidden: RefCell<HashSet<String>>,
+    /// How many tasks each call to complete several at once was given
+    pub completion_batches: RefCell<Vec<usize>>,
+}
+
+impl MockAsana {
+    pub fn new(tasks: Vec<asana::Task>) -> Self {
+        Self {
+            source: TaskSource::UserTaskList("1".to_string()),
+            tasks: RefCell::new(tasks),
+            subtasks: RefCell::default(),
+            comments: RefCell::default(),
+            attachments: RefCell::default(),
+            sections: Vec::new(),
+            failing: RefCell::default(),
+            throttled: Cell::default(),
+            hidden: RefCell::default(),
+            completion_batches: RefCell::default(),
+        }
+    }
+
+    fn check_write(&self, task_gid: &str) -> Result<()> {
+        if self.throttled.get() {
+            return Err(
+                BridgeError::from_status("put", StatusCode::TOO_MANY_REQUESTS, None).into(),
+            );
+        }
+        if self.failing.borrow().contains(task_gid) {
+            bail!("asana refused to update task {task_gid}");
+        }
+        Ok(())
+    }
+
+    pub fn task(&self, gid: &str) -> Option<asana::Task> {
+        self.tasks.borrow().iter().find(|t| t.gid == gid).cloned()
+    }
+
+    pub fn update(&self, gid: &str, f: impl FnOnce(&mut asana::Task)) {
+        let mut tasks = self.tasks.borrow_mut();
+        f(tasks.iter_mut().find(|t| t.gid == gid).unwrap());
+    }
+
+    pub fn delete(&self, gid: &str) {
+        self.tasks.borrow_mut().retain(|t| t.gid != gid);
+    }
+}
+
+impl AsanaApi for MockAsana {
+    fn source(&self) -> &TaskSource {
+        &self.source
+    }
+
+    async fn get_tasks_since(&self, _completed_since: Timestamp) -> Result<TaskResult> {
+        let (complete, incomplete) = self
+            .tasks
+            .borrow()
+            .iter()
+            .filter(|t| t.due_date().is_some() && !self.hidden.borrow().contains(&t.gid))
+            .cloned()
+            .partition(|t| t.completed_at.is_some());
+
+        Ok(TaskResult {
+            incomplete,
+            complete,
+        })
+    }
+
+    async fn get_subtasks(&self, task_gid: &str) -> Result<Vec<asana::Task>> {
+        Ok(self
+            .subtasks
+            .borrow()
+            .get(task_gid)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn get_task(&self, task_gid: &str) -> Result<asana::Task> {
+        self.task(task_gid).context("no such task")
+    }
+
+    async fn get_comments(&self, task_gid: &str) -> Result<Vec<asana::Comment>> {
+        if self.failing.borrow().contains(task_gid) {
+            bail!("asana refused to list the comments of task {task_gid}");
+        }
+        Ok(self
+            .comments
+            .borrow()
+            .get(task_gid)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn add_comment(&self, task_gid: &str, text: &str) -> Result<()> {
+        self.check_write(task_gid)?;
+        self.comments
+            .borrow_mut()
+            .entry(task_gid.to_string())
+            .or_default()
+            .push(asana::Comment {
+                author: None,
+                created_at: Timestamp::now(),
+                text: text.to_string(),
+            });
+        Ok(())
+    }
+
+    async fn get_attachments(&self, task_gid: &str) -> Result<Vec<asana::Attachment>> {
+        if self.failing.borrow().contains(task_gid) {
+            bail!("asana refused to list the attachments of task {task_gid}");
+        }
+        Ok(self
+            .attachments
+            .borrow()
+            .get(task_gid)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn me(&self) -> Result<String> {
+        Ok(MOCK_ME.to_string())
+    }
+
+    async fn task_exists(&self, task_gid: &str) -> Result<bool> {
+        Ok(self.task(task_gid).is_some())
+    }
+
+    async fn get_sections(&self) -> Result<Vec<Section>> {
+        Ok(self.sections.clone())
+    }
+
+    async fn add_task_to_section(&self, task_gid: &str, section_gid: &str) -> Result<()> {
+        let section = self
+            .sections
+            .iter()
+            .find(|s| s.gid == section_gid)
+            .context("no such section")?
+            .clone();
+        let mut tasks = self.tasks.borrow_mut();
+        let task = tasks
+            .iter_mut()
+            .find(|t| t.gid == task_gid)
+            .context("no such task")?;
+        task.assignee_section = Some(section);
+        Ok(())
+    }
+
+    async fn set_parent(&self, task_gid: &str, parent_gid: Option<&str>) -> Result<()> {
+        let mut tasks = self.tasks.borrow_mut();
+        let task = tasks
+            .iter_mut()
+            .find(|t| t.gid == task_gid)
+            .context("no such task")?;
+        task.parent_gid = parent_gid.map(str::to_string);
+        Ok(())
+    }
+
+    async fn complete_task(&self, task_gid: &str) -> Result<()> {
+        self.check_write(task_gid)?;
+        let mut tasks = self.tasks.borrow_mut();
+        let task = tasks
+            .iter_mut()
+            .find(|t| t.gid == task_gid)
+            .context("no such task")?;
+        task.completed_at = Some(Timestamp::now());
+        Ok(())
+    }
+    async fn complete_tasks(&self, task_gids: &[String]) -> Vec<Result<()>> {
+        self.completion_batches.borrow_mut().push(task_gids.len());
+        let mut results = Vec::new();
+        for gid in task_gids {
+            results.push(self.complete_task(gid).await);
+        }
+        results
+    }
+    async fn set_due(&self, task: &asana::Task) -> Result<()> {
+        self.update(&task.gid, |t| {
+            t.due_on = task.due_on;
+            t.due_at = task.due_at;
+        });
+        Ok(())
+    }
+    async fn set_liked(&self, task_gid: &str, liked: bool) -> Result<()> {
+        self.update(task_gid, |t| t.liked = liked);
+        Ok(())
+    }
+    async fn set_text(
+        &self,
+        task_gid: &str,
+        name: Option<&str>,
+        notes: Option<&str>,
+    ) -> Result<()> {
+        self.update(task_gid, |t| {
+            if let Some(name) = name {
+                t.name = name.to_string();
+            }
+            if let Some(notes) = notes {
+                t.notes = notes.to_string();
+            }
+        });
+        Ok(())
+    }
+}
+
+struct MockList {
+    title: String,
+    tasks: Vec<google::Task>,
+}
+
+/// Google task lists keyed by id. Managers made with `for_list` share the same lists.
+#[derive(Clone)]
+pub struct MockGoogle {
+    lists: Rc<RefCell<BTreeMap<String, MockList>>>,
+    next_id: Rc<Cell<usize>>,
+    list_id: String,
+    /// Inserts still to land but fail, like ones whose response got lost
+    pub lost_inserts: Rc<Cell<usize>>,
+}
+
+impl MockGoogle {
+    pub fn new() -> Self {
+        let list = MockList {
+            title: "Asana".to_string(),
+            tasks: Vec::new(),
+        };
+        Self {
+            lists: Rc::new(RefCell::new(BTreeMap::from([("list".to_string(), list)]))),
+            next_id: Rc::new(Cell::new(1)),
+            list_id: "list".to_string(),
+            lost_inserts: Rc::default(),
+        }
+    }
+
+    /// Every task in this manager's list
+    pub fn tasks(&self) -> Vec<google::Task> {
+        self.lists.borrow()[&self.list_id].tasks.clone()
+    }
+
+    pub fn update(&self, id: &str, f: impl FnOnce(&mut google::Task)) {
+        let mut lists = self.lists.borrow_mut();
+        let tasks = &mut lists.get_mut(&self.list_id).unwrap().tasks;
+        f(tasks
+            .iter_mut()
+            .find(|t| t.id.as_deref() == Some(id))
+            .unwrap());
+    }
+
+    fn with_task<T>(&self, id: &str, f: impl FnOnce(&mut google::Task) -> T) -> Result<T> {
+        let mut lists = self.lists.borrow_mut();
+        let tasks = &mut lists.get_mut(&self.list_id).context("no such list")?.tasks;
+        let task = tasks
+            .iter_mut()
+            .find(|t| t.id.as_deref() == Some(id))
+            .context("no such task")?;
+        Ok(f(task))
+    }
+
+    fn next_id(&self, prefix: &str) -> String {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        format!("{prefix}{id}")
+    }
+}
+
+impl GoogleTasksApi for MockGoogle {
+    fn for_list(&self, list_id: &str) -> Self {
+        Self {
+            lists: self.lists.clone(),
+            next_id: self.next_id.clone(),
+            list_id: list_id.to_string(),
+            lost_inserts: self.lost_inserts.clone(),
+        }
+    }
+
+    fn list_id(&self) -> &str {
+        &self.list_id
+    }
+
+    async fn get_lists(&self) -> Result<Vec<TaskList>> {
+        Ok(self
+            .lists
+            .borrow()
+            .iter()
+            .map(|(id, list)| TaskList {
+                id: Some(id.clone()),
+                title: Some(list.title.clone()),
+                ..Default::default()
+            })
+            .collect())
+    }
+
+    async fn create_list(&self, title: &str) -> Result<String> {
+        let id = self.next_id("list");
+        self.lists.borrow_mut().insert(
+            id.clone(),
+            MockList {
+                title: title.to_string(),
+                tasks: Vec::new(),
+            },
+        );
+        Ok(id)
+    }
+
+    async fn delete_list(&self, id: &str) -> Result<()> {
+        self.lists.borrow_mut().remove(id).context("no such list")?;
+        Ok(())
+    }
+
+    async fn new_task_from_asana(
+        &self,
+        task: &asana::Task,
+        title: &str,
+        notes: &str,
+        parent: Option<&str>,
+    ) -> Result<String> {
+        let id = self.next_id("g");
+        let new_task = google::Task {
+            id: Some(id.clone()),
+            title: Some(title.to_string()),
+            due: Some(asana::asana_due_to_string(task)?),
+            notes: Some(notes.to_string()),
+            parent: parent.map(str::to_string),
+            ..Default::default()
+        };
+
+        let mut lists = self.lists.borrow_mut();
+        lists
+            .get_mut(&self.list_id)
+            .context("no such list")?
+            .tasks
+            .push(new_task);
+        if self.lost_inserts.get() > 0 {
+            self.lost_inserts.set(self.lost_inserts.get() - 1);
+            bail!("the insert response was lost");
+        }
+        Ok(id)
+    }
+
+    async fn set_text(&self, id: &str, title: &str, notes: &str) -> Result<()> {
+        self.with_task(id, |task| {
+            task.title = Some(title.to_string());
+            task.notes = Some(notes.to_string());
+        })
+    }
+
+    async fn move_to_top_level(&self, id: &str) -> Result<()> {
+        self.with_task(id, |task| task.parent = None)
+    }
+
+    async fn move_after(&self, id: &str, previous: Option<&str>) -> Result<()> {
+        let mut lists = self.lists.borrow_mut();
+        let tasks = &mut lists.get_mut(&self.list_id).context("no such list")?.tasks;
+        let from = tasks
+            .iter()
+            .position(|t| t.id.as_deref() == Some(id))
+            .context("no such task")?;
+        let task = tasks.remove(from);
+        let to = match previous {
+            Some(previous) => {
+                tasks
+                    .iter()
+                    .position(|t| t.id.as_deref() == Some(previous))
+                    .context("no such task")?
+                    + 1
+            }
+            None => 0,
+        };
+        tasks.insert(to, task);
+        Ok(())
+    }
+
+    async fn get_tasks(&self) -> Result<GTaskResult> {
+        let lists = self.lists.borrow();
+        let Some(list) = lists.get(&self.list_id) else {
+            bail!("no such list");
+        };
+        let (complete, incomplete) = list
+            .tasks
+            .iter()
+            .cloned()
+            .partition(|t| t.completed.is_some());
+
+        Ok(GTaskResult {
+            incomplete,
+            complete,
+        })
+    }
+
+    async fn clear_completed(&self) -> Result<()> {
+        let mut lists = self.lists.borrow_mut();
+        let tasks = &mut lists.get_mut(&self.list_id).context("no such list")?.tasks;
+        for task in tasks.iter_mut().filter(|t| t.completed.is_some()) {
+            task.hidden = Some(true);
+        }
+        Ok(())
+    }
+
+    async fn del_task(&self, id: &str) -> Result<()> {
+        let mut lists = self.lists.borrow_mut();
+        let tasks = &mut lists.get_mut(&self.list_id).context("no such list")?.tasks;
+        let before = tasks.len();
+        tasks.retain(|t| t.id.as_deref() != Some(id));
+        if tasks.len() == before {
+            bail!("no such task");
+        }
+        Ok(())
+    }
+
+    async fn del_task_if(&self, id: &str, etag: &str) -> Result<()> {
+        let current = self.with_task(id, |task| task.etag.clone())?;
+        if current.as_deref() != Some(etag) {
+            return Err(BridgeError::Conflict(format!(
+                "google task {id} was edited since it was read"
+            ))
+            .into());
+        }
+        self.del_task(id).await
+    }
+}
+
+/// Calendar events as `(id, title, start)`, in creation order
+#[cfg(feature = "calendar")]
+#[derive(Default)]
+pub struct MockCalendar {
+    events: RefCell<Vec<(String, String, Timestamp)>>,
+    next_id: Cell<usize>,
+}
+
+#[cfg(feature = "calendar")]
+impl MockCalendar {
+    pub fn events(&self) -> Vec<(String, String, Timestamp)> {
+        self.events.borrow().clone()
+    }
+}
+
+#[cfg(feature = "calendar")]
+impl crate::calendar::CalendarApi for MockCalendar {
+    async fn create_event(&self, title: &str, _notes: &str, start: Timestamp) -> Result<String> {
+        let id = format!("event{}", self.next_id.get());
+        self.next_id.set(self.next_id.get() + 1);
+        self.events
+            .borrow_mut()
+            .push((id.clone(), title.to_string(), start));
+        Ok(id)
+    }
+
+    async fn update_event(
+        &self,
+        id: &str,
+        title: &str,
+        _notes: &str,
+        start: Timestamp,
+    ) -> Result<()> {
+        let mut events = self.events.borrow_mut();
+        let event = events
+            .iter_mut()
+            .find(|e| e.0 == id)
+            .context("no such event")?;
+        event.1 = title.to_string();
+        event.2 = start;
+        Ok(())
+    }
+
+    async fn delete_event(&self, id: &str) -> Result<()> {
+        let mut events = self.events.borrow_mut();
+        let before = events.len();
+        events.retain(|e| e.0 != id);
+        if events.len() == before {
+            bail!("no such event");
+        }
+        Ok(())
+    }
+}
+
+/// Options for running the sync engine against the mocks
+pub fn sync_options() -> SyncOptions {
+    SyncOptions {
+        policies: Policies {
+            flatten: None,
+            cascade: CascadePolicy::Leave,
+            on_google_delete: GoogleDeletePolicy::Recreate,
+            on_asana_delete: AsanaDeletePolicy::Delete,
+            only_mine: false,
+            diff: Arc::new(DefaultPolicy),
+            correlator: Arc::new(NotesMarker),
+            comments: 0,
+            notes_as_comments: false,
+            attachments: false,
+            misplaced: MisplacedPolicy::Off,
+            fields: FieldsConfig::default(),
+            delete_unlinked_completed: false,
+            clear_completed: false,
+            timed_to_calendar: false,
+        },
+        limits: DeletionLimits {
+            max_count: 20,
+            max_percent: 50,
+            force: false,
+        },
+        writeback: WritebackConfig::default(),
+        metrics_path: None,
+        archive_path: None,
+        dry_run: false,
+        order: TaskOrder::None,
+        park_after: 3,
+        state_path: None,
+        max_catch_up_days: 7,
+        catch_up_days: 0,
+        batch_size: 1,
+        asana_batch_size: 1,
+        comments_fetched: Arc::default(),
+        attachments_fetched: Arc::default(),
+        mirror: None,
+    }
+}
+
+/// One sync cycle, fetching asana's tasks like the bridge does
+pub async fn cycle(
+    asana: &MockAsana,
+    google: &MockGoogle,
+    state: &mut State,
+    options: &SyncOptions,
+) -> Result<()> {
+    let asana_tasks = asana.get_tasks().await?;
+    sync::sync_tasks(asana, &asana_tasks, google, state, options).await
+}
+
+/// A bridge with the given asana tasks already synced once
+pub async fn synced(tasks: Vec<asana::Task>) -> (MockAsana, MockGoogle, State) {
+    let asana = MockAsana::new(tasks);
+    let google = MockGoogle::new();
+    let mut state = State::default();
+    cycle(&asana, &google, &mut state, &sync_options())
+        .await
+        .unwrap();
+    (asana, google, state)
+}
@@ -0,0 +1,674 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result, bail};
+use jiff::{Timestamp, ToSpan};
+use log::debug;
+use reqwest::{
+    RequestBuilder, Response,
+    header::{HeaderMap, IF_MATCH},
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tokio::sync::Mutex;
+
+use crate::{
+    asana,
+    canonical::{CanonicalTask, Priority, Status},
+    config::MsTodoConfig,
+    crypt,
+    google::{self, GTaskResult, GoogleTasksApi, Task, TaskList},
+    http,
+};
+
+/// Microsoft Graph's API root, overridable to point at a test server
+pub const DEFAULT_BASE_URL: &str = "https://graph.microsoft.com/v1.0";
+
+const LOGIN_URL: &str = "https://login.microsoftonline.com";
+
+const SCOPES: &str = "Tasks.ReadWrite offline_access";
+
+/// Microsoft To Do behind the same interface as google tasks, so the planner doesn't need to know
+/// which one it's talking to. To Do has no subtasks, so flattened subtasks end up at the top level
+/// of the list.
+#[derive(Clone)]
+pub struct MsTodoMgr {
+    client: reqwest::Client,
+    auth: Arc<Auth>,
+    base_url: String,
+    list_id: String,
+}
+
+impl MsTodoMgr {
+    pub async fn new(config: &MsTodoConfig, list: &str) -> Result<Self> {
+        Self::connect(Self::auth(config).await?, DEFAULT_BASE_URL, list).await
+    }
+
+    /// Manager that isn't bound to a list yet, for picking one with [`GoogleTasksApi::get_lists`]
+    /// and [`GoogleTasksApi::for_list`]. Signs in when there's no cached token.
+    pub async fn unbound(config: &MsTodoConfig) -> Result<Self> {
+        Ok(Self {
+            client: http::client(HeaderMap::new())?,
+            auth: Arc::new(Self::auth(config).await?),
+            base_url: DEFAULT_BASE_URL.to_string(),
+            list_id: String::new(),
+        })
+    }
+
+    /// The cached token, or a new one from the device code flow
+    async fn auth(config: &MsTodoConfig) -> Result<Auth> {
+        let client = http::client(HeaderMap::new())?;
+        let client_id = config
+            .client_id
+            .clone()
+            .context("no microsoft to do client id configured")?;
+
+        let token = match crypt::read(&config.token_cache)? {
+            Some(cached) => serde_json::from_slice(&cached)
+                .with_context(|| format!("invalid token cache {}", config.token_cache))?,
+            None => {
+                let token = device_code_flow(&client, &client_id, &config.tenant).await?;
+                save_token(&config.token_cache, &token)?;
+                token
+            }
+        };
+
+        Ok(Auth {
+            client_id,
+            tenant: config.tenant.clone(),
+            cache_path: Some(config.token_cache.clone()),
+            token: Mutex::new(token),
+        })
+    }
+
+    /// Manager for the list `list` names, see [`google::find_list`], on the graph API at
+    /// `base_url`
+    async fn connect(auth: Auth, base_url: &str, list: &str) -> Result<Self> {
+        let mut mgr = Self {
+            client: http::client(HeaderMap::new())?,
+            auth: Arc::new(auth),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            list_id: String::new(),
+        };
+
+        mgr.list_id = google::find_list(&mgr.get_lists().await?, list)
+            .context("failed to pick the microsoft to do list")?;
+
+        Ok(mgr)
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{path}", self.base_url)
+    }
+
+    fn tasks_url(&self, path: &str) -> String {
+        self.url(&format!("me/todo/lists/{}/tasks{path}", self.list_id))
+    }
+
+    async fn send(&self, request: RequestBuilder, what: &str) -> Result<Response> {
+        let token = self.auth.access_token(&self.client).await?;
+        // 412 is a conflict, the task was edited since it was read
+        Ok(http::send(request.bearer_auth(token), what).await?)
+    }
+
+    /// Follow graph's `@odata.nextLink` pagination until every page has been fetched
+    async fn get_paged<T: DeserializeOwned>(&self, url: String) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+
+        let mut next_page = Some(url);
+        while let Some(url) = next_page {
+            let page: Page<T> = self
+                .send(self.client.get(&url), "fetch")
+                .await?
+                .json()
+                .await?;
+
+            items.extend(page.value);
+            next_page = page.next_link;
+        }
+
+        Ok(items)
+    }
+
+    async fn patch_task(&self, id: &str, patch: &TodoTask) -> Result<()> {
+        self.send(
+            self.client
+                .patch(self.tasks_url(&format!("/{id}")))
+                .json(patch),
+            "patch",
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+impl GoogleTasksApi for MsTodoMgr {
+    fn for_list(&self, list_id: &str) -> Self {
+        Self {
+            client: self.client.clone(),
+            auth: self.auth.clone(),
+            base_url: self.base_url.clone(),
+            list_id: list_id.to_string(),
+        }
+    }
+
+    fn list_id(&self) -> &str {
+        &self.list_id
+    }
+
+    async fn get_lists(&self) -> Result<Vec<TaskList>> {
+        let lists: Vec<TodoList> = self.get_paged(self.url("me/todo/lists")).await?;
+
+        Ok(lists
+            .into_iter()
+            .map(|l| TaskList {
+                id: Some(l.id),
+                title: Some(l.display_name),
+                ..Default::default()
+            })
+            .collect())
+    }
+
+    async fn create_list(&self, title: &str) -> Result<String> {
+        let list = TodoList {
+            id: String::new(),
+            display_name: title.to_string(),
+        };
+
+        let created: TodoList = self
+            .send(
+                self.client.post(self.url("me/todo/lists")).json(&list),
+                "post",
+            )
+            .await?
+            .json()
+            .await?;
+        Ok(created.id)
+    }
+
+    async fn delete_list(&self, id: &str) -> Result<()> {
+        self.send(
+            self.client.delete(self.url(&format!("me/todo/lists/{id}"))),
+            "delete",
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn new_task_from_asana(
+        &self,
+        task: &asana::Task,
+        title: &str,
+        notes: &str,
+        parent: Option<&str>,
+    ) -> Result<String> {
+        if parent.is_some() {
+            debug!("Microsoft To Do has no subtasks, adding \"{title}\" at the top level");
+        }
+
+        let due = asana::asana_due_to_string(task)?;
+        let new_task = TodoTask {
+            title: Some(title.to_string()),
+            body: Some(ItemBody::text(notes)),
+            due_date_time: Some(DateTimeTimeZone {
+                date_time: due.trim_end_matches('Z').to_string(),
+                time_zone: "UTC".to_string(),
+            }),
+            ..Default::default()
+        };
+
+        let created: TodoTask = self
+            .send(self.client.post(self.tasks_url("")).json(&new_task), "post")
+            .await?
+            .json()
+            .await?;
+        created.id.context("created task has no id")
+    }
+
+    async fn set_text(&self, id: &str, title: &str, notes: &str) -> Result<()> {
+        let patch = TodoTask {
+            title: Some(title.to_string()),
+            body: Some(ItemBody::text(notes)),
+            ..Default::default()
+        };
+
+        self.patch_task(id, &patch).await
+    }
+
+    async fn move_to_top_level(&self, _id: &str) -> Result<()> {
+        // every to do task is already at the top level
+        Ok(())
+    }
+
+    async fn move_after(&self, id: &str, _previous: Option<&str>) -> Result<()> {
+        // graph has no way to order tasks, to do sorts them itself
+        debug!("Microsoft To Do can't reorder tasks, leaving {id} in place");
+        Ok(())
+    }
+
+    async fn get_tasks(&self) -> Result<GTaskResult> {
+        let tasks: Vec<TodoTask> = self.get_paged(self.tasks_url("?$top=100")).await?;
+
+        let mut result = GTaskResult {
+            incomplete: Vec::new(),
+            complete: Vec::new(),
+        };
+        for task in tasks
+            .into_iter()
+            .map(|task| Task::from(CanonicalTask::from(task)))
+        {
+            if task.completed.is_some() {
+                result.complete.push(task);
+            } else {
+                result.incomplete.push(task);
+            }
+        }
+
+        Ok(result)
+    }
+
+    async fn del_task(&self, id: &str) -> Result<()> {
+        self.send(
+            self.client.delete(self.tasks_url(&format!("/{id}"))),
+            "delete",
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn del_task_if(&self, id: &str, etag: &str) -> Result<()> {
+        self.send(
+            self.client
+                .delete(self.tasks_url(&format!("/{id}")))
+                .header(IF_MATCH, etag),
+            "delete",
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct Page<T> {
+    value: Vec<T>,
+    #[serde(rename = "@odata.nextLink")]
+    next_link: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TodoList {
+    #[serde(skip_serializing)]
+    id: String,
+    display_name: String,
+}
+
+/// A to do task, every field optional so the same type works for patches
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TodoTask {
+    #[serde(skip_serializing)]
+    id: Option<String>,
+    #[serde(rename = "@odata.etag", skip_serializing)]
+    etag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<ItemBody>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    due_date_time: Option<DateTimeTimeZone>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    completed_date_time: Option<DateTimeTimeZone>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    importance: Option<String>,
+}
+
+impl TodoTask {
+    fn notes(&self) -> String {
+        self.body
+            .as_ref()
+            .map(|b| b.content.replace("\r\n", "\n"))
+            .unwrap_or_default()
+    }
+}
+
+impl From<TodoTask> for CanonicalTask {
+    fn from(task: TodoTask) -> Self {
+        let status = if task.status.as_deref() == Some("completed") {
+            Status::Done(
+                task.completed_date_time
+                    .as_ref()
+                    .map(|c| c.date_time.clone()),
+            )
+        } else {
+            Status::Open
+        };
+
+        CanonicalTask {
+            body: task.notes(),
+            due: task
+                .due_date_time
+                .as_ref()
+                .and_then(|d| d.date_time.get(..10)?.parse().ok()),
+            status,
+            priority: match task.importance.as_deref() {
+                Some("low") => Some(Priority::Low),
+                Some("normal") => Some(Priority::Medium),
+                Some("high") => Some(Priority::High),
+                _ => None,
+            },
+            id: task.id,
+            title: task.title.unwrap_or_default(),
+            revision: task.etag,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ItemBody {
+    content: String,
+    content_type: String,
+}
+
+impl ItemBody {
+    fn text(content: &str) -> Self {
+        Self {
+            content: content.to_string(),
+            content_type: "text".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DateTimeTimeZone {
+    date_time: String,
+    time_zone: String,
+}
+
+/// Cached OAuth tokens
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Token {
+    access_token: String,
+    refresh_token: String,
+    expires_at: Timestamp,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: String,
+    expires_in: i64,
+}
+
+impl From<TokenResponse> for Token {
+    fn from(resp: TokenResponse) -> Self {
+        Self {
+            access_token: resp.access_token,
+            refresh_token: resp.refresh_token,
+            expires_at: Timestamp::now() + resp.expires_in.seconds(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenError {
+    error: String,
+    #[serde(default)]
+    error_description: String,
+}
+
+#[derive(Deserialize)]
+struct DeviceCode {
+    device_code: String,
+    message: String,
+    interval: u64,
+}
+
+/// Shared between the managers of every list, refreshing the access token when it runs out
+struct Auth {
+    client_id: String,
+    tenant: String,
+    /// Where refreshed tokens are saved, `None` to keep them in memory only
+    cache_path: Option<String>,
+    token: Mutex<Token>,
+}
+
+impl Auth {
+    async fn access_token(&self, client: &reqwest::Client) -> Result<String> {
+        let mut token = self.token.lock().await;
+
+        if token.expires_at <= Timestamp::now() + 1.minute() {
+            let resp = client
+                .post(token_url(&self.tenant))
+                .form(&[
+                    ("grant_type", "refresh_token"),
+                    ("client_id", self.client_id.as_str()),
+                    ("refresh_token", token.refresh_token.as_str()),
+                    ("scope", SCOPES),
+                ])
+                .send()
+                .await?;
+            if !resp.status().is_success() {
+                let err: TokenError = resp.json().await?;
+                bail!(
+                    "failed to refresh the microsoft token ({}): {}",
+                    err.error,
+                    err.error_description
+                );
+            }
+
+            *token = resp.json::<TokenResponse>().await?.into();
+            if let Some(path) = &self.cache_path {
+                save_token(path, &token)?;
+            }
+        }
+
+        Ok(token.access_token.clone())
+    }
+}
+
+fn token_url(tenant: &str) -> String {
+    format!("{LOGIN_URL}/{tenant}/oauth2/v2.0/token")
+}
+
+fn save_token(path: &str, token: &Token) -> Result<()> {
+    crypt::write(path, serde_json::to_string(token)?.as_bytes())
+}
+
+/// Sign in by having the user enter a code on another device, which works over SSH
+async fn device_code_flow(
+    client: &reqwest::Client,
+    client_id: &str,
+    tenant: &str,
+) -> Result<Token> {
+    let resp = client
+        .post(format!("{LOGIN_URL}/{tenant}/oauth2/v2.0/devicecode"))
+        .form(&[("client_id", client_id), ("scope", SCOPES)])
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        bail!("Failed to request a device code. Status: {}", resp.status());
+    }
+    let code: DeviceCode = resp.json().await?;
+    println!("{}", code.message);
+
+    let mut interval = code.interval;
+    loop {
+        tokio::time::sleep(std::time::Duration::from_secs(interval)).await;
+
+        let resp = client
+            .post(token_url(tenant))
+            .form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+                ("client_id", client_id),
+                ("device_code", code.device_code.as_str()),
+            ])
+            .send()
+            .await?;
+        if resp.status().is_success() {
+            return Ok(resp.json::<TokenResponse>().await?.into());
+        }
+
+        let err: TokenError = resp.json().await?;
+        match err.error.as_str() {
+            "authorization_pending" => {}
+            "slow_down" => interval += 5,
+            _ => bail!(
+                "microsoft sign-in failed ({}): {}",
+                err.error,
+                err.error_description
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{body_partial_json, header, method, path},
+    };
+
+    use super::*;
+
+    fn auth() -> Auth {
+        Auth {
+            client_id: "client".to_string(),
+            tenant: "consumers".to_string(),
+            cache_path: None,
+            token: Mutex::new(Token {
+                access_token: "token".to_string(),
+                refresh_token: "refresh".to_string(),
+                expires_at: Timestamp::MAX,
+            }),
+        }
+    }
+
+    async fn server_with_lists() -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/me/todo/lists"))
+            .and(header("authorization", "Bearer token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "value": [
+                    {"id": "tasks", "displayName": "Tasks"},
+                    {"id": "asana", "displayName": "Asana"}
+                ]
+            })))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    #[tokio::test]
+    async fn tasks_look_like_google_tasks() {
+        let server = server_with_lists().await;
+        Mock::given(method("GET"))
+            .and(path("/me/todo/lists/asana/tasks"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "value": [{
+                    "id": "t1",
+                    "title": "Write report",
+                    "status": "notStarted",
+                    "body": {"content": "Draft\r\n---\r\n1208834419234561", "contentType": "text"},
+                    "dueDateTime": {"dateTime": "2026-10-20T00:00:00.0000000", "timeZone": "UTC"}
+                }],
+                "@odata.nextLink": format!("{}/me/todo/lists/asana/tasks/page2", server.uri())
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/me/todo/lists/asana/tasks/page2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "value": [{
+                    "id": "t2",
+                    "title": "Pay rent",
+                    "status": "completed",
+                    "completedDateTime": {"dateTime": "2026-10-16T12:00:00.0000000", "timeZone": "UTC"}
+                }]
+            })))
+            .mount(&server)
+            .await;
+
+        let todo = MsTodoMgr::connect(auth(), &server.uri(), "Asana")
+            .await
+            .unwrap();
+        assert_eq!(todo.list_id(), "asana");
+
+        let tasks = todo.get_tasks().await.unwrap();
+        assert_eq!(tasks.incomplete.len(), 1);
+        let task = &tasks.incomplete[0];
+        assert_eq!(
+            google::get_asana_task_gid(task).as_deref(),
+            Some("1208834419234561")
+        );
+        assert_eq!(task.due.as_deref(), Some("2026-10-20T00:00:00.000Z"));
+        assert_eq!(tasks.complete.len(), 1);
+        assert_eq!(tasks.complete[0].title.as_deref(), Some("Pay rent"));
+    }
+
+    #[tokio::test]
+    async fn new_tasks_carry_the_tagged_notes_and_due_day() {
+        let server = server_with_lists().await;
+        Mock::given(method("POST"))
+            .and(path("/me/todo/lists/asana/tasks"))
+            .and(body_partial_json(serde_json::json!({
+                "title": "Write report",
+                "body": {
+                    "content": "Draft\n--- gtasks-asana-bridge:v1 ---\ngid=100",
+                    "contentType": "text"
+                },
+                "dueDateTime": {"dateTime": "2026-10-20T00:00:00", "timeZone": "UTC"}
+            })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(serde_json::json!({
+                "id": "t1",
+                "title": "Write report"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let todo = MsTodoMgr::connect(auth(), &server.uri(), "Asana")
+            .await
+            .unwrap();
+        let task = crate::mock::asana_task("100", "Write report", jiff::civil::date(2026, 10, 20));
+        let id = todo
+            .new_task_from_asana(
+                &task,
+                "Write report",
+                &google::with_marker("Draft", "100"),
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(id, "t1");
+    }
+
+    #[tokio::test]
+    async fn deletes_are_refused_once_the_task_changed() {
+        let server = server_with_lists().await;
+        Mock::given(method("DELETE"))
+            .and(path("/me/todo/lists/asana/tasks/t1"))
+            .and(header("if-match", "W/\"2\""))
+            .respond_with(ResponseTemplate::new(204))
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/me/todo/lists/asana/tasks/t1"))
+            .and(header("if-match", "W/\"1\""))
+            .respond_with(ResponseTemplate::new(412))
+            .mount(&server)
+            .await;
+
+        let todo = MsTodoMgr::connect(auth(), &server.uri(), "Asana")
+            .await
+            .unwrap();
+        let error = todo.del_task_if("t1", "W/\"1\"").await.unwrap_err();
+        assert!(error.to_string().contains("edited since it was read"));
+        todo.del_task_if("t1", "W/\"2\"").await.unwrap();
+    }
+}
@@ -0,0 +1,142 @@
+use std::sync::{Mutex, OnceLock};
+
+use log::{debug, warn};
+use reqwest::header::HeaderMap;
+use serde::Deserialize;
+
+use crate::{config::WebhookConfig, http, logging, sync::SyncAction};
+
+/// What a webhook is told about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifyEvent {
+    /// Failed cycles, including failed logins
+    Errors,
+    /// Things the bridge can't sort out on its own, like broken links or flagged tasks
+    Conflicts,
+    /// Every change made to either side
+    Mutations,
+}
+
+/// Chat service a webhook belongs to, they expect different payloads
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookKind {
+    #[default]
+    Slack,
+    Discord,
+}
+
+static WEBHOOKS: OnceLock<Vec<WebhookConfig>> = OnceLock::new();
+/// Messages waiting for the next [`flush`]
+static PENDING: Mutex<Vec<(NotifyEvent, String)>> = Mutex::new(Vec::new());
+
+/// Send notifications to `webhooks`. Only the first call has any effect.
+pub fn set_webhooks(webhooks: Vec<WebhookConfig>) {
+    let _ = WEBHOOKS.set(webhooks);
+}
+
+fn webhooks() -> &'static [WebhookConfig] {
+    WEBHOOKS.get().map(Vec::as_slice).unwrap_or_default()
+}
+
+/// Queue a message for the webhooks that want `event`
+pub fn push(event: NotifyEvent, message: impl Into<String>) {
+    if !webhooks().iter().any(|w| w.on.contains(&event)) {
+        return;
+    }
+
+    let user = logging::USER
+        .try_with(|name| format!("{name}: "))
+        .unwrap_or_default();
+    PENDING
+        .lock()
+        .unwrap()
+        .push((event, format!("{user}{}", message.into())));
+}
+
+/// Queue a change the bridge made, with a link to the asana task when there is one
+pub fn mutation(action: &SyncAction) {
+    let url = match action {
+        SyncAction::CreateGoogle { task, .. }
+        | SyncAction::RecreateGoogle { task, .. }
+        | SyncAction::UpdateAsana { task, .. } => task.permalink_url.as_deref(),
+        _ => None,
+    };
+    match url {
+        Some(url) => push(NotifyEvent::Mutations, format!("{action} <{url}>")),
+        None => push(NotifyEvent::Mutations, action.to_string()),
+    }
+}
+
+/// Post the queued messages, one post per webhook. Failing to post is only logged, it shouldn't
+/// take the sync down with it.
+pub async fn flush() {
+    let pending = std::mem::take(&mut *PENDING.lock().unwrap());
+    if pending.is_empty() {
+        return;
+    }
+
+    let client = match http::client(HeaderMap::new()) {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("Failed to post to the webhooks: {e:#}");
+            return;
+        }
+    };
+    for webhook in webhooks() {
+        let lines: Vec<&str> = pending
+            .iter()
+            .filter(|(event, _)| webhook.on.contains(event))
+            .map(|(_, message)| message.as_str())
+            .collect();
+        if lines.is_empty() {
+            continue;
+        }
+
+        debug!(
+            "Posting {} notifications to a {:?} webhook",
+            lines.len(),
+            webhook.kind
+        );
+        let body = payload(webhook.kind, &lines.join("\n"));
+        let result = client
+            .post(&webhook.url)
+            .json(&body)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+        if let Err(e) = result {
+            warn!("Failed to post to a webhook: {e}");
+        }
+    }
+}
+
+fn payload(kind: WebhookKind, text: &str) -> serde_json::Value {
+    match kind {
+        WebhookKind::Slack => serde_json::json!({ "text": text }),
+        // discord refuses messages over 2000 characters
+        WebhookKind::Discord => {
+            let mut content: String = text.chars().take(1990).collect();
+            if content.len() < text.len() {
+                content.push_str("\n…");
+            }
+            serde_json::json!({ "content": content })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn discord_messages_are_cut_short() {
+        let long = "x".repeat(3000);
+        let body = payload(WebhookKind::Discord, &long);
+        assert_eq!(body["content"].as_str().unwrap().chars().count(), 1992);
+
+        let body = payload(WebhookKind::Slack, "Cycle failed");
+        assert_eq!(body, serde_json::json!({ "text": "Cycle failed" }));
+    }
+}
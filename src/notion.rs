@@ -0,0 +1,626 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result, bail};
+use log::debug;
+use reqwest::{
+    RequestBuilder, Response,
+    header::{AUTHORIZATION, HeaderMap, HeaderName, HeaderValue},
+};
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::{Map, Value, json};
+
+use crate::{
+    asana,
+    canonical::{CanonicalTask, Status},
+    config::{NotionConfig, NotionProperties},
+    error::BridgeError,
+    google::{self, GTaskResult, GoogleTasksApi, Task, TaskList},
+    http,
+};
+
+/// Notion's API root, overridable to point at a test server
+pub const DEFAULT_BASE_URL: &str = "https://api.notion.com/v1";
+
+/// API version the requests and responses are written against
+const NOTION_VERSION: &str = "2022-06-28";
+
+/// Longest text notion takes in one rich text object
+const TEXT_CHUNK: usize = 2000;
+
+/// A notion database behind the same interface as google tasks, with its pages standing in for
+/// the tasks. Which properties hold the title, notes, due date, status and asana link is
+/// configured, the ones a database doesn't have are left out. Pages the bridge deletes go to the
+/// trash, and the order of the pages is up to notion's views.
+#[derive(Clone)]
+pub struct NotionMgr {
+    client: reqwest::Client,
+    base_url: String,
+    properties: NotionProperties,
+    database: String,
+}
+
+impl NotionMgr {
+    pub async fn new(config: &NotionConfig, database: &str) -> Result<Self> {
+        Self::connect(Self::unbound(config).await?, DEFAULT_BASE_URL, database).await
+    }
+
+    /// Manager that isn't bound to a database yet, for picking one with
+    /// [`GoogleTasksApi::get_lists`] and [`GoogleTasksApi::for_list`]
+    pub async fn unbound(config: &NotionConfig) -> Result<Self> {
+        let token = config
+            .token
+            .as_ref()
+            .context("no notion token configured")?
+            .resolve()
+            .await
+            .context("failed to load the notion token")?;
+
+        // sent with every request, kept out of debug output
+        let mut auth = HeaderValue::from_str(&format!("Bearer {token}"))?;
+        auth.set_sensitive(true);
+        let headers = HeaderMap::from_iter([
+            (AUTHORIZATION, auth),
+            (
+                HeaderName::from_static("notion-version"),
+                HeaderValue::from_static(NOTION_VERSION),
+            ),
+        ]);
+
+        Ok(Self {
+            client: http::client(headers)?,
+            base_url: DEFAULT_BASE_URL.to_string(),
+            properties: config.properties.clone(),
+            database: String::new(),
+        })
+    }
+
+    /// Manager for the database `database` names, see [`google::find_list`], on the API at
+    /// `base_url`. Properties the database lacks are dropped from the mapping, except for the
+    /// title and notes, without which pages can't be matched to their tasks.
+    async fn connect(mut mgr: Self, base_url: &str, database: &str) -> Result<Self> {
+        mgr.base_url = base_url.trim_end_matches('/').to_string();
+        let lists = mgr.get_lists().await?;
+        // ids copied from a database's url have no dashes
+        let database = lists
+            .iter()
+            .filter_map(|l| l.id.as_deref())
+            .find(|id| id.replace('-', "") == database.replace('-', ""))
+            .unwrap_or(database);
+        mgr.database =
+            google::find_list(&lists, database).context("failed to pick the notion database")?;
+
+        let schema: Database = mgr
+            .send(
+                mgr.client
+                    .get(mgr.url(&format!("databases/{}", mgr.database))),
+                "fetch database",
+            )
+            .await?
+            .json()
+            .await?;
+        let kind = |name: &str| schema.properties.get(name).map(|p| p.kind.as_str());
+
+        let props = &mut mgr.properties;
+        for (name, wanted) in [(&props.title, "title"), (&props.notes, "rich_text")] {
+            match kind(name) {
+                Some(kind) if kind == wanted => {}
+                Some(kind) => bail!("notion property {name} is a {kind}, not {wanted}"),
+                None => bail!("the notion database has no property {name}"),
+            }
+        }
+        for (property, wanted) in [
+            (&mut props.due, &["date"][..]),
+            (&mut props.url, &["url"][..]),
+            (&mut props.status, &["status", "select", "checkbox"][..]),
+        ] {
+            let Some(name) = property else {
+                continue;
+            };
+            if !kind(name).is_some_and(|kind| wanted.contains(&kind)) {
+                debug!("the notion database has no {} property {name}", wanted[0]);
+                *property = None;
+            }
+        }
+
+        Ok(mgr)
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{path}", self.base_url)
+    }
+
+    async fn send(&self, request: RequestBuilder, what: &str) -> Result<Response> {
+        Ok(http::send(request, what).await?)
+    }
+
+    /// Follow notion's cursor pagination of a POST endpoint until every page has been fetched
+    async fn get_paged<T: DeserializeOwned>(&self, path: &str, body: Value) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+
+        let mut cursor: Option<String> = None;
+        loop {
+            let mut body = body.clone();
+            body["page_size"] = json!(100);
+            if let Some(cursor) = &cursor {
+                body["start_cursor"] = json!(cursor);
+            }
+
+            let page: Paged<T> = self
+                .send(self.client.post(self.url(path)).json(&body), "fetch")
+                .await?
+                .json()
+                .await?;
+            items.extend(page.results);
+
+            cursor = page.next_cursor.filter(|_| page.has_more);
+            if cursor.is_none() {
+                break;
+            }
+        }
+
+        Ok(items)
+    }
+
+    async fn update_page(&self, id: &str, body: Value) -> Result<()> {
+        self.send(
+            self.client
+                .patch(self.url(&format!("pages/{id}")))
+                .json(&body),
+            "update page",
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Fail unless the page's last edit is still the one it showed as its etag. Notion has no
+    /// conditional requests, and keeps edit times to the minute.
+    async fn check_unchanged(&self, id: &str, etag: &str) -> Result<()> {
+        let page: Page = self
+            .send(
+                self.client.get(self.url(&format!("pages/{id}"))),
+                "fetch page",
+            )
+            .await?
+            .json()
+            .await?;
+        if page.last_edited_time.as_deref() != Some(etag) {
+            return Err(BridgeError::Conflict(format!(
+                "notion page {id} was edited since it was read"
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Property values of a page for an asana task
+    fn task_properties(&self, task: &asana::Task, title: &str, notes: &str) -> Result<Value> {
+        let mut values = self.text_properties(title, notes);
+        if let Some(due) = &self.properties.due {
+            let day = asana::asana_due_to_string(task)?;
+            values.insert(due.clone(), json!({"date": {"start": &day[..10]}}));
+        }
+        if let Some(url) = &self.properties.url {
+            values.insert(url.clone(), json!({"url": task.permalink_url}));
+        }
+        Ok(Value::Object(values))
+    }
+
+    fn text_properties(&self, title: &str, notes: &str) -> Map<String, Value> {
+        Map::from_iter([
+            (
+                self.properties.title.clone(),
+                json!({"title": rich_text(title)}),
+            ),
+            (
+                self.properties.notes.clone(),
+                json!({"rich_text": rich_text(notes)}),
+            ),
+        ])
+    }
+
+    /// The page by its configured properties. Its last edit stands in for the revision.
+    fn canonical(&self, page: Page) -> CanonicalTask {
+        let property = |name: &Option<String>| name.as_ref().and_then(|n| page.properties.get(n));
+        let text = |name: &str, kind: &str| {
+            page.properties
+                .get(name)
+                .map(|p| plain_text(&p[kind]))
+                .unwrap_or_default()
+        };
+
+        let done =
+            property(&self.properties.status).is_some_and(|status| match status["type"].as_str() {
+                Some("checkbox") => status["checkbox"].as_bool() == Some(true),
+                Some(kind) => {
+                    status[kind]["name"].as_str() == Some(self.properties.done_status.as_str())
+                }
+                None => false,
+            });
+
+        CanonicalTask {
+            title: text(&self.properties.title, "title"),
+            body: text(&self.properties.notes, "rich_text"),
+            due: property(&self.properties.due)
+                .and_then(|due| due["date"]["start"].as_str())
+                .and_then(|start| start.get(..10)?.parse().ok()),
+            status: if done {
+                Status::Done(page.last_edited_time.clone())
+            } else {
+                Status::Open
+            },
+            links: property(&self.properties.url)
+                .and_then(|url| url["url"].as_str())
+                .map(str::to_string)
+                .into_iter()
+                .collect(),
+            revision: page.last_edited_time.clone(),
+            id: Some(page.id.clone()),
+            ..Default::default()
+        }
+    }
+}
+
+impl GoogleTasksApi for NotionMgr {
+    fn for_list(&self, list_id: &str) -> Self {
+        Self {
+            database: list_id.to_string(),
+            ..self.clone()
+        }
+    }
+
+    fn list_id(&self) -> &str {
+        &self.database
+    }
+
+    async fn get_lists(&self) -> Result<Vec<TaskList>> {
+        let databases: Vec<Database> = self
+            .get_paged(
+                "search",
+                json!({"filter": {"property": "object", "value": "database"}}),
+            )
+            .await?;
+
+        Ok(databases
+            .into_iter()
+            .map(|d| TaskList {
+                id: Some(d.id),
+                title: Some(plain_text(&d.title)),
+                ..Default::default()
+            })
+            .collect())
+    }
+
+    async fn create_list(&self, title: &str) -> Result<String> {
+        // created next to the bridge's database, the API can't create one at the workspace's top
+        let current: Database = self
+            .send(
+                self.client
+                    .get(self.url(&format!("databases/{}", self.database))),
+                "fetch database",
+            )
+            .await?
+            .json()
+            .await?;
+
+        let props = &self.properties;
+        let mut schema = Map::from_iter([
+            (props.title.clone(), json!({"title": {}})),
+            (props.notes.clone(), json!({"rich_text": {}})),
+        ]);
+        if let Some(due) = &props.due {
+            schema.insert(due.clone(), json!({"date": {}}));
+        }
+        if let Some(url) = &props.url {
+            schema.insert(url.clone(), json!({"url": {}}));
+        }
+        // status properties can't be created through the API
+        if let Some(status) = &props.status {
+            schema.insert(
+                status.clone(),
+                json!({"select": {"options": [{"name": props.done_status}]}}),
+            );
+        }
+
+        let created: Database = self
+            .send(
+                self.client.post(self.url("databases")).json(&json!({
+                    "parent": current.parent,
+                    "title": rich_text(title),
+                    "properties": schema,
+                })),
+                "create database",
+            )
+            .await?
+            .json()
+            .await?;
+        Ok(created.id)
+    }
+
+    async fn delete_list(&self, id: &str) -> Result<()> {
+        // a database is a block of its parent page
+        self.send(
+            self.client.delete(self.url(&format!("blocks/{id}"))),
+            "delete database",
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn new_task_from_asana(
+        &self,
+        task: &asana::Task,
+        title: &str,
+        notes: &str,
+        parent: Option<&str>,
+    ) -> Result<String> {
+        if parent.is_some() {
+            debug!("notion pages have no subtasks, adding \"{title}\" as a page of its own");
+        }
+
+        let created: Page = self
+            .send(
+                self.client.post(self.url("pages")).json(&json!({
+                    "parent": {"database_id": self.database},
+                    "properties": self.task_properties(task, title, notes)?,
+                })),
+                "create page",
+            )
+            .await?
+            .json()
+            .await?;
+        Ok(created.id)
+    }
+
+    async fn update_from_asana(
+        &self,
+        id: &str,
+        etag: Option<&str>,
+        task: &asana::Task,
+        title: &str,
+        notes: &str,
+    ) -> Result<bool> {
+        if let Some(etag) = etag {
+            self.check_unchanged(id, etag).await?;
+        }
+        self.update_page(
+            id,
+            json!({"properties": self.task_properties(task, title, notes)?}),
+        )
+        .await?;
+        Ok(true)
+    }
+
+    async fn set_text(&self, id: &str, title: &str, notes: &str) -> Result<()> {
+        self.update_page(
+            id,
+            json!({"properties": self.text_properties(title, notes)}),
+        )
+        .await
+    }
+
+    async fn move_to_top_level(&self, _id: &str) -> Result<()> {
+        // every page the bridge creates is already at the top level
+        Ok(())
+    }
+
+    async fn move_after(&self, id: &str, _previous: Option<&str>) -> Result<()> {
+        // database views sort pages themselves
+        debug!("notion can't reorder pages, leaving {id} in place");
+        Ok(())
+    }
+
+    async fn get_tasks(&self) -> Result<GTaskResult> {
+        let pages: Vec<Page> = self
+            .get_paged(&format!("databases/{}/query", self.database), json!({}))
+            .await?;
+
+        let mut result = GTaskResult {
+            incomplete: Vec::new(),
+            complete: Vec::new(),
+        };
+        for task in pages.into_iter().map(|p| Task::from(self.canonical(p))) {
+            if task.completed.is_some() {
+                result.complete.push(task);
+            } else {
+                result.incomplete.push(task);
+            }
+        }
+
+        Ok(result)
+    }
+
+    async fn del_task(&self, id: &str) -> Result<()> {
+        // archived pages go to the trash, where they can still be restored
+        self.update_page(id, json!({"archived": true})).await
+    }
+
+    async fn del_task_if(&self, id: &str, etag: &str) -> Result<()> {
+        self.check_unchanged(id, etag).await?;
+        self.del_task(id).await
+    }
+}
+
+/// Rich text holding `text`, split in chunks notion accepts
+fn rich_text(text: &str) -> Value {
+    let chars: Vec<char> = text.chars().collect();
+    let chunks: Vec<Value> = chars
+        .chunks(TEXT_CHUNK)
+        .map(|chunk| json!({"text": {"content": chunk.iter().collect::<String>()}}))
+        .collect();
+    Value::Array(chunks)
+}
+
+/// The text of a rich text array, formatting dropped
+fn plain_text(rich_text: &Value) -> String {
+    rich_text
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|t| t["plain_text"].as_str())
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct Paged<T> {
+    results: Vec<T>,
+    next_cursor: Option<String>,
+    #[serde(default)]
+    has_more: bool,
+}
+
+#[derive(Deserialize)]
+struct Database {
+    id: String,
+    #[serde(default)]
+    title: Value,
+    #[serde(default)]
+    parent: Value,
+    #[serde(default)]
+    properties: HashMap<String, Property>,
+}
+
+#[derive(Deserialize)]
+struct Property {
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Deserialize)]
+struct Page {
+    id: String,
+    last_edited_time: Option<String>,
+    #[serde(default)]
+    properties: Map<String, Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{body_partial_json, header, method, path},
+    };
+
+    use super::*;
+
+    async fn notion(server: &MockServer) -> NotionMgr {
+        Mock::given(method("POST"))
+            .and(path("/search"))
+            .and(header("notion-version", NOTION_VERSION))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "results": [{
+                    "id": "db1",
+                    "title": [{"plain_text": "Asana"}]
+                }],
+                "has_more": false
+            })))
+            .mount(server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/databases/db1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "db1",
+                "properties": {
+                    "Name": {"type": "title"},
+                    "Notes": {"type": "rich_text"},
+                    "Due": {"type": "date"},
+                    "Status": {"type": "status"}
+                }
+            })))
+            .mount(server)
+            .await;
+
+        let mgr = NotionMgr {
+            client: http::client(HeaderMap::from_iter([(
+                HeaderName::from_static("notion-version"),
+                HeaderValue::from_static(NOTION_VERSION),
+            )]))
+            .unwrap(),
+            base_url: String::new(),
+            properties: NotionProperties::default(),
+            database: String::new(),
+        };
+        NotionMgr::connect(mgr, &server.uri(), "asana")
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn missing_properties_are_left_out_of_the_mapping() {
+        let server = MockServer::start().await;
+        let notion = notion(&server).await;
+        assert_eq!(notion.list_id(), "db1");
+        assert!(notion.properties.due.is_some());
+        assert!(notion.properties.url.is_none());
+    }
+
+    #[tokio::test]
+    async fn pages_look_like_google_tasks() {
+        let server = MockServer::start().await;
+        let notion = notion(&server).await;
+        Mock::given(method("POST"))
+            .and(path("/databases/db1/query"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "results": [{
+                    "id": "p1",
+                    "last_edited_time": "2026-10-16T09:00:00.000Z",
+                    "properties": {
+                        "Name": {"type": "title", "title": [{"plain_text": "Write report"}]},
+                        "Notes": {"type": "rich_text", "rich_text": [
+                            {"plain_text": "Draft\n---\n"},
+                            {"plain_text": "1208834419234561"}
+                        ]},
+                        "Due": {"type": "date", "date": {"start": "2026-10-20"}},
+                        "Status": {"type": "status", "status": {"name": "In progress"}}
+                    }
+                }, {
+                    "id": "p2",
+                    "last_edited_time": "2026-10-16T12:00:00.000Z",
+                    "properties": {
+                        "Name": {"type": "title", "title": [{"plain_text": "Pay rent"}]},
+                        "Status": {"type": "status", "status": {"name": "Done"}}
+                    }
+                }],
+                "has_more": false
+            })))
+            .mount(&server)
+            .await;
+
+        let tasks = notion.get_tasks().await.unwrap();
+        assert_eq!(tasks.incomplete.len(), 1);
+        let task = &tasks.incomplete[0];
+        assert_eq!(
+            google::get_asana_task_gid(task).as_deref(),
+            Some("1208834419234561")
+        );
+        assert_eq!(task.due.as_deref(), Some("2026-10-20T00:00:00.000Z"));
+        assert_eq!(tasks.complete.len(), 1);
+        assert_eq!(tasks.complete[0].title.as_deref(), Some("Pay rent"));
+    }
+
+    #[tokio::test]
+    async fn new_pages_fill_the_mapped_properties() {
+        let server = MockServer::start().await;
+        let notion = notion(&server).await;
+        Mock::given(method("POST"))
+            .and(path("/pages"))
+            .and(body_partial_json(json!({
+                "parent": {"database_id": "db1"},
+                "properties": {
+                    "Name": {"title": [{"text": {"content": "Write report"}}]},
+                    "Notes": {"rich_text": [{"text": {"content": "Draft"}}]},
+                    "Due": {"date": {"start": "2026-10-20"}}
+                }
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"id": "p1"})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let task = crate::mock::asana_task("100", "Write report", jiff::civil::date(2026, 10, 20));
+        let id = notion
+            .new_task_from_asana(&task, "Write report", "Draft", None)
+            .await
+            .unwrap();
+        assert_eq!(id, "p1");
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use log::{debug, info};
+use serde::Deserialize;
+
+use crate::{
+    asana::{self, Section, TaskSource},
+    google::{self, GoogleTasksApi},
+    state::State,
+};
+
+/// What order the google copies are kept in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskOrder {
+    /// Leave the order to google, where new tasks go on top
+    #[default]
+    None,
+    /// The order asana lists the tasks in, which for "My Tasks" is the manual order
+    Asana,
+    /// By due date, then by name
+    Due,
+    /// By section in the order asana shows them, like "My Tasks"' Today, Upcoming and Later, then
+    /// by due date. Tasks asana promotes to another section move along.
+    Section,
+}
+
+/// Move linked top level google tasks until they are in the same order as their asana tasks.
+/// Tasks the bridge doesn't know about stay where they are between them. `sections` are those of
+/// `source` in asana's order, only needed for [`TaskOrder::Section`].
+pub async fn reorder(
+    gtasks_mgr: &impl GoogleTasksApi,
+    asana_tasks: &asana::TaskResult,
+    state: &State,
+    order: TaskOrder,
+    source: &TaskSource,
+    sections: &[Section],
+) -> Result<()> {
+    if order == TaskOrder::None {
+        return Ok(());
+    }
+
+    let mut wanted: Vec<&asana::Task> = asana_tasks.incomplete.iter().collect();
+    match order {
+        TaskOrder::Due => {
+            wanted.sort_by(|a, b| (a.due_date(), &a.name).cmp(&(b.due_date(), &b.name)));
+        }
+        TaskOrder::Section => {
+            // tasks outside every section go last
+            let rank = |task: &asana::Task| {
+                source
+                    .section_of(task)
+                    .and_then(|name| sections.iter().position(|s| s.name == name))
+                    .unwrap_or(sections.len())
+            };
+            wanted.sort_by(|a, b| {
+                (rank(a), a.due_date(), &a.name).cmp(&(rank(b), b.due_date(), &b.name))
+            });
+        }
+        TaskOrder::None | TaskOrder::Asana => {}
+    }
+
+    let google_tasks = gtasks_mgr.get_tasks().await?;
+    let mut top_level: Vec<&google::Task> = google_tasks
+        .incomplete
+        .iter()
+        .filter(|t| t.parent.is_none())
+        .collect();
+    top_level.sort_by(|a, b| a.position.cmp(&b.position));
+    let top_level_ids: HashSet<&str> = top_level.iter().filter_map(|t| t.id.as_deref()).collect();
+
+    // a google task linked twice is placed where its first link wants it
+    let mut wanted_ids: HashSet<&str> = HashSet::new();
+    let wanted: Vec<&str> = wanted
+        .iter()
+        .filter_map(|t| state.links.get(&t.gid))
+        .filter(|link| link.google_list == gtasks_mgr.list_id())
+        .map(|link| link.google_id.as_str())
+        .filter(|id| top_level_ids.contains(id) && wanted_ids.insert(*id))
+        .collect();
+    let mut current: Vec<&str> = top_level
+        .iter()
+        .filter_map(|t| t.id.as_deref())
+        .filter(|id| wanted_ids.contains(id))
+        .collect();
+
+    let mut moves = 0;
+    for (i, &id) in wanted.iter().enumerate() {
+        if current.get(i) == Some(&id) {
+            continue;
+        }
+        let Some(from) = current.iter().skip(i).position(|&c| c == id) else {
+            continue;
+        };
+
+        let previous = i.checked_sub(1).map(|p| wanted[p]);
+        debug!("Moving google task {id} after {previous:?}");
+        gtasks_mgr.move_after(id, previous).await?;
+        moves += 1;
+
+        current.remove(i + from);
+        current.insert(i, id);
+    }
+
+    if moves > 0 {
+        info!("Reordered {moves} google tasks to match asana");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::civil::date;
+
+    use super::*;
+    use crate::mock::{MockGoogle, asana_task};
+
+    async fn synced(google: &MockGoogle, tasks: &[asana::Task]) -> State {
+        let mut state = State::default();
+        for task in tasks {
+            let id = google
+                .new_task_from_asana(task, &task.name, "", None)
+                .await
+                .unwrap();
+            state.link(task, google.list_id(), &id, None);
+        }
+        state
+    }
+
+    fn titles(google: &MockGoogle) -> Vec<String> {
+        google
+            .tasks()
+            .into_iter()
+            .map(|t| t.title.unwrap_or_default())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn google_follows_asana_order() {
+        let google = MockGoogle::new();
+        let tasks = vec![
+            asana_task("100", "Pay rent", date(2026, 10, 22)),
+            asana_task("101", "Book flights", date(2026, 10, 20)),
+            asana_task("102", "Call dentist", date(2026, 10, 21)),
+        ];
+        let state = synced(
+            &google,
+            &[tasks[2].clone(), tasks[0].clone(), tasks[1].clone()],
+        )
+        .await;
+        let asana_tasks = asana::TaskResult {
+            incomplete: tasks,
+            complete: Vec::new(),
+        };
+        let source = TaskSource::UserTaskList("1".to_string());
+
+        reorder(
+            &google,
+            &asana_tasks,
+            &state,
+            TaskOrder::Asana,
+            &source,
+            &[],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            titles(&google),
+            ["Pay rent", "Book flights", "Call dentist"]
+        );
+
+        reorder(&google, &asana_tasks, &state, TaskOrder::Due, &source, &[])
+            .await
+            .unwrap();
+        assert_eq!(
+            titles(&google),
+            ["Book flights", "Call dentist", "Pay rent"]
+        );
+    }
+
+    #[tokio::test]
+    async fn a_google_task_linked_twice_is_placed_once() {
+        let google = MockGoogle::new();
+        let tasks = vec![
+            asana_task("100", "Pay rent", date(2026, 10, 22)),
+            asana_task("102", "Pay rent", date(2026, 10, 22)),
+            asana_task("103", "Water the plants", date(2026, 10, 23)),
+            asana_task("101", "Book flights", date(2026, 10, 20)),
+        ];
+        let mut state = synced(
+            &google,
+            &[tasks[3].clone(), tasks[2].clone(), tasks[0].clone()],
+        )
+        .await;
+        let rent = state.links["100"].google_id.clone();
+        state.link(&tasks[1], google.list_id(), &rent, None);
+        let asana_tasks = asana::TaskResult {
+            incomplete: tasks,
+            complete: Vec::new(),
+        };
+        let source = TaskSource::UserTaskList("1".to_string());
+
+        reorder(
+            &google,
+            &asana_tasks,
+            &state,
+            TaskOrder::Asana,
+            &source,
+            &[],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            titles(&google),
+            ["Pay rent", "Water the plants", "Book flights"]
+        );
+    }
+
+    #[tokio::test]
+    async fn my_tasks_sections_come_in_order() {
+        let google = MockGoogle::new();
+        let section = |gid: &str, name: &str| Section {
+            gid: gid.to_string(),
+            name: name.to_string(),
+        };
+        let sections = [
+            section("1", "Today"),
+            section("2", "Upcoming"),
+            section("3", "Later"),
+        ];
+        let in_section = |gid: &str, name: &str, due, section: &Section| {
+            let mut task = asana_task(gid, name, due);
+            task.assignee_section = Some(section.clone());
+            task
+        };
+        let mut tasks = vec![
+            in_section("100", "Pay rent", date(2026, 10, 20), &sections[2]),
+            in_section("101", "Book flights", date(2026, 10, 22), &sections[0]),
+            in_section("102", "Call dentist", date(2026, 10, 21), &sections[1]),
+            asana_task("103", "Water plants", date(2026, 10, 16)),
+        ];
+        let state = synced(&google, &tasks).await;
+        let source = TaskSource::UserTaskList("1".to_string());
+        let result = |tasks: &[asana::Task]| asana::TaskResult {
+            incomplete: tasks.to_vec(),
+            complete: Vec::new(),
+        };
+
+        reorder(
+            &google,
+            &result(&tasks),
+            &state,
+            TaskOrder::Section,
+            &source,
+            &sections,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            titles(&google),
+            ["Book flights", "Call dentist", "Pay rent", "Water plants"]
+        );
+
+        // promoted to today by asana as it comes due
+        tasks[0].assignee_section = Some(sections[0].clone());
+        reorder(
+            &google,
+            &result(&tasks),
+            &state,
+            TaskOrder::Section,
+            &source,
+            &sections,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            titles(&google),
+            ["Pay rent", "Book flights", "Call dentist", "Water plants"]
+        );
+    }
+}
@@ -0,0 +1,45 @@
+use std::{collections::HashSet, fmt};
+
+use crate::{
+    asana::{self, TaskSource},
+    google,
+    sync::{self, GoogleFields},
+};
+
+/// Hooks into the planner for organisation specific rules. Every method defaults to the bridge's
+/// own behaviour, so implementations only override what they need.
+pub trait DiffPolicy: fmt::Debug + Send + Sync {
+    /// Whether an incomplete asana task gets a google copy at all. Copies of tasks that stop
+    /// matching are removed, unless they were completed in google.
+    fn include(&self, _task: &asana::Task) -> bool {
+        true
+    }
+
+    /// What the google copy of a task should contain
+    fn google_fields(&self, task: &asana::Task, duplicate_names: &HashSet<&str>) -> GoogleFields {
+        sync::google_fields(task, duplicate_names)
+    }
+
+    /// Whether a google copy is up to date with its asana task
+    fn same(&self, task: &asana::Task, fields: &GoogleFields, gtask: &google::Task) -> bool {
+        sync::asana_google_same(task, fields, gtask)
+    }
+
+    /// Whether completing a task in google wins over an edit made to it in asana. When it
+    /// doesn't, the google copy is recreated from asana and the task stays open.
+    fn google_completion_wins(&self, _task: &asana::Task, _gtask: &google::Task) -> bool {
+        true
+    }
+
+    /// Asana section whose list a task is mirrored into with section routing, `None` for the base
+    /// list. A task routed to a section the project doesn't have isn't synced.
+    fn route<'a>(&self, source: &TaskSource, task: &'a asana::Task) -> Option<&'a str> {
+        source.section_of(task)
+    }
+}
+
+/// The bridge's built-in rules
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultPolicy;
+
+impl DiffPolicy for DefaultPolicy {}
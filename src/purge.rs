@@ -0,0 +1,372 @@
+use std::{collections::BTreeSet, fmt, io::Write};
+
+use anyhow::{Context, Result};
+use log::info;
+
+use crate::{
+    correlate::{Correlator, NotesMarker, TitleToken},
+    google::{self, GoogleTasksApi},
+    journal,
+    lock::{self, LOCK_LIST},
+    state::State,
+};
+
+/// What purging does to one google task
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    Delete,
+    /// Rewrite it with this title and notes, the tags stripped
+    Untag {
+        title: String,
+        notes: String,
+    },
+}
+
+/// A google task the bridge made or tagged
+#[derive(Debug, Clone)]
+pub struct Planned {
+    pub list: String,
+    pub id: String,
+    pub title: String,
+    /// Nested under another task, which takes it along when it's deleted first
+    pub nested: bool,
+    pub step: Step,
+}
+
+impl fmt::Display for Planned {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verb = match self.step {
+            Step::Delete => "delete",
+            Step::Untag { .. } => "untag",
+        };
+        write!(f, "{verb} google task {} \"{}\"", self.id, self.title)
+    }
+}
+
+/// Whether `gid` is one the bridge tags with: an asana gid, or a github issue's `owner/repo#N`. A
+/// `---` line the user wrote isn't mistaken for an old marker this way.
+fn is_gid(gid: &str) -> bool {
+    let digits = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+    match gid.rsplit_once('#') {
+        Some((repo, number)) => {
+            digits(number)
+                && repo
+                    .split_once('/')
+                    .is_some_and(|(owner, name)| !owner.is_empty() && !name.is_empty())
+        }
+        None => digits(gid),
+    }
+}
+
+/// The bridge's tasks in `list`: those the state store links, and any that carry a tag, whether
+/// `correlator`'s or the notes block or title token another correlation left. With `keep`
+/// they're untagged rather than deleted.
+pub fn plan(
+    list: &str,
+    tasks: &[google::Task],
+    state: &State,
+    correlator: &dyn Correlator,
+    keep: bool,
+) -> Vec<Planned> {
+    let correlators: [&dyn Correlator; 3] = [correlator, &NotesMarker, &TitleToken];
+    tasks
+        .iter()
+        .filter_map(|gtask| {
+            let id = gtask.id.clone()?;
+            let title = gtask.title.clone().unwrap_or_default();
+            let notes = gtask.notes.clone().unwrap_or_default();
+            let tagged = correlators
+                .iter()
+                .any(|c| c.gid(gtask).is_some_and(|gid| is_gid(&gid)));
+            // the title token's tag removes the notes block too
+            let (bare_title, bare_notes) = TitleToken.tag(&title, &notes, None);
+            if !tagged && state.asana_gid_for_google(&id).is_none() {
+                return None;
+            }
+
+            let step = if keep {
+                if !tagged {
+                    return None;
+                }
+                Step::Untag {
+                    title: bare_title.clone(),
+                    notes: bare_notes,
+                }
+            } else {
+                Step::Delete
+            };
+            Some(Planned {
+                list: list.to_string(),
+                id,
+                title: bare_title,
+                nested: gtask.parent.is_some(),
+                step,
+            })
+        })
+        .collect()
+}
+
+/// What purging does to one endpoint, the main backend or a mirror
+struct Survey {
+    planned: Vec<Planned>,
+    /// The list the bridge keeps other bridges out with
+    lock_list: Option<String>,
+}
+
+impl Survey {
+    /// Plan the purge of the endpoint `gtasks_mgr` is bound to, in its list and every list `state`
+    /// links into
+    async fn take(
+        gtasks_mgr: &impl GoogleTasksApi,
+        state: &State,
+        correlator: &dyn Correlator,
+        keep: bool,
+    ) -> Result<Self> {
+        let wanted: BTreeSet<&str> = state
+            .links
+            .values()
+            .map(|l| l.google_list.as_str())
+            .chain([gtasks_mgr.list_id()])
+            .collect();
+
+        let mut planned = Vec::new();
+        // lists that were deleted since they were linked into have nothing left to purge
+        let lists = gtasks_mgr.get_lists().await?;
+        for list in &lists {
+            let Some(id) = list.id.clone().filter(|id| wanted.contains(id.as_str())) else {
+                continue;
+            };
+            let tasks = gtasks_mgr.for_list(&id).get_tasks().await?;
+            let tasks: Vec<google::Task> =
+                tasks.incomplete.into_iter().chain(tasks.complete).collect();
+            planned.extend(plan(&id, &tasks, state, correlator, keep));
+        }
+        // deleting a parent deletes what's nested under it, so those go first
+        planned.sort_by_key(|p| !p.nested);
+
+        let lock_list = lists
+            .iter()
+            .find(|l| l.title.as_deref() == Some(LOCK_LIST))
+            .and_then(|l| l.id.clone());
+        Ok(Self { planned, lock_list })
+    }
+
+    fn print(&self) {
+        for task in &self.planned {
+            println!("Would {task}");
+        }
+        if self.lock_list.is_some() {
+            println!("Would delete google list \"{LOCK_LIST}\"");
+        }
+    }
+
+    async fn carry_out(&self, gtasks_mgr: &impl GoogleTasksApi) -> Result<()> {
+        for task in &self.planned {
+            let list_mgr = gtasks_mgr.for_list(&task.list);
+            match &task.step {
+                Step::Delete => list_mgr.del_task(&task.id).await?,
+                Step::Untag { title, notes } => list_mgr.set_text(&task.id, title, notes).await?,
+            }
+            info!("Purged: {task}");
+        }
+        if let Some(id) = &self.lock_list {
+            gtasks_mgr.delete_list(id).await?;
+        }
+        Ok(())
+    }
+}
+
+fn confirm(question: &str) -> Result<bool> {
+    print!("{question} [y/N] ");
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().eq_ignore_ascii_case("y"))
+}
+
+/// Delete every google task the bridge made, in the main backend and each of `mirrors` by name, or
+/// only strip their tags with `keep`, then remove the state file at `state_path` with its journal
+/// and lock file, so nothing of the bridge is left behind
+pub async fn purge<M: GoogleTasksApi>(
+    gtasks_mgr: &impl GoogleTasksApi,
+    mirrors: &[(&str, &M)],
+    state_path: &str,
+    correlator: &dyn Correlator,
+    keep: bool,
+    yes: bool,
+    dry_run: bool,
+) -> Result<()> {
+    let state = State::load(state_path)?;
+    let main = Survey::take(gtasks_mgr, &state, correlator, keep).await?;
+    main.print();
+    let mut mirrored = Vec::new();
+    for &(name, mirror) in mirrors {
+        let mirror_state = state.mirrors.get(name).cloned().unwrap_or_default();
+        let survey = Survey::take(mirror, &mirror_state, correlator, keep)
+            .await
+            .with_context(|| format!("failed to list the {name} mirror"))?;
+        if !survey.planned.is_empty() || survey.lock_list.is_some() {
+            println!("In the {name} mirror:");
+            survey.print();
+        }
+        mirrored.push((mirror, survey));
+    }
+    if dry_run {
+        return Ok(());
+    }
+    let count = main.planned.len()
+        + mirrored
+            .iter()
+            .map(|(_, survey)| survey.planned.len())
+            .sum::<usize>();
+    let question = format!(
+        "{} {count} google tasks and remove {state_path}?",
+        if keep { "Untag" } else { "Delete" },
+    );
+    if !yes && !confirm(&question)? {
+        println!("Nothing purged");
+        return Ok(());
+    }
+
+    main.carry_out(gtasks_mgr).await?;
+    for (mirror, survey) in &mirrored {
+        survey.carry_out(*mirror).await?;
+    }
+
+    for path in [
+        state_path.to_string(),
+        journal::journal_path(state_path),
+        lock::lock_path(state_path),
+    ] {
+        if std::fs::exists(&path)? {
+            std::fs::remove_file(&path).with_context(|| format!("failed to remove {path}"))?;
+        }
+    }
+    println!("Purged {count} google tasks and removed {state_path}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::civil::date;
+
+    use super::*;
+    use crate::mock::{MockGoogle, asana_task};
+
+    const NO_MIRRORS: [(&str, &MockGoogle); 0] = [];
+
+    #[tokio::test]
+    async fn only_the_bridges_tasks_are_purged() {
+        let google = MockGoogle::new();
+        let mut state = State::default();
+        let rent = asana_task("100", "Pay rent", date(2026, 10, 20));
+        let passport = asana_task("101", "Renew passport", date(2026, 10, 22));
+        // linked, with its marker edited away
+        let linked = google
+            .new_task_from_asana(&rent, "Pay rent", "", None)
+            .await
+            .unwrap();
+        state.link(&rent, google.list_id(), &linked, None);
+        // tagged by the title correlation once, but no longer linked
+        google
+            .new_task_from_asana(&passport, "Renew passport [asana:101]", "", None)
+            .await
+            .unwrap();
+        let own = google
+            .new_task_from_asana(&passport, "Water the plants", "Every other day", None)
+            .await
+            .unwrap();
+        let path = std::env::temp_dir().join("purge_state.json");
+        let path = path.to_str().unwrap();
+        state.save(path).unwrap();
+
+        purge(&google, &NO_MIRRORS, path, &NotesMarker, false, true, false)
+            .await
+            .unwrap();
+
+        let left: Vec<_> = google.tasks().into_iter().filter_map(|t| t.id).collect();
+        assert_eq!(left, [own]);
+        assert!(!std::fs::exists(path).unwrap());
+    }
+
+    #[tokio::test]
+    async fn mirrors_and_leftover_files_are_purged_too() {
+        let (google, todo) = (MockGoogle::new(), MockGoogle::new());
+        let mut state = State::default();
+        let rent = asana_task("100", "Pay rent", date(2026, 10, 20));
+        let issue = asana_task("anichno/bridge#12", "Fix the build", date(2026, 10, 21));
+        // a github issue's marker, with no link left
+        google
+            .new_task_from_asana(
+                &issue,
+                "Fix the build",
+                &google::with_marker("", "anichno/bridge#12"),
+                None,
+            )
+            .await
+            .unwrap();
+        // linked in the mirror only
+        let copy = todo
+            .new_task_from_asana(&rent, "Pay rent", "", None)
+            .await
+            .unwrap();
+        state.mirrors.entry("mstodo".to_string()).or_default().link(
+            &rent,
+            todo.list_id(),
+            &copy,
+            None,
+        );
+        let own = google
+            .new_task_from_asana(&rent, "Call the landlord", "---\nabout the lease", None)
+            .await
+            .unwrap();
+        let path = std::env::temp_dir().join("purge_mirror_state.json");
+        let path = path.to_str().unwrap();
+        state.save(path).unwrap();
+        let leftovers = [journal::journal_path(path), lock::lock_path(path)];
+        for leftover in &leftovers {
+            std::fs::write(leftover, "").unwrap();
+        }
+
+        purge(
+            &google,
+            &[("mstodo", &todo)],
+            path,
+            &NotesMarker,
+            false,
+            true,
+            false,
+        )
+        .await
+        .unwrap();
+
+        let left: Vec<_> = google.tasks().into_iter().filter_map(|t| t.id).collect();
+        assert_eq!(left, [own]);
+        assert!(todo.tasks().is_empty());
+        assert!(!std::fs::exists(path).unwrap());
+        for leftover in &leftovers {
+            assert!(!std::fs::exists(leftover).unwrap());
+        }
+    }
+
+    #[test]
+    fn kept_tasks_lose_their_tags() {
+        let tagged = google::Task {
+            id: Some("g1".to_string()),
+            title: Some("Pay rent".to_string()),
+            notes: Some(google::with_marker("Landlord", "100")),
+            ..Default::default()
+        };
+
+        let planned = plan("list", &[tagged], &State::default(), &NotesMarker, true);
+
+        assert_eq!(
+            planned[0].step,
+            Step::Untag {
+                title: "Pay rent".to_string(),
+                notes: "Landlord".to_string(),
+            }
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use std::sync::OnceLock;
+
+use crate::asana;
+
+/// Title google copies of private tasks get instead of their own
+pub const PRIVATE_TITLE: &str = "Private task";
+
+static PRIVATE_TAG: OnceLock<String> = OnceLock::new();
+
+/// Treat tasks tagged `tag` as private. Only the first call has any effect.
+pub fn set_private_tag(tag: &str) {
+    let _ = PRIVATE_TAG.set(tag.to_string());
+}
+
+/// Tag marking tasks that must not show their details in google
+fn private_tag() -> Option<&'static str> {
+    PRIVATE_TAG.get().map(String::as_str)
+}
+
+/// Whether a task carries the private tag, compared case-insensitively
+pub fn is_private(task: &asana::Task) -> bool {
+    private_tag().is_some_and(|private| {
+        task.tags
+            .iter()
+            .any(|tag| tag.name.eq_ignore_ascii_case(private))
+    })
+}
+
+/// Make `child` private if `ancestor` is, so subtasks don't leak what their parent hides
+pub fn inherit_private(child: &mut asana::Task, ancestor: &asana::Task) {
+    if is_private(ancestor) && !is_private(child) {
+        child.tags.extend(
+            ancestor
+                .tags
+                .iter()
+                .filter(|tag| private_tag().is_some_and(|p| tag.name.eq_ignore_ascii_case(p)))
+                .cloned(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::civil::date;
+
+    use super::*;
+    use crate::{
+        google,
+        hierarchy::FlattenPolicy,
+        mock::{MockAsana, MockGoogle, asana_task, sync_options},
+        state::State,
+        sync::{self, Policies, SyncOptions},
+    };
+
+    #[tokio::test]
+    async fn private_tasks_and_their_subtasks_reach_google_redacted() {
+        set_private_tag("private");
+        let mut party = asana_task("100", "Plan the surprise party", date(2026, 10, 24));
+        party.notes = "Don't tell anyone".to_string();
+        party.tags.push(asana::Tag {
+            name: "Private".to_string(),
+        });
+        let mut cake = asana_task("101", "Order the cake", date(2026, 10, 23));
+        cake.notes = "Chocolate, three tiers".to_string();
+        let asana = MockAsana::new(vec![party]);
+        asana
+            .subtasks
+            .borrow_mut()
+            .insert("100".to_string(), vec![cake]);
+        let google = MockGoogle::new();
+        let mut state = State::default();
+        let options = SyncOptions {
+            policies: Policies {
+                flatten: Some(FlattenPolicy::Promote),
+                ..sync_options().policies
+            },
+            ..sync_options()
+        };
+
+        sync::process_tasks(&asana, &google, &mut state, &options)
+            .await
+            .unwrap();
+
+        let gtasks = google.tasks();
+        assert_eq!(gtasks.len(), 2);
+        for gtask in gtasks {
+            assert_eq!(gtask.title.as_deref(), Some(PRIVATE_TITLE));
+            assert_eq!(
+                google::notes_body(gtask.notes.as_deref().unwrap_or_default()),
+                ""
+            );
+        }
+    }
+}
@@ -0,0 +1,363 @@
+use std::{collections::BTreeMap, fmt};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    asana::{self, AsanaApi},
+    correlate::Correlator,
+    google::{self, GTaskResult, GoogleTasksApi},
+    state::State,
+    sync,
+};
+
+/// Point the tag on google task `google_id` at the asana task `gid`, or remove it for `None`
+async fn retag(
+    gtasks_mgr: &impl GoogleTasksApi,
+    correlator: &dyn Correlator,
+    google_id: &str,
+    gid: Option<&str>,
+) -> Result<()> {
+    let google_tasks = gtasks_mgr.get_tasks().await?;
+    let gtask = google_tasks
+        .incomplete
+        .iter()
+        .chain(&google_tasks.complete)
+        .find(|t| t.id.as_deref() == Some(google_id))
+        .with_context(|| format!("google task {google_id} not found"))?;
+
+    let (title, notes) = correlator.tag(
+        gtask.title.as_deref().unwrap_or_default(),
+        gtask.notes.as_deref().unwrap_or_default(),
+        gid,
+    );
+    gtasks_mgr.set_text(google_id, &title, &notes).await
+}
+
+/// Forget a link and delete the google copy, so the next cycle writes a fresh one. The copy is
+/// deleted rather than untagged, an untagged copy would sit next to the fresh one. The caller
+/// saves the state.
+pub async fn unlink(gtasks_mgr: &impl GoogleTasksApi, state: &mut State, gid: &str) -> Result<()> {
+    let Some(link) = state.links.get(gid).cloned() else {
+        bail!("asana task {gid} is not linked");
+    };
+
+    gtasks_mgr
+        .for_list(&link.google_list)
+        .del_task(&link.google_id)
+        .await?;
+    state.unlink(gid);
+
+    println!(
+        "Unlinked asana task {gid} and deleted its google copy {}",
+        link.google_id
+    );
+    Ok(())
+}
+
+/// Link an asana task to a specific google task. The caller saves the state.
+pub async fn relink(
+    asana_mgr: &impl AsanaApi,
+    gtasks_mgr: &impl GoogleTasksApi,
+    state: &mut State,
+    correlator: &dyn Correlator,
+    gid: &str,
+    google_id: &str,
+) -> Result<()> {
+    let atask: asana::Task = serde_json::from_value(asana_mgr.get_task_raw(gid).await?)
+        .context("failed to parse asana task")?;
+
+    // a google task can only be linked to one asana task
+    if let Some(previous) = state.asana_gid_for_google(google_id).map(str::to_string) {
+        state.unlink(&previous);
+    }
+
+    retag(gtasks_mgr, correlator, google_id, Some(gid)).await?;
+    state.link(&atask, gtasks_mgr.list_id(), google_id, None);
+
+    println!(
+        "Linked asana task {gid} (\"{}\") to google task {google_id}",
+        atask.name
+    );
+    Ok(())
+}
+
+/// Why a google task counts as a copy of an asana task
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Match {
+    /// Its tag, or the state store, point at the asana task
+    Gid,
+    /// Unlinked, with the title and due date the bridge gives the asana task's copy
+    TitleAndDue,
+}
+
+impl fmt::Display for Match {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Match::Gid => write!(f, "linked to the same asana task"),
+            Match::TitleAndDue => write!(f, "same title and due date"),
+        }
+    }
+}
+
+/// Google copies of one asana task that should be a single google task
+#[derive(Debug)]
+pub struct Duplicates {
+    pub gid: String,
+    pub name: String,
+    /// Google task that's kept
+    pub keep: String,
+    /// `keep` lost its tag, it's written back when merging
+    pub mark: bool,
+    /// Copies that are deleted when merging
+    pub extra: Vec<(String, Match)>,
+}
+
+impl fmt::Display for Duplicates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Asana task {} (\"{}\") is google task {}",
+            self.gid, self.name, self.keep
+        )?;
+        for (id, matched) in &self.extra {
+            write!(f, ", duplicated by {id} ({matched})")?;
+        }
+        if self.mark {
+            write!(f, ", which lost its tag")?;
+        }
+        Ok(())
+    }
+}
+
+/// Group the google tasks in `list` by the asana task they copy, and pick the copy to keep where
+/// there is more than one, or where the only copy lost its link
+pub fn find_duplicates(
+    asana_tasks: &asana::TaskResult,
+    google_tasks: &GTaskResult,
+    state: &State,
+    correlator: &dyn Correlator,
+    list: &str,
+) -> Vec<Duplicates> {
+    let duplicate_names = sync::duplicate_names(&asana_tasks.incomplete);
+    let all_google = google_tasks
+        .incomplete
+        .iter()
+        .map(|t| (t, false))
+        .chain(google_tasks.complete.iter().map(|t| (t, true)));
+
+    let mut copies: BTreeMap<String, Vec<(&google::Task, Match, bool)>> = BTreeMap::new();
+    for (gtask, completed) in all_google {
+        if gtask.id.is_none() {
+            continue;
+        }
+        if let Some(gid) = sync::linked_asana_gid(gtask, state, correlator) {
+            copies
+                .entry(gid)
+                .or_default()
+                .push((gtask, Match::Gid, completed));
+            continue;
+        }
+        let untagged = correlator.untagged(gtask);
+        let atask = asana_tasks.incomplete.iter().find(|atask| {
+            untagged.title.as_deref()
+                == Some(sync::google_fields(atask, &duplicate_names).title.as_str())
+                && google::due_date(gtask) == atask.due_date()
+        });
+        if let Some(atask) = atask {
+            copies.entry(atask.gid.clone()).or_default().push((
+                gtask,
+                Match::TitleAndDue,
+                completed,
+            ));
+        }
+    }
+
+    copies
+        .into_iter()
+        .filter_map(|(gid, tasks)| {
+            let linked = state
+                .links
+                .get(&gid)
+                .filter(|l| l.google_list == list)
+                .map(|l| l.google_id.as_str());
+            // a completion made on any copy has to survive the merge, then the copy the state
+            // store knows, then one with a tag
+            let (keep, matched, _) = tasks
+                .iter()
+                .find(|(_, _, completed)| *completed)
+                .or_else(|| tasks.iter().find(|(t, _, _)| t.id.as_deref() == linked))
+                .or_else(|| tasks.iter().find(|(_, m, _)| *m == Match::Gid))
+                .unwrap_or(&tasks[0]);
+            let keep_id = keep.id.clone()?;
+            let extra: Vec<(String, Match)> = tasks
+                .iter()
+                .filter_map(|(t, m, _)| Some((t.id.clone()?, *m)))
+                .filter(|(id, _)| *id != keep_id)
+                .collect();
+            if extra.is_empty() && *matched == Match::Gid {
+                return None;
+            }
+
+            let name = asana_tasks
+                .incomplete
+                .iter()
+                .chain(&asana_tasks.complete)
+                .find(|t| t.gid == gid)
+                .map(|t| t.name.clone())
+                .or_else(|| state.links.get(&gid).map(|l| l.asana_name.clone()))
+                .or_else(|| keep.title.clone())
+                .unwrap_or_default();
+            Some(Duplicates {
+                mark: correlator.tags() && correlator.gid(keep).as_deref() != Some(gid.as_str()),
+                gid,
+                name,
+                keep: keep_id,
+                extra,
+            })
+        })
+        .collect()
+}
+
+/// Delete the extra copies, and point the kept copy's tag and the state store at the asana task.
+/// The caller saves the state.
+pub async fn merge(
+    gtasks_mgr: &impl GoogleTasksApi,
+    asana_tasks: &asana::TaskResult,
+    state: &mut State,
+    correlator: &dyn Correlator,
+    duplicates: &[Duplicates],
+) -> Result<()> {
+    let list = gtasks_mgr.list_id();
+    for dup in duplicates {
+        for (id, _) in &dup.extra {
+            gtasks_mgr.del_task(id).await?;
+        }
+        if dup.mark {
+            retag(gtasks_mgr, correlator, &dup.keep, Some(&dup.gid)).await?;
+        }
+
+        if let Some(link) = state.links.get_mut(&dup.gid) {
+            if link.google_list != list || link.google_id != dup.keep {
+                link.google_list = list.to_string();
+                // the bridge didn't write the kept copy, so its content is compared afresh
+                link.hash = None;
+                state.set_google_id(&dup.gid, &dup.keep);
+            }
+        } else if let Some(atask) = asana_tasks
+            .incomplete
+            .iter()
+            .chain(&asana_tasks.complete)
+            .find(|t| t.gid == dup.gid)
+        {
+            state.link(atask, list, &dup.keep, None);
+        }
+    }
+    Ok(())
+}
+
+/// Find google copies of the same asana task in the manager's list, and fold each set into one
+/// when `apply` is set. The caller saves the state.
+pub async fn dedupe(
+    asana_mgr: &impl AsanaApi,
+    gtasks_mgr: &impl GoogleTasksApi,
+    state: &mut State,
+    correlator: &dyn Correlator,
+    apply: bool,
+) -> Result<Vec<Duplicates>> {
+    let asana_tasks = asana_mgr.get_tasks().await?;
+    let google_tasks = gtasks_mgr.get_tasks().await?;
+
+    let list = gtasks_mgr.list_id();
+    let duplicates = find_duplicates(&asana_tasks, &google_tasks, state, correlator, list);
+    if apply {
+        merge(gtasks_mgr, &asana_tasks, state, correlator, &duplicates).await?;
+    }
+    Ok(duplicates)
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::civil::date;
+
+    use super::*;
+    use crate::{
+        correlate::NotesMarker,
+        mock::{MockAsana, MockGoogle, asana_task, cycle, sync_options, synced},
+    };
+
+    #[tokio::test]
+    async fn unlinked_tasks_arent_duplicated() {
+        let (asana, google, mut state) =
+            synced(vec![asana_task("100", "Write report", date(2026, 10, 20))]).await;
+        let unlinked = state.links["100"].google_id.clone();
+
+        unlink(&google, &mut state, "100").await.unwrap();
+        assert!(state.links.is_empty());
+        for _ in 0..2 {
+            cycle(&asana, &google, &mut state, &sync_options())
+                .await
+                .unwrap();
+        }
+
+        let tasks = google.tasks();
+        assert_eq!(tasks.len(), 1);
+        assert_ne!(tasks[0].id.as_deref(), Some(unlinked.as_str()));
+        assert_eq!(state.links["100"].google_id, tasks[0].id.clone().unwrap());
+    }
+
+    #[tokio::test]
+    async fn copies_are_merged_into_one() {
+        let report = asana_task("100", "Write report", date(2026, 10, 20));
+        let rent = asana_task("101", "Pay rent", date(2026, 10, 22));
+        let asana = MockAsana::new(vec![report.clone(), rent.clone()]);
+        let google = MockGoogle::new();
+        let mut state = State::default();
+        let create = async |task: &asana::Task| {
+            google
+                .new_task_from_asana(task, &task.name, &google::with_marker("", &task.gid), None)
+                .await
+                .unwrap()
+        };
+
+        // created twice, the second one linked
+        let report_first = create(&report).await;
+        let report_linked = create(&report).await;
+        state.link(&report, google.list_id(), &report_linked, None);
+        // a copy whose marker was edited away, then completed in google
+        let rent_linked = create(&rent).await;
+        state.link(&rent, google.list_id(), &rent_linked, None);
+        let rent_done = create(&rent).await;
+        google.update(&rent_done, |t| {
+            t.notes = Some(String::new());
+            t.completed = Some("2026-10-16".to_string());
+        });
+
+        let duplicates = dedupe(&asana, &google, &mut state, &NotesMarker, true)
+            .await
+            .unwrap();
+
+        assert_eq!(duplicates.len(), 2);
+        assert_eq!(duplicates[0].keep, report_linked);
+        assert_eq!(duplicates[0].extra, [(report_first, Match::Gid)]);
+        assert_eq!(duplicates[1].keep, rent_done);
+        assert!(duplicates[1].mark);
+        assert_eq!(duplicates[1].extra, [(rent_linked, Match::Gid)]);
+
+        assert_eq!(google.tasks().len(), 2);
+        assert_eq!(state.links["101"].google_id, rent_done);
+        let rent_copy = google
+            .tasks()
+            .into_iter()
+            .find(|t| t.id == Some(rent_done.clone()));
+        assert_eq!(
+            google::get_asana_task_gid(&rent_copy.unwrap()).as_deref(),
+            Some("101")
+        );
+        assert!(
+            dedupe(&asana, &google, &mut state, &NotesMarker, true)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
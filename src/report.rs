@@ -0,0 +1,295 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use jiff::{Timestamp, ToSpan, civil::Date};
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::{archive, config::ReportConfig, date, google::GoogleTasksApi, state::State};
+
+/// How many days a report covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportPeriod {
+    Day,
+    Week,
+}
+
+impl ReportPeriod {
+    fn days(self) -> i64 {
+        match self {
+            ReportPeriod::Day => 1,
+            ReportPeriod::Week => 7,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    #[default]
+    Markdown,
+    Json,
+}
+
+/// Tasks created, completed and overdue, per google list
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub from: Date,
+    pub to: Date,
+    /// Keyed by list title
+    pub lists: BTreeMap<String, ListReport>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ListReport {
+    /// First linked during the period
+    pub created: Vec<Item>,
+    /// Archived as completed during the period
+    pub completed: Vec<Item>,
+    /// Still open and due before the last day of the period
+    pub overdue: Vec<Item>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Item {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due: Option<Date>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub at: Option<Timestamp>,
+}
+
+/// Report on the `period` ending with the day `to`. `titles` maps google list ids to the titles
+/// the report shows, unknown lists show their id.
+pub fn build(
+    state: &State,
+    archived: &[archive::Entry],
+    titles: &BTreeMap<String, String>,
+    period: ReportPeriod,
+    to: Date,
+) -> Report {
+    let from = to - (period.days() - 1).days();
+    let in_period = |ts: Timestamp| (from..=to).contains(&date::local_date(ts));
+    let title = |list: &str| {
+        titles
+            .get(list)
+            .cloned()
+            .unwrap_or_else(|| list.to_string())
+    };
+
+    let mut lists: BTreeMap<String, ListReport> = BTreeMap::new();
+    for (gid, link) in &state.links {
+        let item = || Item {
+            name: link.asana_name.clone(),
+            gid: Some(gid.clone()),
+            due: link.due,
+            at: link.created,
+        };
+        if link.created.is_some_and(in_period) {
+            let list = lists.entry(title(&link.google_list)).or_default();
+            list.created.push(item());
+        }
+        if link.due.is_some_and(|due| due < to) {
+            let list = lists.entry(title(&link.google_list)).or_default();
+            list.overdue.push(item());
+        }
+    }
+
+    for entry in archived.iter().filter(|e| e.completed && in_period(e.at)) {
+        let list = lists.entry(title(&entry.google.list)).or_default();
+        list.completed.push(Item {
+            name: entry
+                .asana
+                .as_ref()
+                .map_or(&entry.google.title, |a| &a.name)
+                .clone(),
+            gid: entry.asana.as_ref().map(|a| a.gid.clone()),
+            due: entry.asana.as_ref().and_then(|a| a.due_on),
+            at: Some(entry.at),
+        });
+    }
+
+    for list in lists.values_mut() {
+        list.overdue.sort_by_key(|item| item.due);
+    }
+
+    Report { from, to, lists }
+}
+
+impl Report {
+    pub fn render(&self, format: ReportFormat) -> Result<String> {
+        match format {
+            ReportFormat::Markdown => Ok(self.to_markdown()),
+            ReportFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+        }
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut out = format!("# Tasks {} to {}\n", self.from, self.to);
+        if self.lists.is_empty() {
+            out.push_str("\nNothing happened.\n");
+        }
+
+        for (title, list) in &self.lists {
+            let _ = write!(out, "\n## {title}\n");
+            for (heading, items) in [
+                ("Created", &list.created),
+                ("Completed", &list.completed),
+                ("Overdue", &list.overdue),
+            ] {
+                let _ = write!(out, "\n### {heading} ({})\n\n", items.len());
+                if items.is_empty() {
+                    out.push_str("None\n");
+                }
+                for item in items {
+                    match item.due {
+                        Some(due) => {
+                            let _ = writeln!(out, "- {} (due {due})", item.name);
+                        }
+                        None => {
+                            let _ = writeln!(out, "- {}", item.name);
+                        }
+                    }
+                }
+            }
+        }
+
+        out
+    }
+}
+
+/// Report on the `period` ending with the day `to`, showing google's titles for the lists
+pub async fn generate(
+    gtasks_mgr: &impl GoogleTasksApi,
+    state: &State,
+    archive_path: Option<&str>,
+    period: ReportPeriod,
+    to: Date,
+) -> Result<Report> {
+    let titles = gtasks_mgr
+        .get_lists()
+        .await?
+        .into_iter()
+        .filter_map(|list| Some((list.id?, list.title?)))
+        .collect();
+    let archived = match archive_path {
+        Some(path) => archive::load(path)?,
+        None => Vec::new(),
+    };
+
+    Ok(build(state, &archived, &titles, period, to))
+}
+
+/// Write a report on the last full period into the report dir once that period has passed
+pub async fn write_scheduled(
+    gtasks_mgr: &impl GoogleTasksApi,
+    state: &mut State,
+    config: &ReportConfig,
+    archive_path: Option<&str>,
+) -> Result<()> {
+    let Some(period) = config.every else {
+        return Ok(());
+    };
+    let today = date::today();
+    if state
+        .last_report
+        .is_some_and(|last| today < last + period.days().days())
+    {
+        return Ok(());
+    }
+
+    let yesterday = today - 1.day();
+    let report = generate(gtasks_mgr, state, archive_path, period, yesterday).await?;
+    let extension = match config.format {
+        ReportFormat::Markdown => "md",
+        ReportFormat::Json => "json",
+    };
+    std::fs::create_dir_all(&config.dir)
+        .with_context(|| format!("failed to create {}", config.dir))?;
+    let path = format!("{}/report-{yesterday}.{extension}", config.dir);
+    std::fs::write(&path, report.render(config.format)?)
+        .with_context(|| format!("failed to write {path}"))?;
+
+    info!(
+        "Wrote the report for {} to {yesterday} to {path}",
+        report.from
+    );
+    state.last_report = Some(today);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::civil::date;
+
+    use super::*;
+    use crate::{
+        archive::{AsanaSide, Entry, GoogleSide},
+        mock::asana_task,
+    };
+
+    fn at(day: Date) -> Timestamp {
+        day.at(12, 0, 0, 0)
+            .to_zoned(crate::date::local_tz().clone())
+            .unwrap()
+            .timestamp()
+    }
+
+    #[test]
+    fn week_report_groups_by_list() {
+        let to = date(2026, 10, 16);
+        let mut state = State::default();
+        let fresh = asana_task("100", "Write report", date(2026, 10, 20));
+        let late = asana_task("101", "Pay rent", date(2026, 10, 14));
+        state.link(&fresh, "work", "g100", None);
+        state.link(&late, "home", "g101", None);
+        // linked long before the week started
+        state.links.get_mut("101").unwrap().created = Some(at(date(2026, 9, 1)));
+        state.links.get_mut("100").unwrap().created = Some(at(date(2026, 10, 12)));
+
+        let done = |day, list: &str| Entry {
+            at: at(day),
+            completed: true,
+            reason: "completed in google".to_string(),
+            asana: Some(AsanaSide {
+                gid: "102".to_string(),
+                name: "Book flights".to_string(),
+                due_on: None,
+                completed_at: None,
+            }),
+            google: GoogleSide {
+                list: list.to_string(),
+                id: "g102".to_string(),
+                title: "Book flights".to_string(),
+                notes: None,
+                due: None,
+                completed: None,
+            },
+        };
+        let archived = [
+            done(date(2026, 10, 15), "work"),
+            done(date(2026, 10, 1), "work"),
+        ];
+        let titles = BTreeMap::from([("work".to_string(), "Work".to_string())]);
+
+        let report = build(&state, &archived, &titles, ReportPeriod::Week, to);
+
+        assert_eq!(report.from, date(2026, 10, 10));
+        let work = &report.lists["Work"];
+        assert_eq!(work.created[0].name, "Write report");
+        assert_eq!(work.completed.len(), 1);
+        assert!(work.overdue.is_empty());
+        let home = &report.lists["home"];
+        assert!(home.created.is_empty());
+        assert_eq!(home.overdue[0].name, "Pay rent");
+
+        let markdown = report.render(ReportFormat::Markdown).unwrap();
+        assert!(
+            markdown.contains("## Work\n\n### Created (1)\n\n- Write report (due 2026-10-20)\n")
+        );
+    }
+}
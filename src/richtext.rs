@@ -0,0 +1,168 @@
+/// Readable plain text from asana's `html_notes`. Lists become `-` or numbered lines, links
+/// become `text (url)` and any other markup is dropped.
+pub fn to_text(html: &str) -> String {
+    let mut out = String::new();
+    // open lists, with the next number for ordered ones
+    let mut lists: Vec<Option<usize>> = Vec::new();
+    // target of the open link and where its text starts in `out`
+    let mut link: Option<(Option<String>, usize)> = None;
+
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        push_text(&mut out, &rest[..start]);
+        let Some(len) = rest[start..].find('>') else {
+            rest = &rest[start..];
+            break;
+        };
+        let tag = &rest[start + 1..start + len];
+        rest = &rest[start + len + 1..];
+
+        let (closing, tag) = match tag.strip_prefix('/') {
+            Some(tag) => (true, tag),
+            None => (false, tag),
+        };
+        let name_end = tag
+            .find(|c: char| c.is_whitespace() || c == '/')
+            .unwrap_or(tag.len());
+        let (name, attrs) = tag.split_at(name_end);
+        let self_closing = attrs.trim_end().ends_with('/');
+
+        match (closing, name.to_ascii_lowercase().as_str()) {
+            (false, "ul") => lists.push(None),
+            (false, "ol") => lists.push(Some(1)),
+            (true, "ul" | "ol") => {
+                lists.pop();
+                end_line(&mut out);
+            }
+            (false, "li") => {
+                end_line(&mut out);
+                out.push_str(&"  ".repeat(lists.len().saturating_sub(1)));
+                match lists.last_mut() {
+                    Some(Some(n)) => {
+                        out.push_str(&format!("{n}. "));
+                        *n += 1;
+                    }
+                    _ => out.push_str("- "),
+                }
+            }
+            (false, "br") => out.push('\n'),
+            (_, "h1" | "h2" | "h3" | "blockquote" | "pre" | "hr") => end_line(&mut out),
+            (false, "a") if !self_closing => link = Some((attr(attrs, "href"), out.len())),
+            (true, "a") => {
+                if let Some((Some(href), text_start)) = link.take() {
+                    let text = &out[text_start..];
+                    if text.is_empty() {
+                        out.push_str(&href);
+                    } else if text != href && !text.starts_with('@') {
+                        // mentions are left as the person's name
+                        out.push_str(&format!(" ({href})"));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    push_text(&mut out, rest);
+
+    out.trim().to_string()
+}
+
+/// Start a new line unless already at the start of one
+fn end_line(out: &mut String) {
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+fn push_text(out: &mut String, text: &str) {
+    // a line break right after a block that already ended the line
+    let mut rest = match text.strip_prefix('\n') {
+        Some(text) if out.ends_with('\n') => text,
+        _ => text,
+    };
+
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+
+        let decoded = rest
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| Some((entity(&rest[1..end])?, end)));
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+}
+
+fn entity(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        _ => {
+            let code = name.strip_prefix('#')?;
+            let code = match code.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => code.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+/// Value of a double quoted attribute
+fn attr(attrs: &str, name: &str) -> Option<String> {
+    let start = attrs.find(&format!("{name}=\""))? + name.len() + 2;
+    let len = attrs[start..].find('"')?;
+
+    let mut value = String::new();
+    push_text(&mut value, &attrs[start..start + len]);
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_text_passes_through() {
+        assert_eq!(
+            to_text("<body>Call Bob &amp; ask about Q3</body>"),
+            "Call Bob & ask about Q3"
+        );
+    }
+
+    #[test]
+    fn lists_and_links_stay_readable() {
+        let html = "<body>Steps:\n<ul><li><strong>Book</strong> the room</li><li>Send \
+                    <a href=\"https://example.com/a?b=1&amp;c=2\">the invite</a></li></ul>\
+                    Then:<ol><li>one</li><li>two<ul><li>nested</li></ul></li></ol></body>";
+
+        assert_eq!(
+            to_text(html),
+            "Steps:\n- Book the room\n- Send the invite (https://example.com/a?b=1&c=2)\nThen:\n\
+             1. one\n2. two\n  - nested"
+        );
+    }
+
+    #[test]
+    fn bare_links_and_mentions_are_not_repeated() {
+        let html = "<body>See <a href=\"https://example.com\">https://example.com</a>, \
+                    ask <a href=\"https://app.asana.com/0/1/list\" data-asana-type=\"user\">@Jane</a>\
+                    <a data-asana-gid=\"1\"/></body>";
+
+        assert_eq!(to_text(html), "See https://example.com, ask @Jane");
+    }
+}
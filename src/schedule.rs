@@ -0,0 +1,166 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{Context, Error, Result, bail};
+use jiff::civil::{DateTime, Time, Weekday};
+use serde::Deserialize;
+
+/// A stretch of the day cycles run in, like `06:00-23:00` or `mon-fri 09:00-17:00`. One whose end
+/// comes before its start runs past midnight, one that ends when it starts lasts all day.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Window {
+    /// First and last weekday, every day when unset
+    days: Option<(Weekday, Weekday)>,
+    start: Time,
+    end: Time,
+}
+
+const WEEKDAYS: [(&str, Weekday); 7] = [
+    ("mon", Weekday::Monday),
+    ("tue", Weekday::Tuesday),
+    ("wed", Weekday::Wednesday),
+    ("thu", Weekday::Thursday),
+    ("fri", Weekday::Friday),
+    ("sat", Weekday::Saturday),
+    ("sun", Weekday::Sunday),
+];
+
+fn parse_weekday(name: &str) -> Result<Weekday> {
+    WEEKDAYS
+        .iter()
+        .find(|(short, _)| name.eq_ignore_ascii_case(short))
+        .map(|(_, day)| *day)
+        .with_context(|| format!("unknown weekday \"{name}\", use mon, tue, ..., sun"))
+}
+
+fn weekday_name(day: Weekday) -> &'static str {
+    WEEKDAYS.iter().find(|(_, d)| *d == day).unwrap().0
+}
+
+impl FromStr for Window {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (days, hours) = match s.trim().split_once(' ') {
+            Some((days, hours)) => (Some(days), hours.trim()),
+            None => (None, s.trim()),
+        };
+        let days = days
+            .map(|days| match days.split_once('-') {
+                Some((first, last)) => Ok((parse_weekday(first)?, parse_weekday(last)?)),
+                None => parse_weekday(days).map(|day| (day, day)),
+            })
+            .transpose()?;
+
+        let Some((start, end)) = hours.split_once('-') else {
+            bail!("active hours \"{s}\" aren't like \"06:00-23:00\"");
+        };
+        let time = |t: &str| {
+            t.trim()
+                .parse::<Time>()
+                .with_context(|| format!("invalid time \"{t}\" in active hours \"{s}\""))
+        };
+        Ok(Self {
+            days,
+            start: time(start)?,
+            end: time(end)?,
+        })
+    }
+}
+
+impl TryFrom<String> for Window {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Window {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.days {
+            Some((first, last)) if first == last => write!(f, "{} ", weekday_name(first))?,
+            Some((first, last)) => write!(f, "{}-{} ", weekday_name(first), weekday_name(last))?,
+            None => {}
+        }
+        write!(
+            f,
+            "{}-{}",
+            self.start.strftime("%H:%M"),
+            self.end.strftime("%H:%M")
+        )
+    }
+}
+
+impl Window {
+    /// Whether the window runs on `day`, ranges like `fri-mon` going round the week
+    fn on(&self, day: Weekday) -> bool {
+        let Some((first, last)) = self.days else {
+            return true;
+        };
+        let offset = |d: Weekday| d.to_monday_zero_offset();
+        if offset(first) <= offset(last) {
+            (offset(first)..=offset(last)).contains(&offset(day))
+        } else {
+            offset(day) >= offset(first) || offset(day) <= offset(last)
+        }
+    }
+
+    fn contains(&self, at: DateTime) -> bool {
+        let (day, time) = (at.weekday(), at.time());
+        if self.start < self.end {
+            self.on(day) && (self.start..self.end).contains(&time)
+        } else if self.start > self.end {
+            // the part after midnight belongs to the day the window started on
+            (self.on(day) && time >= self.start) || (self.on(day.previous()) && time < self.end)
+        } else {
+            self.on(day)
+        }
+    }
+}
+
+/// Whether cycles run at the local time `at`, always when there are no windows
+pub fn is_active(windows: &[Window], at: DateTime) -> bool {
+    windows.is_empty() || windows.iter().any(|w| w.contains(at))
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::civil::date;
+
+    use super::*;
+
+    fn windows(specs: &[&str]) -> Vec<Window> {
+        specs.iter().map(|s| s.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn cycles_run_only_inside_the_windows() {
+        // 2026-10-16 is a friday
+        let at = |day: i8, hour: i8| date(2026, 10, day).at(hour, 30, 0, 0);
+        let focus = windows(&["06:00-13:00", "15:00-23:00"]);
+        assert!(is_active(&focus, at(16, 6)));
+        assert!(!is_active(&focus, at(16, 14)));
+        assert!(!is_active(&focus, at(16, 23)));
+        assert!(is_active(&[], at(16, 3)));
+
+        let nights = windows(&["fri-sat 22:00-02:00"]);
+        assert!(is_active(&nights, at(16, 22)));
+        // saturday morning, after friday night
+        assert!(is_active(&nights, at(17, 1)));
+        // monday morning, sunday night isn't one of them
+        assert!(!is_active(&nights, at(19, 1)));
+
+        let weekend = windows(&["sat-sun 00:00-00:00"]);
+        assert!(!is_active(&weekend, at(16, 12)));
+        assert!(is_active(&weekend, at(18, 12)));
+    }
+
+    #[test]
+    fn windows_are_written_back_as_parsed() {
+        let window: Window = "Mon-Fri 09:00-17:30".parse().unwrap();
+        assert_eq!(window.to_string(), "mon-fri 09:00-17:30");
+        assert!("06:00".parse::<Window>().is_err());
+        assert!("weekdays 06:00-07:00".parse::<Window>().is_err());
+    }
+}
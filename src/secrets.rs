@@ -0,0 +1,132 @@
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Deserializer};
+
+/// Where a secret is read from
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
+pub enum SecretSource {
+    /// The secret itself
+    Value(String),
+    /// An environment variable
+    Env(String),
+    /// A file, e.g. a docker or systemd credential
+    File(String),
+    /// The output of a command, run without a shell, e.g. `["pass", "show", "asana"]`
+    Command(Vec<String>),
+    /// A field of a KV v2 secret, read with `VAULT_ADDR` and `VAULT_TOKEN`
+    #[cfg(feature = "vault")]
+    Vault {
+        /// Path below the mount, e.g. `bridge/asana`
+        path: String,
+        field: String,
+        #[serde(default = "default_vault_mount")]
+        mount: String,
+    },
+}
+
+#[cfg(feature = "vault")]
+fn default_vault_mount() -> String {
+    "secret".to_string()
+}
+
+impl SecretSource {
+    /// Fetch the secret, with surrounding whitespace trimmed
+    pub async fn resolve(&self) -> Result<String> {
+        let secret = match self {
+            SecretSource::Value(value) => value.clone(),
+            SecretSource::Env(name) => {
+                std::env::var(name).with_context(|| format!("secret env var {name} not set"))?
+            }
+            SecretSource::File(path) => std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read secret file {path}"))?,
+            SecretSource::Command(argv) => run_command(argv)?,
+            #[cfg(feature = "vault")]
+            SecretSource::Vault { path, field, mount } => read_vault(mount, path, field).await?,
+        };
+
+        Ok(secret.trim().to_string())
+    }
+}
+
+fn run_command(argv: &[String]) -> Result<String> {
+    let Some((program, args)) = argv.split_first() else {
+        bail!("secret command is empty");
+    };
+
+    let output = std::process::Command::new(program)
+        .args(args)
+        .output()
+        .with_context(|| format!("failed to run secret command {program}"))?;
+    if !output.status.success() {
+        bail!(
+            "secret command {program} failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    String::from_utf8(output.stdout).context("secret command printed invalid utf-8")
+}
+
+#[cfg(feature = "vault")]
+async fn read_vault(mount: &str, path: &str, field: &str) -> Result<String> {
+    let addr = std::env::var("VAULT_ADDR").context("VAULT_ADDR env var missing")?;
+    let token = std::env::var("VAULT_TOKEN").context("VAULT_TOKEN env var missing")?;
+
+    let url = format!("{}/v1/{mount}/data/{path}", addr.trim_end_matches('/'));
+    let resp = crate::http::client(reqwest::header::HeaderMap::new())?
+        .get(&url)
+        .header("X-Vault-Token", token)
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        bail!(
+            "Failed to read vault secret {mount}/{path}. Status: {}",
+            resp.status()
+        );
+    }
+
+    let body: serde_json::Value = resp.json().await?;
+    body["data"]["data"][field]
+        .as_str()
+        .map(str::to_string)
+        .with_context(|| format!("vault secret {mount}/{path} has no field {field}"))
+}
+
+/// Config form of a secret: a source table, or a bare string whose meaning depends on the field
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SecretConfig {
+    Bare(String),
+    Source(SecretSource),
+}
+
+/// Deserialize a secret where a bare string is the secret itself
+pub fn bare_value<'de, D: Deserializer<'de>>(d: D) -> Result<Option<SecretSource>, D::Error> {
+    Ok(
+        Option::<SecretConfig>::deserialize(d)?.map(|secret| match secret {
+            SecretConfig::Bare(value) => SecretSource::Value(value),
+            SecretConfig::Source(source) => source,
+        }),
+    )
+}
+
+/// Deserialize a secret where a bare string is the path of a file holding it
+pub fn bare_file<'de, D: Deserializer<'de>>(d: D) -> Result<SecretSource, D::Error> {
+    Ok(match SecretConfig::deserialize(d)? {
+        SecretConfig::Bare(path) => SecretSource::File(path),
+        SecretConfig::Source(source) => source,
+    })
+}
+
+/// [`bare_file`] for secrets that are optional
+pub fn optional_bare_file<'de, D: Deserializer<'de>>(
+    d: D,
+) -> Result<Option<SecretSource>, D::Error> {
+    Ok(
+        Option::<SecretConfig>::deserialize(d)?.map(|secret| match secret {
+            SecretConfig::Bare(path) => SecretSource::File(path),
+            SecretConfig::Source(source) => source,
+        }),
+    )
+}
@@ -0,0 +1,223 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+use anyhow::Result;
+use log::{info, warn};
+
+use crate::{
+    asana::{self, AsanaApi},
+    google::{self, GoogleTasksApi},
+    state::State,
+    sync::{self, FailedActions, SyncOptions},
+};
+
+/// Title of the google list mirroring an asana section
+fn section_list_title(base_title: &str, section: &str) -> String {
+    format!("{base_title} / {section}")
+}
+
+/// Sync `asana_tasks` with one google list per asana section of `sections`, creating lists for new
+/// sections as they appear. Tasks without a section stay in the base list.
+pub async fn process_routed<G: GoogleTasksApi>(
+    asana_mgr: &impl AsanaApi,
+    asana_tasks: &asana::TaskResult,
+    sections: &[asana::Section],
+    gtasks_mgr: &G,
+    base_title: &str,
+    state: &mut State,
+    options: &SyncOptions,
+) -> Result<()> {
+    // a task or a list with failed actions doesn't hold up the others
+    let mut failures = FailedActions::default();
+    let lists = gtasks_mgr.get_lists().await?;
+
+    let mut list_ids: HashMap<String, String> = HashMap::new();
+    for list in &lists {
+        if let (Some(title), Some(id)) = (&list.title, &list.id) {
+            list_ids.insert(title.clone(), id.clone());
+        }
+    }
+
+    let mut routes: Vec<(Option<&str>, G)> =
+        vec![(None, gtasks_mgr.for_list(gtasks_mgr.list_id()))];
+    for section in sections {
+        let title = section_list_title(base_title, &section.name);
+        let list_id = match list_ids.get(&title) {
+            Some(id) => id.clone(),
+            None if options.dry_run => {
+                info!(
+                    "[dry run] Asana section \"{}\" has no google list, would create \"{title}\"",
+                    section.name
+                );
+                continue;
+            }
+            None => {
+                info!(
+                    "Asana section \"{}\" has no google list, creating \"{title}\"",
+                    section.name
+                );
+                gtasks_mgr.create_list(&title).await?
+            }
+        };
+        routes.push((Some(section.name.as_str()), gtasks_mgr.for_list(&list_id)));
+    }
+
+    for title in stale_lists(&lists, sections, base_title) {
+        warn!(
+            "Google list \"{title}\" no longer has a matching asana section, run `retire-lists` to remove it"
+        );
+    }
+
+    let source = asana_mgr.source();
+    let diff = &options.policies.diff;
+    for (section, route_mgr) in &routes {
+        let routed = asana::TaskResult {
+            incomplete: asana_tasks
+                .incomplete
+                .iter()
+                .filter(|t| diff.route(source, t) == *section)
+                .cloned()
+                .collect(),
+            complete: asana_tasks
+                .complete
+                .iter()
+                .filter(|t| diff.route(source, t) == *section)
+                .cloned()
+                .collect(),
+        };
+
+        let routed_elsewhere: HashSet<&str> = asana_tasks
+            .incomplete
+            .iter()
+            .filter(|t| diff.route(source, t) != *section)
+            .map(|t| t.gid.as_str())
+            .collect();
+        remove_moved_tasks(route_mgr, &routed_elsewhere, state, options).await?;
+
+        failures.absorb(sync::sync_tasks(asana_mgr, &routed, route_mgr, state, options).await)?;
+    }
+
+    failures.into_result()
+}
+
+/// Drop google copies of tasks whose asana section changed, they get recreated in the new list.
+/// Held to the same deletion limits as the rest of the cycle.
+async fn remove_moved_tasks(
+    gtasks_mgr: &impl GoogleTasksApi,
+    routed_elsewhere: &HashSet<&str>,
+    state: &State,
+    options: &SyncOptions,
+) -> Result<()> {
+    let google_tasks = gtasks_mgr.get_tasks().await?;
+    let correlator = options.policies.correlator.as_ref();
+
+    let moved: Vec<(&str, &str)> = google_tasks
+        .incomplete
+        .iter()
+        .filter(|gtask| {
+            sync::linked_asana_gid(gtask, state, correlator)
+                .is_some_and(|gid| routed_elsewhere.contains(gid.as_str()))
+        })
+        .filter_map(|gtask| {
+            Some((
+                gtask.id.as_deref()?,
+                gtask.title.as_deref().unwrap_or_default(),
+            ))
+        })
+        .collect();
+    options
+        .limits
+        .check(moved.len(), google_tasks.incomplete.len())?;
+
+    for (id, title) in moved {
+        info!("Asana -> Google task \"{title}\" changed section, removing from old list");
+        if !options.dry_run {
+            gtasks_mgr.del_task(id).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Section lists whose asana section has been removed
+fn stale_lists(
+    lists: &[google::TaskList],
+    sections: &[asana::Section],
+    base_title: &str,
+) -> Vec<String> {
+    let prefix = section_list_title(base_title, "");
+    let current: HashSet<String> = sections
+        .iter()
+        .map(|s| section_list_title(base_title, &s.name))
+        .collect();
+
+    lists
+        .iter()
+        .filter_map(|l| l.title.clone())
+        .filter(|t| t.starts_with(&prefix) && !current.contains(t))
+        .collect()
+}
+
+/// Interactively delete google lists left behind by removed asana sections
+pub async fn retire_lists(
+    asana_mgr: &impl AsanaApi,
+    gtasks_mgr: &impl GoogleTasksApi,
+    base_title: &str,
+) -> Result<()> {
+    let sections = asana_mgr.get_sections().await?;
+    let lists = gtasks_mgr.get_lists().await?;
+    let stale = stale_lists(&lists, &sections, base_title);
+
+    if stale.is_empty() {
+        println!("No stale section lists found");
+        return Ok(());
+    }
+
+    // a list without a title or an id can't be asked about or deleted
+    let retiring = lists
+        .iter()
+        .filter_map(|l| Some((l.title.as_ref()?, l.id.as_ref()?)))
+        .filter(|(title, _)| stale.contains(title));
+    for (title, id) in retiring {
+        print!("Delete google list \"{title}\" and all of its tasks? [y/N] ");
+        std::io::stdout().flush()?;
+
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if answer.trim().eq_ignore_ascii_case("y") {
+            info!("Retiring google list \"{title}\"");
+            gtasks_mgr.delete_list(id).await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::civil::date;
+
+    use super::*;
+    use crate::mock::{asana_task, sync_options, synced};
+
+    #[tokio::test]
+    async fn moving_many_tasks_to_other_sections_is_refused() {
+        let tasks: Vec<_> = (100..106)
+            .map(|gid| asana_task(&gid.to_string(), &format!("Task {gid}"), date(2026, 10, 20)))
+            .collect();
+        let (_, google, state) = synced(tasks).await;
+        let routed_elsewhere: HashSet<&str> = ["100", "101", "102", "103", "104"].into();
+
+        let mut options = sync_options();
+        remove_moved_tasks(&google, &routed_elsewhere, &state, &options)
+            .await
+            .unwrap_err();
+        assert_eq!(google.tasks().len(), 6);
+
+        options.limits.force = true;
+        remove_moved_tasks(&google, &routed_elsewhere, &state, &options)
+            .await
+            .unwrap();
+        assert_eq!(google.tasks().len(), 1);
+    }
+}
@@ -0,0 +1,114 @@
+use anyhow::Result;
+use log::{info, warn};
+
+use crate::{
+    asana::{self, AsanaApi, Section, TaskSource},
+    config::WritebackConfig,
+    date,
+    state::State,
+};
+
+/// Keep "My Tasks" triage in line with due dates: tasks pushed past this week move to the later
+/// section, and come back to the today section once they are due
+pub async fn write_back_sections(
+    asana_mgr: &impl AsanaApi,
+    asana_tasks: &asana::TaskResult,
+    state: &State,
+    writeback: &WritebackConfig,
+    dry_run: bool,
+) -> Result<()> {
+    let Some((later, today_section)) = triage_sections(asana_mgr, writeback).await? else {
+        return Ok(());
+    };
+    let source = asana_mgr.source();
+
+    let today = date::today();
+    let end_of_week = date::end_of_week(today);
+
+    for atask in &asana_tasks.incomplete {
+        let Some(due) = atask.due_date() else {
+            continue;
+        };
+        let in_later = source.section_of(atask) == Some(later.name.as_str());
+        let previous_due = state.links.get(&atask.gid).and_then(|l| l.due);
+
+        if !in_later && previous_due.is_some_and(|prev| prev != due) && due > end_of_week {
+            info!(
+                "Task \"{}\" snoozed to {due}, moving to \"{}\" in asana",
+                atask.name, later.name
+            );
+            if !dry_run {
+                asana_mgr
+                    .add_task_to_section(&atask.gid, &later.gid)
+                    .await?;
+            }
+        } else if in_later && due <= today {
+            info!(
+                "Task \"{}\" is due, moving to \"{}\" in asana",
+                atask.name, today_section.name
+            );
+            if !dry_run {
+                asana_mgr
+                    .add_task_to_section(&atask.gid, &today_section.gid)
+                    .await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Move tasks whose due date was just changed in google, and written to asana, to the later
+/// section when that's past this week. [`write_back_sections`] can't tell these apart on the next
+/// cycle, their links are rewritten with the new due date along with asana.
+pub async fn write_back_snoozed(
+    asana_mgr: &impl AsanaApi,
+    snoozed: &[asana::Task],
+    writeback: &WritebackConfig,
+) -> Result<()> {
+    if snoozed.is_empty() {
+        return Ok(());
+    }
+    let Some((later, _)) = triage_sections(asana_mgr, writeback).await? else {
+        return Ok(());
+    };
+    let source = asana_mgr.source();
+    let end_of_week = date::end_of_week(date::today());
+    for task in snoozed {
+        let in_later = source.section_of(task) == Some(later.name.as_str());
+        if !in_later && task.due_date().is_some_and(|due| due > end_of_week) {
+            info!(
+                "Task \"{}\" snoozed in google, moving to \"{}\" in asana",
+                task.name, later.name
+            );
+            asana_mgr.add_task_to_section(&task.gid, &later.gid).await?;
+        }
+    }
+    Ok(())
+}
+
+/// The later and today sections of "My Tasks", `None` when write-back is off or they're missing
+async fn triage_sections(
+    asana_mgr: &impl AsanaApi,
+    writeback: &WritebackConfig,
+) -> Result<Option<(Section, Section)>> {
+    if !writeback.enabled || !matches!(asana_mgr.source(), TaskSource::UserTaskList(_)) {
+        return Ok(None);
+    }
+    let later_name = &writeback.later;
+    let today_name = &writeback.today;
+    let sections = asana_mgr.get_sections().await?;
+    let find = |name: &str| {
+        sections
+            .iter()
+            .find(|s| s.name.eq_ignore_ascii_case(name))
+            .cloned()
+    };
+    let (Some(later), Some(today)) = (find(later_name), find(today_name)) else {
+        warn!(
+            "My Tasks has no \"{later_name}\" or \"{today_name}\" section, skipping section write-back"
+        );
+        return Ok(None);
+    };
+    Ok(Some((later, today)))
+}
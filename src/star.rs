@@ -0,0 +1,68 @@
+use std::sync::OnceLock;
+
+static STAR_MARKER: OnceLock<String> = OnceLock::new();
+
+/// Mark google copies of liked asana tasks with `marker` in front of their title. Only the first
+/// call has any effect.
+pub fn set_star_marker(marker: &str) {
+    let _ = STAR_MARKER.set(marker.to_string());
+}
+
+fn star_marker() -> Option<&'static str> {
+    STAR_MARKER.get().map(String::as_str)
+}
+
+/// Whether stars are mirrored at all
+pub fn enabled() -> bool {
+    star_marker().is_some()
+}
+
+/// `title` with the star marker in front when `liked`
+pub fn add(title: String, liked: bool) -> String {
+    add_with(title, liked, star_marker())
+}
+
+/// Whether `title` starts with the star marker, and the title without it
+pub fn split(title: &str) -> (bool, &str) {
+    split_with(title, star_marker())
+}
+
+fn add_with(title: String, liked: bool, marker: Option<&str>) -> String {
+    match marker {
+        Some(marker) if liked => format!("{marker} {title}"),
+        _ => title,
+    }
+}
+
+fn split_with<'a>(title: &'a str, marker: Option<&str>) -> (bool, &'a str) {
+    match marker.and_then(|marker| title.strip_prefix(marker)) {
+        // the space may be gone if the marker was typed in google
+        Some(rest) => (true, rest.strip_prefix(' ').unwrap_or(rest)),
+        None => (false, title),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marker_round_trips() {
+        let title = add_with("Pay rent".to_string(), true, Some("⭐"));
+        assert_eq!(title, "⭐ Pay rent");
+        assert_eq!(split_with(&title, Some("⭐")), (true, "Pay rent"));
+        assert_eq!(split_with("⭐Pay rent", Some("⭐")), (true, "Pay rent"));
+
+        assert_eq!(
+            add_with("Pay rent".to_string(), false, Some("⭐")),
+            "Pay rent"
+        );
+        assert_eq!(split_with("Pay rent", Some("⭐")), (false, "Pay rent"));
+    }
+
+    #[test]
+    fn nothing_changes_without_a_marker() {
+        assert_eq!(add_with("Pay rent".to_string(), true, None), "Pay rent");
+        assert_eq!(split_with("⭐ Pay rent", None), (false, "⭐ Pay rent"));
+    }
+}
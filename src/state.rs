@@ -0,0 +1,235 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{Context, Result};
+use jiff::{Timestamp, civil};
+use serde::{Deserialize, Serialize};
+
+use crate::{asana, crypt};
+
+/// What the bridge remembers between cycles
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct State {
+    /// Links keyed by asana task gid
+    #[serde(default)]
+    pub links: BTreeMap<String, Link>,
+    /// Calendar events of timed tasks, keyed by asana task gid
+    #[serde(default)]
+    pub events: BTreeMap<String, EventLink>,
+    /// Last day a scheduled report was written
+    #[serde(default)]
+    pub last_report: Option<civil::Date>,
+    /// Google copies about to be inserted, keyed by asana task gid, until they're linked. An
+    /// insert whose outcome is unknown may have landed, a copy matching its intent is linked
+    /// instead of inserted again.
+    #[serde(default)]
+    pub create_intents: BTreeMap<String, CreateIntent>,
+    /// When asana's tasks were fetched by the last cycle that succeeded
+    #[serde(default)]
+    pub last_fetched: Option<Timestamp>,
+    /// Cycles failed in a row, up to the last one
+    #[serde(default)]
+    pub failed_cycles: u32,
+    /// Actions that failed since they last succeeded, keyed by
+    /// [`SyncAction::key`](crate::sync::SyncAction::key)
+    #[serde(default)]
+    pub failures: BTreeMap<String, ActionFailure>,
+    /// What the bridge remembers about each of `bridge.mirrors`, keyed by the backend's name.
+    /// Each mirror keeps links of its own, keyed by the same asana gids as the main ones.
+    #[serde(default)]
+    pub mirrors: BTreeMap<String, State>,
+    /// Asana gids keyed by the google id they're linked to, the reverse of `links`
+    #[serde(skip)]
+    google_ids: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActionFailure {
+    /// What the action was doing, as logged
+    pub action: String,
+    /// Error chain of the last failure
+    pub error: String,
+    pub count: u32,
+    pub first_failed: Timestamp,
+    pub last_failed: Timestamp,
+    /// Left out of cycles until retried by hand
+    #[serde(default)]
+    pub parked: bool,
+}
+
+/// A google copy the bridge set out to insert, as it would be written
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CreateIntent {
+    pub list: String,
+    pub title: String,
+    pub notes: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Link {
+    pub asana_name: String,
+    pub google_list: String,
+    pub google_id: String,
+    pub last_synced: Timestamp,
+    /// Asana gid of the parent task for subtasks nested in google
+    #[serde(default)]
+    pub parent: Option<String>,
+    /// Due date when the link was last written
+    #[serde(default)]
+    pub due: Option<civil::Date>,
+    /// Gid of the asana user the task was assigned to when the link was last written
+    #[serde(default)]
+    pub assignee: Option<String>,
+    /// [`content_hash`](crate::sync::content_hash) of the google copy as last written, `None`
+    /// when the bridge didn't write its content
+    #[serde(default)]
+    pub hash: Option<String>,
+    /// When the asana task was first linked, `None` for links older than this field
+    #[serde(default)]
+    pub created: Option<Timestamp>,
+    /// The user moved the google copy into `google_list`, where it's left, see
+    /// [`MisplacedPolicy::Follow`](crate::misplaced::MisplacedPolicy::Follow)
+    #[serde(default)]
+    pub moved: bool,
+}
+
+/// What a calendar event was last written with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventLink {
+    pub event_id: String,
+    pub title: String,
+    pub notes: String,
+    pub due_at: Timestamp,
+}
+
+impl State {
+    /// Load the state file, starting empty if it doesn't exist yet
+    /// Load the state file, decrypting it when it's [encrypted](crypt)
+    pub fn load(path: &str) -> Result<Self> {
+        let mut state: Self = match crypt::read(path)? {
+            Some(contents) => serde_json::from_slice(&contents)
+                .with_context(|| format!("failed to parse {path}"))?,
+            None => Self::default(),
+        };
+        state.reindex();
+        Ok(state)
+    }
+
+    /// Rebuild the reverse index of `links`, which isn't saved
+    fn reindex(&mut self) {
+        self.google_ids = self
+            .links
+            .iter()
+            .map(|(gid, link)| (link.google_id.clone(), gid.clone()))
+            .collect();
+        for mirror in self.mirrors.values_mut() {
+            mirror.reindex();
+        }
+    }
+
+    /// Replace the state file, encrypted when a key is configured
+    pub fn save(&self, path: &str) -> Result<()> {
+        crypt::write(path, &serde_json::to_vec(self)?)
+    }
+
+    /// Record that an asana task was just written to a google task
+    pub fn link(
+        &mut self,
+        atask: &asana::Task,
+        google_list: &str,
+        google_id: &str,
+        hash: Option<String>,
+    ) {
+        let now = Timestamp::now();
+        self.create_intents.remove(&atask.gid);
+        // relinking the same task, e.g. to a recreated google copy, keeps when it was first linked
+        let created = self
+            .links
+            .get(&atask.gid)
+            .map_or(Some(now), |link| link.created);
+        self.set_google_id(&atask.gid, google_id);
+        self.links.insert(
+            atask.gid.clone(),
+            Link {
+                asana_name: atask.name.clone(),
+                google_list: google_list.to_string(),
+                google_id: google_id.to_string(),
+                last_synced: now,
+                parent: atask.parent_gid.clone(),
+                due: atask.due_date(),
+                assignee: atask.assignee.as_ref().map(|a| a.gid.clone()),
+                hash,
+                created,
+                moved: false,
+            },
+        );
+    }
+
+    /// Asana gids of linked tasks nested under `parent_gid`
+    pub fn children_of(&self, parent_gid: &str) -> Vec<String> {
+        self.links
+            .iter()
+            .filter(|(_, link)| link.parent.as_deref() == Some(parent_gid))
+            .map(|(gid, _)| gid.clone())
+            .collect()
+    }
+
+    pub fn unlink(&mut self, asana_gid: &str) -> Option<Link> {
+        let link = self.links.remove(asana_gid)?;
+        if self.google_ids.get(&link.google_id).map(String::as_str) == Some(asana_gid) {
+            self.google_ids.remove(&link.google_id);
+        }
+        Some(link)
+    }
+
+    /// Point the link of `asana_gid` at another google copy, keeping the reverse index in step
+    pub fn set_google_id(&mut self, asana_gid: &str, google_id: &str) {
+        if let Some(link) = self.links.get_mut(asana_gid) {
+            if self.google_ids.get(&link.google_id).map(String::as_str) == Some(asana_gid) {
+                self.google_ids.remove(&link.google_id);
+            }
+            link.google_id = google_id.to_string();
+        }
+        self.google_ids
+            .insert(google_id.to_string(), asana_gid.to_string());
+    }
+
+    /// Count another failure of the action with `key`, parking it once it failed `park_after`
+    /// times
+    pub fn record_failure(
+        &mut self,
+        key: &str,
+        action: &str,
+        error: &str,
+        park_after: u32,
+    ) -> &ActionFailure {
+        let now = Timestamp::now();
+        let failure = self
+            .failures
+            .entry(key.to_string())
+            .or_insert_with(|| ActionFailure {
+                action: String::new(),
+                error: String::new(),
+                count: 0,
+                first_failed: now,
+                last_failed: now,
+                parked: false,
+            });
+        failure.action = action.to_string();
+        failure.error = error.to_string();
+        failure.count += 1;
+        failure.last_failed = now;
+        failure.parked = park_after > 0 && failure.count >= park_after;
+        failure
+    }
+
+    pub fn asana_gid_for_google(&self, google_id: &str) -> Option<&str> {
+        self.google_ids
+            .get(google_id)
+            .filter(|gid| {
+                self.links
+                    .get(gid.as_str())
+                    .is_some_and(|link| link.google_id == google_id)
+            })
+            .map(String::as_str)
+    }
+}
@@ -0,0 +1,235 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+};
+
+use anyhow::Result;
+use jiff::Timestamp;
+
+use crate::{
+    asana::{self, AsanaApi},
+    google::{self, GoogleTasksApi},
+    state::{Link, State},
+    sync,
+};
+
+/// What differs between a link and the live tasks on either side
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Pending {
+    Nothing,
+    CompletedInGoogle,
+    CompletedInAsana,
+    MissingFromAsana,
+    MissingFromGoogle,
+    FieldsDiffer,
+}
+
+impl fmt::Display for Pending {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pending::Nothing => write!(f, "-"),
+            Pending::CompletedInGoogle => write!(f, "completed in google"),
+            Pending::CompletedInAsana => write!(f, "completed in asana"),
+            Pending::MissingFromAsana => write!(f, "missing from asana"),
+            Pending::MissingFromGoogle => write!(f, "missing from google"),
+            Pending::FieldsDiffer => write!(f, "fields differ"),
+        }
+    }
+}
+
+/// Live tasks from both sides, covering every google list the state store links into
+struct LiveTasks {
+    asana: asana::TaskResult,
+    google: HashMap<String, google::Task>,
+}
+
+impl LiveTasks {
+    async fn fetch(
+        asana_mgr: &impl AsanaApi,
+        gtasks_mgr: &impl GoogleTasksApi,
+        state: &State,
+    ) -> Result<Self> {
+        let asana = asana_mgr.get_tasks().await?;
+
+        let mut google = HashMap::new();
+        for list_id in state
+            .links
+            .values()
+            .map(|l| l.google_list.as_str())
+            .collect::<HashSet<_>>()
+        {
+            let list_tasks = gtasks_mgr.for_list(list_id).get_tasks().await?;
+            for task in list_tasks.incomplete.into_iter().chain(list_tasks.complete) {
+                if let Some(id) = task.id.clone() {
+                    google.insert(id, task);
+                }
+            }
+        }
+
+        Ok(Self { asana, google })
+    }
+
+    fn pending(&self, gid: &str, link: &Link, duplicate_names: &HashSet<&str>) -> Pending {
+        let incomplete = self.asana.incomplete.iter().find(|t| t.gid == gid);
+        let complete = self.asana.complete.iter().any(|t| t.gid == gid);
+
+        match (incomplete, self.google.get(&link.google_id)) {
+            (_, Some(gtask)) if gtask.completed.is_some() => Pending::CompletedInGoogle,
+            (None, _) if complete => Pending::CompletedInAsana,
+            (None, _) => Pending::MissingFromAsana,
+            (Some(_), None) => Pending::MissingFromGoogle,
+            (Some(atask), Some(gtask)) => {
+                let fields = sync::google_fields(atask, duplicate_names);
+                if sync::asana_google_same(atask, &fields, gtask) {
+                    Pending::Nothing
+                } else {
+                    Pending::FieldsDiffer
+                }
+            }
+        }
+    }
+}
+
+/// A link in the state store and what the next cycle is to fix about it
+#[derive(Debug, Clone)]
+pub struct LinkStatus {
+    pub gid: String,
+    pub asana_name: String,
+    pub google_id: String,
+    pub last_synced: Timestamp,
+    /// What differs from the live tasks, `None` when nothing does
+    pub pending: Option<String>,
+}
+
+/// Every link in the state store with whatever differs from the live data, and the names of the
+/// asana tasks that don't have a copy yet
+pub async fn live_status(
+    asana_mgr: &impl AsanaApi,
+    gtasks_mgr: &impl GoogleTasksApi,
+    state: &State,
+) -> Result<(Vec<LinkStatus>, Vec<String>)> {
+    let live = LiveTasks::fetch(asana_mgr, gtasks_mgr, state).await?;
+    let duplicate_names = sync::duplicate_names(&live.asana.incomplete);
+
+    let links = state
+        .links
+        .iter()
+        .map(|(gid, link)| {
+            let pending = live.pending(gid, link, &duplicate_names);
+            LinkStatus {
+                gid: gid.clone(),
+                asana_name: link.asana_name.clone(),
+                google_id: link.google_id.clone(),
+                last_synced: link.last_synced,
+                pending: (pending != Pending::Nothing).then(|| pending.to_string()),
+            }
+        })
+        .collect();
+    let new = live
+        .asana
+        .incomplete
+        .iter()
+        .filter(|t| !state.links.contains_key(&t.gid))
+        .map(|t| t.name.clone())
+        .collect();
+
+    Ok((links, new))
+}
+
+/// Print every link in the state store along with whatever differs from the live data
+pub async fn print_status(
+    asana_mgr: &impl AsanaApi,
+    gtasks_mgr: &impl GoogleTasksApi,
+    state: &State,
+) -> Result<()> {
+    if state.links.is_empty() {
+        println!("No linked tasks");
+        return Ok(());
+    }
+
+    let (links, _) = live_status(asana_mgr, gtasks_mgr, state).await?;
+
+    println!(
+        "{:<20} {:<40} {:<30} {:<25} PENDING",
+        "ASANA GID", "ASANA NAME", "GOOGLE ID", "LAST SYNCED"
+    );
+    for link in &links {
+        println!(
+            "{:<20} {:<40} {:<30} {:<25} {}",
+            link.gid,
+            truncate(&link.asana_name, 40),
+            link.google_id,
+            link.last_synced
+                .strftime("%Y-%m-%d %H:%M:%S UTC")
+                .to_string(),
+            link.pending.as_deref().unwrap_or("-"),
+        );
+    }
+
+    Ok(())
+}
+
+/// Compare the state store with live data, summarising what the first cycle after downtime is about
+/// to fix. `None` when there is nothing to report.
+pub async fn reconciliation_report(
+    asana_mgr: &impl AsanaApi,
+    gtasks_mgr: &impl GoogleTasksApi,
+    state: &State,
+) -> Result<Option<String>> {
+    if state.links.is_empty() {
+        return Ok(None);
+    }
+
+    let live = LiveTasks::fetch(asana_mgr, gtasks_mgr, state).await?;
+    let duplicate_names = sync::duplicate_names(&live.asana.incomplete);
+
+    let mut by_pending: BTreeMap<Pending, Vec<&str>> = BTreeMap::new();
+    for (gid, link) in &state.links {
+        let pending = live.pending(gid, link, &duplicate_names);
+        if pending != Pending::Nothing {
+            by_pending
+                .entry(pending)
+                .or_default()
+                .push(&link.asana_name);
+        }
+    }
+    let new: Vec<&str> = live
+        .asana
+        .incomplete
+        .iter()
+        .filter(|t| !state.links.contains_key(&t.gid))
+        .map(|t| t.name.as_str())
+        .collect();
+
+    if by_pending.is_empty() && new.is_empty() {
+        return Ok(None);
+    }
+
+    let mut report = String::from("Startup reconciliation against the state file:");
+    if !new.is_empty() {
+        report.push_str(&format!(
+            "\n  {} new in asana: {}",
+            new.len(),
+            new.join(", ")
+        ));
+    }
+    for (pending, names) in by_pending {
+        report.push_str(&format!(
+            "\n  {} {pending}: {}",
+            names.len(),
+            names.join(", ")
+        ));
+    }
+
+    Ok(Some(report))
+}
+
+fn truncate(s: &str, width: usize) -> String {
+    if s.chars().count() <= width {
+        s.to_string()
+    } else {
+        let mut truncated: String = s.chars().take(width - 1).collect();
+        truncated.push('…');
+        truncated
+    }
+}
@@ -0,0 +1,214 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use jiff::Timestamp;
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::logging;
+
+/// Longest wait before restarting a failed loop, however often it failed
+const MAX_BACKOFF: Duration = Duration::from_secs(3600);
+
+/// Where [`STATUSES`] is written after every change, unset outside multi-user mode
+static STATUS_PATH: OnceLock<String> = OnceLock::new();
+/// How each user's loop is doing, by user name
+static STATUSES: Mutex<BTreeMap<String, LoopStatus>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoopState {
+    #[default]
+    Running,
+    /// Failed, and waiting to be restarted
+    Retrying,
+    /// Failed more often in a row than its error budget allows, and given up on
+    Stopped,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LoopStatus {
+    pub state: LoopState,
+    /// Times the loop failed since the last cycle that went through
+    pub failures: u32,
+    pub last_ok: Option<Timestamp>,
+    pub last_error: Option<String>,
+}
+
+/// Write every user's status to `path` from now on. Only the first call has any effect.
+pub fn set_status_path(path: &str) {
+    let _ = STATUS_PATH.set(path.to_string());
+}
+
+/// The status of every user's loop in this process
+pub fn statuses() -> BTreeMap<String, LoopStatus> {
+    STATUSES.lock().unwrap().clone()
+}
+
+/// Record a cycle of the current user's loop: one that went through, or one where only some
+/// actions failed and the loop goes on
+pub fn record_cycle(result: &crate::error::Result<()>) {
+    let Ok(name) = logging::USER.try_with(String::clone) else {
+        return;
+    };
+    update(&name, |status| {
+        status.state = LoopState::Running;
+        match result {
+            Ok(()) => {
+                status.failures = 0;
+                status.last_ok = Some(Timestamp::now());
+            }
+            Err(e) => status.last_error = Some(format!("{e:#}")),
+        }
+    });
+}
+
+/// Keep running `user`'s loop, restarting it after a growing multiple of `interval` when it fails,
+/// until it ends on its own or fails `error_budget` times in a row (never with 0). Returns the last
+/// error when the loop was given up on, so the other users' loops can go on without it.
+pub async fn supervise<F, Fut>(
+    user: &str,
+    interval: Duration,
+    error_budget: u32,
+    mut run: F,
+) -> Option<anyhow::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    update(user, |status| status.state = LoopState::Running);
+    loop {
+        let Err(e) = run().await else {
+            return None;
+        };
+
+        let mut failures = 0;
+        update(user, |status| {
+            status.failures += 1;
+            status.last_error = Some(format!("{e:#}"));
+            failures = status.failures;
+            status.state = if error_budget > 0 && status.failures >= error_budget {
+                LoopState::Stopped
+            } else {
+                LoopState::Retrying
+            };
+        });
+        if error_budget > 0 && failures >= error_budget {
+            error!("Sync for user {user} failed {failures} times in a row, giving up on it: {e:#}");
+            return Some(e);
+        }
+
+        let backoff = interval.saturating_mul(failures).min(MAX_BACKOFF);
+        warn!(
+            "Sync for user {user} failed, restarting in {}s: {e:#}",
+            backoff.as_secs()
+        );
+        tokio::time::sleep(backoff).await;
+        update(user, |status| status.state = LoopState::Running);
+    }
+}
+
+fn update(user: &str, change: impl FnOnce(&mut LoopStatus)) {
+    let mut statuses = STATUSES.lock().unwrap();
+    change(statuses.entry(user.to_string()).or_default());
+
+    if let Some(path) = STATUS_PATH.get()
+        && let Err(e) = write(path, &statuses)
+    {
+        warn!("Failed to write the users' status: {e:#}");
+    }
+}
+
+/// Same write then rename as the state file, so `users` never reads a partial file
+fn write(path: &str, statuses: &BTreeMap<String, LoopStatus>) -> Result<()> {
+    let tmp_path = format!("{path}.tmp");
+    let json = serde_json::to_string_pretty(statuses)?;
+    std::fs::write(&tmp_path, json).with_context(|| format!("failed to write {tmp_path}"))?;
+    std::fs::rename(&tmp_path, path).with_context(|| format!("failed to replace {path}"))?;
+    Ok(())
+}
+
+/// Print how each user's loop is doing, as last written to `path` by the running bridge
+pub fn print_statuses(path: &str) -> Result<()> {
+    let json = match std::fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            println!("No users' status at {path}, is the bridge running with several users?");
+            return Ok(());
+        }
+        Err(e) => return Err(e).with_context(|| format!("failed to read {path}")),
+    };
+    let statuses: BTreeMap<String, LoopStatus> =
+        serde_json::from_str(&json).with_context(|| format!("failed to parse {path}"))?;
+
+    println!(
+        "{:<20} {:<10} {:<9} {:<25} LAST ERROR",
+        "USER", "STATE", "FAILURES", "LAST OK"
+    );
+    for (user, status) in &statuses {
+        let state = match status.state {
+            LoopState::Running => "running",
+            LoopState::Retrying => "retrying",
+            LoopState::Stopped => "stopped",
+        };
+        println!(
+            "{:<20} {:<10} {:<9} {:<25} {}",
+            user,
+            state,
+            status.failures,
+            status
+                .last_ok
+                .map(|ts| ts.strftime("%Y-%m-%d %H:%M:%S UTC").to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            status.last_error.as_deref().unwrap_or("-"),
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn a_failing_loop_is_given_up_on_after_its_budget() {
+        let mut runs = 0;
+        let error = supervise("failing", Duration::from_millis(1), 3, || {
+            runs += 1;
+            async { Err(anyhow::anyhow!("403 Forbidden")) }
+        })
+        .await;
+
+        assert_eq!(runs, 3);
+        assert!(error.is_some());
+        let status = &statuses()["failing"];
+        assert_eq!(status.state, LoopState::Stopped);
+        assert_eq!(status.failures, 3);
+        assert_eq!(status.last_error.as_deref(), Some("403 Forbidden"));
+    }
+
+    #[tokio::test]
+    async fn a_loop_is_restarted_until_it_ends() {
+        let mut runs = 0;
+        let error = supervise("flaky", Duration::from_millis(1), 3, || {
+            runs += 1;
+            let fail = runs < 3;
+            async move {
+                if fail {
+                    anyhow::bail!("connection reset");
+                }
+                Ok(())
+            }
+        })
+        .await;
+
+        assert_eq!(runs, 3);
+        assert!(error.is_none());
+        assert_eq!(statuses()["flaky"].state, LoopState::Running);
+    }
+}
@@ -0,0 +1,3225 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+    sync::Arc,
+};
+
+use anyhow::Result;
+use jiff::{Timestamp, ToSpan, civil};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    archive,
+    asana::{self, AsanaApi},
+    attachments, catchup, comments,
+    config::{FieldsConfig, SyncConfig, WritebackConfig},
+    correlate::Correlator,
+    error::{self, BridgeError, Disposition},
+    fetched::Fetched,
+    filter::TaskFilter,
+    google::{self, GoogleTasksApi},
+    guardrail::DeletionLimits,
+    hierarchy::{self, CascadePolicy, FlattenPolicy},
+    integrity,
+    journal::{Journal, MirrorOf},
+    misplaced::{self, MisplacedPolicy},
+    notify::{self, NotifyEvent},
+    order::{self, TaskOrder},
+    policy::{DefaultPolicy, DiffPolicy},
+    redact, snooze, star,
+    state::{CreateIntent, State},
+    watchdog,
+};
+
+/// What to do with an asana task whose linked google copy was deleted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GoogleDeletePolicy {
+    /// Create a fresh google copy
+    Recreate,
+    /// Complete the asana task
+    Complete,
+}
+
+/// What to do with the google copy of an asana task that was deleted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AsanaDeletePolicy {
+    Delete,
+    /// Keep the google task, flagged and unlinked
+    Flag,
+}
+
+/// Which way edits to a field of a task are carried over
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldSync {
+    /// Edits on either side are carried over, asana wins when both changed
+    Both,
+    /// Asana edits overwrite the google copy, google edits are undone
+    ToGoogle,
+    /// Google edits are written to asana, asana edits don't reach google
+    ToAsana,
+}
+
+impl FieldSync {
+    pub fn to_google(self) -> bool {
+        self != FieldSync::ToAsana
+    }
+
+    pub fn to_asana(self) -> bool {
+        self != FieldSync::ToGoogle
+    }
+}
+
+/// Policies steering what the planner does
+#[derive(Debug, Clone)]
+pub struct Policies {
+    /// How subtasks are synced, `None` when they aren't
+    pub flatten: Option<FlattenPolicy>,
+    pub cascade: CascadePolicy,
+    pub on_google_delete: GoogleDeletePolicy,
+    pub on_asana_delete: AsanaDeletePolicy,
+    /// Only sync tasks assigned to the token's user, for shared projects
+    pub only_mine: bool,
+    /// Filters, comparisons and conflict rules, see [`DiffPolicy`]
+    pub diff: Arc<dyn DiffPolicy>,
+    /// How google tasks are tied to their asana tasks
+    pub correlator: Arc<dyn Correlator>,
+    /// Latest asana comments shown in the google notes, none when 0
+    pub comments: usize,
+    /// Post text added to google notes as asana comments before the notes are overwritten
+    pub notes_as_comments: bool,
+    /// List each task's attachments in the google notes
+    pub attachments: bool,
+    /// What happens to copies the user moved to another list
+    pub misplaced: MisplacedPolicy,
+    /// Which way each field syncs
+    pub fields: FieldsConfig,
+    /// Delete completed google tasks that aren't linked to an asana task, instead of leaving them
+    pub delete_unlinked_completed: bool,
+    /// Hide completed google tasks with one call once the cycle is through, instead of deleting
+    /// them one by one
+    pub clear_completed: bool,
+    /// Tasks with a due time are mirrored as calendar events. They get no google copy, and a copy
+    /// made before they had a time is removed.
+    pub timed_to_calendar: bool,
+}
+
+impl Policies {
+    pub fn from_config(config: &SyncConfig) -> Self {
+        Self {
+            flatten: config.subtasks,
+            cascade: config.completion_cascade,
+            on_google_delete: config.on_google_delete,
+            on_asana_delete: config.on_asana_delete,
+            only_mine: config.only_mine,
+            diff: if config.filter.is_empty() {
+                Arc::new(DefaultPolicy)
+            } else {
+                Arc::new(
+                    TaskFilter::new(&config.filter).expect("filters are checked with the config"),
+                )
+            },
+            correlator: config.correlation.correlator(),
+            comments: config.comments,
+            notes_as_comments: config.notes_as_comments,
+            attachments: config.attachments,
+            misplaced: config.misplaced_tasks,
+            fields: config.fields,
+            delete_unlinked_completed: config.delete_unlinked_completed,
+            clear_completed: config.clear_completed,
+            // the calendar is configured apart from the sync, the engine turns this on
+            timed_to_calendar: false,
+        }
+    }
+}
+
+/// Everything that shapes a sync cycle besides the data itself
+#[derive(Debug, Clone)]
+pub struct SyncOptions {
+    pub policies: Policies,
+    pub limits: DeletionLimits,
+    pub writeback: WritebackConfig,
+    /// Prometheus textfile for integrity gauges
+    pub metrics_path: Option<String>,
+    /// Record google tasks here before deleting them
+    pub archive_path: Option<String>,
+    /// Log the planned actions instead of executing them
+    pub dry_run: bool,
+    /// Move google tasks into this order after syncing
+    pub order: TaskOrder,
+    /// Park actions after this many failures, never when 0
+    pub park_after: u32,
+    /// State file to save after every action, with a journal next to it for finishing an action
+    /// that was cut short. Nothing is journaled without it.
+    pub state_path: Option<String>,
+    /// Furthest back, in days, that completed asana tasks are fetched from after an outage
+    pub max_catch_up_days: u32,
+    /// Look up every linked asana task the fetch left out, catching up on completions up to this
+    /// many days old. 0 for regular cycles, only the first cycle after startup does this.
+    pub catch_up_days: u32,
+    /// Send up to this many google inserts and deletes at once, one at a time when 1
+    pub batch_size: usize,
+    /// Complete up to this many asana tasks in one request, one at a time when 1
+    pub asana_batch_size: usize,
+    /// Comments fetched by earlier cycles
+    pub comments_fetched: Arc<Fetched<asana::Comment>>,
+    /// Attachments fetched by earlier cycles
+    pub attachments_fetched: Arc<Fetched<asana::Attachment>>,
+    /// Set while syncing one of `bridge.mirrors`, whose state the journal saves into the rest
+    pub mirror: Option<Arc<MirrorOf>>,
+}
+
+/// Kept off the last fetch, for tasks completed while it was running
+const FETCH_OVERLAP_MINUTES: i64 = 10;
+
+/// Since when completed asana tasks are fetched: the last successful cycle's fetch, so
+/// completions made while the bridge was down or failing aren't missed, but no further back than
+/// `max_days`. The first cycle looks back a day.
+fn completed_since(state: &State, now: Timestamp, max_days: u32) -> Timestamp {
+    let Some(last_fetched) = state.last_fetched else {
+        return now - 24.hours();
+    };
+    let since = last_fetched - FETCH_OVERLAP_MINUTES.minutes();
+    since.max(now - (i64::from(max_days) * 24).hours())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeleteReason {
+    CompletedInGoogle,
+    CompletedInAsana,
+    DeletedInAsana,
+    Reassigned,
+    FilteredOut,
+    ParentCompleted,
+    /// Moved back from the list the user dragged it into
+    Misplaced,
+    /// Given a due time, it's a calendar event now
+    Timed,
+}
+
+impl fmt::Display for DeleteReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeleteReason::CompletedInGoogle => write!(f, "completed in google"),
+            DeleteReason::CompletedInAsana => write!(f, "completed in asana"),
+            DeleteReason::DeletedInAsana => write!(f, "deleted in asana"),
+            DeleteReason::Reassigned => write!(f, "assigned to someone else"),
+            DeleteReason::FilteredOut => write!(f, "no longer matches the filter"),
+            DeleteReason::ParentCompleted => write!(f, "parent completed"),
+            DeleteReason::Misplaced => write!(f, "moved to another list"),
+            DeleteReason::Timed => write!(f, "given a due time, on the calendar instead"),
+        }
+    }
+}
+
+/// One change the bridge makes to either side or to its state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SyncAction {
+    CreateGoogle {
+        task: asana::Task,
+        fields: GoogleFields,
+    },
+    /// Replace a google task whose fields drifted from asana, or update it in place where the
+    /// backend can
+    RecreateGoogle {
+        google_id: String,
+        /// Etag of the google task when it was compared, it's left alone if it changed since
+        #[serde(default)]
+        etag: Option<String>,
+        task: asana::Task,
+        fields: GoogleFields,
+    },
+    /// Record a link that exists in google but not yet in the state store, or refresh its hash
+    LinkGoogle {
+        task: asana::Task,
+        google_id: String,
+        hash: String,
+    },
+    CompleteAsana {
+        gid: String,
+        name: String,
+        /// When the asana task was last edited as planned against, it's left alone if that changed
+        #[serde(default)]
+        modified_at: Option<Timestamp>,
+    },
+    /// Post text added to the notes in google as a comment, instead of losing it to the next
+    /// update from asana
+    CommentAsana {
+        gid: String,
+        name: String,
+        text: String,
+    },
+    /// Carry fields edited in google over to asana, `task` already has them
+    UpdateAsana {
+        task: asana::Task,
+        /// New name, when it was edited
+        #[serde(default)]
+        name: Option<String>,
+        /// New notes, when they were edited
+        #[serde(default)]
+        notes: Option<String>,
+        /// New due date, when it was moved
+        due: Option<civil::Date>,
+        /// Whether the task is now starred, when that changed
+        liked: Option<bool>,
+        google_id: String,
+        fields: GoogleFields,
+    },
+    DeleteGoogle {
+        list: String,
+        google_id: String,
+        title: String,
+        reason: DeleteReason,
+    },
+    /// Mark a google task whose asana task was deleted and stop syncing it
+    FlagGoogle {
+        task: google::Task,
+    },
+    /// Turn a subtask into a top level task on both sides
+    DetachSubtask {
+        gid: String,
+        name: String,
+        list: String,
+        google_id: String,
+    },
+    Unlink {
+        gid: String,
+    },
+}
+
+impl SyncAction {
+    /// Asana gid the action is about, if any
+    pub fn asana_gid(&self) -> Option<&str> {
+        match self {
+            SyncAction::CreateGoogle { task, .. }
+            | SyncAction::RecreateGoogle { task, .. }
+            | SyncAction::LinkGoogle { task, .. }
+            | SyncAction::UpdateAsana { task, .. } => Some(&task.gid),
+            SyncAction::CompleteAsana { gid, .. }
+            | SyncAction::CommentAsana { gid, .. }
+            | SyncAction::DetachSubtask { gid, .. }
+            | SyncAction::Unlink { gid } => Some(gid),
+            SyncAction::DeleteGoogle { .. } | SyncAction::FlagGoogle { .. } => None,
+        }
+    }
+
+    /// Identifies the action across cycles, for remembering its failures
+    pub fn key(&self) -> String {
+        let kind = match self {
+            SyncAction::CreateGoogle { .. } => "create-google",
+            SyncAction::RecreateGoogle { .. } => "recreate-google",
+            SyncAction::LinkGoogle { .. } => "link-google",
+            SyncAction::CompleteAsana { .. } => "complete-asana",
+            SyncAction::CommentAsana { .. } => "comment-asana",
+            SyncAction::UpdateAsana { .. } => "update-asana",
+            SyncAction::DeleteGoogle { .. } => "delete-google",
+            SyncAction::FlagGoogle { .. } => "flag-google",
+            SyncAction::DetachSubtask { .. } => "detach-subtask",
+            SyncAction::Unlink { .. } => "unlink",
+        };
+        let id = self.asana_gid().or(self.google_id()).unwrap_or_default();
+        format!("{kind}:{id}")
+    }
+
+    /// Google task id the action is about, if any
+    pub fn google_id(&self) -> Option<&str> {
+        match self {
+            SyncAction::RecreateGoogle { google_id, .. }
+            | SyncAction::LinkGoogle { google_id, .. }
+            | SyncAction::UpdateAsana { google_id, .. }
+            | SyncAction::DeleteGoogle { google_id, .. }
+            | SyncAction::DetachSubtask { google_id, .. } => Some(google_id),
+            SyncAction::FlagGoogle { task } => task.id.as_deref(),
+            SyncAction::CreateGoogle { .. }
+            | SyncAction::CompleteAsana { .. }
+            | SyncAction::CommentAsana { .. }
+            | SyncAction::Unlink { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for SyncAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncAction::CreateGoogle { task, .. } => {
+                write!(
+                    f,
+                    "Asana -> Google new task \"{}\", creating in google",
+                    task.name
+                )
+            }
+            SyncAction::RecreateGoogle { task, .. } => write!(
+                f,
+                "Asana -> Google task mismatch, updating google task (Asana: \"{}\")",
+                task.name
+            ),
+            SyncAction::LinkGoogle {
+                task, google_id, ..
+            } => {
+                write!(
+                    f,
+                    "Linking asana task \"{}\" to google task {google_id}",
+                    task.name
+                )
+            }
+            SyncAction::CompleteAsana { name, .. } => {
+                write!(
+                    f,
+                    "Google -> Asana task \"{name}\" complete, completing in asana"
+                )
+            }
+            SyncAction::CommentAsana { name, .. } => {
+                write!(
+                    f,
+                    "Google -> Asana task \"{name}\" notes added to, commenting in asana"
+                )
+            }
+            SyncAction::UpdateAsana {
+                task,
+                name,
+                notes,
+                due,
+                liked,
+                ..
+            } => {
+                write!(f, "Google -> Asana task \"{}\"", task.name)?;
+                if name.is_some() {
+                    write!(f, " renamed,")?;
+                }
+                if notes.is_some() {
+                    write!(f, " notes edited,")?;
+                }
+                if let Some(due) = due {
+                    write!(f, " moved to {due},")?;
+                }
+                match liked {
+                    Some(true) => write!(f, " starred,")?,
+                    Some(false) => write!(f, " unstarred,")?,
+                    None => {}
+                }
+                write!(f, " updating asana")
+            }
+            SyncAction::DeleteGoogle { title, reason, .. } => {
+                write!(f, "Deleting task \"{title}\" from google ({reason})")
+            }
+            SyncAction::FlagGoogle { task } => write!(
+                f,
+                "Asana -> Google task \"{}\" deleted, flagging in google",
+                task.title.as_deref().unwrap_or_default()
+            ),
+            SyncAction::DetachSubtask { name, .. } => {
+                write!(f, "Promoting subtask \"{name}\" to a top level task")
+            }
+            SyncAction::Unlink { gid } => write!(f, "Unlinking asana task {gid}"),
+        }
+    }
+}
+
+/// Fetch the asana tasks a cycle works on, subtasks included, along with when they were fetched
+/// for [`State::last_fetched`]
+pub async fn fetch_asana_tasks(
+    asana_mgr: &impl AsanaApi,
+    state: &State,
+    options: &SyncOptions,
+) -> Result<(Timestamp, asana::TaskResult)> {
+    let fetched_at = Timestamp::now();
+    watchdog::phase("fetching asana tasks");
+    let mut asana_tasks = asana_mgr
+        .get_tasks_since(completed_since(
+            state,
+            fetched_at,
+            options.max_catch_up_days,
+        ))
+        .await?;
+    if let Some(policy) = options.policies.flatten {
+        watchdog::phase("fetching asana subtasks");
+        hierarchy::expand_subtasks(asana_mgr, &mut asana_tasks, policy).await?;
+    }
+
+    if options.catch_up_days > 0 {
+        watchdog::phase("looking up linked asana tasks to catch up on");
+        let lookback = fetched_at - (i64::from(options.catch_up_days) * 24).hours();
+        let names = catchup::completed_links(asana_mgr, &mut asana_tasks, state, lookback).await?;
+        if names.is_empty() {
+            info!("Catch-up: no linked task was completed in asana beyond the regular window");
+        } else {
+            info!(
+                "Catch-up: {} linked tasks were completed in asana while the bridge was away, completing their google copies: {}",
+                names.len(),
+                names.join(", ")
+            );
+        }
+    }
+
+    Ok((fetched_at, asana_tasks))
+}
+
+/// Sync the asana tasks into one endpoint, fetching them first
+pub async fn process_tasks(
+    asana_mgr: &impl AsanaApi,
+    gtasks_mgr: &impl GoogleTasksApi,
+    state: &mut State,
+    options: &SyncOptions,
+) -> Result<()> {
+    let mut failures = FailedActions::default();
+    let (fetched_at, asana_tasks) =
+        prepare_asana_tasks(asana_mgr, state, options, &mut failures).await?;
+    failures.absorb(sync_endpoint(asana_mgr, asana_tasks, gtasks_mgr, state, options).await)?;
+    failures.into_result()?;
+    state.last_fetched = Some(fetched_at);
+    Ok(())
+}
+
+/// The asana tasks a cycle syncs into every endpoint, fetched once: with the sections written
+/// back, other people's tasks dropped and comments and attachments attached. Tasks whose comments
+/// or attachments couldn't be fetched are added to `failures`.
+pub async fn prepare_asana_tasks(
+    asana_mgr: &impl AsanaApi,
+    state: &State,
+    options: &SyncOptions,
+    failures: &mut FailedActions,
+) -> Result<(Timestamp, asana::TaskResult)> {
+    let (fetched_at, mut asana_tasks) = fetch_asana_tasks(asana_mgr, state, options).await?;
+    snooze::write_back_sections(
+        asana_mgr,
+        &asana_tasks,
+        state,
+        &options.writeback,
+        options.dry_run,
+    )
+    .await?;
+    drop_others_tasks(asana_mgr, &mut asana_tasks, options.policies.only_mine).await?;
+    comments::attach(
+        asana_mgr,
+        &mut asana_tasks,
+        options.policies.comments,
+        &options.comments_fetched,
+        failures,
+    )
+    .await;
+    if options.policies.attachments {
+        attachments::attach(
+            asana_mgr,
+            &mut asana_tasks,
+            &options.attachments_fetched,
+            failures,
+        )
+        .await;
+    }
+    Ok((fetched_at, asana_tasks))
+}
+
+/// Bring one endpoint in line with `asana_tasks`, as [`prepare_asana_tasks`] gives them
+pub async fn sync_endpoint(
+    asana_mgr: &impl AsanaApi,
+    mut asana_tasks: asana::TaskResult,
+    gtasks_mgr: &impl GoogleTasksApi,
+    state: &mut State,
+    options: &SyncOptions,
+) -> Result<()> {
+    let mut failures = FailedActions::default();
+    misplaced::handle(
+        asana_mgr,
+        gtasks_mgr,
+        &mut asana_tasks,
+        state,
+        options,
+        &mut failures,
+    )
+    .await?;
+    failures.absorb(sync_tasks(asana_mgr, &asana_tasks, gtasks_mgr, state, options).await)?;
+    failures.into_result()
+}
+
+pub async fn sync_tasks(
+    asana_mgr: &impl AsanaApi,
+    asana_tasks: &asana::TaskResult,
+    gtasks_mgr: &impl GoogleTasksApi,
+    state: &mut State,
+    options: &SyncOptions,
+) -> Result<()> {
+    watchdog::phase("fetching google tasks");
+    let google_tasks = gtasks_mgr.get_tasks().await?;
+    integrity::record(
+        gtasks_mgr.list_id(),
+        integrity::check(
+            gtasks_mgr.list_id(),
+            &google_tasks,
+            state,
+            options.policies.correlator.as_ref(),
+        ),
+        options.metrics_path.as_deref(),
+    )?;
+
+    let vanished = find_vanished_asana_tasks(
+        asana_mgr,
+        asana_tasks,
+        &google_tasks,
+        state,
+        &options.policies,
+    )
+    .await?;
+
+    let actions = plan(
+        asana_tasks,
+        &google_tasks,
+        state,
+        &vanished,
+        gtasks_mgr.list_id(),
+        &options.policies,
+    );
+
+    // refuse to go on if the cycle would wipe out an unusual share of the google list
+    let incomplete_ids: HashSet<&str> = google_tasks
+        .incomplete
+        .iter()
+        .filter_map(|t| t.id.as_deref())
+        .collect();
+    let planned_deletions = actions
+        .iter()
+        .filter(|a| {
+            matches!(a, SyncAction::DeleteGoogle { google_id, .. } if incomplete_ids.contains(google_id.as_str()))
+        })
+        .count();
+    options
+        .limits
+        .check(planned_deletions, google_tasks.incomplete.len())?;
+
+    // completed tasks still in view, cleared once their asana tasks took the completion
+    let to_clear = options.policies.clear_completed
+        && google_tasks.complete.iter().any(|t| t.hidden != Some(true));
+
+    if options.dry_run {
+        for action in &actions {
+            info!("[dry run] {action}");
+        }
+        if to_clear {
+            info!("[dry run] Would clear the completed google tasks");
+        }
+        return Ok(());
+    }
+
+    if let Some(path) = &options.archive_path {
+        let entries = archive::entries(
+            &actions,
+            asana_tasks,
+            &google_tasks,
+            state,
+            options.policies.correlator.as_ref(),
+            Timestamp::now(),
+        );
+        archive::append(path, &entries)?;
+    }
+
+    // due dates moved in google, asana's side only shows them once they're written
+    let snoozed: Vec<asana::Task> = actions
+        .iter()
+        .filter_map(|action| match action {
+            SyncAction::UpdateAsana {
+                task, due: Some(_), ..
+            } => Some(task.clone()),
+            _ => None,
+        })
+        .collect();
+    let executed = execute(actions, asana_mgr, gtasks_mgr, state, options).await;
+    // a failed completion would be hidden before asana took it
+    if to_clear && executed.is_ok() {
+        gtasks_mgr.clear_completed().await?;
+        debug!("Cleared the completed google tasks");
+    }
+    let snoozed: Vec<asana::Task> = snoozed
+        .into_iter()
+        .filter(|task| {
+            state
+                .links
+                .get(&task.gid)
+                .is_some_and(|link| link.due == task.due_date())
+        })
+        .collect();
+    snooze::write_back_snoozed(asana_mgr, &snoozed, &options.writeback).await?;
+    let sections = if options.order == TaskOrder::Section {
+        asana_mgr.get_sections().await?
+    } else {
+        Vec::new()
+    };
+    order::reorder(
+        gtasks_mgr,
+        asana_tasks,
+        state,
+        options.order,
+        asana_mgr.source(),
+        &sections,
+    )
+    .await?;
+    executed
+}
+
+/// Linked tasks that left the asana task list without being completed
+#[derive(Debug, Default)]
+pub struct Vanished {
+    pub deleted: HashSet<String>,
+    /// Now assigned to someone else, or to nobody
+    pub reassigned: HashSet<String>,
+    /// No longer found by the search the tasks come from
+    pub unmatched: HashSet<String>,
+}
+
+/// Find out why linked tasks went missing from asana's task list
+pub async fn find_vanished_asana_tasks(
+    asana_mgr: &impl AsanaApi,
+    asana_tasks: &asana::TaskResult,
+    google_tasks: &google::GTaskResult,
+    state: &State,
+    policies: &Policies,
+) -> Result<Vanished> {
+    let seen_asana_gids: HashSet<&str> = asana_tasks
+        .incomplete
+        .iter()
+        .chain(asana_tasks.complete.iter())
+        .map(|t| t.gid.as_str())
+        .collect();
+    // "My Tasks" and assigned issues only hold the user's own tasks, so a task leaving them may
+    // have been reassigned
+    let check_assignee = policies.only_mine
+        || matches!(
+            asana_mgr.source(),
+            asana::TaskSource::UserTaskList(_) | asana::TaskSource::GitHubIssues
+        );
+
+    let mut vanished = Vanished::default();
+    for gtask in &google_tasks.incomplete {
+        if let Some(asana_task_gid) = linked_asana_gid(gtask, state, policies.correlator.as_ref())
+            && !seen_asana_gids.contains(asana_task_gid.as_str())
+            && state.links.contains_key(&asana_task_gid)
+        {
+            if !asana_mgr.task_exists(&asana_task_gid).await? {
+                vanished.deleted.insert(asana_task_gid);
+            } else if check_assignee
+                && !asana_mgr
+                    .get_task(&asana_task_gid)
+                    .await?
+                    .assigned_to(&asana_mgr.me().await?)
+            {
+                vanished.reassigned.insert(asana_task_gid);
+            } else if matches!(asana_mgr.source(), asana::TaskSource::Search { .. }) {
+                vanished.unmatched.insert(asana_task_gid);
+            }
+        }
+    }
+
+    Ok(vanished)
+}
+
+/// Leave out tasks assigned to anyone but the token's user when `only_mine` is set, their google
+/// copies are removed as reassigned
+pub async fn drop_others_tasks(
+    asana_mgr: &impl AsanaApi,
+    asana_tasks: &mut asana::TaskResult,
+    only_mine: bool,
+) -> Result<()> {
+    if only_mine {
+        let me = asana_mgr.me().await?;
+        asana_tasks.incomplete.retain(|t| t.assigned_to(&me));
+    }
+
+    Ok(())
+}
+
+/// Work out every action needed to bring google in line with asana and vice versa. Doesn't touch
+/// the network, so it can be run for dry runs and inspection.
+pub fn plan(
+    asana_tasks: &asana::TaskResult,
+    google_tasks: &google::GTaskResult,
+    state: &State,
+    vanished: &Vanished,
+    list_id: &str,
+    policies: &Policies,
+) -> Vec<SyncAction> {
+    let mut actions = Vec::new();
+
+    let correlator = policies.correlator.as_ref();
+    // google tasks without an id can't be updated or deleted, every step leaves them out
+    let google_incomplete = with_ids(&google_tasks.incomplete);
+    let google_complete = with_ids(&google_tasks.complete);
+    // keyed by asana gid, with their google ids, and compared with asana without the tag
+    let mut google_by_gid: HashMap<String, (String, google::Task)> = index_by_asana_gid(
+        google_incomplete
+            .iter()
+            .chain(google_complete.iter())
+            .map(|(_, gtask)| *gtask),
+        state,
+        correlator,
+    )
+    .into_iter()
+    .filter_map(|(gid, gtask)| Some((gid, (gtask.id.clone()?, correlator.untagged(gtask)))))
+    .collect();
+    let complete_asana_gids: HashSet<&str> = asana_tasks
+        .complete
+        .iter()
+        .map(|t| t.gid.as_str())
+        .collect();
+    let duplicate_names = duplicate_names(&asana_tasks.incomplete);
+
+    // links dropped earlier in this plan
+    let mut unlinked: HashSet<String> = HashSet::new();
+    // google ids completed in google, but recreated because the asana edit won
+    let mut reopened: HashSet<String> = HashSet::new();
+
+    // One way sync of new asana task to google task
+    for atask in &asana_tasks.incomplete {
+        let timed = policies.timed_to_calendar && atask.due_at.is_some();
+        if timed || !policies.diff.include(atask) {
+            // the task stopped matching or became an event, its copy goes unless the user
+            // already completed it
+            if let Some((google_id, google_task)) = google_by_gid.get(atask.gid.as_str())
+                && google_task.completed.is_none()
+            {
+                actions.push(SyncAction::DeleteGoogle {
+                    list: list_id.to_string(),
+                    google_id: google_id.clone(),
+                    title: google_task.title.clone().unwrap_or_default(),
+                    reason: if timed {
+                        DeleteReason::Timed
+                    } else {
+                        DeleteReason::FilteredOut
+                    },
+                });
+                actions.push(SyncAction::Unlink {
+                    gid: atask.gid.clone(),
+                });
+            }
+            continue;
+        }
+
+        let fields = policies.diff.google_fields(atask, &duplicate_names);
+        if let Some((google_id, google_task)) = google_by_gid.get(atask.gid.as_str()) {
+            let completed_in_google = google_task.completed.is_some();
+            if completed_in_google {
+                // by default completing in google wins over asana edits, the completion is
+                // handled below
+                if policies.diff.google_completion_wins(atask, google_task)
+                    || policies.diff.same(atask, &fields, google_task)
+                {
+                    continue;
+                }
+                reopened.insert(google_id.clone());
+            }
+
+            // check if it needs updating, since asana might report different names or notes
+            let recorded = state
+                .links
+                .get(&atask.gid)
+                .and_then(|link| link.hash.as_deref());
+            // only when asana is unchanged do the fields show what the bridge last wrote to google
+            if policies.notes_as_comments
+                && !completed_in_google
+                && recorded == Some(fields.hash(atask).as_str())
+                && let Some(text) = added_notes(
+                    &fields.notes,
+                    google::notes_body(google_task.notes.as_deref().unwrap_or_default()),
+                )
+            {
+                // the recreate below then puts the asana notes back
+                actions.push(SyncAction::CommentAsana {
+                    gid: atask.gid.clone(),
+                    name: atask.name.clone(),
+                    text,
+                });
+            }
+            let asana_unchanged = recorded == Some(fields.hash(atask).as_str());
+            // what google is to show, with the fields that only sync to asana as google has them
+            let target = keep_google(atask, &fields, google_task, policies.fields);
+            let target_fields = policies.diff.google_fields(&target, &duplicate_names);
+            if !completed_in_google
+                && let Some(edited) = edited_in_google(
+                    atask,
+                    &fields,
+                    google_task,
+                    asana_unchanged,
+                    policies.fields,
+                )
+            {
+                actions.push(SyncAction::UpdateAsana {
+                    name: (edited.name != atask.name).then(|| edited.name.clone()),
+                    notes: (edited.notes != atask.notes).then(|| edited.notes.clone()),
+                    due: edited
+                        .due_date()
+                        .filter(|due| atask.due_date() != Some(*due)),
+                    liked: (edited.liked != atask.liked).then_some(edited.liked),
+                    fields: policies.diff.google_fields(&edited, &duplicate_names),
+                    task: edited,
+                    google_id: google_id.clone(),
+                });
+            } else if completed_in_google
+                || !policies.diff.same(&target, &target_fields, google_task)
+            {
+                let changed_in = match recorded {
+                    Some(hash) if hash == google_hash(google_task) => "asana",
+                    Some(_) if asana_unchanged => "google",
+                    _ => "both",
+                };
+                debug!(
+                    "\"{}\" changed in {changed_in} since the last sync",
+                    atask.name
+                );
+                actions.push(SyncAction::RecreateGoogle {
+                    google_id: google_id.clone(),
+                    etag: google_task.etag.clone(),
+                    task: target,
+                    fields: target_fields,
+                });
+
+                // google drops subtasks along with their parent, so they need recreating too
+                for child_gid in state.children_of(&atask.gid) {
+                    google_by_gid.remove(&child_gid);
+                    actions.push(SyncAction::Unlink {
+                        gid: child_gid.clone(),
+                    });
+                    unlinked.insert(child_gid);
+                }
+            } else if recorded != Some(target_fields.hash(&target).as_str()) {
+                // linked before the state file existed, or by hand
+                actions.push(SyncAction::LinkGoogle {
+                    hash: target_fields.hash(&target),
+                    task: target,
+                    google_id: google_id.clone(),
+                });
+            }
+        } else if policies.on_google_delete == GoogleDeletePolicy::Complete
+            && !unlinked.contains(&atask.gid)
+            && state
+                .links
+                .get(&atask.gid)
+                .is_some_and(|link| link.google_list == list_id)
+        {
+            // the google copy was deleted by the user
+            actions.push(SyncAction::CompleteAsana {
+                gid: atask.gid.clone(),
+                name: atask.name.clone(),
+                modified_at: atask.modified_at,
+            });
+            actions.push(SyncAction::Unlink {
+                gid: atask.gid.clone(),
+            });
+        } else {
+            actions.push(SyncAction::CreateGoogle {
+                task: atask.clone(),
+                fields,
+            });
+        }
+    }
+
+    // google ids already taken care of by a cascade
+    let mut cascaded: HashSet<String> = HashSet::new();
+
+    // remove google completed tasks from asana
+    for (google_id, gtask) in google_complete.iter().cloned() {
+        if cascaded.contains(&google_id) || reopened.contains(&google_id) {
+            continue;
+        }
+        // cleared by an earlier cycle, which completed its asana task. One still linked was
+        // completed between that cycle fetching the tasks and clearing them.
+        if policies.clear_completed
+            && gtask.hidden == Some(true)
+            && state.asana_gid_for_google(&google_id).is_none()
+        {
+            continue;
+        }
+
+        let linked = linked_asana_gid(gtask, state, correlator);
+        if linked.is_none() && !policies.delete_unlinked_completed {
+            // the user's own task, completed in google, isn't the bridge's to remove
+            continue;
+        }
+        if let Some(asana_task_gid) = linked {
+            actions.push(SyncAction::CompleteAsana {
+                modified_at: asana_tasks.modified_at(&asana_task_gid),
+                gid: asana_task_gid.clone(),
+                name: gtask.title.clone().unwrap_or_default(),
+            });
+            plan_cascade(
+                &mut actions,
+                &mut cascaded,
+                asana_tasks,
+                state,
+                &asana_task_gid,
+                policies.cascade,
+            );
+            actions.push(SyncAction::Unlink {
+                gid: asana_task_gid,
+            });
+        }
+
+        // remove this google task, or leave it to be cleared along with the rest
+        if !policies.clear_completed {
+            actions.push(SyncAction::DeleteGoogle {
+                list: list_id.to_string(),
+                google_id,
+                title: gtask.title.clone().unwrap_or_default(),
+                reason: DeleteReason::CompletedInGoogle,
+            });
+        }
+    }
+
+    // remove asana completed tasks from google
+    for (google_id, gtask) in google_incomplete.iter().cloned() {
+        if let Some(asana_task_gid) = linked_asana_gid(gtask, state, correlator)
+            && complete_asana_gids.contains(asana_task_gid.as_str())
+            && !cascaded.contains(&google_id)
+        {
+            plan_cascade(
+                &mut actions,
+                &mut cascaded,
+                asana_tasks,
+                state,
+                &asana_task_gid,
+                policies.cascade,
+            );
+            actions.push(SyncAction::DeleteGoogle {
+                list: list_id.to_string(),
+                google_id,
+                title: gtask.title.clone().unwrap_or_default(),
+                reason: DeleteReason::CompletedInAsana,
+            });
+            actions.push(SyncAction::Unlink {
+                gid: asana_task_gid,
+            });
+        }
+    }
+
+    // remove google copies of asana tasks that were deleted, assigned to someone else or no longer
+    // found by the search
+    for (google_id, gtask) in google_incomplete.iter().cloned() {
+        let Some(asana_task_gid) = linked_asana_gid(gtask, state, correlator) else {
+            continue;
+        };
+        if cascaded.contains(&google_id) {
+            continue;
+        }
+
+        if vanished.reassigned.contains(&asana_task_gid) {
+            actions.push(SyncAction::DeleteGoogle {
+                list: list_id.to_string(),
+                google_id,
+                title: gtask.title.clone().unwrap_or_default(),
+                reason: DeleteReason::Reassigned,
+            });
+            actions.push(SyncAction::Unlink {
+                gid: asana_task_gid,
+            });
+        } else if vanished.unmatched.contains(&asana_task_gid) {
+            actions.push(SyncAction::DeleteGoogle {
+                list: list_id.to_string(),
+                google_id,
+                title: gtask.title.clone().unwrap_or_default(),
+                reason: DeleteReason::FilteredOut,
+            });
+            actions.push(SyncAction::Unlink {
+                gid: asana_task_gid,
+            });
+        } else if vanished.deleted.contains(&asana_task_gid) {
+            if policies.on_asana_delete == AsanaDeletePolicy::Flag {
+                actions.push(SyncAction::FlagGoogle {
+                    task: gtask.clone(),
+                });
+            } else {
+                actions.push(SyncAction::DeleteGoogle {
+                    list: list_id.to_string(),
+                    google_id,
+                    title: gtask.title.clone().unwrap_or_default(),
+                    reason: DeleteReason::DeletedInAsana,
+                });
+            }
+            actions.push(SyncAction::Unlink {
+                gid: asana_task_gid,
+            });
+        }
+    }
+
+    actions
+}
+
+/// Google tasks along with their ids, leaving out any without one
+fn with_ids(tasks: &[google::Task]) -> Vec<(String, &google::Task)> {
+    tasks
+        .iter()
+        .filter_map(|t| Some((t.id.clone()?, t)))
+        .collect()
+}
+
+/// Plan what happens to the linked subtasks of a parent that just completed
+fn plan_cascade(
+    actions: &mut Vec<SyncAction>,
+    cascaded: &mut HashSet<String>,
+    asana_tasks: &asana::TaskResult,
+    state: &State,
+    parent_gid: &str,
+    policy: CascadePolicy,
+) {
+    for child_gid in state.children_of(parent_gid) {
+        let link = &state.links[&child_gid];
+
+        match policy {
+            CascadePolicy::Complete => {
+                // a subtask edited since it was read is left for the next cycle, like its parent
+                actions.push(SyncAction::CompleteAsana {
+                    gid: child_gid.clone(),
+                    name: link.asana_name.clone(),
+                    modified_at: asana_tasks.modified_at(&child_gid),
+                });
+                actions.push(SyncAction::DeleteGoogle {
+                    list: link.google_list.clone(),
+                    google_id: link.google_id.clone(),
+                    title: link.asana_name.clone(),
+                    reason: DeleteReason::ParentCompleted,
+                });
+                actions.push(SyncAction::Unlink { gid: child_gid });
+            }
+            CascadePolicy::Promote => {
+                actions.push(SyncAction::DetachSubtask {
+                    gid: child_gid,
+                    name: link.asana_name.clone(),
+                    list: link.google_list.clone(),
+                    google_id: link.google_id.clone(),
+                });
+            }
+            CascadePolicy::Leave => {
+                actions.push(SyncAction::DeleteGoogle {
+                    list: link.google_list.clone(),
+                    google_id: link.google_id.clone(),
+                    title: link.asana_name.clone(),
+                    reason: DeleteReason::ParentCompleted,
+                });
+                actions.push(SyncAction::Unlink { gid: child_gid });
+            }
+        }
+
+        cascaded.insert(link.google_id.clone());
+    }
+}
+
+/// Why a task's remaining actions are held back for the rest of a cycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Hold {
+    Failed,
+    Parked,
+}
+
+/// Both ids of every task with a failed or parked action
+#[derive(Default)]
+struct Held {
+    gids: HashMap<String, Hold>,
+    google_ids: HashMap<String, Hold>,
+}
+
+impl Held {
+    /// Why `action`, for the task with these ids, is held back, if it is
+    fn get(
+        &self,
+        action: &SyncAction,
+        gid: &Option<String>,
+        google_id: &Option<String>,
+        state: &State,
+    ) -> Option<Hold> {
+        if state.failures.get(&action.key()).is_some_and(|f| f.parked) {
+            return Some(Hold::Parked);
+        }
+        let by_gid = gid.as_ref().and_then(|gid| self.gids.get(gid));
+        let by_google_id = google_id.as_ref().and_then(|id| self.google_ids.get(id));
+        by_gid.or(by_google_id).copied()
+    }
+
+    fn insert(&mut self, gid: Option<String>, google_id: Option<String>, hold: Hold) {
+        self.gids.extend(gid.map(|gid| (gid, hold)));
+        self.google_ids.extend(google_id.map(|id| (id, hold)));
+    }
+}
+
+/// The asana gid and google id of the task `action` is for, as far as they're known
+fn task_ids(action: &SyncAction, state: &State) -> (Option<String>, Option<String>) {
+    let gid = action
+        .asana_gid()
+        .or_else(|| {
+            action
+                .google_id()
+                .and_then(|id| state.asana_gid_for_google(id))
+        })
+        .map(str::to_string);
+    let google_id = action
+        .google_id()
+        .or_else(|| {
+            gid.as_ref()
+                .and_then(|gid| state.links.get(gid))
+                .map(|l| l.google_id.as_str())
+        })
+        .map(str::to_string);
+    (gid, google_id)
+}
+
+/// Actions of a kind that are sent along with others
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Batch {
+    /// Google inserts and deletes, sent at once
+    Google,
+    /// Asana completions, sent in one request
+    Asana,
+}
+
+/// How `action` can be sent along with others, if it can. Google inserts and deletes take a
+/// single request each, except that subtasks wait for their parent's insert and inserts that may
+/// have landed before are looked up first.
+fn batch_of(action: &SyncAction, list: &str, state: &State) -> Option<Batch> {
+    match action {
+        SyncAction::CreateGoogle { task, .. }
+            if task.parent_gid.is_some()
+                || state
+                    .create_intents
+                    .get(&task.gid)
+                    .is_some_and(|intent| intent.list == list) =>
+        {
+            None
+        }
+        SyncAction::CreateGoogle { .. } | SyncAction::DeleteGoogle { .. } => Some(Batch::Google),
+        SyncAction::CompleteAsana { .. } => Some(Batch::Asana),
+        _ => None,
+    }
+}
+
+/// Apply planned actions in order, keeping the state store in step. An action that fails doesn't
+/// stop the others, only the later actions for the same task are skipped. The failures come back
+/// as [`FailedActions`] once everything else ran. Actions that failed `park_after` times are
+/// parked in the state store and left out, along with the rest of their task's actions, until
+/// they're retried by hand. With a state path each action is journaled before it runs and the
+/// state saved after it, see [`crate::journal`]. Up to `batch_size` google inserts and deletes are
+/// sent at once, see [`execute_batch`], and up to `asana_batch_size` asana completions in one
+/// request. Those run ahead of actions for other tasks, never of earlier ones for their own.
+pub async fn execute(
+    actions: Vec<SyncAction>,
+    asana_mgr: &impl AsanaApi,
+    gtasks_mgr: &impl GoogleTasksApi,
+    state: &mut State,
+    options: &SyncOptions,
+) -> Result<()> {
+    let correlator = options.policies.correlator.as_ref();
+    let mut journal = options
+        .state_path
+        .as_deref()
+        .map(|path| Journal::open(path, options.mirror.clone()))
+        .transpose()?;
+    let mut failures = FailedActions::default();
+    let mut held = Held::default();
+
+    // every request after being told to slow down would be refused too
+    let mut rate_limited = false;
+
+    let list = gtasks_mgr.list_id();
+    let mut actions = VecDeque::from(actions);
+    while let Some(action) = actions.pop_front() {
+        if rate_limited {
+            failures.skipped.push(action.to_string());
+            continue;
+        }
+        let (gid, google_id) = task_ids(&action, state);
+        match held.get(&action, &gid, &google_id, state) {
+            Some(Hold::Parked) => {
+                debug!("Leaving out: {action}, it or an earlier step for this task is parked");
+                held.insert(gid, google_id, Hold::Parked);
+                continue;
+            }
+            Some(Hold::Failed) => {
+                // e.g. deleting the google copy after completing the asana task failed
+                warn!("Skipping: {action}, an earlier step for this task failed");
+                failures.skipped.push(action.to_string());
+                continue;
+            }
+            None => {}
+        }
+
+        // later actions of the same kind are sent along with this one, pulled ahead of the
+        // actions in between as long as none of those is for the same task
+        let kind = batch_of(&action, list, state);
+        let size = match kind {
+            Some(Batch::Google) => options.batch_size,
+            Some(Batch::Asana) => options.asana_batch_size,
+            None => 1,
+        };
+        let mut claimed = vec![(gid.clone(), google_id.clone())];
+        let mut batch = vec![(action, gid, google_id)];
+        let mut i = 0;
+        while batch.len() < size && i < actions.len() {
+            let next = &actions[i];
+            let ids = task_ids(next, state);
+            let related = claimed.iter().any(|other| {
+                (ids.0.is_some() && ids.0 == other.0) || (ids.1.is_some() && ids.1 == other.1)
+            });
+            claimed.push(ids.clone());
+            if related
+                || batch_of(next, list, state) != kind
+                || held.get(next, &ids.0, &ids.1, state).is_some()
+            {
+                i += 1;
+                continue;
+            }
+            if let Some(next) = actions.remove(i) {
+                batch.push((next, ids.0, ids.1));
+            }
+        }
+
+        for (action, ..) in &batch {
+            match action {
+                SyncAction::Unlink { .. } | SyncAction::LinkGoogle { .. } => debug!("{action}"),
+                _ => {
+                    info!("{action}");
+                    notify::mutation(action);
+                }
+            }
+        }
+
+        let mut ids = Vec::new();
+        let mut batched = Vec::new();
+        for (action, gid, google_id) in batch {
+            ids.push((action.key(), action.to_string(), gid, google_id));
+            batched.push(action);
+        }
+        let results = match <[_; 1]>::try_from(batched) {
+            Ok([action]) => {
+                if let Some(journal) = &mut journal {
+                    journal.begin(list, &action)?;
+                }
+                watchdog::phase(format!("running: {action}"));
+                vec![execute_action(action, asana_mgr, gtasks_mgr, state, correlator).await]
+            }
+            Err(batched) => {
+                if let Some(journal) = &mut journal {
+                    journal.begin_batch(list, &batched)?;
+                }
+                if kind == Some(Batch::Asana) {
+                    watchdog::phase(format!("running: {} asana completions", batched.len()));
+                    complete_asana(&batched, asana_mgr).await
+                } else {
+                    watchdog::phase(format!(
+                        "running: {} google inserts and deletes",
+                        batched.len()
+                    ));
+                    execute_batch(batched, gtasks_mgr, state, correlator).await
+                }
+            }
+        };
+
+        // the first failure that stops the cycle, returned once the whole batch is settled
+        let mut halt = None;
+        let mut any_failed = false;
+        for ((key, described, gid, google_id), result) in ids.into_iter().zip(results) {
+            let Err(e) = result else {
+                state.failures.remove(&key);
+                continue;
+            };
+            any_failed = true;
+            let disposition = error::disposition(&e);
+            if disposition == Disposition::Halt {
+                halt.get_or_insert(e.context(format!("Failed: {described}, stopping the cycle")));
+                continue;
+            }
+            let error = format!("{e:#}");
+            warn!("Failed: {described}: {error}");
+            notify::push(NotifyEvent::Errors, format!("Failed: {described}: {error}"));
+            // the service failed rather than the action, it's tried again without counting
+            let failure = match disposition {
+                Disposition::Retry => None,
+                _ => Some(state.record_failure(&key, &described, &error, options.park_after)),
+            };
+            if let Some(failure) = failure
+                && failure.parked
+            {
+                let message = format!(
+                    "Parked after {} failures: {described}, see `failed list`",
+                    failure.count
+                );
+                warn!("{message}");
+                notify::push(NotifyEvent::Conflicts, message);
+            }
+            held.insert(gid, google_id, Hold::Failed);
+            failures.failed.push((described, error));
+            if !rate_limited
+                && let Some(BridgeError::RateLimited { retry_after, .. }) = BridgeError::of(&e)
+            {
+                match retry_after {
+                    Some(wait) => warn!(
+                        "Rate limited for {}s, leaving the rest for the next cycle",
+                        wait.as_secs()
+                    ),
+                    None => warn!("Rate limited, leaving the rest for the next cycle"),
+                }
+                rate_limited = true;
+            }
+        }
+
+        if let Some(journal) = &mut journal {
+            if any_failed {
+                journal.fail(state)?;
+            } else {
+                journal.commit(state)?;
+            }
+        }
+        if let Some(e) = halt {
+            return Err(e);
+        }
+    }
+
+    if let Some(journal) = journal {
+        journal.close()?;
+    }
+    failures.into_result()
+}
+
+/// Send the google inserts and deletes of a [`Batch::Google`], all for different tasks, at once
+/// instead of waiting on each in turn. Every insert's intent is recorded before any is sent. The
+/// results come back in the order of `actions`.
+async fn execute_batch(
+    actions: Vec<SyncAction>,
+    gtasks_mgr: &impl GoogleTasksApi,
+    state: &mut State,
+    correlator: &dyn Correlator,
+) -> Vec<Result<()>> {
+    let list = gtasks_mgr.list_id();
+    for action in &actions {
+        if let SyncAction::CreateGoogle { task, fields } = action {
+            record_create_intent(task, fields, list, state, correlator);
+        }
+    }
+
+    let intents = &state.create_intents;
+    let requests = actions.iter().map(move |action| async move {
+        match action {
+            SyncAction::CreateGoogle { task, .. } => {
+                let intent = &intents[&task.gid];
+                gtasks_mgr
+                    .new_task_from_asana(task, &intent.title, &intent.notes, None)
+                    .await
+                    .map(Some)
+            }
+            SyncAction::DeleteGoogle {
+                list, google_id, ..
+            } => gtasks_mgr
+                .for_list(list)
+                .del_task(google_id)
+                .await
+                .map(|()| None),
+            _ => unreachable!("{action} can't be batched"),
+        }
+    });
+    let responses = futures::future::join_all(requests).await;
+
+    actions
+        .into_iter()
+        .zip(responses)
+        .map(|(action, response)| -> Result<()> {
+            if let (SyncAction::CreateGoogle { task, fields }, Some(google_id)) =
+                (action, response?)
+            {
+                state.link(&task, list, &google_id, Some(fields.hash(&task)));
+            }
+            Ok(())
+        })
+        .collect()
+}
+
+/// Complete the asana tasks of a [`Batch::Asana`] in as few requests as the source takes, with
+/// how each went in the order of `actions`
+async fn complete_asana(actions: &[SyncAction], asana_mgr: &impl AsanaApi) -> Vec<Result<()>> {
+    // tasks edited since the plan are left out of the batch
+    let mut checked = Vec::new();
+    let mut gids = Vec::new();
+    for action in actions {
+        let SyncAction::CompleteAsana {
+            gid, modified_at, ..
+        } = action
+        else {
+            unreachable!("{action} isn't an asana completion");
+        };
+        let unedited = check_unedited(asana_mgr, gid, *modified_at).await;
+        if unedited.is_ok() {
+            gids.push(gid.clone());
+        }
+        checked.push(unedited);
+    }
+
+    let mut completed = asana_mgr.complete_tasks(&gids).await.into_iter();
+    checked
+        .into_iter()
+        .map(|unedited| unedited.and_then(|()| completed.next().expect("a result per gid")))
+        .collect()
+}
+
+/// Fail with a conflict when the asana task `gid` was edited since `read`, the time of its last
+/// edit when the plan was made. The next cycle plans against the edit instead of overwriting it.
+async fn check_unedited(
+    asana_mgr: &impl AsanaApi,
+    gid: &str,
+    read: Option<Timestamp>,
+) -> Result<()> {
+    let Some(read) = read else {
+        return Ok(());
+    };
+    match asana_mgr.modified_at(gid).await? {
+        Some(modified_at) if modified_at != read => Err(BridgeError::Conflict(format!(
+            "asana task {gid} was edited since it was read"
+        ))
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+/// Remember that a google copy of `task` is about to be inserted into `list`
+pub fn record_create_intent(
+    task: &asana::Task,
+    fields: &GoogleFields,
+    list: &str,
+    state: &mut State,
+    correlator: &dyn Correlator,
+) {
+    let (title, notes) = correlator.tag(&fields.title, &fields.notes, Some(&task.gid));
+    state.create_intents.insert(
+        task.gid.clone(),
+        CreateIntent {
+            list: list.to_string(),
+            title,
+            notes,
+        },
+    );
+}
+
+/// Insert a google copy of `task` and return its id. When an earlier insert of the same copy may
+/// have landed without the bridge learning its id, e.g. because the response was lost, a copy
+/// matching that insert's intent is returned instead of inserting a second one.
+async fn create_google(
+    task: &asana::Task,
+    fields: &GoogleFields,
+    gtasks_mgr: &impl GoogleTasksApi,
+    state: &mut State,
+    correlator: &dyn Correlator,
+) -> Result<String> {
+    let list = gtasks_mgr.list_id();
+    if let Some(intent) = state.create_intents.get(&task.gid)
+        && intent.list == list
+    {
+        let google_tasks = gtasks_mgr.get_tasks().await?;
+        let landed = google_tasks
+            .incomplete
+            .iter()
+            .chain(&google_tasks.complete)
+            .filter(|t| linked_asana_gid(t, state, correlator).is_none_or(|gid| gid == task.gid))
+            .find(|t| {
+                t.title.as_deref().map(str::trim) == Some(intent.title.trim())
+                    && t.notes.as_deref().unwrap_or_default().trim() == intent.notes.trim()
+            });
+        if let Some(google_id) = landed.and_then(|t| t.id.clone()) {
+            info!(
+                "\"{}\" was already inserted into google, linking it instead",
+                task.name
+            );
+            return Ok(google_id);
+        }
+    }
+
+    record_create_intent(task, fields, list, state, correlator);
+    let intent = &state.create_intents[&task.gid];
+    gtasks_mgr
+        .new_task_from_asana(
+            task,
+            &intent.title,
+            &intent.notes,
+            google_parent(task, state).as_deref(),
+        )
+        .await
+}
+
+pub async fn execute_action(
+    action: SyncAction,
+    asana_mgr: &impl AsanaApi,
+    gtasks_mgr: &impl GoogleTasksApi,
+    state: &mut State,
+    correlator: &dyn Correlator,
+) -> Result<()> {
+    match action {
+        SyncAction::CreateGoogle { task, fields } => {
+            let google_id = create_google(&task, &fields, gtasks_mgr, state, correlator).await?;
+            let hash = fields.hash(&task);
+            state.link(&task, gtasks_mgr.list_id(), &google_id, Some(hash));
+        }
+        SyncAction::RecreateGoogle {
+            google_id,
+            etag,
+            task,
+            fields,
+        } => {
+            let (title, notes) = correlator.tag(&fields.title, &fields.notes, Some(&task.gid));
+            let updated = gtasks_mgr
+                .update_from_asana(&google_id, etag.as_deref(), &task, &title, &notes)
+                .await?;
+            let google_id = if updated {
+                google_id
+            } else {
+                // an edit made in google since the plan is compared again next cycle, not lost
+                match etag {
+                    Some(etag) => gtasks_mgr.del_task_if(&google_id, &etag).await?,
+                    None => gtasks_mgr.del_task(&google_id).await?,
+                }
+                create_google(&task, &fields, gtasks_mgr, state, correlator).await?
+            };
+            let hash = fields.hash(&task);
+            state.link(&task, gtasks_mgr.list_id(), &google_id, Some(hash));
+        }
+        SyncAction::LinkGoogle {
+            task,
+            google_id,
+            hash,
+        } => {
+            state.link(&task, gtasks_mgr.list_id(), &google_id, Some(hash));
+        }
+        SyncAction::CompleteAsana {
+            gid, modified_at, ..
+        } => {
+            check_unedited(asana_mgr, &gid, modified_at).await?;
+            asana_mgr.complete_task(&gid).await?;
+        }
+        SyncAction::CommentAsana { gid, text, .. } => {
+            asana_mgr.add_comment(&gid, &text).await?;
+        }
+        SyncAction::UpdateAsana {
+            task,
+            name,
+            notes,
+            due,
+            liked,
+            google_id,
+            fields,
+        } => {
+            check_unedited(asana_mgr, &task.gid, task.modified_at).await?;
+            if name.is_some() || notes.is_some() {
+                asana_mgr
+                    .set_text(&task.gid, name.as_deref(), notes.as_deref())
+                    .await?;
+            }
+            if due.is_some() {
+                asana_mgr.set_due(&task).await?;
+            }
+            if let Some(liked) = liked {
+                asana_mgr.set_liked(&task.gid, liked).await?;
+            }
+            let hash = fields.hash(&task);
+            state.link(&task, gtasks_mgr.list_id(), &google_id, Some(hash));
+        }
+        SyncAction::DeleteGoogle {
+            list, google_id, ..
+        } => {
+            gtasks_mgr.for_list(&list).del_task(&google_id).await?;
+        }
+        SyncAction::FlagGoogle { task } => {
+            // left in the list, but no longer tagged or synced
+            let (title, notes) = correlator.tag(
+                task.title.as_deref().unwrap_or_default(),
+                task.notes.as_deref().unwrap_or_default(),
+                None,
+            );
+            gtasks_mgr
+                .set_text(
+                    task.id.as_deref().unwrap_or_default(),
+                    &format!("[Deleted in Asana] {title}"),
+                    &notes,
+                )
+                .await?;
+            notify::push(
+                NotifyEvent::Conflicts,
+                format!(
+                    "Google task \"{}\" was deleted in asana, it's flagged and no longer synced",
+                    task.title.as_deref().unwrap_or_default()
+                ),
+            );
+        }
+        SyncAction::DetachSubtask {
+            gid,
+            list,
+            google_id,
+            ..
+        } => {
+            asana_mgr.set_parent(&gid, None).await?;
+            gtasks_mgr
+                .for_list(&list)
+                .move_to_top_level(&google_id)
+                .await?;
+            if let Some(link) = state.links.get_mut(&gid) {
+                link.parent = None;
+            }
+        }
+        SyncAction::Unlink { gid } => {
+            state.unlink(&gid);
+        }
+    }
+
+    Ok(())
+}
+
+/// Actions that failed in a cycle that otherwise ran to the end. Nothing was recorded for them,
+/// so the next cycle plans and tries them again.
+#[derive(Debug, Default)]
+pub struct FailedActions {
+    /// Each failed action with its error chain
+    pub failed: Vec<(String, String)>,
+    /// Later actions for the same tasks, left alone so they don't build on a failed step
+    pub skipped: Vec<String>,
+}
+
+impl FailedActions {
+    pub fn into_result(self) -> Result<()> {
+        if self.failed.is_empty() {
+            Ok(())
+        } else {
+            Err(self.into())
+        }
+    }
+
+    /// Take in the failures from `result`, passing any other error on
+    pub fn absorb(&mut self, result: Result<()>) -> Result<()> {
+        match result.map_err(anyhow::Error::downcast::<FailedActions>) {
+            Ok(()) => Ok(()),
+            Err(Ok(other)) => {
+                self.failed.extend(other.failed);
+                self.skipped.extend(other.skipped);
+                Ok(())
+            }
+            Err(Err(e)) => Err(e),
+        }
+    }
+}
+
+impl fmt::Display for FailedActions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} actions failed", self.failed.len())?;
+        if !self.skipped.is_empty() {
+            write!(f, " and {} were skipped", self.skipped.len())?;
+        }
+        write!(f, ", they're retried next cycle")
+    }
+}
+
+impl std::error::Error for FailedActions {}
+
+/// Asana gid a google task is linked to, from its tag or, if the tag was mangled or there is none,
+/// from the state store
+pub fn linked_asana_gid(
+    gtask: &google::Task,
+    state: &State,
+    correlator: &dyn Correlator,
+) -> Option<String> {
+    correlator.gid(gtask).or_else(|| {
+        gtask
+            .id
+            .as_deref()
+            .and_then(|id| state.asana_gid_for_google(id))
+            .map(str::to_string)
+    })
+}
+
+/// Google id of the task a flattened subtask should be nested under
+fn google_parent(atask: &asana::Task, state: &State) -> Option<String> {
+    let parent_gid = atask.parent_gid.as_ref()?;
+    state.links.get(parent_gid).map(|l| l.google_id.clone())
+}
+
+/// Index google tasks by their linked asana gid. The first task linked to a gid wins.
+pub fn index_by_asana_gid<'a>(
+    tasks: impl Iterator<Item = &'a google::Task>,
+    state: &State,
+    correlator: &dyn Correlator,
+) -> HashMap<String, &'a google::Task> {
+    let mut index = HashMap::new();
+    for task in tasks {
+        if let Some(gid) = linked_asana_gid(task, state, correlator) {
+            index.entry(gid).or_insert(task);
+        }
+    }
+
+    index
+}
+
+/// Asana task names shared by more than one task
+pub fn duplicate_names(tasks: &[asana::Task]) -> HashSet<&str> {
+    let mut seen = HashSet::new();
+    tasks
+        .iter()
+        .map(|t| t.name.as_str())
+        .filter(|name| !seen.insert(*name))
+        .collect()
+}
+
+/// What the google copy of an asana task should contain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GoogleFields {
+    pub title: String,
+    /// Notes body, without the bridge's marker
+    pub notes: String,
+}
+
+pub fn google_fields(atask: &asana::Task, duplicate_names: &HashSet<&str>) -> GoogleFields {
+    // private tasks show up in google without any of their details
+    if redact::is_private(atask) {
+        return GoogleFields {
+            title: redact::PRIVATE_TITLE.to_string(),
+            notes: String::new(),
+        };
+    }
+
+    // When several asana tasks share a name, the end of the gid is appended so they can be told
+    // apart
+    let title = if duplicate_names.contains(atask.name.as_str()) {
+        let suffix = &atask.gid[atask.gid.len().saturating_sub(4)..];
+        format!("{} [#{suffix}]", atask.name)
+    } else {
+        atask.name.clone()
+    };
+    let title = star::add(title, atask.liked);
+
+    // links to the attachments and the latest comments, then a link back to asana for the rest
+    let attachments = attachments::render(&atask.attachments);
+    let comments = comments::render(&atask.comments);
+    let notes = [
+        atask.notes.as_str(),
+        attachments.as_str(),
+        comments.as_str(),
+        atask.permalink_url.as_deref().unwrap_or_default(),
+    ]
+    .into_iter()
+    .filter(|part| !part.is_empty())
+    .collect::<Vec<_>>()
+    .join("\n\n");
+
+    // Google rejects long titles, so cut them down and keep the full title in the notes
+    if title.chars().count() > google::MAX_TITLE_CHARS {
+        let mut truncated: String = title.chars().take(google::MAX_TITLE_CHARS - 1).collect();
+        truncated.push('…');
+
+        GoogleFields {
+            title: truncated,
+            notes: format!("Full title: {}\n\n{notes}", atask.name),
+        }
+    } else {
+        GoogleFields { title, notes }
+    }
+}
+
+impl GoogleFields {
+    /// [`content_hash`] of the google copy of `atask` with these fields
+    pub fn hash(&self, atask: &asana::Task) -> String {
+        let due = asana::asana_due_to_string(atask).unwrap_or_default();
+        content_hash(&self.title, &self.notes, &due)
+    }
+}
+
+/// [`content_hash`] of what a google task currently shows
+pub fn google_hash(gtask: &google::Task) -> String {
+    let notes = gtask.notes.as_deref().unwrap_or_default();
+    let due = gtask.due.as_deref().unwrap_or_default();
+    content_hash(
+        gtask.title.as_deref().unwrap_or_default(),
+        google::notes_body(notes),
+        &due.replace(".000Z", "Z"),
+    )
+}
+
+/// Fingerprint of a google task's title, notes body and due date. Trailing whitespace is ignored,
+/// google drops it.
+pub fn content_hash(title: &str, notes: &str, due: &str) -> String {
+    // FNV-1a, std's hasher may change between releases and the hashes are kept in the state file
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for part in [title.trim_end(), notes.trim_end(), due] {
+        // the trailing zero keeps "ab" + "c" apart from "a" + "bc"
+        for byte in part.bytes().chain([0]) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    format!("{hash:016x}")
+}
+
+/// Text added in google to notes the bridge wrote as `written`. Lines that were changed count as
+/// added, removed lines are ignored.
+fn added_notes(written: &str, edited: &str) -> Option<String> {
+    let (written, edited) = (written.trim_end(), edited.trim_end());
+    if written == edited {
+        return None;
+    }
+
+    let added = match edited.strip_prefix(written) {
+        Some(rest) => rest.trim().to_string(),
+        None => {
+            let kept: HashSet<&str> = written.lines().collect();
+            let lines: Vec<&str> = edited.lines().filter(|l| !kept.contains(l)).collect();
+            lines.join("\n").trim().to_string()
+        }
+    };
+    (!added.is_empty()).then_some(added)
+}
+
+/// The asana task with the fields edited in its google copy, when every edit is one that's carried
+/// over to asana. Fields syncing both ways, and the star, are only carried over while asana is
+/// unchanged since the last sync.
+fn edited_in_google(
+    atask: &asana::Task,
+    fields: &GoogleFields,
+    gtask: &google::Task,
+    asana_unchanged: bool,
+    sync: FieldsConfig,
+) -> Option<asana::Task> {
+    let carried = |field: FieldSync| {
+        field == FieldSync::ToAsana || (field == FieldSync::Both && asana_unchanged)
+    };
+    // private tasks show a placeholder in google, none of it is theirs to carry over
+    let private = redact::is_private(atask);
+    let (starred, google_title) = star::split(gtask.title.as_deref().unwrap_or_default());
+    let written_title = star::split(&fields.title).1;
+    let google_notes = google::notes_body(gtask.notes.as_deref().unwrap_or_default());
+
+    let mut edited = atask.clone();
+    if google_title.trim_end() != written_title.trim_end() {
+        if private || !carried(sync.title) {
+            return None;
+        }
+        edited.name = asana_name(google_title, atask, written_title);
+    }
+    if google_notes.trim_end() != fields.notes.trim_end() {
+        if private || !carried(sync.notes) {
+            return None;
+        }
+        edited.notes = asana_notes(google_notes, atask, &fields.notes);
+    }
+    if let Some(day) = google::due_date(gtask)
+        && atask.due_date() != Some(day)
+    {
+        if !carried(sync.due) {
+            return None;
+        }
+        edited = edited.moved_to(day).ok()?;
+    }
+    if star::enabled() && starred != atask.liked {
+        if !asana_unchanged {
+            return None;
+        }
+        edited.liked = starred;
+    }
+
+    (edited.name != atask.name
+        || edited.notes != atask.notes
+        || edited.liked != atask.liked
+        || edited.due_date() != atask.due_date())
+    .then_some(edited)
+}
+
+/// The asana task with what its google copy shows for the fields that only sync to asana, so they
+/// aren't overwritten in google
+fn keep_google(
+    atask: &asana::Task,
+    fields: &GoogleFields,
+    gtask: &google::Task,
+    sync: FieldsConfig,
+) -> asana::Task {
+    let mut kept = atask.clone();
+    let private = redact::is_private(atask);
+    let google_title = star::split(gtask.title.as_deref().unwrap_or_default()).1;
+    let written_title = star::split(&fields.title).1;
+    let google_notes = google::notes_body(gtask.notes.as_deref().unwrap_or_default());
+
+    if !sync.title.to_google() && !private && google_title.trim_end() != written_title.trim_end() {
+        kept.name = asana_name(google_title, atask, written_title);
+    }
+    if !sync.notes.to_google() && !private && google_notes.trim_end() != fields.notes.trim_end() {
+        kept.notes = asana_notes(google_notes, atask, &fields.notes);
+    }
+    if !sync.due.to_google()
+        && let Some(day) = google::due_date(gtask)
+        && atask.due_date() != Some(day)
+        && let Ok(moved) = kept.moved_to(day)
+    {
+        kept = moved;
+    }
+    kept
+}
+
+/// Asana name for a title edited in google, without the suffix that tells apart tasks sharing a
+/// name
+fn asana_name(google_title: &str, atask: &asana::Task, written_title: &str) -> String {
+    let title = google_title.trim_end();
+    match written_title.strip_prefix(atask.name.as_str()) {
+        Some(suffix) if !suffix.is_empty() => title.strip_suffix(suffix).unwrap_or(title),
+        _ => title,
+    }
+    .to_string()
+}
+
+/// Asana notes for notes edited in google, without the full title, attachments, comments and link
+/// back to asana written around them
+fn asana_notes(google_notes: &str, atask: &asana::Task, written_notes: &str) -> String {
+    let notes = google_notes.trim_end();
+    let Some((before, after)) = written_notes.split_once(atask.notes.as_str()) else {
+        return notes.to_string();
+    };
+    let notes = notes.strip_prefix(before).unwrap_or(notes);
+    notes
+        .strip_suffix(after.trim_end())
+        .unwrap_or(notes)
+        .trim_end()
+        .to_string()
+}
+
+pub fn asana_google_same(atask: &asana::Task, fields: &GoogleFields, gtask: &google::Task) -> bool {
+    let same = google_hash(gtask) == fields.hash(atask);
+    if !same {
+        debug!(
+            "google task \"{}\" differs from asana task \"{}\"",
+            gtask.title.as_deref().unwrap_or_default(),
+            atask.name
+        );
+    }
+    same
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::civil::date;
+
+    use super::*;
+    use crate::{
+        correlate::{Correlation, NotesMarker},
+        mock::{MOCK_ME, MockAsana, MockGoogle, asana_task, cycle, sync_options, synced},
+    };
+
+    fn google_id(state: &State, gid: &str) -> String {
+        state.links[gid].google_id.clone()
+    }
+
+    #[tokio::test]
+    async fn creates_google_copy_of_new_asana_task() {
+        let (_, google, state) =
+            synced(vec![asana_task("100", "Write report", date(2026, 10, 20))]).await;
+
+        let tasks = google.tasks();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].title.as_deref(), Some("Write report"));
+        assert_eq!(tasks[0].due.as_deref(), Some("2026-10-20T00:00:00Z"));
+        assert_eq!(
+            google::get_asana_task_gid(&tasks[0]).as_deref(),
+            Some("100")
+        );
+        assert_eq!(tasks[0].id, Some(google_id(&state, "100")));
+    }
+
+    #[tokio::test]
+    async fn title_tokens_survive_a_lost_state_store() {
+        let asana = MockAsana::new(vec![asana_task("100", "Write report", date(2026, 10, 20))]);
+        let google = MockGoogle::new();
+        let mut options = sync_options();
+        options.policies.correlator = Correlation::Title.correlator();
+        cycle(&asana, &google, &mut State::default(), &options)
+            .await
+            .unwrap();
+
+        let tasks = google.tasks();
+        assert_eq!(tasks[0].title.as_deref(), Some("Write report [asana:100]"));
+        assert_eq!(google::get_asana_task_gid(&tasks[0]), None);
+
+        // found again by its title, and the token doesn't count as a change
+        let mut state = State::default();
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+        assert_eq!(google.tasks().len(), 1);
+        assert_eq!(Some(google_id(&state, "100")), tasks[0].id);
+    }
+
+    #[test]
+    fn notes_link_back_to_asana() {
+        let mut task = asana_task("100", "Write report", date(2026, 10, 20));
+        task.permalink_url = Some("https://app.asana.com/0/0/100".to_string());
+        assert_eq!(
+            google_fields(&task, &HashSet::new()).notes,
+            "https://app.asana.com/0/0/100"
+        );
+
+        task.notes = "Use the new template".to_string();
+        assert_eq!(
+            google_fields(&task, &HashSet::new()).notes,
+            "Use the new template\n\nhttps://app.asana.com/0/0/100"
+        );
+    }
+
+    #[tokio::test]
+    async fn unchanged_tasks_are_left_alone() {
+        let (asana, google, mut state) =
+            synced(vec![asana_task("100", "Write report", date(2026, 10, 20))]).await;
+        let before = google_id(&state, "100");
+
+        cycle(&asana, &google, &mut state, &sync_options())
+            .await
+            .unwrap();
+
+        assert_eq!(google.tasks().len(), 1);
+        assert_eq!(google_id(&state, "100"), before);
+    }
+
+    #[tokio::test]
+    async fn extra_lines_in_google_notes_are_noticed() {
+        let (asana, google, mut state) =
+            synced(vec![asana_task("100", "Write report", date(2026, 10, 20))]).await;
+        let synced_hash = state.links["100"].hash.clone();
+        assert!(synced_hash.is_some());
+
+        google.update(&google_id(&state, "100"), |t| {
+            t.notes = Some(google::with_marker("Added in google", "100"));
+        });
+        cycle(&asana, &google, &mut state, &sync_options())
+            .await
+            .unwrap();
+
+        let tasks = google.tasks();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(google::notes_body(tasks[0].notes.as_deref().unwrap()), "");
+        assert_eq!(state.links["100"].hash, synced_hash);
+    }
+
+    #[tokio::test]
+    async fn asana_edits_replace_the_google_copy() {
+        let (asana, google, mut state) =
+            synced(vec![asana_task("100", "Write report", date(2026, 10, 20))]).await;
+        let before = google_id(&state, "100");
+
+        asana.update("100", |t| {
+            t.name = "Write final report".to_string();
+            t.due_on = Some(date(2026, 10, 22));
+        });
+        cycle(&asana, &google, &mut state, &sync_options())
+            .await
+            .unwrap();
+
+        let tasks = google.tasks();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].title.as_deref(), Some("Write final report"));
+        assert_eq!(tasks[0].due.as_deref(), Some("2026-10-22T00:00:00Z"));
+        assert_ne!(google_id(&state, "100"), before);
+        assert_eq!(tasks[0].id, Some(google_id(&state, "100")));
+    }
+
+    #[tokio::test]
+    async fn google_edits_made_while_syncing_are_compared_again() {
+        let (asana, google, mut state) =
+            synced(vec![asana_task("100", "Write report", date(2026, 10, 20))]).await;
+        let id = google_id(&state, "100");
+        google.update(&id, |t| t.etag = Some("1".to_string()));
+
+        asana.update("100", |t| t.name = "Write final report".to_string());
+        let task = asana.task("100").unwrap();
+        let action = SyncAction::RecreateGoogle {
+            google_id: id.clone(),
+            etag: Some("1".to_string()),
+            fields: google_fields(&task, &Default::default()),
+            task,
+        };
+        // edited in google after the plan compared it
+        google.update(&id, |t| {
+            t.title = Some("Write report today".to_string());
+            t.etag = Some("2".to_string());
+        });
+        execute_action(action, &asana, &google, &mut state, &NotesMarker)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            google.tasks()[0].title.as_deref(),
+            Some("Write report today")
+        );
+
+        // changed on both sides, which the next cycle settles like any other conflict
+        cycle(&asana, &google, &mut state, &sync_options())
+            .await
+            .unwrap();
+        let tasks = google.tasks();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].title.as_deref(), Some("Write final report"));
+    }
+
+    #[tokio::test]
+    async fn google_due_changes_move_the_asana_task() {
+        let (asana, google, mut state) =
+            synced(vec![asana_task("100", "Write report", date(2026, 10, 20))]).await;
+        let before = google_id(&state, "100");
+
+        google.update(&before, |t| {
+            t.due = Some("2026-10-23T00:00:00.000Z".to_string());
+        });
+        cycle(&asana, &google, &mut state, &sync_options())
+            .await
+            .unwrap();
+
+        assert_eq!(asana.task("100").unwrap().due_on, Some(date(2026, 10, 23)));
+        assert_eq!(google_id(&state, "100"), before);
+
+        // settled, nothing left to do on the next cycle
+        cycle(&asana, &google, &mut state, &sync_options())
+            .await
+            .unwrap();
+        assert_eq!(google_id(&state, "100"), before);
+        assert_eq!(
+            google.tasks()[0].due.as_deref(),
+            Some("2026-10-23T00:00:00.000Z")
+        );
+    }
+
+    #[tokio::test]
+    async fn tasks_snoozed_in_google_move_to_later() {
+        let today = crate::date::today();
+        let (mut asana, google, mut state) =
+            synced(vec![asana_task("100", "Write report", today)]).await;
+        asana.sections = vec![
+            asana::Section {
+                gid: "1".to_string(),
+                name: "Today".to_string(),
+            },
+            asana::Section {
+                gid: "2".to_string(),
+                name: "Later".to_string(),
+            },
+        ];
+        let mut options = sync_options();
+        options.writeback.enabled = true;
+
+        let snoozed = today + 30.days();
+        google.update(&google_id(&state, "100"), |t| {
+            t.due = Some(format!("{snoozed}T00:00:00.000Z"));
+        });
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+
+        let task = asana.task("100").unwrap();
+        assert_eq!(task.due_on, Some(snoozed));
+        assert_eq!(
+            task.assignee_section.map(|s| s.name).as_deref(),
+            Some("Later")
+        );
+    }
+
+    #[tokio::test]
+    async fn titles_syncing_both_ways_are_renamed_in_asana() {
+        let mut options = sync_options();
+        options.policies.fields.title = FieldSync::Both;
+        let (asana, google, mut state) =
+            synced(vec![asana_task("100", "Write report", date(2026, 10, 20))]).await;
+        let before = google_id(&state, "100");
+
+        google.update(&before, |t| {
+            t.title = Some("Write final report".to_string())
+        });
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+        assert_eq!(asana.task("100").unwrap().name, "Write final report");
+
+        // settled, the google copy was never replaced
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+        assert_eq!(google_id(&state, "100"), before);
+        assert_eq!(
+            google.tasks()[0].title.as_deref(),
+            Some("Write final report")
+        );
+    }
+
+    #[tokio::test]
+    async fn fields_syncing_to_asana_are_never_overwritten_in_google() {
+        let mut options = sync_options();
+        options.policies.fields.notes = FieldSync::ToAsana;
+        options.policies.fields.due = FieldSync::ToAsana;
+        let mut task = asana_task("100", "Write report", date(2026, 10, 20));
+        task.permalink_url = Some("https://app.asana.com/0/0/100".to_string());
+        let (asana, google, mut state) = synced(vec![task]).await;
+        let id = google_id(&state, "100");
+
+        // edited in google, written to asana without the link the bridge added
+        google.update(&id, |t| {
+            t.notes = Some(google::with_marker(
+                "Use last year's figures\n\nhttps://app.asana.com/0/0/100",
+                "100",
+            ));
+        });
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+        assert_eq!(asana.task("100").unwrap().notes, "Use last year's figures");
+
+        // edited in asana, google keeps its due date and pulls asana back to it, while the name
+        // still goes to google
+        asana.update("100", |t| {
+            t.name = "Write final report".to_string();
+            t.due_on = Some(date(2026, 10, 22));
+        });
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+        let tasks = google.tasks();
+        assert_eq!(tasks[0].title.as_deref(), Some("Write final report"));
+        assert_eq!(google::due_date(&tasks[0]), Some(date(2026, 10, 20)));
+        assert_eq!(
+            google::notes_body(tasks[0].notes.as_deref().unwrap()),
+            "Use last year's figures\n\nhttps://app.asana.com/0/0/100"
+        );
+        assert_eq!(asana.task("100").unwrap().due_on, Some(date(2026, 10, 20)));
+    }
+
+    #[tokio::test]
+    async fn google_completion_completes_asana_task() {
+        let (asana, google, mut state) =
+            synced(vec![asana_task("100", "Write report", date(2026, 10, 20))]).await;
+
+        google.update(&google_id(&state, "100"), |t| {
+            t.completed = Some("2026-10-16T12:00:00.000Z".to_string());
+        });
+        cycle(&asana, &google, &mut state, &sync_options())
+            .await
+            .unwrap();
+
+        assert!(asana.task("100").unwrap().completed_at.is_some());
+        assert!(google.tasks().is_empty());
+        assert!(state.links.is_empty());
+    }
+
+    #[tokio::test]
+    async fn one_failing_task_doesnt_hold_up_the_others() {
+        let (asana, google, mut state) = synced(vec![
+            asana_task("100", "Write report", date(2026, 10, 20)),
+            asana_task("101", "Pay rent", date(2026, 10, 21)),
+        ])
+        .await;
+        for gid in ["100", "101"] {
+            google.update(&google_id(&state, gid), |t| {
+                t.completed = Some("2026-10-16T12:00:00.000Z".to_string());
+            });
+        }
+
+        asana.failing.borrow_mut().insert("100".to_string());
+        let err = cycle(&asana, &google, &mut state, &sync_options())
+            .await
+            .unwrap_err();
+        let failures = err.downcast_ref::<FailedActions>().unwrap();
+        assert_eq!(failures.failed.len(), 1);
+        assert!(!failures.skipped.is_empty());
+
+        assert!(asana.task("101").unwrap().completed_at.is_some());
+        assert!(!state.links.contains_key("101"));
+        // the completed google copy stays until asana takes the completion
+        assert!(state.links.contains_key("100"));
+        assert_eq!(google.tasks().len(), 1);
+
+        asana.failing.borrow_mut().clear();
+        cycle(&asana, &google, &mut state, &sync_options())
+            .await
+            .unwrap();
+        assert!(asana.task("100").unwrap().completed_at.is_some());
+        assert!(google.tasks().is_empty());
+    }
+
+    #[tokio::test]
+    async fn actions_that_keep_failing_are_parked() {
+        let (asana, google, mut state) =
+            synced(vec![asana_task("100", "Write report", date(2026, 10, 20))]).await;
+        google.update(&google_id(&state, "100"), |t| {
+            t.completed = Some("2026-10-16T12:00:00.000Z".to_string());
+        });
+
+        asana.failing.borrow_mut().insert("100".to_string());
+        for _ in 0..3 {
+            assert!(
+                cycle(&asana, &google, &mut state, &sync_options())
+                    .await
+                    .is_err()
+            );
+        }
+        let failure = &state.failures["complete-asana:100"];
+        assert!(failure.parked);
+        assert_eq!(failure.count, 3);
+
+        // parked, the task is left alone instead of failing every cycle
+        cycle(&asana, &google, &mut state, &sync_options())
+            .await
+            .unwrap();
+        assert_eq!(google.tasks().len(), 1);
+
+        asana.failing.borrow_mut().clear();
+        state.failures.get_mut("complete-asana:100").unwrap().parked = false;
+        cycle(&asana, &google, &mut state, &sync_options())
+            .await
+            .unwrap();
+        assert!(asana.task("100").unwrap().completed_at.is_some());
+        assert!(state.failures.is_empty());
+    }
+
+    #[tokio::test]
+    async fn being_rate_limited_leaves_the_rest_for_later() {
+        let (asana, google, mut state) = synced(vec![
+            asana_task("100", "Write report", date(2026, 10, 20)),
+            asana_task("101", "Pay rent", date(2026, 10, 21)),
+        ])
+        .await;
+        for gid in ["100", "101"] {
+            google.update(&google_id(&state, gid), |t| {
+                t.completed = Some("2026-10-16T12:00:00.000Z".to_string());
+            });
+        }
+
+        asana.throttled.set(true);
+        let err = cycle(&asana, &google, &mut state, &sync_options())
+            .await
+            .unwrap_err();
+        let failures = err.downcast_ref::<FailedActions>().unwrap();
+        assert_eq!(failures.failed.len(), 1);
+        assert!(!failures.skipped.is_empty());
+        // asana failed rather than the actions, nothing counts towards parking
+        assert!(state.failures.is_empty());
+
+        asana.throttled.set(false);
+        cycle(&asana, &google, &mut state, &sync_options())
+            .await
+            .unwrap();
+        assert!(asana.task("100").unwrap().completed_at.is_some());
+        assert!(asana.task("101").unwrap().completed_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn inserts_and_deletes_are_sent_in_batches() {
+        let options = SyncOptions {
+            batch_size: 10,
+            ..sync_options()
+        };
+        let tasks: Vec<_> = (100..125)
+            .map(|gid| asana_task(&gid.to_string(), &format!("Task {gid}"), date(2026, 10, 20)))
+            .collect();
+        let asana = MockAsana::new(tasks);
+        let google = MockGoogle::new();
+        let mut state = State::default();
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+        assert_eq!(google.tasks().len(), 25);
+        assert_eq!(state.links.len(), 25);
+        assert!(state.create_intents.is_empty());
+        let mut google_ids: Vec<_> = state.links.values().map(|l| &l.google_id).collect();
+        google_ids.sort();
+        google_ids.dedup();
+        assert_eq!(google_ids.len(), 25);
+
+        for gid in 100..112 {
+            asana.update(&gid.to_string(), |t| {
+                t.completed_at = Some(Timestamp::now())
+            });
+        }
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+        assert_eq!(google.tasks().len(), 13);
+        assert!(!state.links.contains_key("100"));
+        assert!(state.links.contains_key("112"));
+    }
+
+    #[tokio::test]
+    async fn an_insert_that_landed_without_an_answer_isnt_repeated() {
+        let mut options = sync_options();
+        options.policies.correlator = Correlation::State.correlator();
+        let asana = MockAsana::new(vec![asana_task("100", "Write report", date(2026, 10, 20))]);
+        let google = MockGoogle::new();
+        let mut state = State::default();
+
+        google.lost_inserts.set(1);
+        cycle(&asana, &google, &mut state, &options)
+            .await
+            .unwrap_err();
+        assert_eq!(google.tasks().len(), 1);
+        assert!(state.links.is_empty());
+        assert!(state.create_intents.contains_key("100"));
+
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+        let tasks = google.tasks();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(state.links["100"].google_id, tasks[0].id.clone().unwrap());
+        assert!(state.create_intents.is_empty());
+    }
+
+    #[tokio::test]
+    async fn asana_completions_are_sent_together() {
+        let (asana, google, mut state) = synced(vec![
+            asana_task("100", "Write report", date(2026, 10, 20)),
+            asana_task("101", "Pay rent", date(2026, 10, 21)),
+            asana_task("102", "Call mom", date(2026, 10, 22)),
+        ])
+        .await;
+        for gid in ["100", "101", "102"] {
+            google.update(&google_id(&state, gid), |t| {
+                t.completed = Some("2026-10-16T12:00:00.000Z".to_string());
+            });
+        }
+
+        let options = SyncOptions {
+            asana_batch_size: 10,
+            ..sync_options()
+        };
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+        assert_eq!(*asana.completion_batches.borrow(), [3]);
+        for gid in ["100", "101", "102"] {
+            assert!(asana.task(gid).unwrap().completed_at.is_some());
+        }
+        assert!(google.tasks().is_empty());
+        assert!(state.links.is_empty());
+    }
+
+    #[tokio::test]
+    async fn asana_tasks_edited_during_the_cycle_arent_written_over() {
+        let read: Timestamp = "2026-10-16T08:00:00Z".parse().unwrap();
+        let tasks = [("100", "Write report"), ("101", "Pay rent")].map(|(gid, name)| {
+            let mut task = asana_task(gid, name, date(2026, 10, 20));
+            task.modified_at = Some(read);
+            task
+        });
+        let (asana, google, mut state) = synced(tasks.to_vec()).await;
+        for gid in ["100", "101"] {
+            google.update(&google_id(&state, gid), |t| {
+                t.completed = Some("2026-10-16T12:00:00.000Z".to_string());
+            });
+        }
+        let options = SyncOptions {
+            asana_batch_size: 10,
+            ..sync_options()
+        };
+
+        let asana_tasks = asana.get_tasks().await.unwrap();
+        // renamed in asana after the cycle fetched the tasks
+        asana.update("100", |t| {
+            t.name = "Write the report".to_string();
+            t.modified_at = Some(read + 1.hour());
+        });
+        let err = sync_tasks(&asana, &asana_tasks, &google, &mut state, &options)
+            .await
+            .unwrap_err();
+        let failures = err.downcast_ref::<FailedActions>().unwrap();
+        assert_eq!(failures.failed.len(), 1);
+        assert!(asana.task("100").unwrap().completed_at.is_none());
+        assert!(asana.task("101").unwrap().completed_at.is_some());
+        assert!(state.links.contains_key("100"));
+
+        // the next cycle plans against the rename, and the google completion still wins
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+        let report = asana.task("100").unwrap();
+        assert!(report.completed_at.is_some());
+        assert_eq!(report.name, "Write the report");
+    }
+
+    /// "Write report" with its subtask "Draft", synced and nested, and the parent then completed in
+    /// google
+    async fn completed_parent() -> (MockAsana, MockGoogle, State) {
+        let mut draft = asana_task("101", "Draft", date(2026, 10, 20));
+        draft.parent_gid = Some("100".to_string());
+        let (asana, google, state) = synced(vec![
+            asana_task("100", "Write report", date(2026, 10, 20)),
+            draft,
+        ])
+        .await;
+        assert_eq!(state.children_of("100"), ["101"]);
+        google.update(&google_id(&state, "100"), |t| {
+            t.completed = Some("2026-10-16T12:00:00.000Z".to_string());
+        });
+        (asana, google, state)
+    }
+
+    fn cascading(cascade: CascadePolicy) -> SyncOptions {
+        SyncOptions {
+            policies: Policies {
+                cascade,
+                ..sync_options().policies
+            },
+            ..sync_options()
+        }
+    }
+
+    #[tokio::test]
+    async fn completing_a_parent_cascades_to_its_subtasks() {
+        let (asana, google, mut state) = completed_parent().await;
+        cycle(
+            &asana,
+            &google,
+            &mut state,
+            &cascading(CascadePolicy::Complete),
+        )
+        .await
+        .unwrap();
+        assert!(asana.task("101").unwrap().completed_at.is_some());
+        assert!(google.tasks().is_empty());
+        assert!(state.links.is_empty());
+
+        let (asana, google, mut state) = completed_parent().await;
+        cycle(
+            &asana,
+            &google,
+            &mut state,
+            &cascading(CascadePolicy::Promote),
+        )
+        .await
+        .unwrap();
+        let draft = asana.task("101").unwrap();
+        assert!(draft.completed_at.is_none());
+        assert_eq!(draft.parent_gid, None);
+        assert!(state.links.contains_key("101"));
+        assert_eq!(google.tasks().len(), 1);
+        assert_eq!(google.tasks()[0].parent, None);
+
+        let (asana, google, mut state) = completed_parent().await;
+        cycle(
+            &asana,
+            &google,
+            &mut state,
+            &cascading(CascadePolicy::Leave),
+        )
+        .await
+        .unwrap();
+        assert!(asana.task("101").unwrap().completed_at.is_none());
+        assert!(google.tasks().is_empty());
+        assert!(state.links.is_empty());
+    }
+
+    #[tokio::test]
+    async fn subtasks_edited_during_the_cycle_arent_completed_with_their_parent() {
+        let read: Timestamp = "2026-10-16T08:00:00Z".parse().unwrap();
+        let (asana, google, mut state) = completed_parent().await;
+        asana.update("101", |t| t.modified_at = Some(read));
+
+        let asana_tasks = asana.get_tasks().await.unwrap();
+        asana.update("101", |t| {
+            t.name = "First draft".to_string();
+            t.modified_at = Some(read + 1.hour());
+        });
+        let options = cascading(CascadePolicy::Complete);
+        let err = sync_tasks(&asana, &asana_tasks, &google, &mut state, &options)
+            .await
+            .unwrap_err();
+        assert_eq!(err.downcast_ref::<FailedActions>().unwrap().failed.len(), 1);
+        assert!(asana.task("100").unwrap().completed_at.is_some());
+        assert!(asana.task("101").unwrap().completed_at.is_none());
+        assert!(state.links.contains_key("101"));
+    }
+
+    #[tokio::test]
+    async fn untitled_google_tasks_dont_panic() {
+        let (asana, google, mut state) = synced(vec![
+            asana_task("100", "Write report", date(2026, 10, 20)),
+            asana_task("101", "Pay rent", date(2026, 10, 21)),
+        ])
+        .await;
+        // completed in google after clearing the title
+        google.update(&google_id(&state, "100"), |t| {
+            t.title = None;
+            t.notes = None;
+            t.completed = Some("2026-10-16T12:00:00.000Z".to_string());
+        });
+        // completed in asana while the google copy lost its title
+        google.update(&google_id(&state, "101"), |t| t.title = None);
+        asana.update("101", |t| t.completed_at = Some(jiff::Timestamp::now()));
+
+        cycle(&asana, &google, &mut state, &sync_options())
+            .await
+            .unwrap();
+        assert!(asana.task("100").unwrap().completed_at.is_some());
+        assert!(google.tasks().is_empty());
+        assert!(state.links.is_empty());
+    }
+
+    #[test]
+    fn google_tasks_without_an_id_are_left_out() {
+        let id_less = |completed: Option<&str>| google::Task {
+            title: Some("Pay rent".to_string()),
+            completed: completed.map(str::to_string),
+            ..Default::default()
+        };
+        let google_tasks = google::GTaskResult {
+            incomplete: vec![id_less(None)],
+            complete: vec![id_less(Some("2026-10-16T12:00:00.000Z"))],
+        };
+        let asana_tasks = asana::TaskResult {
+            incomplete: Vec::new(),
+            complete: Vec::new(),
+        };
+        let mut policies = sync_options().policies;
+        policies.delete_unlinked_completed = true;
+
+        let actions = plan(
+            &asana_tasks,
+            &google_tasks,
+            &State::default(),
+            &Vanished::default(),
+            "list",
+            &policies,
+        );
+        assert!(actions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn asana_completion_removes_google_copy() {
+        let (asana, google, mut state) =
+            synced(vec![asana_task("100", "Write report", date(2026, 10, 20))]).await;
+
+        asana.update("100", |t| t.completed_at = Some(jiff::Timestamp::now()));
+        cycle(&asana, &google, &mut state, &sync_options())
+            .await
+            .unwrap();
+
+        assert!(google.tasks().is_empty());
+        assert!(state.links.is_empty());
+    }
+
+    #[test]
+    fn completions_are_caught_up_on_since_the_last_fetch() {
+        let now: Timestamp = "2026-10-16T12:00:00Z".parse().unwrap();
+        let mut state = State::default();
+        assert_eq!(
+            completed_since(&state, now, 7),
+            "2026-10-15T12:00:00Z".parse().unwrap()
+        );
+
+        state.last_fetched = Some("2026-10-13T08:00:00Z".parse().unwrap());
+        assert_eq!(
+            completed_since(&state, now, 7),
+            "2026-10-13T07:50:00Z".parse().unwrap()
+        );
+        assert_eq!(
+            completed_since(&state, now, 2),
+            "2026-10-14T12:00:00Z".parse().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn only_successful_cycles_move_the_completion_window() {
+        let asana = MockAsana::new(vec![asana_task("100", "Write report", date(2026, 10, 20))]);
+        let google = MockGoogle::new();
+        let mut state = State::default();
+        process_tasks(&asana, &google, &mut state, &sync_options())
+            .await
+            .unwrap();
+        let fetched = state.last_fetched.unwrap();
+
+        google.update(&google_id(&state, "100"), |t| {
+            t.completed = Some("2026-10-16T12:00:00.000Z".to_string());
+        });
+        asana.failing.borrow_mut().insert("100".to_string());
+        process_tasks(&asana, &google, &mut state, &sync_options())
+            .await
+            .unwrap_err();
+        assert_eq!(state.last_fetched, Some(fetched));
+
+        asana.failing.borrow_mut().clear();
+        process_tasks(&asana, &google, &mut state, &sync_options())
+            .await
+            .unwrap();
+        assert!(state.last_fetched.unwrap() > fetched);
+    }
+
+    #[tokio::test]
+    async fn catch_up_completes_copies_of_tasks_completed_long_ago() {
+        let (asana, google, mut state) =
+            synced(vec![asana_task("100", "Write report", date(2026, 10, 1))]).await;
+        asana.update("100", |t| {
+            t.completed_at = Some(Timestamp::now() - 240.hours())
+        });
+        asana.hidden.borrow_mut().insert("100".to_string());
+
+        process_tasks(&asana, &google, &mut state, &sync_options())
+            .await
+            .unwrap();
+        assert_eq!(google.tasks().len(), 1);
+
+        let mut options = sync_options();
+        options.catch_up_days = 30;
+        process_tasks(&asana, &google, &mut state, &options)
+            .await
+            .unwrap();
+        assert!(google.tasks().is_empty());
+        assert!(state.links.is_empty());
+    }
+
+    #[tokio::test]
+    async fn notes_added_in_google_become_asana_comments() {
+        let mut task = asana_task("100", "Pay rent", date(2026, 10, 22));
+        task.notes = "Landlord: 555-0100".to_string();
+        let (asana, google, mut state) = synced(vec![task]).await;
+        let mut options = sync_options();
+        options.policies.notes_as_comments = true;
+
+        google.update(&google_id(&state, "100"), |t| {
+            let notes = "Landlord: 555-0100\n\nWants cash this month";
+            t.notes = Some(google::with_marker(notes, "100"));
+        });
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+
+        let comments = asana.get_comments("100").await.unwrap();
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments[0].text, "Wants cash this month");
+        // google is back to the asana notes, and nothing is posted twice
+        let notes = google.tasks()[0].notes.clone().unwrap();
+        assert_eq!(google::notes_body(&notes), "Landlord: 555-0100");
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+        assert_eq!(asana.get_comments("100").await.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn changed_lines_count_as_added() {
+        assert_eq!(added_notes("Call Bob", "Call Bob"), None);
+        assert_eq!(added_notes("Call Bob\nBring the forms", "Call Bob"), None);
+        assert_eq!(
+            added_notes(
+                "Call Bob\nBring the forms",
+                "Call Bob\nBring the signed forms"
+            )
+            .as_deref(),
+            Some("Bring the signed forms")
+        );
+    }
+
+    #[tokio::test]
+    async fn asana_deletion_removes_google_copy() {
+        let (asana, google, mut state) =
+            synced(vec![asana_task("100", "Write report", date(2026, 10, 20))]).await;
+
+        asana.delete("100");
+        cycle(&asana, &google, &mut state, &sync_options())
+            .await
+            .unwrap();
+
+        assert!(google.tasks().is_empty());
+        assert!(state.links.is_empty());
+    }
+
+    #[tokio::test]
+    async fn asana_deletion_flags_google_copy_when_configured() {
+        let (asana, google, mut state) =
+            synced(vec![asana_task("100", "Write report", date(2026, 10, 20))]).await;
+        let mut options = sync_options();
+        options.policies.on_asana_delete = AsanaDeletePolicy::Flag;
+
+        asana.delete("100");
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+
+        let tasks = google.tasks();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(
+            tasks[0].title.as_deref(),
+            Some("[Deleted in Asana] Write report")
+        );
+        assert_eq!(google::get_asana_task_gid(&tasks[0]), None);
+        assert!(state.links.is_empty());
+    }
+
+    #[tokio::test]
+    async fn reassigned_tasks_leave_google_until_assigned_back() {
+        let mut options = sync_options();
+        options.policies.only_mine = true;
+        let (asana, google, mut state) =
+            synced(vec![asana_task("100", "Write report", date(2026, 10, 20))]).await;
+
+        asana.update("100", |t| {
+            t.assignee = Some(asana::UserRef {
+                gid: "7".to_string(),
+            })
+        });
+        process_tasks(&asana, &google, &mut state, &options)
+            .await
+            .unwrap();
+        assert!(google.tasks().is_empty());
+        assert!(state.links.is_empty());
+
+        asana.update("100", |t| {
+            t.assignee = Some(asana::UserRef {
+                gid: MOCK_ME.to_string(),
+            })
+        });
+        process_tasks(&asana, &google, &mut state, &options)
+            .await
+            .unwrap();
+        assert_eq!(google.tasks().len(), 1);
+        assert!(state.links.contains_key("100"));
+    }
+
+    /// Options that look for copies moved to other lists, and a synced task whose copy was then
+    /// dragged into another list
+    async fn moved(
+        policy: MisplacedPolicy,
+    ) -> (MockAsana, MockGoogle, MockGoogle, State, SyncOptions) {
+        let (asana, google, state) =
+            synced(vec![asana_task("100", "Pay rent", date(2026, 10, 20))]).await;
+        let mut options = sync_options();
+        options.policies.misplaced = policy;
+
+        let other = google.for_list(&google.create_list("Personal").await.unwrap());
+        let copy = google.tasks().remove(0);
+        google.del_task(copy.id.as_deref().unwrap()).await.unwrap();
+        other
+            .new_task_from_asana(
+                &asana.task("100").unwrap(),
+                copy.title.as_deref().unwrap(),
+                copy.notes.as_deref().unwrap(),
+                None,
+            )
+            .await
+            .unwrap();
+        (asana, google, other, state, options)
+    }
+
+    #[tokio::test]
+    async fn tasks_moved_to_another_list_are_moved_back() {
+        let (asana, google, other, mut state, options) = moved(MisplacedPolicy::Return).await;
+
+        process_tasks(&asana, &google, &mut state, &options)
+            .await
+            .unwrap();
+
+        assert!(other.tasks().is_empty());
+        assert_eq!(google.tasks().len(), 1);
+        assert_eq!(state.links["100"].google_list, "list");
+    }
+
+    #[tokio::test]
+    async fn tasks_moved_to_another_list_are_followed_there() {
+        let (asana, google, other, mut state, options) = moved(MisplacedPolicy::Follow).await;
+
+        for _ in 0..2 {
+            process_tasks(&asana, &google, &mut state, &options)
+                .await
+                .unwrap();
+            assert!(google.tasks().is_empty());
+            assert_eq!(other.tasks().len(), 1);
+            assert!(state.links["100"].moved);
+        }
+
+        other.update(&google_id(&state, "100"), |t| {
+            t.completed = Some("2026-10-16T12:00:00.000Z".to_string());
+        });
+        process_tasks(&asana, &google, &mut state, &options)
+            .await
+            .unwrap();
+        assert!(asana.task("100").unwrap().completed_at.is_some());
+        assert!(google.tasks().is_empty());
+        assert!(state.links.is_empty());
+    }
+
+    #[tokio::test]
+    async fn tasks_leave_google_when_the_search_stops_finding_them() {
+        let options = sync_options();
+        let (mut asana, google, mut state) =
+            synced(vec![asana_task("100", "Write report", date(2026, 10, 20))]).await;
+        asana.source = asana::TaskSource::Search {
+            workspace_gid: "1".to_string(),
+            query: Default::default(),
+        };
+
+        asana.hidden.borrow_mut().insert("100".to_string());
+        process_tasks(&asana, &google, &mut state, &options)
+            .await
+            .unwrap();
+        assert!(google.tasks().is_empty());
+        assert!(state.links.is_empty());
+    }
+
+    #[tokio::test]
+    async fn tasks_leave_google_when_they_stop_matching_the_filter() {
+        let mut options = sync_options();
+        options.policies.diff = Arc::new(
+            TaskFilter::new(&crate::config::FilterConfig {
+                tags: vec!["sync".to_string()],
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+        let sync_tag = || asana::Tag {
+            name: "sync".to_string(),
+        };
+
+        let mut tagged = asana_task("100", "Write report", date(2026, 10, 20));
+        tagged.tags = vec![sync_tag()];
+        let asana = MockAsana::new(vec![
+            tagged,
+            asana_task("101", "Book flights", date(2026, 10, 20)),
+        ]);
+        let google = MockGoogle::new();
+        let mut state = State::default();
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+        assert_eq!(google.tasks().len(), 1);
+        assert!(state.links.contains_key("100"));
+
+        asana.update("100", |t| t.tags.clear());
+        asana.update("101", |t| t.tags = vec![sync_tag()]);
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+
+        let tasks = google.tasks();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].title.as_deref(), Some("Book flights"));
+        assert!(!state.links.contains_key("100"));
+    }
+
+    #[tokio::test]
+    async fn tasks_given_a_due_time_leave_google_for_the_calendar() {
+        let mut options = sync_options();
+        options.policies.timed_to_calendar = true;
+        let (asana, google, mut state) = synced(vec![
+            asana_task("100", "Write report", date(2026, 10, 20)),
+            asana_task("101", "Dentist", date(2026, 10, 21)),
+        ])
+        .await;
+        assert_eq!(google.tasks().len(), 2);
+
+        asana.update("101", |t| {
+            t.due_at = Some("2026-10-21T09:30:00Z".parse().unwrap())
+        });
+        // removed, and not created again
+        for _ in 0..2 {
+            cycle(&asana, &google, &mut state, &options).await.unwrap();
+            let tasks = google.tasks();
+            assert_eq!(tasks.len(), 1);
+            assert_eq!(tasks[0].title.as_deref(), Some("Write report"));
+            assert!(!state.links.contains_key("101"));
+        }
+    }
+
+    #[tokio::test]
+    async fn tasks_follow_the_due_horizon() {
+        use jiff::ToSpan;
+
+        let mut options = sync_options();
+        options.policies.diff = Arc::new(
+            TaskFilter::new(&crate::config::FilterConfig {
+                due_within_days: Some(7),
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+        let today = crate::date::today();
+
+        let asana = MockAsana::new(vec![asana_task("100", "Renew passport", today + 60.days())]);
+        let google = MockGoogle::new();
+        let mut state = State::default();
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+        assert!(google.tasks().is_empty());
+
+        asana.update("100", |t| t.due_on = Some(today + 7.days()));
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+        assert_eq!(google.tasks().len(), 1);
+
+        asana.update("100", |t| t.due_on = Some(today + 8.days()));
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+        assert!(google.tasks().is_empty());
+        assert!(state.links.is_empty());
+    }
+
+    #[tokio::test]
+    async fn google_deletion_recreates_or_completes() {
+        let (asana, google, mut state) = synced(vec![
+            asana_task("100", "Write report", date(2026, 10, 20)),
+            asana_task("200", "Book flights", date(2026, 10, 21)),
+        ])
+        .await;
+
+        google.del_task(&google_id(&state, "100")).await.unwrap();
+        cycle(&asana, &google, &mut state, &sync_options())
+            .await
+            .unwrap();
+        assert_eq!(google.tasks().len(), 2);
+        assert!(asana.task("100").unwrap().completed_at.is_none());
+
+        let mut options = sync_options();
+        options.policies.on_google_delete = GoogleDeletePolicy::Complete;
+        google.del_task(&google_id(&state, "200")).await.unwrap();
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+        assert_eq!(google.tasks().len(), 1);
+        assert!(asana.task("200").unwrap().completed_at.is_some());
+        assert!(!state.links.contains_key("200"));
+    }
+
+    #[tokio::test]
+    async fn mangled_marker_falls_back_to_state_link() {
+        let (asana, google, mut state) =
+            synced(vec![asana_task("100", "Write report", date(2026, 10, 20))]).await;
+        let id = google_id(&state, "100");
+
+        google.update(&id, |t| t.notes = Some(String::new()));
+        google.update(&id, |t| {
+            t.completed = Some("2026-10-16T12:00:00.000Z".to_string())
+        });
+        cycle(&asana, &google, &mut state, &sync_options())
+            .await
+            .unwrap();
+
+        assert!(asana.task("100").unwrap().completed_at.is_some());
+        assert!(google.tasks().is_empty());
+    }
+
+    #[tokio::test]
+    async fn google_completion_wins_over_asana_edit() {
+        let (asana, google, mut state) =
+            synced(vec![asana_task("100", "Write report", date(2026, 10, 20))]).await;
+
+        asana.update("100", |t| t.name = "Write final report".to_string());
+        google.update(&google_id(&state, "100"), |t| {
+            t.completed = Some("2026-10-16T12:00:00.000Z".to_string());
+        });
+        cycle(&asana, &google, &mut state, &sync_options())
+            .await
+            .unwrap();
+
+        assert!(asana.task("100").unwrap().completed_at.is_some());
+        assert!(google.tasks().is_empty());
+        assert!(state.links.is_empty());
+    }
+
+    /// Lets asana edits win conflicts and skips tasks whose name starts with "draft"
+    #[derive(Debug)]
+    struct AsanaWins;
+
+    impl DiffPolicy for AsanaWins {
+        fn include(&self, task: &asana::Task) -> bool {
+            !task.name.starts_with("draft")
+        }
+
+        fn google_completion_wins(&self, _task: &asana::Task, _gtask: &google::Task) -> bool {
+            false
+        }
+    }
+
+    #[tokio::test]
+    async fn custom_diff_policy_is_consulted() {
+        let mut options = sync_options();
+        options.policies.diff = Arc::new(AsanaWins);
+
+        let asana = MockAsana::new(vec![
+            asana_task("100", "Write report", date(2026, 10, 20)),
+            asana_task("101", "draft agenda", date(2026, 10, 20)),
+        ]);
+        let google = MockGoogle::new();
+        let mut state = State::default();
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+        assert_eq!(google.tasks().len(), 1);
+
+        asana.update("100", |t| t.name = "Write final report".to_string());
+        google.update(&google_id(&state, "100"), |t| {
+            t.completed = Some("2026-10-16T12:00:00.000Z".to_string());
+        });
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+
+        assert!(asana.task("100").unwrap().completed_at.is_none());
+        let tasks = google.tasks();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].title.as_deref(), Some("Write final report"));
+        assert!(tasks[0].completed.is_none());
+    }
+
+    #[tokio::test]
+    async fn completed_google_tasks_of_the_users_own_are_left_alone() {
+        let (asana, google, mut state) =
+            synced(vec![asana_task("100", "Write report", date(2026, 10, 20))]).await;
+        let own = asana_task("900", "Water plants", date(2026, 10, 20));
+        let own = google
+            .new_task_from_asana(&own, "Water plants", "", None)
+            .await
+            .unwrap();
+        google.update(&own, |t| {
+            t.completed = Some("2026-10-16T12:00:00.000Z".to_string());
+        });
+
+        cycle(&asana, &google, &mut state, &sync_options())
+            .await
+            .unwrap();
+        assert_eq!(google.tasks().len(), 2);
+
+        let mut options = sync_options();
+        options.policies.delete_unlinked_completed = true;
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+        let tasks = google.tasks();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].title.as_deref(), Some("Write report"));
+    }
+
+    #[tokio::test]
+    async fn completed_google_tasks_are_cleared_in_one_go() {
+        let (asana, google, mut state) = synced(vec![
+            asana_task("100", "Write report", date(2026, 10, 20)),
+            asana_task("101", "Pay rent", date(2026, 10, 21)),
+        ])
+        .await;
+        let id = google_id(&state, "100");
+        google.update(&id, |t| {
+            t.completed = Some("2026-10-16T12:00:00.000Z".to_string());
+        });
+
+        let mut options = sync_options();
+        options.policies.clear_completed = true;
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+        assert!(asana.task("100").unwrap().completed_at.is_some());
+        assert!(!state.links.contains_key("100"));
+        let tasks = google.tasks();
+        assert_eq!(tasks.len(), 2);
+        let cleared = tasks.iter().find(|t| t.id == Some(id.clone())).unwrap();
+        assert_eq!(cleared.hidden, Some(true));
+
+        // the cleared task is done with, asana isn't asked to complete it again
+        asana.failing.borrow_mut().insert("100".to_string());
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn completed_on_both_sides_is_cleaned_up() {
+        let (asana, google, mut state) =
+            synced(vec![asana_task("100", "Write report", date(2026, 10, 20))]).await;
+
+        asana.update("100", |t| t.completed_at = Some(jiff::Timestamp::now()));
+        google.update(&google_id(&state, "100"), |t| {
+            t.completed = Some("2026-10-16T12:00:00.000Z".to_string());
+        });
+        cycle(&asana, &google, &mut state, &sync_options())
+            .await
+            .unwrap();
+
+        assert!(google.tasks().is_empty());
+        assert!(state.links.is_empty());
+    }
+
+    #[tokio::test]
+    async fn mass_deletion_is_refused() {
+        let tasks = (0..10)
+            .map(|i| asana_task(&format!("{i}00"), &format!("Task {i}"), date(2026, 10, 20)))
+            .collect();
+        let (asana, google, mut state) = synced(tasks).await;
+
+        for i in 0..8 {
+            asana.delete(&format!("{i}00"));
+        }
+        assert!(
+            cycle(&asana, &google, &mut state, &sync_options())
+                .await
+                .is_err()
+        );
+        assert_eq!(google.tasks().len(), 10);
+
+        let mut options = sync_options();
+        options.limits.force = true;
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+        assert_eq!(google.tasks().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn dry_run_changes_nothing() {
+        let (asana, google, mut state) =
+            synced(vec![asana_task("100", "Write report", date(2026, 10, 20))]).await;
+        let mut options = sync_options();
+        options.dry_run = true;
+
+        asana.update("100", |t| t.name = "Write final report".to_string());
+        asana
+            .tasks
+            .borrow_mut()
+            .push(asana_task("200", "Book flights", date(2026, 10, 21)));
+        cycle(&asana, &google, &mut state, &options).await.unwrap();
+
+        let tasks = google.tasks();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].title.as_deref(), Some("Write report"));
+        assert_eq!(state.links.len(), 1);
+    }
+}
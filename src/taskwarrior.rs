@@ -0,0 +1,406 @@
+use std::{
+    hash::{BuildHasher, RandomState},
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, Result, bail};
+use jiff::{Timestamp, civil::DateTime, tz::TimeZone};
+use log::debug;
+use serde_json::{Value, json};
+
+use crate::{
+    asana,
+    canonical::{CanonicalTask, Priority, Status},
+    config::TaskwarriorConfig,
+    date,
+    error::BridgeError,
+    google::{GTaskResult, GoogleTasksApi, Task, TaskList},
+};
+
+/// User defined attribute holding the notes, taskwarrior keeps it even when the taskrc doesn't
+/// define it
+pub const NOTES_UDA: &str = "asananotes";
+
+/// Format of every timestamp in taskwarrior's JSON
+const TIMESTAMP: &str = "%Y%m%dT%H%M%SZ";
+
+/// The local taskwarrior database behind the same interface as google tasks, driven through
+/// `task export` and `task import`. A project stands in for the task list, and the notes go in
+/// the [`NOTES_UDA`] attribute. Deleted tasks are marked deleted like `task delete` does, and
+/// taskwarrior orders tasks by urgency itself.
+#[derive(Clone)]
+pub struct TaskwarriorMgr {
+    /// Program and leading arguments, e.g. `["task", "rc:~/.taskrc-work"]`
+    command: Vec<String>,
+    project: String,
+}
+
+impl TaskwarriorMgr {
+    pub fn new(config: &TaskwarriorConfig, project: &str) -> Result<Self> {
+        if config.command.is_empty() {
+            bail!("the taskwarrior command is empty");
+        }
+        Ok(Self {
+            command: config.command.clone(),
+            project: project.to_string(),
+        })
+    }
+
+    /// Run `task` with `args`, feeding it `input`, and return what it printed
+    fn run(&self, args: &[&str], input: Option<&str>) -> Result<String> {
+        let (program, leading) = self
+            .command
+            .split_first()
+            .context("the taskwarrior command is empty")?;
+
+        let mut child = Command::new(program)
+            .args(leading)
+            // nothing to confirm or chat about, the output is parsed
+            .args([
+                "rc.confirmation=off",
+                "rc.verbose=nothing",
+                "rc.json.array=on",
+            ])
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("failed to run {program}"))?;
+        if let Some(input) = input {
+            child
+                .stdin
+                .take()
+                .context("no stdin to write to")?
+                .write_all(input.as_bytes())?;
+        }
+        drop(child.stdin.take());
+
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            bail!(
+                "{program} {} failed ({}): {}",
+                args.join(" "),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        String::from_utf8(output.stdout).context("taskwarrior printed invalid utf-8")
+    }
+
+    fn export(&self, filter: &[&str]) -> Result<Vec<Value>> {
+        let mut args = filter.to_vec();
+        args.push("export");
+        let out = self.run(&args, None)?;
+        serde_json::from_str(&out).context("failed to parse the taskwarrior export")
+    }
+
+    /// The task with the uuid `id`, with every attribute it has so none are lost on import
+    fn export_one(&self, id: &str) -> Result<Value> {
+        self.export(&[id])?
+            .into_iter()
+            .next()
+            .with_context(|| format!("no taskwarrior task {id}"))
+    }
+
+    fn import(&self, task: &Value) -> Result<()> {
+        self.run(&["import", "-"], Some(&serde_json::to_string(&[task])?))?;
+        Ok(())
+    }
+
+    /// Change a task's attributes, failing when it was modified since it showed `etag`
+    fn modify(&self, id: &str, etag: Option<&str>, change: impl FnOnce(&mut Value)) -> Result<()> {
+        let mut task = self.export_one(id)?;
+        if let Some(etag) = etag
+            && task["modified"].as_str() != Some(etag)
+        {
+            return Err(BridgeError::Conflict(format!(
+                "taskwarrior task {id} was edited since it was read"
+            ))
+            .into());
+        }
+
+        change(&mut task);
+        task["modified"] = json!(format_timestamp(Timestamp::now()));
+        self.import(&task)
+    }
+}
+
+impl GoogleTasksApi for TaskwarriorMgr {
+    fn for_list(&self, list_id: &str) -> Self {
+        Self {
+            command: self.command.clone(),
+            project: list_id.to_string(),
+        }
+    }
+
+    fn list_id(&self) -> &str {
+        &self.project
+    }
+
+    async fn get_lists(&self) -> Result<Vec<TaskList>> {
+        Ok(self
+            .run(&["_projects"], None)?
+            .lines()
+            .filter(|p| !p.is_empty())
+            .map(|p| TaskList {
+                id: Some(p.to_string()),
+                title: Some(p.to_string()),
+                ..Default::default()
+            })
+            .collect())
+    }
+
+    async fn create_list(&self, title: &str) -> Result<String> {
+        // projects exist as long as a task names them
+        Ok(title.to_string())
+    }
+
+    async fn delete_list(&self, _id: &str) -> Result<()> {
+        Ok(())
+    }
+
+    async fn new_task_from_asana(
+        &self,
+        task: &asana::Task,
+        title: &str,
+        notes: &str,
+        parent: Option<&str>,
+    ) -> Result<String> {
+        if parent.is_some() {
+            debug!("adding \"{title}\" to taskwarrior as a task of its own");
+        }
+
+        let uuid = new_uuid();
+        let now = format_timestamp(Timestamp::now());
+        self.import(&json!({
+            "uuid": uuid,
+            "status": "pending",
+            "entry": now,
+            "modified": now,
+            "project": self.project,
+            "description": title,
+            "due": due(task)?,
+            NOTES_UDA: notes,
+        }))?;
+        Ok(uuid)
+    }
+
+    async fn update_from_asana(
+        &self,
+        id: &str,
+        etag: Option<&str>,
+        task: &asana::Task,
+        title: &str,
+        notes: &str,
+    ) -> Result<bool> {
+        let due = due(task)?;
+        self.modify(id, etag, |t| {
+            t["description"] = json!(title);
+            t[NOTES_UDA] = json!(notes);
+            t["due"] = json!(due);
+        })?;
+        Ok(true)
+    }
+
+    async fn set_text(&self, id: &str, title: &str, notes: &str) -> Result<()> {
+        self.modify(id, None, |t| {
+            t["description"] = json!(title);
+            t[NOTES_UDA] = json!(notes);
+        })
+    }
+
+    async fn move_to_top_level(&self, _id: &str) -> Result<()> {
+        // the bridge doesn't add dependencies, every task is at the top level
+        Ok(())
+    }
+
+    async fn move_after(&self, _id: &str, _previous: Option<&str>) -> Result<()> {
+        // reports sort by urgency
+        Ok(())
+    }
+
+    async fn get_tasks(&self) -> Result<GTaskResult> {
+        // `project:` would match its subprojects too
+        let project = format!("project.is:{}", self.project);
+        let tasks = self.export(&[
+            &project,
+            "(",
+            "status:pending",
+            "or",
+            "status:waiting",
+            "or",
+            "status:completed",
+            ")",
+        ])?;
+
+        let mut result = GTaskResult {
+            incomplete: Vec::new(),
+            complete: Vec::new(),
+        };
+        for task in tasks.iter().map(canonical) {
+            let task = Task::from(task?);
+            if task.completed.is_some() {
+                result.complete.push(task);
+            } else {
+                result.incomplete.push(task);
+            }
+        }
+
+        Ok(result)
+    }
+
+    async fn del_task(&self, id: &str) -> Result<()> {
+        self.modify(id, None, |t| {
+            t["status"] = json!("deleted");
+            t["end"] = json!(format_timestamp(Timestamp::now()));
+        })
+    }
+
+    async fn del_task_if(&self, id: &str, etag: &str) -> Result<()> {
+        self.modify(id, Some(etag), |t| {
+            t["status"] = json!("deleted");
+            t["end"] = json!(format_timestamp(Timestamp::now()));
+        })
+    }
+}
+
+/// The exported task, with its modification time standing in for the revision
+fn canonical(task: &Value) -> Result<CanonicalTask> {
+    let due = match task["due"].as_str() {
+        // due dates are set at local midnight
+        Some(due) => Some(date::local_date(parse_timestamp(due)?)),
+        None => None,
+    };
+    let status = if task["status"] == "completed" {
+        Status::Done(
+            task["end"]
+                .as_str()
+                .and_then(|end| parse_timestamp(end).ok())
+                .map(|end| end.to_string()),
+        )
+    } else {
+        Status::Open
+    };
+    let strings = |key: &str| {
+        task[key]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|v| v.as_str().map(String::from))
+            .collect()
+    };
+
+    Ok(CanonicalTask {
+        id: task["uuid"].as_str().map(String::from),
+        title: task["description"].as_str().unwrap_or_default().to_string(),
+        body: task[NOTES_UDA].as_str().unwrap_or_default().to_string(),
+        due,
+        status,
+        tags: strings("tags"),
+        priority: match task["priority"].as_str() {
+            Some("L") => Some(Priority::Low),
+            Some("M") => Some(Priority::Medium),
+            Some("H") => Some(Priority::High),
+            _ => None,
+        },
+        revision: task["modified"].as_str().map(String::from),
+        ..Default::default()
+    })
+}
+
+/// Local midnight of the asana task's due day
+fn due(task: &asana::Task) -> Result<String> {
+    let day = task
+        .due_date()
+        .context("Somehow got to taskwarrior with no due date")?;
+    Ok(format_timestamp(
+        day.to_zoned(date::local_tz().clone())?.timestamp(),
+    ))
+}
+
+fn format_timestamp(ts: Timestamp) -> String {
+    ts.strftime(TIMESTAMP).to_string()
+}
+
+fn parse_timestamp(s: &str) -> Result<Timestamp> {
+    let utc = DateTime::strptime(TIMESTAMP, s)
+        .with_context(|| format!("invalid taskwarrior timestamp {s}"))?;
+    Ok(utc.to_zoned(TimeZone::UTC)?.timestamp())
+}
+
+/// Random version 4 uuid, which taskwarrior needs to find the task again
+fn new_uuid() -> String {
+    let state = RandomState::new();
+    let high = state.hash_one(Timestamp::now());
+    let low = state.hash_one(high);
+    let bits = (u128::from(high) << 64 | u128::from(low)) & !(0xf << 76) & !(0x3 << 62)
+        | (0x4 << 76)
+        | (0x2 << 62);
+    let hex = format!("{bits:032x}");
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::google;
+
+    #[test]
+    fn exported_tasks_look_like_google_tasks() {
+        let due = jiff::civil::date(2026, 10, 20)
+            .to_zoned(date::local_tz().clone())
+            .unwrap()
+            .timestamp();
+        let exported = json!({
+            "uuid": "5e7a4c0e-4d4b-4f47-9d7e-2b9b3c1d9e10",
+            "status": "pending",
+            "description": "Write report",
+            "due": format_timestamp(due),
+            "modified": "20261016T090000Z",
+            "tags": ["home"],
+            "priority": "H",
+            NOTES_UDA: "Draft\n---\n1208834419234561"
+        });
+
+        let task = canonical(&exported).unwrap();
+        assert_eq!(task.tags, ["home"]);
+        assert_eq!(task.priority, Some(Priority::High));
+        let task = Task::from(task);
+        assert_eq!(
+            google::get_asana_task_gid(&task).as_deref(),
+            Some("1208834419234561")
+        );
+        assert_eq!(task.due.as_deref(), Some("2026-10-20T00:00:00.000Z"));
+        assert_eq!(task.etag.as_deref(), Some("20261016T090000Z"));
+        assert!(task.completed.is_none());
+
+        let done = json!({
+            "uuid": "5e7a4c0e-4d4b-4f47-9d7e-2b9b3c1d9e11",
+            "status": "completed",
+            "description": "Pay rent",
+            "end": "20261016T120000Z"
+        });
+        assert_eq!(
+            Task::from(canonical(&done).unwrap()).completed.as_deref(),
+            Some("2026-10-16T12:00:00Z")
+        );
+    }
+
+    #[test]
+    fn uuids_are_version_4() {
+        let uuid = new_uuid();
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(&uuid[14..15], "4");
+        assert!(matches!(&uuid[19..20], "8" | "9" | "a" | "b"));
+        assert_ne!(uuid, new_uuid());
+    }
+}
@@ -0,0 +1,248 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    sync::Mutex,
+};
+
+use anyhow::{Context, Result};
+use jiff::Timestamp;
+use log::warn;
+use serde_json::Value;
+
+use crate::logging::USER;
+
+/// Size the trace file grows to before it's rotated
+const MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Rotated trace files kept next to the current one, `.1` being the latest
+const KEEP: usize = 3;
+
+/// Longest body written, the rest is cut off
+const MAX_BODY_BYTES: usize = 64 * 1024;
+
+/// Written instead of a secret
+const REDACTED: &str = "[redacted]";
+
+/// Where requests and responses are written with `--trace-http`, nowhere without
+static TRACE: Mutex<Option<Trace>> = Mutex::new(None);
+
+/// A trace file, rotated once it grows past `max_bytes`
+struct Trace {
+    path: String,
+    file: File,
+    written: u64,
+    max_bytes: u64,
+}
+
+impl Trace {
+    fn open(path: &str, max_bytes: u64) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("failed to open {path}"))?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_string(),
+            file,
+            written,
+            max_bytes,
+        })
+    }
+
+    fn write(&mut self, entry: &str) -> Result<()> {
+        self.file.write_all(entry.as_bytes())?;
+        self.written += entry.len() as u64;
+        if self.written >= self.max_bytes {
+            self.rotate()?;
+        }
+        Ok(())
+    }
+
+    /// Move the file to `.1`, the one there to `.2` and so on, dropping the oldest
+    fn rotate(&mut self) -> Result<()> {
+        let path = self.path.clone();
+        for i in (1..KEEP).rev() {
+            let from = format!("{path}.{i}");
+            if std::fs::exists(&from)? {
+                std::fs::rename(&from, format!("{path}.{}", i + 1))?;
+            }
+        }
+        std::fs::rename(&path, format!("{path}.1"))?;
+        *self = Self::open(&path, self.max_bytes)?;
+        Ok(())
+    }
+}
+
+/// Write every request sent to the APIs and its response to `path`, with secrets redacted
+pub fn enable(path: &str) -> Result<()> {
+    *TRACE.lock().unwrap() = Some(Trace::open(path, MAX_BYTES)?);
+    Ok(())
+}
+
+pub fn enabled() -> bool {
+    TRACE.lock().unwrap().is_some()
+}
+
+/// Note one request, `target` being its url or whatever else names what it went to. The status is
+/// left out when no response came.
+pub fn exchange(
+    method: &str,
+    target: &str,
+    status: Option<u16>,
+    request: Option<&[u8]>,
+    response: Option<&[u8]>,
+) {
+    let mut trace = TRACE.lock().unwrap();
+    let Some(file) = trace.as_mut() else {
+        return;
+    };
+    let entry = entry(Timestamp::now(), method, target, status, request, response);
+    if let Err(e) = file.write(&entry) {
+        warn!("Failed to write the http trace, no longer tracing: {e:#}");
+        *trace = None;
+    }
+}
+
+fn entry(
+    now: Timestamp,
+    method: &str,
+    target: &str,
+    status: Option<u16>,
+    request: Option<&[u8]>,
+    response: Option<&[u8]>,
+) -> String {
+    let user = USER
+        .try_with(|name| format!("{name}: "))
+        .unwrap_or_default();
+    let status = status.map_or("no response".to_string(), |s| s.to_string());
+    let mut entry = format!(
+        "--- {now} {user}{method} {} -> {status}\n",
+        redact_url(target)
+    );
+    for (arrow, body) in [(">", request), ("<", response)] {
+        if let Some(body) = body.filter(|b| !b.is_empty()) {
+            entry.push_str(&format!("{arrow} {}\n", redact_body(body)));
+        }
+    }
+    entry
+}
+
+/// Whether a field or parameter called `name` holds something that mustn't end up in a trace
+fn is_secret(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    ["token", "secret", "password", "assertion", "authorization"]
+        .iter()
+        .any(|part| name.contains(part))
+        || ["key", "api_key", "apikey", "code", "device_code"].contains(&name.as_str())
+}
+
+/// `url` with the values of secret query parameters, like trello's key and token, redacted
+fn redact_url(url: &str) -> String {
+    let Some((base, query)) = url.split_once('?') else {
+        return url.to_string();
+    };
+    format!("{base}?{}", redact_form(query))
+}
+
+/// Redact the secret pairs of a form encoded body or query
+fn redact_form(form: &str) -> String {
+    form.split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _)) if is_secret(name) => format!("{name}={REDACTED}"),
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// A body as text, with the secrets in it redacted and cut off at [`MAX_BODY_BYTES`]
+fn redact_body(body: &[u8]) -> String {
+    let text = match serde_json::from_slice::<Value>(body) {
+        Ok(mut json) => {
+            redact_json(&mut json);
+            json.to_string()
+        }
+        Err(_) => {
+            let text = String::from_utf8_lossy(body);
+            // token requests and responses are form encoded
+            if text.contains('=') && !text.contains(char::is_whitespace) {
+                redact_form(&text)
+            } else {
+                text.into_owned()
+            }
+        }
+    };
+    if text.len() <= MAX_BODY_BYTES {
+        return text;
+    }
+    let mut end = MAX_BODY_BYTES;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}... ({} bytes)", &text[..end], text.len())
+}
+
+fn redact_json(json: &mut Value) {
+    match json {
+        Value::Object(fields) => {
+            for (name, value) in fields {
+                if is_secret(name) && !value.is_null() {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    redact_json(value);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_json),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secrets_are_redacted() {
+        let entry = entry(
+            "2026-10-16T12:00:00Z".parse().unwrap(),
+            "POST",
+            "https://api.trello.com/1/cards?idList=1&key=abc&token=def",
+            Some(200),
+            Some(b"grant_type=refresh_token&refresh_token=1//0g&client_secret=shh"),
+            Some(
+                br#"{"access_token": "ya29", "expires_in": 3599, "data": [{"name": "Pay rent"}]}"#,
+            ),
+        );
+        assert_eq!(
+            entry,
+            "--- 2026-10-16T12:00:00Z POST https://api.trello.com/1/cards?idList=1&key=[redacted]&token=[redacted] -> 200\n\
+             > grant_type=refresh_token&refresh_token=[redacted]&client_secret=[redacted]\n\
+             < {\"access_token\":\"[redacted]\",\"data\":[{\"name\":\"Pay rent\"}],\"expires_in\":3599}\n"
+        );
+    }
+
+    #[test]
+    fn the_trace_is_rotated_once_it_grows_too_big() {
+        let path = std::env::temp_dir().join("http_trace_rotation.log");
+        let path = path.to_str().unwrap();
+        for i in 0..=KEEP {
+            let _ = std::fs::remove_file(format!("{path}.{i}"));
+        }
+        let _ = std::fs::remove_file(path);
+
+        let mut trace = Trace::open(path, 100).unwrap();
+        for _ in 0..KEEP + 2 {
+            trace.write(&"x".repeat(60)).unwrap();
+            trace.write(&"y".repeat(60)).unwrap();
+        }
+        assert!(std::fs::exists(format!("{path}.{KEEP}")).unwrap());
+        assert!(!std::fs::exists(format!("{path}.{}", KEEP + 1)).unwrap());
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "");
+        assert_eq!(
+            std::fs::read_to_string(format!("{path}.1")).unwrap().len(),
+            120
+        );
+    }
+}
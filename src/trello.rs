@@ -0,0 +1,484 @@
+use anyhow::{Context, Result};
+use log::debug;
+use reqwest::{RequestBuilder, Response, header::HeaderMap};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    asana,
+    canonical::{CanonicalTask, Status},
+    config::TrelloConfig,
+    error::BridgeError,
+    google::{self, GTaskResult, GoogleTasksApi, Task, TaskList},
+    http,
+};
+
+/// Trello's API root, overridable to point at a test server
+pub const DEFAULT_BASE_URL: &str = "https://api.trello.com/1";
+
+const CARD_FIELDS: &str = "name,desc,due,dueComplete,dateLastActivity,pos";
+
+/// Gap trello leaves between the positions of cards added at the bottom
+const POS_STEP: f64 = 65536.0;
+
+/// A trello board behind the same interface as google tasks, with the board's lists standing in
+/// for task lists and cards for tasks. Cards the bridge deletes are archived instead, and
+/// completed cards are those with their due date marked complete. Trello has no subtasks, so
+/// flattened subtasks end up as cards of their own.
+#[derive(Clone)]
+pub struct TrelloMgr {
+    client: reqwest::Client,
+    base_url: String,
+    key: String,
+    token: String,
+    board: String,
+    list_id: String,
+}
+
+impl TrelloMgr {
+    pub async fn new(config: &TrelloConfig, list: &str) -> Result<Self> {
+        let board = config
+            .board
+            .as_deref()
+            .context("no trello board configured")?;
+        Self::connect(Self::unbound(config).await?.for_board(board), list).await
+    }
+
+    /// Manager that isn't bound to a board or list yet, for picking them with [`Self::boards`],
+    /// [`Self::for_board`] and [`GoogleTasksApi::get_lists`]
+    pub async fn unbound(config: &TrelloConfig) -> Result<Self> {
+        let token = config
+            .token
+            .as_ref()
+            .context("no trello token configured")?
+            .resolve()
+            .await
+            .context("failed to load the trello token")?;
+
+        Ok(Self {
+            client: http::client(HeaderMap::new())?,
+            base_url: DEFAULT_BASE_URL.to_string(),
+            key: config.key.clone().context("no trello api key configured")?,
+            token,
+            board: String::new(),
+            list_id: String::new(),
+        })
+    }
+
+    /// Manager for the lists of another board, by id or the short link in its url
+    pub fn for_board(&self, board: &str) -> Self {
+        Self {
+            board: board.to_string(),
+            ..self.clone()
+        }
+    }
+
+    /// Open boards of the token's member, as lists so their names and ids can be picked from
+    pub async fn boards(&self) -> Result<Vec<TaskList>> {
+        let boards: Vec<Named> = self
+            .send(
+                self.client
+                    .get(self.url("members/me/boards"))
+                    .query(&[("filter", "open"), ("fields", "name")]),
+                "fetch boards",
+            )
+            .await?
+            .json()
+            .await?;
+
+        Ok(boards.into_iter().map(Named::into_list).collect())
+    }
+
+    /// Bind `mgr` to the list `list` names on its board, see [`google::find_list`]
+    async fn connect(mut mgr: Self, list: &str) -> Result<Self> {
+        mgr.list_id = google::find_list(&mgr.get_lists().await?, list)
+            .context("failed to pick the trello list")?;
+        Ok(mgr)
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{path}", self.base_url)
+    }
+
+    async fn send(&self, request: RequestBuilder, what: &str) -> Result<Response> {
+        let request = request.query(&[("key", &self.key), ("token", &self.token)]);
+        Ok(http::send(request, what).await?)
+    }
+
+    async fn get_cards(&self) -> Result<Vec<Card>> {
+        Ok(self
+            .send(
+                self.client
+                    .get(self.url(&format!("lists/{}/cards", self.list_id)))
+                    .query(&[("fields", CARD_FIELDS)]),
+                "fetch cards",
+            )
+            .await?
+            .json()
+            .await?)
+    }
+
+    async fn edit_card(&self, id: &str, body: serde_json::Value) -> Result<()> {
+        self.send(
+            self.client
+                .put(self.url(&format!("cards/{id}")))
+                .json(&body),
+            "edit card",
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Fail unless the card's last activity is still the one it showed as its etag, trello has
+    /// no conditional requests
+    async fn check_unchanged(&self, id: &str, etag: &str) -> Result<()> {
+        let card: Card = self
+            .send(
+                self.client
+                    .get(self.url(&format!("cards/{id}")))
+                    .query(&[("fields", CARD_FIELDS)]),
+                "fetch card",
+            )
+            .await?
+            .json()
+            .await?;
+        if card.date_last_activity.as_deref() != Some(etag) {
+            return Err(BridgeError::Conflict(format!(
+                "trello card {id} was edited since it was read"
+            ))
+            .into());
+        }
+        Ok(())
+    }
+}
+
+impl GoogleTasksApi for TrelloMgr {
+    fn for_list(&self, list_id: &str) -> Self {
+        Self {
+            list_id: list_id.to_string(),
+            ..self.clone()
+        }
+    }
+
+    fn list_id(&self) -> &str {
+        &self.list_id
+    }
+
+    async fn get_lists(&self) -> Result<Vec<TaskList>> {
+        let lists: Vec<Named> = self
+            .send(
+                self.client
+                    .get(self.url(&format!("boards/{}/lists", self.board)))
+                    .query(&[("filter", "open"), ("fields", "name")]),
+                "fetch lists",
+            )
+            .await?
+            .json()
+            .await?;
+
+        Ok(lists.into_iter().map(Named::into_list).collect())
+    }
+
+    async fn create_list(&self, title: &str) -> Result<String> {
+        let created: Named = self
+            .send(
+                self.client
+                    .post(self.url("lists"))
+                    .json(&json!({"name": title, "idBoard": self.board, "pos": "bottom"})),
+                "create list",
+            )
+            .await?
+            .json()
+            .await?;
+        Ok(created.id)
+    }
+
+    async fn delete_list(&self, id: &str) -> Result<()> {
+        // trello lists can only be archived
+        self.send(
+            self.client
+                .put(self.url(&format!("lists/{id}/closed")))
+                .json(&json!({"value": true})),
+            "archive list",
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn new_task_from_asana(
+        &self,
+        task: &asana::Task,
+        title: &str,
+        notes: &str,
+        parent: Option<&str>,
+    ) -> Result<String> {
+        if parent.is_some() {
+            debug!("trello has no subtasks, adding \"{title}\" as a card of its own");
+        }
+
+        let created: Named = self
+            .send(
+                self.client.post(self.url("cards")).json(&json!({
+                    "idList": self.list_id,
+                    "name": title,
+                    "desc": notes,
+                    "due": due(task)?,
+                    "pos": "bottom",
+                })),
+                "create card",
+            )
+            .await?
+            .json()
+            .await?;
+        Ok(created.id)
+    }
+
+    async fn update_from_asana(
+        &self,
+        id: &str,
+        etag: Option<&str>,
+        task: &asana::Task,
+        title: &str,
+        notes: &str,
+    ) -> Result<bool> {
+        if let Some(etag) = etag {
+            self.check_unchanged(id, etag).await?;
+        }
+        self.edit_card(id, json!({"name": title, "desc": notes, "due": due(task)?}))
+            .await?;
+        Ok(true)
+    }
+
+    async fn set_text(&self, id: &str, title: &str, notes: &str) -> Result<()> {
+        self.edit_card(id, json!({"name": title, "desc": notes}))
+            .await
+    }
+
+    async fn move_to_top_level(&self, _id: &str) -> Result<()> {
+        // every card is already at the top level
+        Ok(())
+    }
+
+    async fn move_after(&self, id: &str, previous: Option<&str>) -> Result<()> {
+        let pos = match previous {
+            None => json!("top"),
+            Some(previous) => {
+                let cards = self.get_cards().await?;
+                let at = cards
+                    .iter()
+                    .position(|c| c.id == previous)
+                    .with_context(|| format!("card {previous} isn't in the list"))?;
+                let next = cards[at + 1..].iter().find(|c| c.id != id);
+                match next {
+                    Some(next) => json!((cards[at].pos + next.pos) / 2.0),
+                    None => json!(cards[at].pos + POS_STEP),
+                }
+            }
+        };
+
+        self.edit_card(id, json!({"pos": pos})).await
+    }
+
+    async fn get_tasks(&self) -> Result<GTaskResult> {
+        let mut result = GTaskResult {
+            incomplete: Vec::new(),
+            complete: Vec::new(),
+        };
+        for task in self
+            .get_cards()
+            .await?
+            .into_iter()
+            .map(|card| Task::from(CanonicalTask::from(card)))
+        {
+            if task.completed.is_some() {
+                result.complete.push(task);
+            } else {
+                result.incomplete.push(task);
+            }
+        }
+
+        Ok(result)
+    }
+
+    async fn del_task(&self, id: &str) -> Result<()> {
+        // archived cards aren't fetched with the list's, and can still be restored in trello
+        self.edit_card(id, json!({"closed": true})).await
+    }
+
+    async fn del_task_if(&self, id: &str, etag: &str) -> Result<()> {
+        self.check_unchanged(id, etag).await?;
+        self.del_task(id).await
+    }
+}
+
+/// Due timestamp of a card for the asana task's due day. Noon UTC shows as the same day in
+/// almost every timezone, trello shows due dates in the viewer's.
+fn due(task: &asana::Task) -> Result<String> {
+    let due = asana::asana_due_to_string(task)?;
+    Ok(format!("{}T12:00:00.000Z", &due[..10]))
+}
+
+#[derive(Deserialize)]
+struct Named {
+    id: String,
+    name: String,
+}
+
+impl Named {
+    fn into_list(self) -> TaskList {
+        TaskList {
+            id: Some(self.id),
+            title: Some(self.name),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Card {
+    id: String,
+    name: String,
+    #[serde(default)]
+    desc: String,
+    due: Option<String>,
+    #[serde(default)]
+    due_complete: bool,
+    date_last_activity: Option<String>,
+    #[serde(default)]
+    pos: f64,
+}
+
+/// Its last activity stands in for the revision, and for the completion time of completed cards
+impl From<Card> for CanonicalTask {
+    fn from(card: Card) -> Self {
+        let status = if card.due_complete {
+            Status::Done(card.date_last_activity.clone())
+        } else {
+            Status::Open
+        };
+
+        CanonicalTask {
+            id: Some(card.id),
+            title: card.name,
+            body: card.desc,
+            due: card.due.as_deref().and_then(|d| d.get(..10)?.parse().ok()),
+            status,
+            revision: card.date_last_activity,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{body_partial_json, method, path, query_param},
+    };
+
+    use super::*;
+
+    async fn trello(server: &MockServer) -> TrelloMgr {
+        Mock::given(method("GET"))
+            .and(path("/boards/b1/lists"))
+            .and(query_param("key", "key"))
+            .and(query_param("token", "token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                {"id": "l1", "name": "To do"},
+                {"id": "l2", "name": "Asana"}
+            ])))
+            .mount(server)
+            .await;
+
+        let mgr = TrelloMgr {
+            client: http::client(HeaderMap::new()).unwrap(),
+            base_url: server.uri(),
+            key: "key".to_string(),
+            token: "token".to_string(),
+            board: "b1".to_string(),
+            list_id: String::new(),
+        };
+        TrelloMgr::connect(mgr, "asana").await.unwrap()
+    }
+
+    fn cards() -> serde_json::Value {
+        json!([{
+            "id": "c1",
+            "name": "Write report",
+            "desc": "Draft\n---\n1208834419234561",
+            "due": "2026-10-20T12:00:00.000Z",
+            "dueComplete": false,
+            "dateLastActivity": "2026-10-16T09:00:00.000Z",
+            "pos": 16384
+        }, {
+            "id": "c2",
+            "name": "Pay rent",
+            "dueComplete": true,
+            "dateLastActivity": "2026-10-16T12:00:00.000Z",
+            "pos": 32768
+        }, {
+            "id": "c3",
+            "name": "Call the bank",
+            "pos": 65536
+        }])
+    }
+
+    #[tokio::test]
+    async fn cards_look_like_google_tasks() {
+        let server = MockServer::start().await;
+        let trello = trello(&server).await;
+        assert_eq!(trello.list_id(), "l2");
+        Mock::given(method("GET"))
+            .and(path("/lists/l2/cards"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(cards()))
+            .mount(&server)
+            .await;
+
+        let tasks = trello.get_tasks().await.unwrap();
+        assert_eq!(tasks.incomplete.len(), 2);
+        let task = &tasks.incomplete[0];
+        assert_eq!(
+            google::get_asana_task_gid(task).as_deref(),
+            Some("1208834419234561")
+        );
+        assert_eq!(task.due.as_deref(), Some("2026-10-20T00:00:00.000Z"));
+        assert_eq!(tasks.complete.len(), 1);
+        assert_eq!(tasks.complete[0].title.as_deref(), Some("Pay rent"));
+    }
+
+    #[tokio::test]
+    async fn cards_are_placed_between_their_neighbours() {
+        let server = MockServer::start().await;
+        let trello = trello(&server).await;
+        Mock::given(method("GET"))
+            .and(path("/lists/l2/cards"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(cards()))
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/cards/c3"))
+            .and(body_partial_json(json!({"pos": 24576.0})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        trello.move_after("c3", Some("c1")).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn deleted_cards_are_archived() {
+        let server = MockServer::start().await;
+        let trello = trello(&server).await;
+        Mock::given(method("PUT"))
+            .and(path("/cards/c1"))
+            .and(body_partial_json(json!({"closed": true})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        trello.del_task("c1").await.unwrap();
+    }
+}
@@ -0,0 +1,257 @@
+use std::{cell::RefCell, time::Duration};
+
+use anyhow::Result;
+use gtasks_asana_bridge::{
+    AsanaApi, GoogleTasksApi, SyncEngine,
+    breaker::BackendFailing,
+    control, date, http, logging,
+    status::{self, LinkStatus},
+    sync::FailedActions,
+    watchdog::Stalled,
+};
+use jiff::Timestamp;
+use log::warn;
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Modifier, Style},
+    widgets::{Block, List, Paragraph, Row, Table},
+};
+use tokio::sync::Notify;
+
+/// How often the screen is redrawn and keys are read
+const TICK: Duration = Duration::from_millis(250);
+
+/// What the dashboard shows besides the log and rate limits, refreshed after every cycle
+#[derive(Default)]
+struct Dashboard {
+    links: Vec<LinkStatus>,
+    /// Asana tasks without a copy yet
+    new: Vec<String>,
+    /// Failing actions from the state store
+    failures: Vec<String>,
+    /// When the last cycle ended, and whether every action in it went through
+    last_cycle: Option<(Timestamp, bool)>,
+    next_cycle: Option<Timestamp>,
+    syncing: bool,
+}
+
+/// Run the sync loop behind a live view of the links, what the next cycle is to fix, recent
+/// errors and the rate limits the APIs reported, until `q` is pressed. `s` syncs right away, `p`
+/// pauses or resumes syncing.
+pub async fn run<A: AsanaApi, G: GoogleTasksApi>(
+    engine: &SyncEngine<A, G>,
+    interval: Duration,
+) -> Result<()> {
+    let dashboard = RefCell::new(Dashboard::default());
+    let sync_now = Notify::new();
+
+    let mut terminal = ratatui::try_init()?;
+    // log lines would scribble over the screen, the latest warnings are shown in it instead
+    logging::capture();
+    let result = tokio::select! {
+        result = show(&mut terminal, &dashboard, &sync_now) => result,
+        result = sync(engine, &dashboard, &sync_now, interval) => result,
+    };
+    ratatui::restore();
+    logging::release();
+    result
+}
+
+/// Redraw every tick and act on key presses, until one quits
+async fn show(
+    terminal: &mut DefaultTerminal,
+    dashboard: &RefCell<Dashboard>,
+    sync_now: &Notify,
+) -> Result<()> {
+    loop {
+        terminal.draw(|frame| draw(frame, &dashboard.borrow()))?;
+        while event::poll(Duration::ZERO)? {
+            if let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Char('s') => sync_now.notify_one(),
+                    KeyCode::Char('p') if control::is_paused() => control::resume(),
+                    KeyCode::Char('p') => control::pause(),
+                    _ => {}
+                }
+            }
+        }
+        tokio::time::sleep(TICK).await;
+    }
+}
+
+/// The sync loop of [`SyncEngine::run_forever`], refreshing the dashboard after every cycle and
+/// cutting the wait short when asked to
+async fn sync<A: AsanaApi, G: GoogleTasksApi>(
+    engine: &SyncEngine<A, G>,
+    dashboard: &RefCell<Dashboard>,
+    sync_now: &Notify,
+    interval: Duration,
+) -> Result<()> {
+    loop {
+        dashboard.borrow_mut().syncing = true;
+        let ok = match engine.run_once().await {
+            Err(e) if e.is::<FailedActions>() || e.is::<Stalled>() || e.is::<BackendFailing>() => {
+                warn!("{e:#}");
+                false
+            }
+            cycle => cycle.map(|()| true)?,
+        };
+        refresh(engine, dashboard).await;
+
+        let now = Timestamp::now();
+        {
+            let mut dashboard = dashboard.borrow_mut();
+            dashboard.syncing = false;
+            dashboard.last_cycle = Some((now, ok));
+            dashboard.next_cycle = now.checked_add(interval).ok();
+        }
+        tokio::select! {
+            () = tokio::time::sleep(interval) => {}
+            () = sync_now.notified() => {}
+        }
+    }
+}
+
+/// Compare the links with the live tasks again, and pick up the failing actions
+async fn refresh<A: AsanaApi, G: GoogleTasksApi>(
+    engine: &SyncEngine<A, G>,
+    dashboard: &RefCell<Dashboard>,
+) {
+    let Some(state) = engine.state() else {
+        return;
+    };
+    let live = status::live_status(engine.source(), engine.backend(), &state).await;
+
+    let mut dashboard = dashboard.borrow_mut();
+    match live {
+        Ok((links, new)) => {
+            dashboard.links = links;
+            dashboard.new = new;
+        }
+        Err(e) => warn!("Failed to compare the links with the live tasks: {e:#}"),
+    }
+    dashboard.failures = state
+        .failures
+        .values()
+        .map(|failure| {
+            let parked = if failure.parked { ", parked" } else { "" };
+            format!(
+                "{} ({} times{parked}): {}",
+                failure.action, failure.count, failure.error
+            )
+        })
+        .collect();
+}
+
+fn draw(frame: &mut Frame, dashboard: &Dashboard) {
+    let [header, body] =
+        Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(frame.area());
+    let [links, side] =
+        Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(body);
+    let [pending, errors, limits] = Layout::vertical([
+        Constraint::Percentage(35),
+        Constraint::Percentage(40),
+        Constraint::Percentage(25),
+    ])
+    .areas(side);
+
+    frame.render_widget(Paragraph::new(summary(dashboard)), header);
+
+    let bold = Style::new().add_modifier(Modifier::BOLD);
+    let rows = dashboard.links.iter().map(|link| {
+        Row::new([
+            link.asana_name.clone(),
+            link.pending.clone().unwrap_or_default(),
+            local_time(link.last_synced, "%m-%d %H:%M"),
+        ])
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Fill(1),
+            Constraint::Length(20),
+            Constraint::Length(11),
+        ],
+    )
+    .header(Row::new(["Task", "Pending", "Synced"]).style(bold))
+    .block(Block::bordered().title(format!(" Links ({}) ", dashboard.links.len())));
+    frame.render_widget(table, links);
+
+    let planned: Vec<String> = dashboard
+        .new
+        .iter()
+        .map(|name| format!("{name}: new in asana"))
+        .chain(dashboard.links.iter().filter_map(|link| {
+            let pending = link.pending.as_deref()?;
+            Some(format!("{}: {pending}", link.asana_name))
+        }))
+        .collect();
+    let title = format!(" Pending ({}) ", planned.len());
+    frame.render_widget(
+        List::new(planned).block(Block::bordered().title(title)),
+        pending,
+    );
+
+    // failing actions, then the log with its latest lines first
+    let recent = dashboard
+        .failures
+        .iter()
+        .cloned()
+        .chain(logging::captured().into_iter().rev());
+    frame.render_widget(
+        List::new(recent).block(Block::bordered().title(" Errors ")),
+        errors,
+    );
+
+    let headroom = http::rate_limits()
+        .into_iter()
+        .map(|(host, limit)| {
+            let of = limit.limit.map(|l| format!("/{l}")).unwrap_or_default();
+            format!(
+                "{host}: {}{of} left at {}",
+                limit.remaining,
+                local_time(limit.seen, "%H:%M:%S")
+            )
+        })
+        .collect::<Vec<_>>();
+    frame.render_widget(
+        List::new(headroom).block(Block::bordered().title(" Rate limits ")),
+        limits,
+    );
+}
+
+/// One line on the cycles and the keys
+fn summary(dashboard: &Dashboard) -> String {
+    let cycle = match (dashboard.syncing, dashboard.last_cycle) {
+        (true, _) => "Syncing...".to_string(),
+        (false, Some((at, ok))) => {
+            let outcome = if ok { "ok" } else { "with errors" };
+            let next = dashboard
+                .next_cycle
+                .map(|next| format!(", next at {}", local_time(next, "%H:%M:%S")))
+                .unwrap_or_default();
+            format!(
+                "Last cycle at {} {outcome}{next}",
+                local_time(at, "%H:%M:%S")
+            )
+        }
+        (false, None) => "Starting".to_string(),
+    };
+    let paused = if control::is_paused() {
+        " | Paused"
+    } else {
+        ""
+    };
+    format!("{cycle}{paused} | s: sync now, p: pause/resume, q: quit")
+}
+
+fn local_time(ts: Timestamp, format: &str) -> String {
+    ts.to_zoned(date::local_tz().clone())
+        .strftime(format)
+        .to_string()
+}
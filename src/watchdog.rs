@@ -0,0 +1,78 @@
+use std::{cell::RefCell, fmt, time::Duration};
+
+use anyhow::Result;
+
+tokio::task_local! {
+    /// What the guarded cycle is doing, to tell where it stalled
+    static PHASE: RefCell<String>;
+}
+
+/// Note what the running cycle is doing now. Does nothing outside [`guard`].
+pub fn phase(what: impl fmt::Display) {
+    let _ = PHASE.try_with(|phase| *phase.borrow_mut() = what.to_string());
+}
+
+/// A cycle that ran past its budget and was abandoned
+#[derive(Debug)]
+pub struct Stalled {
+    pub budget: Duration,
+    /// Last phase the cycle noted
+    pub phase: String,
+}
+
+impl fmt::Display for Stalled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Cycle abandoned after {}s while {}, it's retried next cycle",
+            self.budget.as_secs(),
+            self.phase
+        )
+    }
+}
+
+impl std::error::Error for Stalled {}
+
+/// Run `cycle`, dropping it when it takes longer than `budget` and failing with [`Stalled`]. No
+/// budget lets it run as long as it takes.
+pub async fn guard<T>(
+    budget: Option<Duration>,
+    cycle: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let Some(budget) = budget else {
+        return cycle.await;
+    };
+
+    PHASE
+        .scope(RefCell::new("starting".to_string()), async {
+            match tokio::time::timeout(budget, cycle).await {
+                Ok(result) => result,
+                Err(_) => Err(Stalled {
+                    budget,
+                    phase: PHASE.with(|phase| phase.take()),
+                }
+                .into()),
+            }
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn stalled_cycles_name_their_phase() {
+        let cycle = async {
+            phase("fetching google tasks");
+            std::future::pending::<()>().await;
+            Ok(())
+        };
+
+        let error = guard(Some(Duration::from_millis(10)), cycle)
+            .await
+            .unwrap_err();
+        let stalled = error.downcast::<Stalled>().unwrap();
+        assert_eq!(stalled.phase, "fetching google tasks");
+    }
+}